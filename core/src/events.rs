@@ -1,13 +1,17 @@
 use simple_error::SimpleError;
+use smallvec::SmallVec;
+
+/// Effects produced by handling a `Cause` (kept inline as causes produce one or two effects).
+pub type Effects<E> = SmallVec<[E; 2]>;
 
 /// Handles `Causes` by producing `Effects`.
 ///
 /// Handle receives `causes` and returns `effects`.
-/// Apply receives `effects`.
+/// Apply receives `effects` by value (kept effects move into history; clone first to publish once applied).
 pub trait Actor<C: Cause, E: Effect> {
     type Id;
-    fn handle(&self, command: C) -> Result<Vec<E>, SimpleError>;
-    fn apply(&mut self, events: Vec<E>);
+    fn handle(&self, command: C) -> Result<Effects<E>, SimpleError>;
+    fn apply<I: IntoIterator<Item = E>>(&mut self, events: I);
 }
 
 /// Contributes to production of an Effect.
@@ -0,0 +1,405 @@
+//! Lifecycle hooks of processing plugins (or closures) are registered into (custom enrichment or side-effects).
+//!
+//! Hooks run in the order registered:
+//! - `on_command` before a command is handled (commands may be enriched, errors reject the command)
+//! - `on_events` once events of a command are applied
+//! - `on_reject` for records unparseable or commands rejected
+//! - `on_complete` once every source is processed
+//!
+//! Plugins implement `Plugin` (every hook defaults to doing nothing) or register closures of a single hook. Hooks are
+//! generic over accounts (`A`) kept by the embedding (e.g. the cli passes its projection store).
+
+use std::fmt;
+
+use serde::Serialize;
+use simple_error::SimpleError;
+
+use crate::events::Cause;
+use crate::models::{ClientId, Command, CommandType, Currency, Event, TransactionId};
+
+/// Plugin observing (or enriching) the processing lifecycle of `A` accounts.
+pub trait Plugin<A: ?Sized> {
+    /// Runs before `command` is handled using `accounts` (returning an error rejects `command`).
+    fn on_command(&mut self, _command: &mut Command, _accounts: &A) -> Result<(), SimpleError> {
+        Ok(())
+    }
+
+    /// Runs once `events` are applied to account of `client` (already updated in `accounts`).
+    fn on_events(&mut self, _client: ClientId, _events: &[Event], _accounts: &A) -> Result<(), SimpleError> {
+        Ok(())
+    }
+
+    /// Runs for each record unparseable or command rejected.
+    fn on_reject(&mut self, _reject: &Reject) -> Result<(), SimpleError> {
+        Ok(())
+    }
+
+    /// Runs once every source is processed.
+    fn on_complete(&mut self, _accounts: &A) -> Result<(), SimpleError> {
+        Ok(())
+    }
+}
+
+/// Plugin of a closure run by `on_command`.
+struct CommandHook<F>(F);
+
+impl<A: ?Sized, F: FnMut(&mut Command, &A) -> Result<(), SimpleError>> Plugin<A> for CommandHook<F> {
+    fn on_command(&mut self, command: &mut Command, accounts: &A) -> Result<(), SimpleError> {
+        (self.0)(command, accounts)
+    }
+}
+
+/// Plugin of a closure run by `on_events`.
+struct EventsHook<F>(F);
+
+impl<A: ?Sized, F: FnMut(ClientId, &[Event], &A) -> Result<(), SimpleError>> Plugin<A> for EventsHook<F> {
+    fn on_events(&mut self, client: ClientId, events: &[Event], accounts: &A) -> Result<(), SimpleError> {
+        (self.0)(client, events, accounts)
+    }
+}
+
+/// Plugin of a closure run by `on_reject`.
+struct RejectHook<F>(F);
+
+impl<A: ?Sized, F: FnMut(&Reject) -> Result<(), SimpleError>> Plugin<A> for RejectHook<F> {
+    fn on_reject(&mut self, reject: &Reject) -> Result<(), SimpleError> {
+        (self.0)(reject)
+    }
+}
+
+/// Plugin of a closure run by `on_complete`.
+struct CompleteHook<F>(F);
+
+impl<A: ?Sized, F: FnMut(&A) -> Result<(), SimpleError>> Plugin<A> for CompleteHook<F> {
+    fn on_complete(&mut self, accounts: &A) -> Result<(), SimpleError> {
+        (self.0)(accounts)
+    }
+}
+
+/// Plugins registered into the processing lifecycle of `A` accounts (run in the order registered).
+pub struct Hooks<A: ?Sized> {
+    plugins: Vec<Box<dyn Plugin<A>>>,
+}
+
+impl<A: ?Sized> Default for Hooks<A> {
+    fn default() -> Self {
+        Hooks { plugins: vec![] }
+    }
+}
+
+impl<A: ?Sized> Hooks<A> {
+    /// Returns new `Hooks` without plugins.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns true when no plugins are registered.
+    pub fn is_empty(&self) -> bool {
+        self.plugins.is_empty()
+    }
+
+    /// Registers `plugin` running every hook.
+    pub fn register(&mut self, plugin: Box<dyn Plugin<A>>) {
+        self.plugins.push(plugin);
+    }
+
+    /// Registers `hook` run before commands are handled.
+    pub fn on_command<F>(&mut self, hook: F)
+    where
+        F: FnMut(&mut Command, &A) -> Result<(), SimpleError> + 'static
+    {
+        self.register(Box::new(CommandHook(hook)));
+    }
+
+    /// Registers `hook` run once events are applied.
+    pub fn on_events<F>(&mut self, hook: F)
+    where
+        F: FnMut(ClientId, &[Event], &A) -> Result<(), SimpleError> + 'static
+    {
+        self.register(Box::new(EventsHook(hook)));
+    }
+
+    /// Registers `hook` run for records or commands rejected.
+    pub fn on_reject<F: FnMut(&Reject) -> Result<(), SimpleError> + 'static>(&mut self, hook: F) {
+        self.register(Box::new(RejectHook(hook)));
+    }
+
+    /// Registers `hook` run once every source is processed.
+    pub fn on_complete<F: FnMut(&A) -> Result<(), SimpleError> + 'static>(&mut self, hook: F) {
+        self.register(Box::new(CompleteHook(hook)));
+    }
+
+    /// Runs `on_command` of every plugin (stopping at the first rejecting `command`).
+    pub fn run_command(&mut self, command: &mut Command, accounts: &A) -> Result<(), SimpleError> {
+        for plugin in self.plugins.iter_mut() {
+            plugin.on_command(command, accounts)?;
+        }
+        Ok(())
+    }
+
+    /// Runs `on_events` of every plugin.
+    pub fn run_events(&mut self, client: ClientId, events: &[Event], accounts: &A) -> Result<(), SimpleError> {
+        for plugin in self.plugins.iter_mut() {
+            plugin.on_events(client, events, accounts)?;
+        }
+        Ok(())
+    }
+
+    /// Runs `on_reject` of every plugin.
+    pub fn run_reject(&mut self, reject: &Reject) -> Result<(), SimpleError> {
+        for plugin in self.plugins.iter_mut() {
+            plugin.on_reject(reject)?;
+        }
+        Ok(())
+    }
+
+    /// Runs `on_complete` of every plugin.
+    pub fn run_complete(&mut self, accounts: &A) -> Result<(), SimpleError> {
+        for plugin in self.plugins.iter_mut() {
+            plugin.on_complete(accounts)?;
+        }
+        Ok(())
+    }
+}
+
+/// Row of the rejects (dead-letter) report.
+///
+/// Transaction fields are none for records unable to be parsed.
+#[derive(Debug, Serialize)]
+pub struct Reject {
+    source: String,
+    line: usize,
+    #[serde(rename = "type")]
+    name: Option<CommandType>,
+    client: Option<ClientId>,
+    tx: Option<TransactionId>,
+    amount: Option<Currency>,
+    reason: String,
+}
+
+impl Reject {
+    /// Returns new `Reject` of `source` record at `line` (`command` when parsed) rejected for `reason`.
+    pub fn new(source: &str, line: usize, command: Option<&Command>, reason: &SimpleError) -> Self {
+        Reject {
+            source: source.to_string(),
+            line,
+            name: command.map(|command| { command.name().clone() }),
+            client: command.map(|command| { command.actor_id() }),
+            tx: command.map(|command| { command.tx() }),
+            amount: command.and_then(|command| { command.amount() }),
+            reason: reason.to_string(),
+        }
+    }
+
+    /// Returns source record was read from.
+    pub fn source(&self) -> &str { &self.source }
+
+    /// Returns line of source record was read at.
+    pub fn line(&self) -> usize { self.line }
+
+    /// Returns client of command (none when unparseable).
+    pub fn client(&self) -> Option<ClientId> { self.client }
+
+    /// Returns transaction of command (none when unparseable).
+    pub fn tx(&self) -> Option<TransactionId> { self.tx }
+
+    /// Returns reason record was rejected.
+    pub fn reason(&self) -> &str { &self.reason }
+
+    /// Returns code of reason record was rejected.
+    pub fn code(&self) -> RejectCode {
+        match self.name {
+            Some(_) => RejectCode::of(&self.reason),
+            None => RejectCode::Unparseable,
+        }
+    }
+}
+
+/// Code of reason a record is rejected (stable across reason wording, e.g. for log pipelines).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RejectCode {
+    Unparseable,
+    MissingAmount,
+    InsufficientFunds,
+    CreditLimitExceeded,
+    WithdrawalLimitReached,
+    AccountLocked,
+    DuplicateTransaction,
+    TransactionNotFound,
+    TransactionNotDisputed,
+    RejectedByRules,
+    /// Rejected by plugins without a code.
+    Rejected,
+}
+
+impl RejectCode {
+    /// Returns code of `reason` a parsed command is rejected (reasons of account aggregate and rules are coded).
+    fn of(reason: &str) -> Self {
+        // disputed is matched before account transaction not found (both are unable to find)
+        const CODES: [(&str, RejectCode); 9] = [
+            ("amount is none", RejectCode::MissingAmount),
+            ("exceeds available", RejectCode::InsufficientFunds),
+            ("exceeds credit limit", RejectCode::CreditLimitExceeded),
+            ("withdrawal limit", RejectCode::WithdrawalLimitReached),
+            ("locked account", RejectCode::AccountLocked),
+            ("duplicate", RejectCode::DuplicateTransaction),
+            ("unable to find disputed", RejectCode::TransactionNotDisputed),
+            ("unable to find account", RejectCode::TransactionNotFound),
+            ("rejected by rules", RejectCode::RejectedByRules),
+        ];
+        CODES.iter()
+            .find(|(phrase, _)| { reason.contains(phrase) })
+            .map_or(RejectCode::Rejected, |(_, code)| { *code })
+    }
+
+    /// Returns snake case name of code.
+    pub fn name(&self) -> &'static str {
+        match self {
+            RejectCode::Unparseable => "unparseable",
+            RejectCode::MissingAmount => "missing_amount",
+            RejectCode::InsufficientFunds => "insufficient_funds",
+            RejectCode::CreditLimitExceeded => "credit_limit_exceeded",
+            RejectCode::WithdrawalLimitReached => "withdrawal_limit_reached",
+            RejectCode::AccountLocked => "account_locked",
+            RejectCode::DuplicateTransaction => "duplicate_transaction",
+            RejectCode::TransactionNotFound => "transaction_not_found",
+            RejectCode::TransactionNotDisputed => "transaction_not_disputed",
+            RejectCode::RejectedByRules => "rejected_by_rules",
+            RejectCode::Rejected => "rejected",
+        }
+    }
+}
+
+impl fmt::Display for RejectCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::rc::Rc;
+    use rust_decimal::prelude::Decimal;
+    use simple_error::bail;
+
+    use crate::events::Actor;
+    use crate::models::{Account, AccountMetadata, AccountType};
+
+    /// Accounts kept by embeddings of tests.
+    type Accounts = HashMap<ClientId, Account>;
+
+    /// Plugin recording hooks run (rejecting tx 2).
+    struct Recorder {
+        calls: Rc<RefCell<Vec<&'static str>>>,
+    }
+
+    impl Plugin<Accounts> for Recorder {
+        fn on_command(&mut self, command: &mut Command, _accounts: &Accounts) -> Result<(), SimpleError> {
+            self.calls.borrow_mut().push("validate");
+            match command.tx() {
+                2 => bail!("blocked tx"),
+                _ => Ok(()),
+            }
+        }
+
+        fn on_events(&mut self, _client: ClientId, events: &[Event], _accounts: &Accounts) -> Result<(), SimpleError> {
+            self.calls.borrow_mut().push(events[0].name());
+            Ok(())
+        }
+
+        fn on_complete(&mut self, _accounts: &Accounts) -> Result<(), SimpleError> {
+            self.calls.borrow_mut().push("complete");
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn hooks_run_in_order_registered() {
+        let calls = Rc::new(RefCell::new(vec![]));
+        let mut hooks = Hooks::new();
+        assert!(hooks.is_empty());
+        let recorded = calls.clone();
+        hooks.on_command(move |command, _| {
+            recorded.borrow_mut().push("enrich");
+            *command = command.clone().with_merchant(Some(7));
+            Ok(())
+        });
+        hooks.register(Box::new(Recorder { calls: calls.clone() }));
+        let recorded = calls.clone();
+        hooks.on_reject(move |reject| {
+            recorded.borrow_mut().push("reject");
+            assert_eq!((reject.line(), reject.tx()), (3, Some(2)));
+            Ok(())
+        });
+
+        let mut accounts = Accounts::new();
+        let mut command = Command::new(CommandType::Deposit, 1, 1, Some(Decimal::new(15, 1)));
+        hooks.run_command(&mut command, &accounts).unwrap();
+        assert_eq!(command.merchant(), Some(7));
+        let mut account = Account::new(1);
+        let events = account.handle(command).unwrap();
+        account.apply(events.clone());
+        accounts.insert(1, account);
+        hooks.run_events(1, &events, &accounts).unwrap();
+
+        let mut command = Command::new(CommandType::Deposit, 1, 2, Some(Decimal::new(15, 1)));
+        let e = hooks.run_command(&mut command, &accounts).unwrap_err();
+        hooks.run_reject(&Reject::new("tx.csv", 3, Some(&command), &e)).unwrap();
+        hooks.run_complete(&accounts).unwrap();
+        assert_eq!(*calls.borrow(), vec!["enrich", "validate", "credited", "enrich", "validate", "reject", "complete"]);
+    }
+
+    #[test]
+    fn closures_registered_see_accounts() {
+        let balances = Rc::new(RefCell::new(vec![]));
+        let mut hooks: Hooks<Accounts> = Hooks::new();
+        let seen = balances.clone();
+        hooks.on_events(move |client, _events, accounts| {
+            seen.borrow_mut().push(accounts[&client].total());
+            Ok(())
+        });
+        let seen = balances.clone();
+        hooks.on_complete(move |accounts| {
+            seen.borrow_mut().push(Decimal::from(accounts.len() as u64));
+            Ok(())
+        });
+
+        let mut account = Account::new(1);
+        let events = account.handle(Command::new(CommandType::Deposit, 1, 1, Some(Decimal::new(25, 1)))).unwrap();
+        account.apply(events.clone());
+        let accounts: Accounts = vec![(1, account)].into_iter().collect();
+        hooks.run_events(1, &events, &accounts).unwrap();
+        hooks.run_complete(&accounts).unwrap();
+
+        assert_eq!(*balances.borrow(), vec![Decimal::new(25, 1), Decimal::from(1)]);
+    }
+
+    /// Returns code of `command` rejected by `account`.
+    fn rejected(account: &mut Account, command: Command) -> RejectCode {
+        let reason = account.handle(command.clone()).unwrap_err();
+        Reject::new("source.csv", 2, Some(&command), &reason).code()
+    }
+
+    #[test]
+    fn rejects_coded_by_reason() {
+        let mut account = Account::new(1);
+        let deposit = Command::new(CommandType::Deposit, 1, 1, Some(Decimal::new(5, 0)));
+        account.apply(account.handle(deposit.clone()).unwrap());
+        assert_eq!(rejected(&mut account, deposit), RejectCode::DuplicateTransaction);
+        assert_eq!(rejected(&mut account, Command::new(CommandType::Deposit, 1, 2, None)), RejectCode::MissingAmount);
+        assert_eq!(rejected(&mut account, Command::new(CommandType::Withdraw, 1, 3, Some(Decimal::new(9, 0)))), RejectCode::InsufficientFunds);
+        assert_eq!(rejected(&mut account, Command::new(CommandType::Dispute, 1, 4, None)), RejectCode::TransactionNotFound);
+        assert_eq!(rejected(&mut account, Command::new(CommandType::Resolve, 1, 1, None)), RejectCode::TransactionNotDisputed);
+        let metadata = AccountMetadata { kind: AccountType::Credit, limit: Some(Decimal::new(1, 0)), ..AccountMetadata::new(2) };
+        let withdraw = Command::new(CommandType::Withdraw, 2, 5, Some(Decimal::new(9, 0)));
+        assert_eq!(rejected(&mut Account::with_metadata(&metadata), withdraw), RejectCode::CreditLimitExceeded);
+        assert_eq!(RejectCode::of("rejected by rules, blocked merchant"), RejectCode::RejectedByRules);
+        assert_eq!(RejectCode::of("blocked tx"), RejectCode::Rejected);
+
+        let unparseable = Reject::new("source.csv", 3, None, &SimpleError::new("invalid type"));
+        assert_eq!((unparseable.code().to_string(), unparseable.client()), ("unparseable".to_string(), None));
+    }
+}
@@ -0,0 +1,8 @@
+//! Core engine of accounts aggregates: domain models (`Account` aggregates handling commands), their events and
+//! lifecycle hooks plugins (or closures) are registered into.
+//!
+//! Shared by the cli and its embeddings (wasm and C ABI) so every build applies the same business rules.
+
+pub mod events;
+pub mod hooks;
+pub mod models;
@@ -0,0 +1,1990 @@
+//! Domain models for event sourcing the `Account` aggregate.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use simple_error::*;
+use smallvec::smallvec;
+use rust_decimal::prelude::{Decimal, ToPrimitive};
+use chrono::{DateTime, Utc};
+use serde::{Serialize, Deserialize};
+use uuid::Uuid;
+
+use crate::events::{Actor, Cause, Effect, Effects};
+
+/// Version used to determine events applied to `Account` aggregate. Increments with event stream.
+pub type Version = u32;
+/// Client Id which is equivalent to `Account` aggregate Id.
+pub type ClientId = u16;
+/// Transaction Id representing initial command to aggregate (Withdrawal or Deposit).
+pub type TransactionId = u32;
+/// Current using Decimal package to avoid float arithmetic issues. (91 bits)
+pub type Currency = Decimal;
+/// Idempotency Key (UUID Version 4)
+pub type IdempotencyKey = [u8; 16];
+/// Wallet Id naming a balance bucket of an `Account`.
+pub type WalletId = String;
+/// Merchant Id which is equivalent to `Merchant` aggregate Id.
+pub type MerchantId = u16;
+/// Category (tag) of a deposit or withdrawal used for spend breakdowns.
+pub type Category = String;
+/// Time a transaction occurred (RFC 3339).
+pub type Timestamp = DateTime<Utc>;
+
+/// Wallet used when `Commands` do not specify one.
+pub const DEFAULT_WALLET: &str = "main";
+
+/// An action to perform for a given `Account` aggregate.
+///
+/// `Commands` are decoupled from query responsibilities.
+/// Every command results in _at least_ one event.
+///
+/// (See [CQRS](https://martinfowler.com/bliki/CQRS.html)).
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Command {
+    #[serde(rename = "type")]
+    name: CommandType,
+    client: ClientId,
+    tx: TransactionId,
+    #[serde(default)]
+    amount: Option<Currency>,
+    #[serde(default)]
+    wallet: Option<WalletId>,
+    #[serde(default)]
+    merchant: Option<MerchantId>,
+    #[serde(default)]
+    timestamp: Option<Timestamp>,
+    #[serde(default)]
+    category: Option<Category>
+}
+
+impl Command {
+    /// Returns new `Command` of type `name` for `client` transaction `tx` without optional attributes.
+    pub fn new(name: CommandType, client: ClientId, tx: TransactionId, amount: Option<Currency>) -> Self {
+        Command {
+            name,
+            client,
+            tx,
+            amount,
+            wallet: None,
+            merchant: None,
+            timestamp: None,
+            category: None
+        }
+    }
+
+    /// Returns command with `wallet` set.
+    pub fn with_wallet(mut self, wallet: Option<WalletId>) -> Self {
+        self.wallet = wallet;
+        self
+    }
+
+    /// Returns command with `merchant` set.
+    pub fn with_merchant(mut self, merchant: Option<MerchantId>) -> Self {
+        self.merchant = merchant;
+        self
+    }
+
+    /// Returns command with `timestamp` set.
+    pub fn with_timestamp(mut self, timestamp: Option<Timestamp>) -> Self {
+        self.timestamp = timestamp;
+        self
+    }
+
+    /// Returns command with `category` set.
+    pub fn with_category(mut self, category: Option<Category>) -> Self {
+        self.category = category;
+        self
+    }
+
+    /// Returns type of command.
+    pub fn name(&self) -> &CommandType { &self.name }
+
+    /// Returns transaction id.
+    pub fn tx(&self) -> TransactionId { self.tx }
+
+    /// Returns transaction amount (if any).
+    pub fn amount(&self) -> Option<Currency> { self.amount }
+
+    /// Returns wallet as supplied (none targets `DEFAULT_WALLET`).
+    pub fn wallet(&self) -> Option<&WalletId> { self.wallet.as_ref() }
+
+    /// Returns merchant referenced by transaction (if any).
+    pub fn merchant(&self) -> Option<MerchantId> { self.merchant }
+
+    /// Returns time transaction occurred (if known).
+    pub fn timestamp(&self) -> Option<Timestamp> { self.timestamp }
+
+    /// Returns category of transaction (if any).
+    pub fn category(&self) -> Option<&Category> { self.category.as_ref() }
+
+    /// Returns `wallet` targeted by command or `DEFAULT_WALLET` when none.
+    fn target_wallet(&self) -> WalletId {
+        self.wallet.clone().unwrap_or_else(|| { DEFAULT_WALLET.to_string() })
+    }
+}
+
+impl Cause for Command {
+    type ActorId = ClientId;
+    fn actor_id(&self) -> Self::ActorId { self.client }
+}
+
+/// Type of `Commands` that can be handled by the `Account` aggregate.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum CommandType {
+    Deposit,
+    Withdraw,
+    Dispute,
+    Resolve,
+    Chargeback,
+}
+
+/// Type of `Account` determining which business rules apply when handling commands.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum AccountType {
+    #[default]
+    Checking,
+    Savings,
+    Credit,
+}
+
+/// Attributes used to open an `Account` supplied by the accounts metadata source.
+///
+/// `limit` is interpreted per `AccountType`:
+/// - `Savings` maximum number of withdrawals (defaults to `SAVINGS_WITHDRAWAL_LIMIT`)
+/// - `Credit` maximum negative available balance (unlimited when none)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AccountMetadata {
+    pub client: ClientId,
+    #[serde(rename = "type")]
+    pub kind: AccountType,
+    #[serde(default)]
+    pub limit: Option<Currency>,
+    /// Balances account is opened with (seeded from a previous run's snapshot).
+    #[serde(default)]
+    pub opening: Vec<OpeningBalance>,
+}
+
+impl AccountMetadata {
+    /// Returns metadata of `client` using default type and limit.
+    pub fn new(client: ClientId) -> Self {
+        AccountMetadata { client, kind: AccountType::default(), limit: None, opening: vec![] }
+    }
+
+    /// Checks `limit` is valid for account type (savings limits are whole counts and no limit is negative).
+    pub fn validate(&self) -> Result<(), SimpleError> {
+        if let Some(limit) = self.limit {
+            let valid = match self.kind {
+                AccountType::Savings => limit.fract().is_zero() && limit.to_u32().is_some(),
+                AccountType::Checking | AccountType::Credit => !limit.is_sign_negative(),
+            };
+            if !valid {
+                bail!("invalid limit({}) of {:?} account({})", limit, self.kind, self.client);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Balances of a snapshot record (account or wallet row of a previous run's output) an `Account` is opened with.
+///
+/// Records without a wallet seed `DEFAULT_WALLET`. Held funds stay held as their disputes are unknown.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct OpeningBalance {
+    pub client: ClientId,
+    #[serde(default)]
+    pub wallet: Option<WalletId>,
+    pub available: Currency,
+    pub held: Currency,
+    pub locked: bool,
+}
+
+/// Default number of withdrawals permitted for `Savings` accounts.
+const SAVINGS_WITHDRAWAL_LIMIT: u32 = 6;
+
+/// Events that can occur from the `Account` aggregate.
+///
+/// When a change happens to an `Account` those effects are propagated outward using events.
+///
+/// Events moving funds between an `Account` and a `Merchant` reference the merchant (if any).
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum Event {
+    Credited { version: Version, key: IdempotencyKey, timestamp: Option<Timestamp>, tx: TransactionId, wallet: WalletId, merchant: Option<MerchantId>, category: Option<Category>, amount: Currency },
+    Debited { version: Version, key: IdempotencyKey, timestamp: Option<Timestamp>, tx: TransactionId, wallet: WalletId, merchant: Option<MerchantId>, category: Option<Category>, amount: Currency },
+    Held { version: Version, key: IdempotencyKey, timestamp: Option<Timestamp>, tx: TransactionId, wallet: WalletId, amount: Currency },
+    Released { version: Version, key: IdempotencyKey, timestamp: Option<Timestamp>, tx: TransactionId, wallet: WalletId, amount: Currency },
+    Reversed { version: Version, key: IdempotencyKey, timestamp: Option<Timestamp>, tx: TransactionId, wallet: WalletId, merchant: Option<MerchantId>, amount: Currency },
+    Locked { version: Version, key: IdempotencyKey, timestamp: Option<Timestamp> },
+}
+
+impl Event {
+    /// Returns lowercase name of event type.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Event::Credited {..} => "credited",
+            Event::Debited {..} => "debited",
+            Event::Held {..} => "held",
+            Event::Released {..} => "released",
+            Event::Reversed {..} => "reversed",
+            Event::Locked {..} => "locked",
+        }
+    }
+
+    /// Returns time event occurred (if known).
+    pub fn timestamp(&self) -> Option<Timestamp> {
+        match self {
+            Event::Credited {timestamp, ..} |
+            Event::Debited {timestamp, ..} |
+            Event::Held {timestamp, ..} |
+            Event::Released {timestamp, ..} |
+            Event::Reversed {timestamp, ..} |
+            Event::Locked {timestamp, ..} => { *timestamp }
+        }
+    }
+
+    /// Returns category of deposit or withdrawal events (if any).
+    pub fn category(&self) -> Option<&Category> {
+        match self {
+            Event::Credited {category, ..} |
+            Event::Debited {category, ..} => { category.as_ref() }
+            _ => None,
+        }
+    }
+
+    /// Returns type of command event is applied for (none for `Locked`).
+    pub fn command_type(&self) -> Option<CommandType> {
+        match self {
+            Event::Credited {..} => Some(CommandType::Deposit),
+            Event::Debited {..} => Some(CommandType::Withdraw),
+            Event::Held {..} => Some(CommandType::Dispute),
+            Event::Released {..} => Some(CommandType::Resolve),
+            Event::Reversed {..} => Some(CommandType::Chargeback),
+            Event::Locked {..} => None,
+        }
+    }
+
+    /// Returns transaction id(`tx`) and `amount` for events affecting balances.
+    pub fn transaction(&self) -> Option<(TransactionId, Currency)> {
+        match self {
+            Event::Credited {tx, amount, ..} |
+            Event::Debited {tx, amount, ..} |
+            Event::Held {tx, amount, ..} |
+            Event::Released {tx, amount, ..} |
+            Event::Reversed {tx, amount, ..} => Some((*tx, *amount)),
+            Event::Locked {..} => None,
+        }
+    }
+}
+
+impl Effect for Event {
+    type Version = Version;
+    type Key = IdempotencyKey;
+    fn version(&self) -> Self::Version {
+        match self {
+            Event::Credited {version, ..} |
+            Event::Debited {version, ..} |
+            Event::Held {version, ..} |
+            Event::Released {version, ..} |
+            Event::Reversed {version, ..} |
+            Event::Locked {version, ..} => { *version }
+        }
+    }
+    fn idempotency_key(&self) -> Self::Key {
+        match self {
+            Event::Credited {key, ..} |
+            Event::Debited {key, ..} |
+            Event::Held {key, ..} |
+            Event::Released {key, ..} |
+            Event::Reversed {key, ..} |
+            Event::Locked {key, ..} => { *key }
+        }
+    }
+}
+
+/// Balance bucket of an `Account` tracking available and held funds independently.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Wallet {
+    available: Currency,
+    held: Currency,
+}
+
+impl Wallet {
+    /// Returns new empty `Wallet`.
+    pub fn new() -> Self {
+        Wallet {
+            available: Currency::new(0, 4),
+            held: Currency::new(0, 4),
+        }
+    }
+}
+
+impl Default for Wallet {
+    fn default() -> Self { Wallet::new() }
+}
+
+/// Snapshot of a single `Wallet` belonging to an `Account` used for output.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct WalletSnapshot {
+    client: ClientId,
+    wallet: WalletId,
+    available: Currency,
+    held: Currency,
+    total: Currency,
+    locked: bool,
+}
+
+/// Transaction (deposit or withdrawal) of an `Account` referenced by disputes, resolves and chargebacks.
+#[derive(Debug, Clone, PartialEq)]
+struct TxRecord {
+    kind: CommandType,
+    wallet: WalletId,
+    merchant: Option<MerchantId>,
+    amount: Currency,
+    disputed: bool,
+}
+
+/// Aggregate that summarizes all `client` transactions.
+///
+/// Equivalent of a bank account.
+///
+/// Balances are tracked per `Wallet` while `available`, `held` and `total` summarize all wallets.
+/// Locking an account locks every wallet.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Account {
+    #[serde(skip_serializing)]
+    version: Version,
+    #[serde(skip_serializing)]
+    kind: AccountType,
+    #[serde(skip_serializing)]
+    limit: Option<Currency>,
+    client:  ClientId,
+    available: Currency,
+    held: Currency,
+    total: Currency,
+    locked: bool,
+    #[serde(skip_serializing)]
+    wallets: BTreeMap<WalletId, Wallet>,
+    #[serde(skip_serializing)]
+    opening: Vec<OpeningBalance>,
+    #[serde(skip_serializing)]
+    events: Vec<Event>,
+    /// True once event history is discarded (events applied are no longer kept).
+    #[serde(skip)]
+    projection_only: bool,
+    /// Withdrawals applied (savings withdrawal limit).
+    #[serde(skip)]
+    withdrawals: u32,
+    /// Command types and transactions of events applied (duplicate checks are constant time).
+    #[serde(skip)]
+    applied: HashSet<(CommandType, TransactionId)>,
+    /// First deposit or withdrawal applied of each transaction (lookups are constant time).
+    #[serde(skip)]
+    transactions: HashMap<TransactionId, TxRecord>
+}
+
+impl Account {
+    /// Returns new `Account` with `client` id set and defaults.
+    pub fn new(client: ClientId) -> Self {
+        Account {
+            version: 0,
+            kind: AccountType::default(),
+            limit: None,
+            client,
+            available: Currency::new(0, 4),
+            held: Currency::new(0, 4),
+            total: Currency::new(0, 4),
+            locked: false,
+            wallets: BTreeMap::new(),
+            opening: vec![],
+            events: vec![],
+            projection_only: false,
+            withdrawals: 0,
+            applied: HashSet::new(),
+            transactions: HashMap::new()
+        }
+    }
+
+    /// Returns new `Account` with `client` id, type, limit and opening balances set from `metadata`.
+    ///
+    /// Opening balances are not events (account version starts at zero).
+    pub fn with_metadata(metadata: &AccountMetadata) -> Self {
+        let mut account = Account::new(metadata.client);
+        account.kind = metadata.kind;
+        account.limit = metadata.limit;
+        for balance in &metadata.opening {
+            let id = balance.wallet.clone().unwrap_or_else(|| { DEFAULT_WALLET.to_string() });
+            let wallet = account.wallets.entry(id).or_default();
+            wallet.available += balance.available;
+            wallet.held += balance.held;
+            account.available += balance.available;
+            account.held += balance.held;
+            account.locked |= balance.locked;
+        }
+        account.total = account.available + account.held;
+        account.opening = metadata.opening.clone();
+        account
+    }
+
+    /// Returns `client` id of account.
+    pub fn client(&self) -> ClientId { self.client }
+
+    /// Returns metadata (type, limit and opening balances) account was opened with.
+    pub fn metadata(&self) -> AccountMetadata {
+        AccountMetadata { client: self.client, kind: self.kind, limit: self.limit, opening: self.opening.clone() }
+    }
+
+    /// Returns version of account (number of events applied).
+    pub fn version(&self) -> Version { self.version }
+
+    /// Returns funds available to account.
+    pub fn available(&self) -> Currency { self.available }
+
+    /// Returns funds held (disputed) by account.
+    pub fn held(&self) -> Currency { self.held }
+
+    /// Returns total funds of account.
+    pub fn total(&self) -> Currency { self.total }
+
+    /// Returns true when account is locked.
+    pub fn locked(&self) -> bool { self.locked }
+
+    /// Returns ordered stream of events applied to account (empty once history is discarded).
+    pub fn events(&self) -> &[Event] { &self.events }
+
+    /// Returns true once event history is discarded.
+    pub fn is_projection_only(&self) -> bool { self.projection_only }
+
+    /// Discards event history keeping balances and the transaction indexes (duplicates and disputes) only.
+    ///
+    /// Events subsequently applied are not kept either.
+    pub fn discard_history(&mut self) {
+        if !self.projection_only {
+            self.projection_only = true;
+            self.events = vec![];
+        }
+    }
+
+    /// Returns approximate bytes of memory used by account including event history (excluding strings of events).
+    pub fn memory_bytes(&self) -> usize {
+        let wallets: usize = self.wallets.keys().map(|id| {
+            std::mem::size_of::<WalletId>() + id.capacity() + std::mem::size_of::<Wallet>()
+        }).sum();
+        std::mem::size_of::<Account>()
+            + wallets
+            + self.opening.capacity() * std::mem::size_of::<OpeningBalance>()
+            + self.events.capacity() * std::mem::size_of::<Event>()
+            + self.applied.capacity() * std::mem::size_of::<(CommandType, TransactionId)>()
+            + self.transactions.capacity() * std::mem::size_of::<(TransactionId, TxRecord)>()
+    }
+
+    /// Releases unused capacity of event history (and its indexes).
+    pub fn compact(&mut self) {
+        self.events.shrink_to_fit();
+        self.applied.shrink_to_fit();
+        self.transactions.shrink_to_fit();
+    }
+
+    /// Releases transaction indexes (duplicates and disputes) once no further commands are handled by account.
+    ///
+    /// Indexes are rebuilt when account is replayed from events (e.g. loaded from a persistent store).
+    pub fn release_indexes(&mut self) {
+        self.applied = HashSet::new();
+        self.transactions = HashMap::new();
+    }
+
+    /// Returns snapshot for each `Wallet` of account ordered by wallet id.
+    pub fn wallets(&self) -> Vec<WalletSnapshot> {
+        self.wallets.iter().map(|(id, wallet)| {
+            WalletSnapshot {
+                client: self.client,
+                wallet: id.clone(),
+                available: wallet.available,
+                held: wallet.held,
+                total: wallet.available + wallet.held,
+                locked: self.locked,
+            }
+        }).collect()
+    }
+
+    /// Returns `available` funds of `wallet` (zero when wallet does not exist).
+    fn wallet_available(&self, wallet: &str) -> Currency {
+        self.wallets.get(wallet).map_or(Currency::new(0, 4), |w| { w.available })
+    }
+
+    /// Evaluates `AccountType` business rules for a withdrawal of `amount` from `command` wallet.
+    ///
+    /// - `Checking` withdrawals cannot exceed available funds
+    /// - `Savings` withdrawals cannot exceed available funds nor withdrawal limit
+    /// - `Credit` withdrawals can overdraw available funds up to credit limit (if any)
+    fn check_withdraw_rules(&self, command: &Command, amount: Currency) -> Result<(), SimpleError> {
+        let available = self.wallet_available(&command.target_wallet());
+        match self.kind {
+            AccountType::Checking => {
+                if amount > available {
+                    bail!("amount({}) exceeds available({}) withdraw account({}) transaction({})", amount, available, command.client, command.tx);
+                }
+            }
+            AccountType::Savings => {
+                if amount > available {
+                    bail!("amount({}) exceeds available({}) withdraw account({}) transaction({})", amount, available, command.client, command.tx);
+                }
+                let limit = self.limit
+                    .and_then(|l| { l.to_u32() })
+                    .unwrap_or(SAVINGS_WITHDRAWAL_LIMIT);
+                if self.withdrawals >= limit {
+                    bail!("withdrawal limit({}) reached for savings account({}) transaction({})", limit, command.client, command.tx);
+                }
+            }
+            AccountType::Credit => {
+                if let Some(limit) = self.limit {
+                    if amount > available + limit {
+                        bail!("amount({}) exceeds credit limit({}) withdraw account({}) transaction({})", amount, limit, command.client, command.tx);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns true when an event of the same type has been applied for the transaction of `event`.
+    fn has_event(&self, event: &Event) -> bool {
+        match (event.command_type(), event.transaction()) {
+            (Some(name), Some((tx, _))) => self.applied.contains(&(name, tx)),
+            _ => false,
+        }
+    }
+
+    /// Returns `wallet` and `amount` of first deposit or withdrawal (ordered) of transaction id(`tx`).
+    fn find_genesis_amount(&self, key: TransactionId) -> Option<(WalletId, Currency)> {
+        self.transactions.get(&key).map(|record| { (record.wallet.clone(), record.amount) })
+    }
+
+    /// Returns `merchant` of first deposit or withdrawal (ordered) of transaction id(`tx`).
+    fn find_genesis_merchant(&self, key: TransactionId) -> Option<MerchantId> {
+        self.transactions.get(&key).and_then(|record| { record.merchant })
+    }
+
+    /// Returns `wallet` and `amount` of transaction id(`tx`) once disputed.
+    ///
+    /// `Event::Held` is emitted for `dispute` commands.
+    fn find_dispute_amount(&self, key: TransactionId) -> Option<(WalletId, Currency)> {
+        self.transactions.get(&key)
+            .filter(|record| { record.disputed })
+            .map(|record| { (record.wallet.clone(), record.amount) })
+    }
+
+    /// Indexes transaction of `event` (deposits and withdrawals recorded, disputes marking their record).
+    fn index_transaction(&mut self, event: &Event) {
+        match event {
+            Event::Credited { tx, wallet, merchant, amount, .. } |
+            Event::Debited { tx, wallet, merchant, amount, .. } => {
+                self.transactions.entry(*tx).or_insert_with(|| {
+                    TxRecord {
+                        kind: event.command_type().unwrap(),
+                        wallet: wallet.clone(),
+                        merchant: *merchant,
+                        amount: *amount,
+                        disputed: false,
+                    }
+                });
+            }
+            Event::Held { tx, .. } => {
+                if let Some(record) = self.transactions.get_mut(tx) {
+                    record.disputed = true;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Applies `events` read from a store or log (moved into event history rather than cloned).
+    pub fn replay<I: IntoIterator<Item = Event>>(&mut self, events: I) {
+        for event in events {
+            self.apply_event(&event);
+            if !self.projection_only {
+                self.events.push(event);
+            }
+        }
+    }
+
+    /// Applies `event` to balances and indexes (event history excluded).
+    fn apply_event(&mut self, event: &Event) {
+        let _v: u32 = 1;
+        match event {
+            Event::Credited { version: _v, wallet, amount, .. } => {
+                let wallet = self.wallet_mut(wallet);
+                wallet.available += amount;
+                self.available += amount;
+            }
+            Event::Debited { version: _v, wallet, amount, .. } => {
+                let wallet = self.wallet_mut(wallet);
+                wallet.available -= amount;
+                self.available -= amount;
+                self.withdrawals += 1;
+            }
+            Event::Held { version: _v, wallet, amount, .. } => {
+                let wallet = self.wallet_mut(wallet);
+                wallet.available -= amount;
+                wallet.held += amount;
+                self.available -= amount;
+                self.held += amount;
+            }
+            Event::Released { version: _v, wallet, amount, .. } => {
+                let wallet = self.wallet_mut(wallet);
+                wallet.held -= amount;
+                wallet.available += amount;
+                self.held -= amount;
+                self.available += amount;
+            }
+            Event::Reversed { version: _v, wallet, amount, .. } => {
+                let wallet = self.wallet_mut(wallet);
+                wallet.held -= amount;
+                self.held -= amount;
+            }
+            Event::Locked { version: _v, .. } => {
+                self.locked = true;
+            }
+        };
+        self.total = self.available + self.held;
+        self.version += 1;
+        if let (Some(name), Some((tx, _))) = (event.command_type(), event.transaction()) {
+            self.applied.insert((name, tx));
+        }
+        self.index_transaction(event);
+    }
+
+    /// Returns balances of `wallet` (opened when missing, its id only cloned to open it).
+    fn wallet_mut(&mut self, wallet: &WalletId) -> &mut Wallet {
+        if !self.wallets.contains_key(wallet) {
+            self.wallets.insert(wallet.clone(), Wallet::default());
+        }
+        self.wallets.get_mut(wallet).unwrap()
+    }
+}
+
+impl Actor<Command, Event> for Account {
+    type Id = ClientId;
+
+    fn handle(&self, command: Command) -> Result<Effects<Event>, SimpleError> {
+        if self.locked {
+            bail!("unable to process transaction({}) having locked account({})", command.tx, command.client);
+        }
+
+        let namespace = Uuid::NAMESPACE_OID;
+
+        let events = match command.name {
+            CommandType::Deposit => {
+                let amount = command.amount;
+                if amount.is_none() {
+                    bail!("amount is none for deposit account({}) transaction({})", command.client, command.tx);
+                }
+                let event = Event::Credited {
+                    version: 1,
+                    key: *Uuid::new_v3(&namespace, &command.tx.to_le_bytes()).as_bytes(),
+                    timestamp: command.timestamp,
+                    tx: command.tx,
+                    wallet: command.target_wallet(),
+                    merchant: command.merchant,
+                    category: command.category,
+                    amount: amount.unwrap()
+                };
+                if self.has_event(&event) {
+                    bail!("duplicate deposit account({}) transaction({})", command.client, command.tx);
+                }
+                smallvec![event]
+            }
+            CommandType::Withdraw => {
+                let amount = command.amount;
+                if amount.is_none() {
+                    bail!("amount is none for withdraw account({}) transaction({})", command.client, command.tx);
+                }
+                let amount_value = amount.unwrap();
+                let event = Event::Debited {
+                    version: 1,
+                    key: *Uuid::new_v3(&namespace, &command.tx.to_le_bytes()).as_bytes(),
+                    timestamp: command.timestamp,
+                    tx: command.tx,
+                    wallet: command.target_wallet(),
+                    merchant: command.merchant,
+                    category: command.category.clone(),
+                    amount: amount_value
+                };
+                if self.has_event(&event) {
+                    bail!("duplicate withdraw account({}) transaction({})", command.client, command.tx);
+                }
+                self.check_withdraw_rules(&command, amount_value)?;
+                smallvec![event]
+            }
+            CommandType::Dispute => {
+                let amount = self.find_genesis_amount(command.tx);
+                if amount.is_none() {
+                    bail!("unable to find account({}) transaction({}) to dispute", command.client, command.tx);
+                }
+                let (wallet, amount) = amount.unwrap();
+                let event = Event::Held {
+                    version: 1,
+                    key: *Uuid::new_v3(&namespace, &command.tx.to_le_bytes()).as_bytes(),
+                    timestamp: command.timestamp,
+                    tx: command.tx,
+                    wallet,
+                    amount
+                };
+                if self.has_event(&event) {
+                    bail!("duplicate dispute account({}) transaction({})", command.client, command.tx);
+                }
+                smallvec![event]
+            }
+            CommandType::Resolve => {
+                let amount = self.find_dispute_amount(command.tx);
+                if amount.is_none() {
+                    bail!("unable to find disputed account({}) transaction({}) to resolve", command.client, command.tx);
+                }
+                let (wallet, amount) = amount.unwrap();
+                let event = Event::Released {
+                    version: 1,
+                    key: *Uuid::new_v3(&namespace, &command.tx.to_le_bytes()).as_bytes(),
+                    timestamp: command.timestamp,
+                    tx: command.tx,
+                    wallet,
+                    amount
+                };
+                if self.has_event(&event) {
+                    bail!("duplicate resolve account({}) transaction({})", command.client, command.tx);
+                }
+                smallvec![event]
+            }
+            CommandType::Chargeback => {
+                let amount = self.find_dispute_amount(command.tx);
+                if amount.is_none() {
+                    bail!("unable to find disputed account({}) transaction({}) to chargeback", command.client, command.tx);
+                }
+                let (wallet, amount) = amount.unwrap();
+                let event = Event::Reversed {
+                    version: 1,
+                    key: *Uuid::new_v3(&namespace, &command.tx.to_le_bytes()).as_bytes(),
+                    timestamp: command.timestamp,
+                    tx: command.tx,
+                    wallet,
+                    merchant: self.find_genesis_merchant(command.tx),
+                    amount
+                };
+                if self.has_event(&event) {
+                    bail!("duplicate chargeback account({}) transaction({})", command.client, command.tx);
+                }
+                smallvec![event, Event::Locked {version: 1, key: *Uuid::new_v4().as_bytes(), timestamp: command.timestamp}]
+            }
+        };
+
+        Ok(events)
+    }
+
+    fn apply<I: IntoIterator<Item = Event>>(&mut self, events: I) {
+        self.replay(events);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deposit_accepted() {
+        let client = 1;
+        let tx = 10;
+
+        let mut account = Account::new(client);
+        let command = Command {
+            name: CommandType::Deposit,
+            client,
+            tx,
+            amount: Some(Decimal::new(990000, 4)),
+            wallet: None,
+            merchant: None,
+            timestamp: None,
+            category: None
+        };
+        let events = account.handle(command).unwrap();
+        account.apply(events);
+
+        assert_eq!(account.version, 1);
+        assert_eq!(account.client, client);
+        assert_eq!(account.available, Decimal::new(990000, 4));
+        assert_eq!(account.held, Decimal::new(0, 4));
+        assert_eq!(account.total, Decimal::new(990000, 4));
+        assert!(!account.locked);
+        assert_eq!(account.events.len(), 1);
+    }
+
+    #[test]
+    fn deposit_when_locked_declined() {
+        let client = 1;
+        let tx = 10;
+
+        let mut account = Account::new(client);
+        account.locked = true;
+        let command = Command {
+            name: CommandType::Deposit,
+            client,
+            tx,
+            amount: Some(Decimal::new(990000, 4)),
+            wallet: None,
+            merchant: None,
+            timestamp: None,
+            category: None
+        };
+        let events = account.handle(command);
+
+        assert!(events.is_err());
+        assert_eq!(account.version, 0);
+        assert_eq!(account.client, client);
+        assert_eq!(account.available, Decimal::new(0, 4));
+        assert_eq!(account.held, Decimal::new(0, 4));
+        assert_eq!(account.total, Decimal::new(0, 4));
+        assert!(account.locked);
+        assert_eq!(account.events.len(), 0);
+    }
+
+    #[test]
+    fn deposit_duplicate_declined() {
+        let client = 1;
+        let tx = 10;
+
+        let mut account = Account::new(client);
+        let command = Command {
+            name: CommandType::Deposit,
+            client,
+            tx,
+            amount: Some(Decimal::new(990000, 4)),
+            wallet: None,
+            merchant: None,
+            timestamp: None,
+            category: None
+        };
+        let events = account.handle(command.clone()).unwrap();
+        account.apply(events);
+        let events = account.handle(command);
+
+        assert!(events.is_err());
+        assert_eq!(account.version, 1);
+        assert_eq!(account.client, client);
+        assert_eq!(account.available, Decimal::new(990000, 4));
+        assert_eq!(account.held, Decimal::new(0, 4));
+        assert_eq!(account.total, Decimal::new(990000, 4));
+        assert!(!account.locked);
+        assert_eq!(account.events.len(), 1);
+    }
+
+    #[test]
+    fn withdraw_accepted() {
+        let client = 1;
+        let tx = 10;
+
+        let mut account = Account::new(client);
+        let command = Command {
+            name: CommandType::Deposit,
+            client,
+            tx,
+            amount: Some(Decimal::new(990000, 4)),
+            wallet: None,
+            merchant: None,
+            timestamp: None,
+            category: None
+        };
+        let events = account.handle(command).unwrap();
+        account.apply(events);
+        let command = Command {
+            name: CommandType::Withdraw,
+            client,
+            tx: tx + 1,
+            amount: Some(Decimal::new(980000, 4)),
+            wallet: None,
+            merchant: None,
+            timestamp: None,
+            category: None
+        };
+        let events = account.handle(command).unwrap();
+        account.apply(events);
+
+        assert_eq!(account.version, 2);
+        assert_eq!(account.client, client);
+        assert_eq!(account.available, Decimal::new(10000, 4));
+        assert_eq!(account.held, Decimal::new(0, 4));
+        assert_eq!(account.total, Decimal::new(10000, 4));
+        assert!(!account.locked);
+        assert_eq!(account.events.len(), 2);
+    }
+
+    #[test]
+    fn withdraw_when_locked_declined() {
+        let client = 1;
+        let tx = 10;
+
+        let mut account = Account::new(client);
+        let command = Command {
+            name: CommandType::Deposit,
+            client,
+            tx,
+            amount: Some(Decimal::new(990000, 4)),
+            wallet: None,
+            merchant: None,
+            timestamp: None,
+            category: None
+        };
+        let events = account.handle(command).unwrap();
+        account.apply(events);
+        account.locked = true;
+        let command = Command {
+            name: CommandType::Withdraw,
+            client,
+            tx: tx + 1,
+            amount: Some(Decimal::new(400000, 4)),
+            wallet: None,
+            merchant: None,
+            timestamp: None,
+            category: None
+        };
+        let events = account.handle(command);
+
+        assert!(events.is_err());
+        assert_eq!(account.version, 1);
+        assert_eq!(account.client, client);
+        assert_eq!(account.available, Decimal::new(990000, 4));
+        assert_eq!(account.held, Decimal::new(0, 4));
+        assert_eq!(account.total, Decimal::new(990000, 4));
+        assert!(account.locked);
+        assert_eq!(account.events.len(), 1);
+    }
+
+    #[test]
+    fn withdraw_duplicate_declined() {
+        let client = 1;
+        let tx = 10;
+
+        let mut account = Account::new(client);
+        let command = Command {
+            name: CommandType::Deposit,
+            client,
+            tx,
+            amount: Some(Decimal::new(990000, 4)),
+            wallet: None,
+            merchant: None,
+            timestamp: None,
+            category: None
+        };
+        let events = account.handle(command).unwrap();
+        account.apply(events);
+        let command = Command {
+            name: CommandType::Withdraw,
+            client,
+            tx: tx + 1,
+            amount: Some(Decimal::new(400000, 4)),
+            wallet: None,
+            merchant: None,
+            timestamp: None,
+            category: None
+        };
+        let events = account.handle(command.clone()).unwrap();
+        account.apply(events);
+        let events = account.handle(command);
+
+        assert!(events.is_err());
+        assert_eq!(account.version, 2);
+        assert_eq!(account.client, client);
+        assert_eq!(account.available, Decimal::new(590000, 4));
+        assert_eq!(account.held, Decimal::new(0, 4));
+        assert_eq!(account.total, Decimal::new(590000, 4));
+        assert!(!account.locked);
+        assert_eq!(account.events.len(), 2);
+    }
+
+    #[test]
+    fn withdraw_when_balance_insufficient_declined() {
+        let client = 1;
+        let tx = 10;
+
+        let mut account = Account::new(client);
+        let command = Command {
+            name: CommandType::Deposit,
+            client,
+            tx,
+            amount: Some(Decimal::new(990000, 4)),
+            wallet: None,
+            merchant: None,
+            timestamp: None,
+            category: None
+        };
+        let events = account.handle(command).unwrap();
+        account.apply(events);
+        let command = Command {
+            name: CommandType::Withdraw,
+            client,
+            tx: tx + 1,
+            amount: Some(Decimal::new(1000000, 4)),
+            wallet: None,
+            merchant: None,
+            timestamp: None,
+            category: None
+        };
+        let events = account.handle(command);
+
+        assert!(events.is_err());
+        assert_eq!(account.version, 1);
+        assert_eq!(account.client, client);
+        assert_eq!(account.available, Decimal::new(990000, 4));
+        assert_eq!(account.held, Decimal::new(0, 4));
+        assert_eq!(account.total, Decimal::new(990000, 4));
+        assert!(!account.locked);
+        assert_eq!(account.events.len(), 1);
+    }
+
+    #[test]
+    fn dispute_accepted() {
+        let client = 1;
+        let tx = 10;
+
+        let mut account = Account::new(client);
+        let command = Command {
+            name: CommandType::Deposit,
+            client,
+            tx,
+            amount: Some(Decimal::new(990000, 4)),
+            wallet: None,
+            merchant: None,
+            timestamp: None,
+            category: None
+        };
+        let events = account.handle(command).unwrap();
+        account.apply(events);
+        let command = Command {
+            name: CommandType::Dispute,
+            client,
+            tx,
+            amount: None,
+            wallet: None,
+            merchant: None,
+            timestamp: None,
+            category: None
+        };
+        let events = account.handle(command).unwrap();
+        account.apply(events);
+
+        assert_eq!(account.version, 2);
+        assert_eq!(account.client, client);
+        assert_eq!(account.available, Decimal::new(0, 4));
+        assert_eq!(account.held, Decimal::new(990000, 4));
+        assert_eq!(account.total, Decimal::new(990000, 4));
+        assert!(!account.locked);
+        assert_eq!(account.events.len(), 2);
+    }
+
+    #[test]
+    fn dispute_when_locked_declined() {
+        let client = 1;
+        let tx = 10;
+
+        let mut account = Account::new(client);
+        let command = Command {
+            name: CommandType::Deposit,
+            client,
+            tx,
+            amount: Some(Decimal::new(990000, 4)),
+            wallet: None,
+            merchant: None,
+            timestamp: None,
+            category: None
+        };
+        let events = account.handle(command).unwrap();
+        account.apply(events);
+        account.locked = true;
+        let command = Command {
+            name: CommandType::Dispute,
+            client,
+            tx,
+            amount: None,
+            wallet: None,
+            merchant: None,
+            timestamp: None,
+            category: None
+        };
+        let events = account.handle(command);
+
+        assert!(events.is_err());
+        assert_eq!(account.version, 1);
+        assert_eq!(account.client, client);
+        assert_eq!(account.available, Decimal::new(990000, 4));
+        assert_eq!(account.held, Decimal::new(0, 4));
+        assert_eq!(account.total, Decimal::new(990000, 4));
+        assert!(account.locked);
+        assert_eq!(account.events.len(), 1);
+    }
+
+    #[test]
+    fn dispute_missing_transaction_declined() {
+        let client = 1;
+        let tx = 10;
+
+        let mut account = Account::new(client);
+        let command = Command {
+            name: CommandType::Deposit,
+            client,
+            tx,
+            amount: Some(Decimal::new(990000, 4)),
+            wallet: None,
+            merchant: None,
+            timestamp: None,
+            category: None
+        };
+        let events = account.handle(command).unwrap();
+        account.apply(events);
+        let command = Command {
+            name: CommandType::Dispute,
+            client,
+            tx: tx + 1,
+            amount: None,
+            wallet: None,
+            merchant: None,
+            timestamp: None,
+            category: None
+        };
+        let events = account.handle(command);
+
+        assert!(events.is_err());
+        assert_eq!(account.version, 1);
+        assert_eq!(account.client, client);
+        assert_eq!(account.available, Decimal::new(990000, 4));
+        assert_eq!(account.held, Decimal::new(0, 4));
+        assert_eq!(account.total, Decimal::new(990000, 4));
+        assert!(!account.locked);
+        assert_eq!(account.events.len(), 1);
+    }
+
+    #[test]
+    fn resolve_for_dispute_accepted() {
+        let client = 1;
+        let tx = 10;
+
+        let mut account = Account::new(client);
+        let command = Command {
+            name: CommandType::Deposit,
+            client,
+            tx,
+            amount: Some(Decimal::new(990000, 4)),
+            wallet: None,
+            merchant: None,
+            timestamp: None,
+            category: None
+        };
+        let events = account.handle(command).unwrap();
+        account.apply(events);
+        let command = Command {
+            name: CommandType::Dispute,
+            client,
+            tx,
+            amount: None,
+            wallet: None,
+            merchant: None,
+            timestamp: None,
+            category: None
+        };
+        let events = account.handle(command).unwrap();
+        account.apply(events);
+        let command = Command {
+            name: CommandType::Resolve,
+            client,
+            tx,
+            amount: None,
+            wallet: None,
+            merchant: None,
+            timestamp: None,
+            category: None
+        };
+        let events = account.handle(command).unwrap();
+        account.apply(events);
+
+        assert_eq!(account.version, 3);
+        assert_eq!(account.client, client);
+        assert_eq!(account.available, Decimal::new(990000, 4));
+        assert_eq!(account.held, Decimal::new(0, 4));
+        assert_eq!(account.total, Decimal::new(990000, 4));
+        assert!(!account.locked);
+        assert_eq!(account.events.len(), 3);
+    }
+
+    #[test]
+    fn resolve_for_dispute_duplicate_declined() {
+        let client = 1;
+        let tx = 10;
+
+        let mut account = Account::new(client);
+        let command = Command {
+            name: CommandType::Deposit,
+            client,
+            tx,
+            amount: Some(Decimal::new(990000, 4)),
+            wallet: None,
+            merchant: None,
+            timestamp: None,
+            category: None
+        };
+        let events = account.handle(command).unwrap();
+        account.apply(events);
+        let command = Command {
+            name: CommandType::Dispute,
+            client,
+            tx,
+            amount: None,
+            wallet: None,
+            merchant: None,
+            timestamp: None,
+            category: None
+        };
+        let events = account.handle(command).unwrap();
+        account.apply(events);
+        let command = Command {
+            name: CommandType::Resolve,
+            client,
+            tx,
+            amount: None,
+            wallet: None,
+            merchant: None,
+            timestamp: None,
+            category: None
+        };
+        let events = account.handle(command).unwrap();
+        account.apply(events);
+        let command = Command {
+            name: CommandType::Resolve,
+            client,
+            tx,
+            amount: None,
+            wallet: None,
+            merchant: None,
+            timestamp: None,
+            category: None
+        };
+        let events = account.handle(command);
+
+        assert!(events.is_err());
+        assert_eq!(account.version, 3);
+        assert_eq!(account.client, client);
+        assert_eq!(account.available, Decimal::new(990000, 4));
+        assert_eq!(account.held, Decimal::new(0, 4));
+        assert_eq!(account.total, Decimal::new(990000, 4));
+        assert!(!account.locked);
+        assert_eq!(account.events.len(), 3);
+    }
+
+    #[test]
+    fn resolve_for_dispute_when_locked_declined() {
+        let client = 1;
+        let tx = 10;
+
+        let mut account = Account::new(client);
+        let command = Command {
+            name: CommandType::Deposit,
+            client,
+            tx,
+            amount: Some(Decimal::new(990000, 4)),
+            wallet: None,
+            merchant: None,
+            timestamp: None,
+            category: None
+        };
+        let events = account.handle(command).unwrap();
+        account.apply(events);
+        let command = Command {
+            name: CommandType::Dispute,
+            client,
+            tx,
+            amount: None,
+            wallet: None,
+            merchant: None,
+            timestamp: None,
+            category: None
+        };
+        let events = account.handle(command).unwrap();
+        account.apply(events);
+        account.locked = true;
+        let command = Command {
+            name: CommandType::Resolve,
+            client,
+            tx,
+            amount: None,
+            wallet: None,
+            merchant: None,
+            timestamp: None,
+            category: None
+        };
+        let events = account.handle(command);
+
+        assert!(events.is_err());
+        assert_eq!(account.version, 2);
+        assert_eq!(account.client, client);
+        assert_eq!(account.available, Decimal::new(0, 4));
+        assert_eq!(account.held, Decimal::new(990000, 4));
+        assert_eq!(account.total, Decimal::new(990000, 4));
+        assert!(account.locked);
+        assert_eq!(account.events.len(), 2);
+    }
+
+    #[test]
+    fn resolve_for_dispute_when_missing_transaction_declined() {
+        let client = 1;
+        let tx = 10;
+
+        let mut account = Account::new(client);
+        let command = Command {
+            name: CommandType::Deposit,
+            client,
+            tx,
+            amount: Some(Decimal::new(990000, 4)),
+            wallet: None,
+            merchant: None,
+            timestamp: None,
+            category: None
+        };
+        let events = account.handle(command).unwrap();
+        account.apply(events);
+        let command = Command {
+            name: CommandType::Dispute,
+            client,
+            tx,
+            amount: None,
+            wallet: None,
+            merchant: None,
+            timestamp: None,
+            category: None
+        };
+        let events = account.handle(command).unwrap();
+        account.apply(events);
+        let command = Command {
+            name: CommandType::Resolve,
+            client,
+            tx: tx + 1,
+            amount: None,
+            wallet: None,
+            merchant: None,
+            timestamp: None,
+            category: None
+        };
+        let events = account.handle(command);
+
+        assert!(events.is_err());
+        assert_eq!(account.version, 2);
+        assert_eq!(account.client, client);
+        assert_eq!(account.available, Decimal::new(0, 4));
+        assert_eq!(account.held, Decimal::new(990000, 4));
+        assert_eq!(account.total, Decimal::new(990000, 4));
+        assert!(!account.locked);
+        assert_eq!(account.events.len(), 2);
+    }
+
+    #[test]
+    fn resolve_when_dispute_missing_declined() {
+        let client = 1;
+        let tx = 10;
+
+        let mut account = Account::new(client);
+        let command = Command {
+            name: CommandType::Deposit,
+            client,
+            tx,
+            amount: Some(Decimal::new(990000, 4)),
+            wallet: None,
+            merchant: None,
+            timestamp: None,
+            category: None
+        };
+        let events = account.handle(command).unwrap();
+        account.apply(events);
+        let command = Command {
+            name: CommandType::Resolve,
+            client,
+            tx,
+            amount: None,
+            wallet: None,
+            merchant: None,
+            timestamp: None,
+            category: None
+        };
+        let events = account.handle(command);
+
+        assert!(events.is_err());
+        assert_eq!(account.version, 1);
+        assert_eq!(account.client, client);
+        assert_eq!(account.available, Decimal::new(990000, 4));
+        assert_eq!(account.held, Decimal::new(0, 4));
+        assert_eq!(account.total, Decimal::new(990000, 4));
+        assert!(!account.locked);
+        assert_eq!(account.events.len(), 1);
+    }
+
+    #[test]
+    fn chargeback_for_dispute_accepted() {
+        let client = 1;
+        let tx = 10;
+
+        let mut account = Account::new(client);
+        let command = Command {
+            name: CommandType::Deposit,
+            client,
+            tx,
+            amount: Some(Decimal::new(990000, 4)),
+            wallet: None,
+            merchant: None,
+            timestamp: None,
+            category: None
+        };
+        let events = account.handle(command).unwrap();
+        account.apply(events);
+        let command = Command {
+            name: CommandType::Dispute,
+            client,
+            tx,
+            amount: None,
+            wallet: None,
+            merchant: None,
+            timestamp: None,
+            category: None
+        };
+        let events = account.handle(command).unwrap();
+        account.apply(events);
+        let command = Command {
+            name: CommandType::Chargeback,
+            client,
+            tx,
+            amount: None,
+            wallet: None,
+            merchant: None,
+            timestamp: None,
+            category: None
+        };
+        let events = account.handle(command).unwrap();
+        account.apply(events);
+
+        assert_eq!(account.version, 4);
+        assert_eq!(account.client, client);
+        assert_eq!(account.available, Decimal::new(0, 4));
+        assert_eq!(account.held, Decimal::new(0, 4));
+        assert_eq!(account.total, Decimal::new(0, 4));
+        assert!(account.locked);
+        assert_eq!(account.events.len(), 4);
+    }
+
+    #[test]
+    fn chargeback_for_dispute_when_locked_declined() {
+        let client = 1;
+        let tx = 10;
+
+        let mut account = Account::new(client);
+        let command = Command {
+            name: CommandType::Deposit,
+            client,
+            tx,
+            amount: Some(Decimal::new(990000, 4)),
+            wallet: None,
+            merchant: None,
+            timestamp: None,
+            category: None
+        };
+        let events = account.handle(command).unwrap();
+        account.apply(events);
+        let command = Command {
+            name: CommandType::Dispute,
+            client,
+            tx,
+            amount: None,
+            wallet: None,
+            merchant: None,
+            timestamp: None,
+            category: None
+        };
+        let events = account.handle(command).unwrap();
+        account.apply(events);
+        account.locked = true;
+        let command = Command {
+            name: CommandType::Chargeback,
+            client,
+            tx,
+            amount: None,
+            wallet: None,
+            merchant: None,
+            timestamp: None,
+            category: None
+        };
+        let events = account.handle(command);
+
+        assert!(events.is_err());
+        assert_eq!(account.version, 2);
+        assert_eq!(account.client, client);
+        assert_eq!(account.available, Decimal::new(0, 4));
+        assert_eq!(account.held, Decimal::new(990000, 4));
+        assert_eq!(account.total, Decimal::new(990000, 4));
+        assert!(account.locked);
+        assert_eq!(account.events.len(), 2);
+    }
+
+    #[test]
+    fn chargeback_for_dispute_when_missing_transaction_declined() {
+        let client = 1;
+        let tx = 10;
+
+        let mut account = Account::new(client);
+        let command = Command {
+            name: CommandType::Deposit,
+            client,
+            tx,
+            amount: Some(Decimal::new(990000, 4)),
+            wallet: None,
+            merchant: None,
+            timestamp: None,
+            category: None
+        };
+        let events = account.handle(command).unwrap();
+        account.apply(events);
+        let command = Command {
+            name: CommandType::Dispute,
+            client,
+            tx,
+            amount: None,
+            wallet: None,
+            merchant: None,
+            timestamp: None,
+            category: None
+        };
+        let events = account.handle(command).unwrap();
+        account.apply(events);
+        account.locked = true;
+        let command = Command {
+            name: CommandType::Chargeback,
+            client,
+            tx: tx + 1,
+            amount: None,
+            wallet: None,
+            merchant: None,
+            timestamp: None,
+            category: None
+        };
+        let events = account.handle(command);
+
+        assert!(events.is_err());
+        assert_eq!(account.version, 2);
+        assert_eq!(account.client, client);
+        assert_eq!(account.available, Decimal::new(0, 4));
+        assert_eq!(account.held, Decimal::new(990000, 4));
+        assert_eq!(account.total, Decimal::new(990000, 4));
+        assert!(account.locked);
+        assert_eq!(account.events.len(), 2);
+    }
+
+    #[test]
+    fn chargeback_when_dispute_missing_declined() {
+        let client = 1;
+        let tx = 10;
+
+        let mut account = Account::new(client);
+        let command = Command {
+            name: CommandType::Deposit,
+            client,
+            tx,
+            amount: Some(Decimal::new(990000, 4)),
+            wallet: None,
+            merchant: None,
+            timestamp: None,
+            category: None
+        };
+        let events = account.handle(command).unwrap();
+        account.apply(events);
+        let command = Command {
+            name: CommandType::Chargeback,
+            client,
+            tx,
+            amount: None,
+            wallet: None,
+            merchant: None,
+            timestamp: None,
+            category: None
+        };
+        let events = account.handle(command);
+
+        assert!(events.is_err());
+        assert_eq!(account.version, 1);
+        assert_eq!(account.client, client);
+        assert_eq!(account.available, Decimal::new(990000, 4));
+        assert_eq!(account.held, Decimal::new(0, 4));
+        assert_eq!(account.total, Decimal::new(990000, 4));
+        assert!(!account.locked);
+        assert_eq!(account.events.len(), 1);
+    }
+
+    #[test]
+    fn metadata_limits_validated() {
+        let metadata = |kind: AccountType, limit: Decimal| { AccountMetadata { client: 1, kind, limit: Some(limit), opening: vec![] } };
+
+        assert!(metadata(AccountType::Savings, Decimal::new(2, 0)).validate().is_ok());
+        assert!(metadata(AccountType::Credit, Decimal::new(5005, 1)).validate().is_ok());
+        assert!(AccountMetadata::new(1).validate().is_ok());
+        // fractional savings limits are not truncated
+        assert_eq!(metadata(AccountType::Savings, Decimal::new(25, 1)).validate().unwrap_err().as_str(), "invalid limit(2.5) of Savings account(1)");
+        // negative savings limits do not fall back to the default limit
+        assert!(metadata(AccountType::Savings, Decimal::new(-2, 0)).validate().is_err());
+        // negative credit limits would be stricter than checking
+        assert_eq!(metadata(AccountType::Credit, Decimal::new(-100, 0)).validate().unwrap_err().as_str(), "invalid limit(-100) of Credit account(1)");
+    }
+
+    #[test]
+    fn withdraw_savings_when_limit_reached_declined() {
+        let client = 1;
+        let tx = 10;
+
+        let mut account = Account::with_metadata(&AccountMetadata {
+            client,
+            kind: AccountType::Savings,
+            limit: Some(Decimal::new(1, 0)),
+            opening: vec![]
+        });
+        let command = Command {
+            name: CommandType::Deposit,
+            client,
+            tx,
+            amount: Some(Decimal::new(990000, 4)),
+            wallet: None,
+            merchant: None,
+            timestamp: None,
+            category: None
+        };
+        let events = account.handle(command).unwrap();
+        account.apply(events);
+        let command = Command {
+            name: CommandType::Withdraw,
+            client,
+            tx: tx + 1,
+            amount: Some(Decimal::new(100000, 4)),
+            wallet: None,
+            merchant: None,
+            timestamp: None,
+            category: None
+        };
+        let events = account.handle(command).unwrap();
+        account.apply(events);
+        let command = Command {
+            name: CommandType::Withdraw,
+            client,
+            tx: tx + 2,
+            amount: Some(Decimal::new(100000, 4)),
+            wallet: None,
+            merchant: None,
+            timestamp: None,
+            category: None
+        };
+        let events = account.handle(command);
+
+        assert!(events.is_err());
+        assert_eq!(account.version, 2);
+        assert_eq!(account.available, Decimal::new(890000, 4));
+        assert_eq!(account.total, Decimal::new(890000, 4));
+        assert_eq!(account.events.len(), 2);
+    }
+
+    #[test]
+    fn withdraw_credit_when_balance_insufficient_accepted() {
+        let client = 1;
+        let tx = 10;
+
+        let mut account = Account::with_metadata(&AccountMetadata {
+            client,
+            kind: AccountType::Credit,
+            limit: None,
+            opening: vec![]
+        });
+        let command = Command {
+            name: CommandType::Withdraw,
+            client,
+            tx,
+            amount: Some(Decimal::new(100000, 4)),
+            wallet: None,
+            merchant: None,
+            timestamp: None,
+            category: None
+        };
+        let events = account.handle(command).unwrap();
+        account.apply(events);
+
+        assert_eq!(account.version, 1);
+        assert_eq!(account.available, Decimal::new(-100000, 4));
+        assert_eq!(account.held, Decimal::new(0, 4));
+        assert_eq!(account.total, Decimal::new(-100000, 4));
+        assert!(!account.locked);
+        assert_eq!(account.events.len(), 1);
+    }
+
+    #[test]
+    fn withdraw_credit_when_limit_exceeded_declined() {
+        let client = 1;
+        let tx = 10;
+
+        let account = Account::with_metadata(&AccountMetadata {
+            client,
+            kind: AccountType::Credit,
+            limit: Some(Decimal::new(500000, 4)),
+            opening: vec![]
+        });
+        let command = Command {
+            name: CommandType::Withdraw,
+            client,
+            tx,
+            amount: Some(Decimal::new(500001, 4)),
+            wallet: None,
+            merchant: None,
+            timestamp: None,
+            category: None
+        };
+        let events = account.handle(command);
+
+        assert!(events.is_err());
+        assert_eq!(account.version, 0);
+        assert_eq!(account.available, Decimal::new(0, 4));
+        assert_eq!(account.events.len(), 0);
+    }
+
+    #[test]
+    fn withdraw_when_wallet_balance_insufficient_declined() {
+        let client = 1;
+        let tx = 10;
+
+        let mut account = Account::new(client);
+        let command = Command {
+            name: CommandType::Deposit,
+            client,
+            tx,
+            amount: Some(Decimal::new(990000, 4)),
+            wallet: Some(String::from("savings")),
+            merchant: None,
+            timestamp: None,
+            category: None
+        };
+        let events = account.handle(command).unwrap();
+        account.apply(events);
+        let command = Command {
+            name: CommandType::Withdraw,
+            client,
+            tx: tx + 1,
+            amount: Some(Decimal::new(10000, 4)),
+            wallet: None,
+            merchant: None,
+            timestamp: None,
+            category: None
+        };
+        let events = account.handle(command);
+
+        assert!(events.is_err());
+        assert_eq!(account.version, 1);
+        assert_eq!(account.available, Decimal::new(990000, 4));
+        assert_eq!(account.total, Decimal::new(990000, 4));
+        assert_eq!(account.wallets().len(), 1);
+        assert_eq!(account.wallets()[0].wallet, "savings");
+    }
+
+    #[test]
+    fn dispute_wallet_transaction_accepted() {
+        let client = 1;
+        let tx = 10;
+
+        let mut account = Account::new(client);
+        let command = Command {
+            name: CommandType::Deposit,
+            client,
+            tx,
+            amount: Some(Decimal::new(990000, 4)),
+            wallet: None,
+            merchant: None,
+            timestamp: None,
+            category: None
+        };
+        let events = account.handle(command).unwrap();
+        account.apply(events);
+        let command = Command {
+            name: CommandType::Deposit,
+            client,
+            tx: tx + 1,
+            amount: Some(Decimal::new(10000, 4)),
+            wallet: Some(String::from("rewards")),
+            merchant: None,
+            timestamp: None,
+            category: None
+        };
+        let events = account.handle(command).unwrap();
+        account.apply(events);
+        let command = Command {
+            name: CommandType::Dispute,
+            client,
+            tx: tx + 1,
+            amount: None,
+            wallet: None,
+            merchant: None,
+            timestamp: None,
+            category: None
+        };
+        let events = account.handle(command).unwrap();
+        account.apply(events);
+
+        let wallets = account.wallets();
+        assert_eq!(account.version, 3);
+        assert_eq!(account.available, Decimal::new(990000, 4));
+        assert_eq!(account.held, Decimal::new(10000, 4));
+        assert_eq!(account.total, Decimal::new(1000000, 4));
+        assert_eq!(wallets[0].wallet, DEFAULT_WALLET);
+        assert_eq!(wallets[0].available, Decimal::new(990000, 4));
+        assert_eq!(wallets[0].held, Decimal::new(0, 4));
+        assert_eq!(wallets[1].wallet, "rewards");
+        assert_eq!(wallets[1].available, Decimal::new(0, 4));
+        assert_eq!(wallets[1].held, Decimal::new(10000, 4));
+        assert_eq!(wallets[1].total, Decimal::new(10000, 4));
+    }
+
+    #[test]
+    fn opening_balances_seed_account_without_events() {
+        let client = 1;
+        let mut metadata = AccountMetadata::new(client);
+        metadata.opening.push(OpeningBalance {
+            client,
+            wallet: None,
+            available: Decimal::new(990000, 4),
+            held: Decimal::new(10000, 4),
+            locked: false
+        });
+        metadata.opening.push(OpeningBalance {
+            client,
+            wallet: Some(String::from("rewards")),
+            available: Decimal::new(50000, 4),
+            held: Decimal::new(0, 4),
+            locked: false
+        });
+
+        let mut account = Account::with_metadata(&metadata);
+        assert_eq!(account.version, 0);
+        assert_eq!(account.available, Decimal::new(1040000, 4));
+        assert_eq!(account.held, Decimal::new(10000, 4));
+        assert_eq!(account.total, Decimal::new(1050000, 4));
+        assert_eq!(account.metadata(), metadata);
+
+        let command = Command::new(CommandType::Withdraw, client, 10, Some(Decimal::new(990000, 4)));
+        let events = account.handle(command).unwrap();
+        account.apply(events);
+        assert_eq!(account.version, 1);
+        assert_eq!(account.available, Decimal::new(50000, 4));
+        assert_eq!(account.wallets()[0].available, Decimal::new(0, 4));
+    }
+
+    #[test]
+    fn duplicates_indexed_by_type_and_transaction() {
+        let client = 1;
+        let tx = 10;
+
+        let mut account = Account::new(client);
+        for name in [CommandType::Deposit, CommandType::Dispute, CommandType::Resolve] {
+            let events = account.handle(Command::new(name, client, tx, Some(Decimal::new(990000, 4)))).unwrap();
+            account.apply(events);
+        }
+        assert_eq!(account.applied.len(), 3);
+
+        for name in [CommandType::Deposit, CommandType::Dispute, CommandType::Resolve] {
+            assert!(account.handle(Command::new(name, client, tx, Some(Decimal::new(990000, 4)))).is_err());
+        }
+        // withdraw of a transaction deposited is a different event type
+        let events = account.handle(Command::new(CommandType::Withdraw, client, tx, Some(Decimal::new(10000, 4)))).unwrap();
+        account.apply(events);
+        assert_eq!(account.version, 4);
+        assert!(account.applied.contains(&(CommandType::Withdraw, tx)));
+        assert!(account.handle(Command::new(CommandType::Deposit, client, tx + 1, Some(Decimal::new(10000, 4)))).is_ok());
+    }
+
+    #[test]
+    fn transactions_indexed_by_first_deposit_or_withdrawal() {
+        let client = 1;
+        let tx = 10;
+
+        let mut account = Account::new(client);
+        let command = Command::new(CommandType::Deposit, client, tx, Some(Decimal::new(990000, 4)))
+            .with_wallet(Some(String::from("savings")))
+            .with_merchant(Some(7));
+        let events = account.handle(command).unwrap();
+        account.apply(events);
+        let command = Command::new(CommandType::Withdraw, client, tx, Some(Decimal::new(10000, 4)))
+            .with_wallet(Some(String::from("savings")));
+        let events = account.handle(command).unwrap();
+        account.apply(events);
+        assert_eq!(account.transactions.len(), 1);
+        assert_eq!(account.transactions[&tx].kind, CommandType::Deposit);
+        assert_eq!(account.find_dispute_amount(tx), None);
+        assert!(account.handle(Command::new(CommandType::Resolve, client, tx, None)).is_err());
+
+        let events = account.handle(Command::new(CommandType::Dispute, client, tx, None)).unwrap();
+        assert_eq!(events[0].transaction(), Some((tx, Decimal::new(990000, 4))));
+        account.apply(events);
+        assert!(account.transactions[&tx].disputed);
+        assert_eq!(account.find_dispute_amount(tx), Some((String::from("savings"), Decimal::new(990000, 4))));
+
+        let events = account.handle(Command::new(CommandType::Chargeback, client, tx, None)).unwrap();
+        assert!(matches!(events[0], Event::Reversed { merchant: Some(7), .. }));
+        account.apply(events);
+        assert!(account.locked);
+        assert_eq!(account.held, Decimal::new(0, 4));
+    }
+
+    #[test]
+    fn projection_only_account_discards_history() {
+        let client = 1;
+        let tx = 10;
+
+        let mut account = Account::new(client);
+        account.kind = AccountType::Savings;
+        account.limit = Some(Decimal::new(2, 0));
+        let events = account.handle(Command::new(CommandType::Deposit, client, tx, Some(Decimal::new(990000, 4)))).unwrap();
+        account.apply(events);
+        let retained = account.memory_bytes();
+        account.discard_history();
+        assert!(account.is_projection_only());
+        assert!(account.events().is_empty());
+        assert!(account.memory_bytes() < retained);
+
+        for tx in [11, 12] {
+            let events = account.handle(Command::new(CommandType::Withdraw, client, tx, Some(Decimal::new(10000, 4)))).unwrap();
+            account.apply(events);
+        }
+        // duplicates, disputes and withdrawal limits are checked without history
+        assert!(account.handle(Command::new(CommandType::Withdraw, client, 13, Some(Decimal::new(10000, 4)))).is_err());
+        assert!(account.handle(Command::new(CommandType::Deposit, client, tx, Some(Decimal::new(10000, 4)))).is_err());
+        let events = account.handle(Command::new(CommandType::Dispute, client, tx, None)).unwrap();
+        account.apply(events);
+        assert!(account.events().is_empty());
+        assert_eq!(account.version, 4);
+        assert_eq!((account.available, account.held), (Decimal::new(-20000, 4), Decimal::new(990000, 4)));
+        let events = account.handle(Command::new(CommandType::Resolve, client, tx, None)).unwrap();
+        account.apply(events);
+        assert_eq!((account.available, account.held), (Decimal::new(970000, 4), Decimal::new(0, 4)));
+    }
+
+    #[test]
+    fn effects_handled_inline_and_replayed() {
+        let client = 1;
+        let tx = 10;
+
+        let mut account = Account::new(client);
+        let events = account.handle(Command::new(CommandType::Deposit, client, tx, Some(Decimal::new(990000, 4)))).unwrap();
+        account.apply(events);
+        let events = account.handle(Command::new(CommandType::Dispute, client, tx, None)).unwrap();
+        account.apply(events);
+        let events = account.handle(Command::new(CommandType::Chargeback, client, tx, None)).unwrap();
+        assert_eq!(events.len(), 2);
+        assert!(!events.spilled());
+        account.apply(events);
+
+        let mut replayed = Account::new(client);
+        replayed.replay(account.events().to_vec());
+        assert_eq!(replayed.events(), account.events());
+        assert_eq!((replayed.version, replayed.locked, replayed.held, replayed.total), (4, true, Decimal::new(0, 4), Decimal::new(0, 4)));
+        assert!(replayed.handle(Command::new(CommandType::Deposit, client, tx + 1, Some(Decimal::new(10000, 4)))).is_err());
+    }
+}
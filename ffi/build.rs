@@ -0,0 +1,15 @@
+//! Generates the C header of exported functions (`header` feature).
+
+fn main() {
+    #[cfg(feature = "header")]
+    {
+        println!("cargo:rerun-if-changed=src/lib.rs");
+        let directory = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+        cbindgen::Builder::new()
+            .with_crate(&directory)
+            .with_config(cbindgen::Config::from_file(format!("{}/cbindgen.toml", directory)).unwrap())
+            .generate()
+            .expect("unable to generate header")
+            .write_to_file(format!("{}/include/accounts_aggregate.h", directory));
+    }
+}
@@ -0,0 +1,91 @@
+#ifndef ACCOUNTS_AGGREGATE_H
+#define ACCOUNTS_AGGREGATE_H
+
+/* Generated by cbindgen (cargo build --features header), do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// Bytes of decimal strings of balances (including the terminating nul).
+#define AA_DECIMAL_LEN 40
+
+// Command applied.
+#define AA_APPLIED 0
+
+// Command rejected by business rules (reason returned by `aa_account_reason`).
+#define AA_REJECTED 1
+
+// Pointer supplied is null.
+#define AA_NULL_POINTER -1
+
+// Amount supplied isn't a decimal string.
+#define AA_INVALID_AMOUNT -2
+
+// Type of command handled by an account.
+typedef enum AaCommandType {
+  AA_COMMAND_TYPE_DEPOSIT,
+  AA_COMMAND_TYPE_WITHDRAW,
+  AA_COMMAND_TYPE_DISPUTE,
+  AA_COMMAND_TYPE_RESOLVE,
+  AA_COMMAND_TYPE_CHARGEBACK,
+} AaCommandType;
+
+// Account handling commands (opaque to callers).
+typedef struct AaAccount AaAccount;
+
+// Balances of an account (amounts as nul terminated decimal strings).
+typedef struct AaBalances {
+  uint16_t client;
+  char available[AA_DECIMAL_LEN];
+  char held[AA_DECIMAL_LEN];
+  char total[AA_DECIMAL_LEN];
+  bool locked;
+} AaBalances;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Returns new account of `client` (freed using `aa_account_free`).
+struct AaAccount *aa_account_new(uint16_t client);
+
+// Frees `account` (null is ignored).
+//
+// # Safety
+//
+// `account` must be null or returned by `aa_account_new` and not yet freed.
+void aa_account_free(struct AaAccount *account);
+
+// Handles command `command_type` of transaction `tx` with `amount` (decimal string, null unless deposit or withdraw)
+// returning `AA_APPLIED`, `AA_REJECTED` or an error code (negative).
+//
+// # Safety
+//
+// `account` must be returned by `aa_account_new` and `amount` null or a nul terminated string.
+int32_t aa_handle_command(struct AaAccount *account,
+                          enum AaCommandType command_type,
+                          uint32_t tx,
+                          const char *amount);
+
+// Returns reason the last command handled by `account` was rejected (empty unless rejected).
+//
+// # Safety
+//
+// `account` must be returned by `aa_account_new`. The string is valid until the next command is handled.
+const char *aa_account_reason(const struct AaAccount *account);
+
+// Writes balances of `account` to `balances` returning `AA_APPLIED` (or an error code).
+//
+// # Safety
+//
+// `account` must be returned by `aa_account_new` and `balances` point to writable `AaBalances`.
+int32_t aa_get_balances(const struct AaAccount *account, struct AaBalances *balances);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* ACCOUNTS_AGGREGATE_H */
@@ -0,0 +1,222 @@
+//! C ABI of the core engine (`Account` aggregates handling commands) for embedding in other runtimes.
+//!
+//! Domain models are those of the `core` crate shared with the cli so C/C++ (or Java using JNA/Panama) services apply the same
+//! business rules without reimplementing them. Accounts are opaque handles owned by the caller (freed using
+//! `aa_account_free`) and amounts cross the boundary as decimal strings so no precision is lost:
+//!
+//! ```c
+//! #include "accounts_aggregate.h"
+//!
+//! AaAccount *account = aa_account_new(1);
+//! aa_handle_command(account, AA_COMMAND_TYPE_DEPOSIT, 1, "2.5");
+//! if (aa_handle_command(account, AA_COMMAND_TYPE_WITHDRAW, 2, "5.0") == AA_REJECTED) {
+//!     puts(aa_account_reason(account));
+//! }
+//! AaBalances balances;
+//! aa_get_balances(account, &balances);
+//! aa_account_free(account);
+//! ```
+//!
+//! The header (`include/accounts_aggregate.h`) is generated by cbindgen building with the `header` feature.
+
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::ptr;
+use std::str::FromStr;
+
+use rust_decimal::prelude::Decimal;
+
+use accounts_aggregate_core::events::Actor;
+use accounts_aggregate_core::models::{Account, Command, CommandType, Currency};
+
+/// Bytes of decimal strings of balances (including the terminating nul).
+pub const AA_DECIMAL_LEN: usize = 40;
+
+/// Command applied.
+pub const AA_APPLIED: i32 = 0;
+/// Command rejected by business rules (reason returned by `aa_account_reason`).
+pub const AA_REJECTED: i32 = 1;
+/// Pointer supplied is null.
+pub const AA_NULL_POINTER: i32 = -1;
+/// Amount supplied isn't a decimal string.
+pub const AA_INVALID_AMOUNT: i32 = -2;
+
+/// Type of command handled by an account.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AaCommandType {
+    Deposit,
+    Withdraw,
+    Dispute,
+    Resolve,
+    Chargeback,
+}
+
+impl From<AaCommandType> for CommandType {
+    fn from(command_type: AaCommandType) -> Self {
+        match command_type {
+            AaCommandType::Deposit => CommandType::Deposit,
+            AaCommandType::Withdraw => CommandType::Withdraw,
+            AaCommandType::Dispute => CommandType::Dispute,
+            AaCommandType::Resolve => CommandType::Resolve,
+            AaCommandType::Chargeback => CommandType::Chargeback,
+        }
+    }
+}
+
+/// Balances of an account (amounts as nul terminated decimal strings).
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct AaBalances {
+    pub client: u16,
+    pub available: [c_char; AA_DECIMAL_LEN],
+    pub held: [c_char; AA_DECIMAL_LEN],
+    pub total: [c_char; AA_DECIMAL_LEN],
+    pub locked: bool,
+}
+
+/// Account handling commands (opaque to callers).
+pub struct AaAccount {
+    account: Account,
+    /// Reason last command was rejected (empty unless rejected).
+    reason: CString,
+}
+
+/// Returns new account of `client` (freed using `aa_account_free`).
+#[no_mangle]
+pub extern "C" fn aa_account_new(client: u16) -> *mut AaAccount {
+    Box::into_raw(Box::new(AaAccount { account: Account::new(client), reason: CString::default() }))
+}
+
+/// Frees `account` (null is ignored).
+///
+/// # Safety
+///
+/// `account` must be null or returned by `aa_account_new` and not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn aa_account_free(account: *mut AaAccount) {
+    if !account.is_null() {
+        drop(Box::from_raw(account));
+    }
+}
+
+/// Handles command `command_type` of transaction `tx` with `amount` (decimal string, null unless deposit or withdraw)
+/// returning `AA_APPLIED`, `AA_REJECTED` or an error code (negative).
+///
+/// # Safety
+///
+/// `account` must be returned by `aa_account_new` and `amount` null or a nul terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn aa_handle_command(
+    account: *mut AaAccount,
+    command_type: AaCommandType,
+    tx: u32,
+    amount: *const c_char,
+) -> i32 {
+    let account = match account.as_mut() {
+        Some(account) => account,
+        None => return AA_NULL_POINTER,
+    };
+    let amount = match parse_amount(amount) {
+        Ok(amount) => amount,
+        Err(code) => return code,
+    };
+    let command = Command::new(command_type.into(), account.account.client(), tx, amount);
+    match account.account.handle(command) {
+        Ok(events) => {
+            account.account.apply(events);
+            account.reason = CString::default();
+            AA_APPLIED
+        }
+        Err(e) => {
+            // reasons never hold nul bytes
+            account.reason = CString::new(e.to_string()).unwrap_or_default();
+            AA_REJECTED
+        }
+    }
+}
+
+/// Returns reason the last command handled by `account` was rejected (empty unless rejected).
+///
+/// # Safety
+///
+/// `account` must be returned by `aa_account_new`. The string is valid until the next command is handled.
+#[no_mangle]
+pub unsafe extern "C" fn aa_account_reason(account: *const AaAccount) -> *const c_char {
+    match account.as_ref() {
+        Some(account) => account.reason.as_ptr(),
+        None => ptr::null(),
+    }
+}
+
+/// Writes balances of `account` to `balances` returning `AA_APPLIED` (or an error code).
+///
+/// # Safety
+///
+/// `account` must be returned by `aa_account_new` and `balances` point to writable `AaBalances`.
+#[no_mangle]
+pub unsafe extern "C" fn aa_get_balances(account: *const AaAccount, balances: *mut AaBalances) -> i32 {
+    let (account, balances) = match (account.as_ref(), balances.as_mut()) {
+        (Some(account), Some(balances)) => (&account.account, balances),
+        _ => return AA_NULL_POINTER,
+    };
+    *balances = AaBalances {
+        client: account.client(),
+        available: decimal_chars(account.available()),
+        held: decimal_chars(account.held()),
+        total: decimal_chars(account.total()),
+        locked: account.locked(),
+    };
+    AA_APPLIED
+}
+
+/// Returns amount of nul terminated decimal string `amount` (none when null).
+unsafe fn parse_amount(amount: *const c_char) -> Result<Option<Currency>, i32> {
+    if amount.is_null() {
+        return Ok(None);
+    }
+    let amount = CStr::from_ptr(amount).to_str().map_err(|_| { AA_INVALID_AMOUNT })?;
+    Decimal::from_str(amount.trim()).map(Some).map_err(|_| { AA_INVALID_AMOUNT })
+}
+
+/// Returns `amount` as nul terminated decimal string.
+fn decimal_chars(amount: Currency) -> [c_char; AA_DECIMAL_LEN] {
+    let mut chars = [0; AA_DECIMAL_LEN];
+    // decimals are at most 31 characters (28 digits, sign, point and leading zero)
+    for (c, byte) in chars.iter_mut().zip(amount.to_string().bytes().take(AA_DECIMAL_LEN - 1)) {
+        *c = byte as c_char;
+    }
+    chars
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(chars: &[c_char]) -> String {
+        unsafe { CStr::from_ptr(chars.as_ptr()) }.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn commands_handled_through_c_abi() {
+        unsafe {
+            let (deposited, withdrawn, invalid) = (CString::new("2.5").unwrap(), CString::new("5.0").unwrap(), CString::new("2,5").unwrap());
+            let account = aa_account_new(7);
+            assert_eq!(aa_handle_command(account, AaCommandType::Deposit, 1, deposited.as_ptr()), AA_APPLIED);
+            assert_eq!(aa_handle_command(account, AaCommandType::Withdraw, 2, withdrawn.as_ptr()), AA_REJECTED);
+            assert!(!CStr::from_ptr(aa_account_reason(account)).to_bytes().is_empty());
+            assert_eq!(aa_handle_command(account, AaCommandType::Dispute, 1, ptr::null()), AA_APPLIED);
+            assert!(CStr::from_ptr(aa_account_reason(account)).to_bytes().is_empty());
+            assert_eq!(aa_handle_command(account, AaCommandType::Deposit, 3, invalid.as_ptr()), AA_INVALID_AMOUNT);
+            assert_eq!(aa_handle_command(ptr::null_mut(), AaCommandType::Deposit, 3, ptr::null()), AA_NULL_POINTER);
+
+            let mut balances = AaBalances { client: 0, available: [0; AA_DECIMAL_LEN], held: [0; AA_DECIMAL_LEN], total: [0; AA_DECIMAL_LEN], locked: true };
+            assert_eq!(aa_get_balances(account, &mut balances), AA_APPLIED);
+            assert_eq!(
+                (balances.client, string(&balances.available), string(&balances.held), string(&balances.total), balances.locked),
+                (7, "0.0".to_string(), "2.5".to_string(), "2.5".to_string(), false)
+            );
+            aa_account_free(account);
+        }
+    }
+}
@@ -0,0 +1,338 @@
+//! Async (tokio) variant of the processing pipeline (`async` feature).
+//!
+//! Sources are read by `AsyncSourceReader`s (`AsyncCsvReader` and Kafka's `AsyncKafkaReader`) awaiting input rather
+//! than blocking a thread per source. The cli reads sources (`--async`) as tasks of a runtime shared by every source,
+//! handing parsed records to the processing loop through a bounded channel (`AsyncPipelineReader`).
+//!
+//! Readers are internal to the cli (there is no library target), so services can't embed them without vendoring
+//! the module.
+
+use std::future::Future;
+use std::mem;
+use std::panic;
+use std::sync::{Arc, OnceLock};
+use std::time::Instant;
+use std::vec;
+
+use csv::{ByteRecord, Position, StringRecord};
+use csv_core::ReadRecordResult;
+use simple_error::*;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt};
+use tokio::runtime::{Builder, Runtime};
+use tokio::sync::mpsc::{self, Receiver, Sender};
+use tokio::sync::mpsc::error::{TryRecvError, TrySendError};
+use tokio::task::JoinHandle;
+use tracing::Instrument;
+
+use crate::input::SourceReader;
+use crate::models::Command;
+use crate::metrics::QueueMetrics;
+use crate::pipeline;
+
+/// Worker threads of the runtime shared by async sources of the cli.
+const WORKER_THREADS: usize = 2;
+
+/// Initial capacity (bytes) of fields of a csv record.
+const FIELDS_CAPACITY: usize = 256;
+
+/// Initial capacity of field ends of a csv record.
+const ENDS_CAPACITY: usize = 16;
+
+/// Reader of transaction sources yielding `Command`s (or errors of unparseable records) once read.
+pub trait AsyncSourceReader: Send {
+    /// Returns line (or record number) in source of last record read.
+    fn line(&self) -> usize;
+
+    /// Returns next command read (none once source is read).
+    fn next(&mut self) -> impl Future<Output = Option<Result<Command, SimpleError>>> + Send;
+}
+
+/// Reads records of a csv source as `Command`s (as lenient as `input::csv_reader`).
+///
+/// Like `CsvCommandReader`, records are read into a single `ByteRecord` reused for every record.
+pub struct AsyncCsvReader<R> {
+    source: R,
+    parser: csv_core::Reader,
+    fields: Vec<u8>,
+    ends: Vec<usize>,
+    position: Position,
+    record: ByteRecord,
+    headers: StringRecord,
+    byte_headers: ByteRecord,
+    line: usize,
+    done: bool,
+}
+
+impl<R: AsyncBufRead + Unpin + Send> AsyncCsvReader<R> {
+    /// Returns new `AsyncCsvReader` reading records of `source` using field `delimiter` (headers read).
+    pub async fn new(source: R, delimiter: u8) -> Result<Self, SimpleError> {
+        let mut position = Position::new();
+        position.set_line(1);
+        let mut reader = AsyncCsvReader {
+            source,
+            parser: csv_core::ReaderBuilder::new().delimiter(delimiter).build(),
+            fields: vec![0; FIELDS_CAPACITY],
+            ends: vec![0; ENDS_CAPACITY],
+            position,
+            record: ByteRecord::new(),
+            headers: StringRecord::new(),
+            byte_headers: ByteRecord::new(),
+            line: 1,
+            done: false,
+        };
+        if reader.read_record().await? {
+            let headers = try_with!(StringRecord::from_byte_record(reader.record.clone()), "invalid utf-8 in headers");
+            reader.headers = headers.iter()
+                .map(|header| { header.trim_start_matches('\u{feff}').to_lowercase() })
+                .collect();
+            reader.byte_headers = reader.headers.as_byte_record().clone();
+        }
+        Ok(reader)
+    }
+
+    /// Returns lowercased headers of source.
+    pub fn headers(&self) -> &StringRecord {
+        &self.headers
+    }
+
+    /// Reads next record (fields trimmed) of source into `record` returning false once read.
+    async fn read_record(&mut self) -> Result<bool, SimpleError> {
+        if self.done {
+            return Ok(false);
+        }
+        let position = self.position.clone();
+        let (mut fields, mut ends) = (0, 0);
+        loop {
+            let input = match self.source.fill_buf().await {
+                Ok(input) => input,
+                Err(e) => {
+                    // sources are not read past errors reading them
+                    self.done = true;
+                    bail!("unable to read csv source, {}", e);
+                }
+            };
+            let (result, read, written, ended) = self.parser.read_record(
+                input,
+                &mut self.fields[fields..],
+                &mut self.ends[ends..]
+            );
+            self.source.consume(read);
+            let byte = self.position.byte();
+            self.position.set_byte(byte + read as u64).set_line(self.parser.line());
+            fields += written;
+            ends += ended;
+            match result {
+                ReadRecordResult::InputEmpty => {}
+                ReadRecordResult::OutputFull => self.fields.resize(self.fields.len() * 2, 0),
+                ReadRecordResult::OutputEndsFull => self.ends.resize(self.ends.len() * 2, 0),
+                ReadRecordResult::Record => break,
+                ReadRecordResult::End => {
+                    self.done = true;
+                    return Ok(false);
+                }
+            }
+        }
+        self.line = position.line() as usize;
+        let record = self.position.record();
+        self.position.set_record(record + 1);
+        self.record.clear();
+        let mut start = 0;
+        for end in self.ends[..ends].iter() {
+            self.record.push_field(&self.fields[start..*end]);
+            start = *end;
+        }
+        self.record.set_position(Some(position));
+        self.record.trim();
+        Ok(true)
+    }
+}
+
+impl<R: AsyncBufRead + Unpin + Send> AsyncSourceReader for AsyncCsvReader<R> {
+    fn line(&self) -> usize {
+        self.line
+    }
+
+    async fn next(&mut self) -> Option<Result<Command, SimpleError>> {
+        let command = match self.read_record().await {
+            Ok(false) => return None,
+            Ok(true) => self.record.deserialize(Some(&self.byte_headers)).map_err(|e| { SimpleError::new(e.to_string()) }),
+            Err(e) => Err(e),
+        };
+        Some(command)
+    }
+}
+
+/// Returns runtime (shared by every async source of the cli) running tasks on worker threads.
+pub fn runtime() -> &'static Runtime {
+    static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+    RUNTIME.get_or_init(|| {
+        Builder::new_multi_thread()
+            .worker_threads(WORKER_THREADS)
+            .thread_name("async-source")
+            .enable_all()
+            .build()
+            .expect("unable to build async runtime")
+    })
+}
+
+/// Command (or error of unparseable record) and line of source record.
+type Parsed = (Result<Command, SimpleError>, usize);
+
+/// Reads records of an `AsyncSourceReader` read by a task of the shared runtime.
+pub struct AsyncPipelineReader {
+    batches: Receiver<Vec<Parsed>>,
+    batch: vec::IntoIter<Parsed>,
+    line: usize,
+    task: Option<JoinHandle<()>>,
+    queue: Arc<QueueMetrics>,
+}
+
+impl AsyncPipelineReader {
+    /// Returns new `AsyncPipelineReader` of `reader` (read by a new task) queueing records up to capacity of `queue`.
+    pub fn spawn<S: AsyncSourceReader + 'static>(mut reader: S, queue: Arc<QueueMetrics>) -> Self {
+        let (records, batches) = pipeline::batches(queue.capacity());
+        let (sender, receiver) = mpsc::channel(batches);
+        let metrics = queue.clone();
+        let task = runtime().spawn(async move {
+            let mut batch = Vec::with_capacity(records);
+            while let Some(result) = reader.next().await {
+                batch.push((result, reader.line()));
+                if batch.len() == records {
+                    // processing loop stopped reading (reader dropped)
+                    if !send(&sender, mem::replace(&mut batch, Vec::with_capacity(records)), &metrics).await {
+                        return;
+                    }
+                }
+            }
+            if !batch.is_empty() {
+                send(&sender, batch, &metrics).await;
+            }
+        // records are parsed within the span of the stage reading them
+        }.instrument(tracing::info_span!("parse")));
+        AsyncPipelineReader { batches: receiver, batch: vec![].into_iter(), line: 0, task: Some(task), queue }
+    }
+}
+
+impl SourceReader for AsyncPipelineReader {
+    fn line(&self) -> usize {
+        self.line
+    }
+}
+
+impl Iterator for AsyncPipelineReader {
+    type Item = Result<Command, SimpleError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((result, line)) = self.batch.next() {
+                self.line = line;
+                self.queue.received(1);
+                return Some(result);
+            }
+            let received = match self.batches.try_recv() {
+                Err(TryRecvError::Empty) => {
+                    let started = Instant::now();
+                    let received = self.batches.blocking_recv();
+                    self.queue.waited(started);
+                    received
+                }
+                received => received.ok(),
+            };
+            match received {
+                Some(batch) => self.batch = batch.into_iter(),
+                None => {
+                    self.queue.close();
+                    // panics of reading task are raised by the processing loop
+                    if let Some(Err(e)) = self.task.take().map(|task| { runtime().block_on(task) }) {
+                        if e.is_panic() {
+                            panic::resume_unwind(e.into_panic());
+                        }
+                    }
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+/// Sends `batch` to `sender` awaiting while the queue is full (returns false once receiver is dropped).
+async fn send(sender: &Sender<Vec<Parsed>>, batch: Vec<Parsed>, queue: &QueueMetrics) -> bool {
+    let records = batch.len();
+    let sent = match sender.try_send(batch) {
+        Err(TrySendError::Full(batch)) => {
+            let started = Instant::now();
+            let sent = sender.send(batch).await.is_ok();
+            queue.blocked(started);
+            sent
+        }
+        result => result.is_ok(),
+    };
+    if sent {
+        queue.sent(records);
+    }
+    sent
+}
+
+/// Reads records of an `AsyncSourceReader` blocking on the shared runtime (each record read once the previous record
+/// is processed, e.g. messages acknowledged once handled).
+#[cfg(feature = "kafka")]
+pub struct BlockingReader<S> {
+    reader: S,
+}
+
+#[cfg(feature = "kafka")]
+impl<S: AsyncSourceReader> BlockingReader<S> {
+    /// Returns new `BlockingReader` of `reader`.
+    pub fn new(reader: S) -> Self {
+        BlockingReader { reader }
+    }
+}
+
+#[cfg(feature = "kafka")]
+impl<S: AsyncSourceReader> SourceReader for BlockingReader<S> {
+    fn line(&self) -> usize {
+        self.reader.line()
+    }
+}
+
+#[cfg(feature = "kafka")]
+impl<S: AsyncSourceReader> Iterator for BlockingReader<S> {
+    type Item = Result<Command, SimpleError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        runtime().block_on(self.reader.next())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    use crate::input::{CsvCommandReader, csv_reader};
+
+    const CSV: &str = "\u{feff}Type, Client, TX, Amount\n\
+        deposit, 1, 1, 10.0\n\
+        \"withdraw\",1,2,\"2.5\"\n\
+        unknown,1,3,1.0\n\
+        withdraw,2,4,1.0\n\
+        dispute,1,1\n";
+
+    #[test]
+    fn records_read_as_csv_reader() {
+        let runtime = Builder::new_current_thread().build().unwrap();
+        let mut reader = runtime.block_on(AsyncCsvReader::new(CSV.as_bytes(), b',')).unwrap();
+        assert_eq!(reader.headers(), &StringRecord::from(vec!["type", "client", "tx", "amount"]));
+        let mut expected = CsvCommandReader::new(csv_reader(Cursor::new(CSV), b',').unwrap()).unwrap();
+        while let Some(result) = runtime.block_on(reader.next()) {
+            let other = expected.next().unwrap();
+            assert_eq!(reader.line(), expected.line());
+            match (result, other) {
+                (Ok(command), Ok(other)) => assert_eq!(command, other),
+                (Err(e), Err(other)) => assert_eq!(e.to_string(), other.to_string()),
+                (result, other) => panic!("{:?} read as {:?}", other, result),
+            }
+        }
+        assert!(expected.next().is_none());
+    }
+}
@@ -0,0 +1,159 @@
+//! Audit trail of applied `Account` events exported as JSON Lines.
+//!
+//! Records flatten events into readable columns (event name, UUID idempotency key and decimal amounts)
+//! with fields not applicable to an event being null.
+
+use std::io::Read;
+
+use serde::{Serialize, Deserialize};
+use simple_error::*;
+use uuid::Uuid;
+
+use crate::events::Effect;
+use crate::models::{Category, ClientId, Currency, Event, MerchantId, Timestamp, TransactionId, Version, WalletId};
+
+/// Row of the audit trail of an event applied to `client` account.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AuditRecord {
+    client: ClientId,
+    event: String,
+    version: Version,
+    key: Uuid,
+    timestamp: Option<Timestamp>,
+    tx: Option<TransactionId>,
+    wallet: Option<WalletId>,
+    merchant: Option<MerchantId>,
+    category: Option<Category>,
+    amount: Option<Currency>,
+}
+
+impl AuditRecord {
+    /// Returns new `AuditRecord` of `event` applied to `client` account.
+    pub fn from_event(client: ClientId, event: &Event) -> Self {
+        let mut record = AuditRecord {
+            client,
+            event: event.name().to_string(),
+            version: event.version(),
+            key: Uuid::from_bytes(event.idempotency_key()),
+            timestamp: None,
+            tx: None,
+            wallet: None,
+            merchant: None,
+            category: None,
+            amount: None,
+        };
+        match event {
+            Event::Credited { timestamp, tx, wallet, merchant, category, amount, .. } |
+            Event::Debited { timestamp, tx, wallet, merchant, category, amount, .. } => {
+                record.timestamp = *timestamp;
+                record.tx = Some(*tx);
+                record.wallet = Some(wallet.clone());
+                record.merchant = *merchant;
+                record.category = category.clone();
+                record.amount = Some(*amount);
+            }
+            Event::Held { timestamp, tx, wallet, amount, .. } |
+            Event::Released { timestamp, tx, wallet, amount, .. } => {
+                record.timestamp = *timestamp;
+                record.tx = Some(*tx);
+                record.wallet = Some(wallet.clone());
+                record.amount = Some(*amount);
+            }
+            Event::Reversed { timestamp, tx, wallet, merchant, amount, .. } => {
+                record.timestamp = *timestamp;
+                record.tx = Some(*tx);
+                record.wallet = Some(wallet.clone());
+                record.merchant = *merchant;
+                record.amount = Some(*amount);
+            }
+            Event::Locked { timestamp, .. } => record.timestamp = *timestamp,
+        }
+        record
+    }
+
+    /// Returns client and event of record (inverse of `from_event`).
+    pub fn into_event(self) -> Result<(ClientId, Event), SimpleError> {
+        let version = self.version;
+        let key = *self.key.as_bytes();
+        let timestamp = self.timestamp;
+        let missing = |field: &str| {
+            SimpleError::new(format!("{} is none for {} account({}) event({})", field, self.event, self.client, self.key))
+        };
+        let tx = self.tx.ok_or_else(|| { missing("tx") });
+        let wallet = self.wallet.clone().ok_or_else(|| { missing("wallet") });
+        let amount = self.amount.ok_or_else(|| { missing("amount") });
+        let event = match self.event.as_str() {
+            "credited" => Event::Credited {
+                version, key, timestamp, tx: tx?, wallet: wallet?, merchant: self.merchant, category: self.category, amount: amount?
+            },
+            "debited" => Event::Debited {
+                version, key, timestamp, tx: tx?, wallet: wallet?, merchant: self.merchant, category: self.category, amount: amount?
+            },
+            "held" => Event::Held { version, key, timestamp, tx: tx?, wallet: wallet?, amount: amount? },
+            "released" => Event::Released { version, key, timestamp, tx: tx?, wallet: wallet?, amount: amount? },
+            "reversed" => Event::Reversed { version, key, timestamp, tx: tx?, wallet: wallet?, merchant: self.merchant, amount: amount? },
+            "locked" => Event::Locked { version, key, timestamp },
+            name => bail!("unsupported event({}) of account({})", name, self.client),
+        };
+        Ok((self.client, event))
+    }
+}
+
+/// Returns client and event of each audit record read from JSON Lines `reader`.
+pub fn read_events<R: Read>(reader: R) -> impl Iterator<Item = Result<(ClientId, Event), SimpleError>> {
+    serde_json::Deserializer::from_reader(reader)
+        .into_iter::<AuditRecord>()
+        .map(|result| {
+            let record = try_with!(result, "invalid audit record");
+            record.into_event()
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::prelude::Decimal;
+
+    #[test]
+    fn audit_records_read_as_events() {
+        let events = [
+            Event::Credited {
+                version: 1,
+                key: [1; 16],
+                timestamp: None,
+                tx: 10,
+                wallet: String::from("main"),
+                merchant: Some(7),
+                category: Some(String::from("groceries")),
+                amount: Decimal::new(990000, 4)
+            },
+            Event::Locked { version: 1, key: [2; 16], timestamp: None },
+        ];
+        let trail: String = events.iter()
+            .map(|event| { serde_json::to_string(&AuditRecord::from_event(3, event)).unwrap() + "\n" })
+            .collect();
+
+        let read: Vec<(ClientId, Event)> = read_events(trail.as_bytes()).map(|result| { result.unwrap() }).collect();
+
+        assert_eq!(read, vec![(3, events[0].clone()), (3, events[1].clone())]);
+    }
+
+    #[test]
+    fn audit_record_serialized() {
+        let event = Event::Held {
+            version: 2,
+            key: [1; 16],
+            timestamp: None,
+            tx: 10,
+            wallet: String::from("main"),
+            amount: Decimal::new(990000, 4)
+        };
+        let record = serde_json::to_string(&AuditRecord::from_event(1, &event)).unwrap();
+
+        assert_eq!(
+            record,
+            "{\"client\":1,\"event\":\"held\",\"version\":2,\"key\":\"01010101-0101-0101-0101-010101010101\",\
+            \"timestamp\":null,\"tx\":10,\"wallet\":\"main\",\"merchant\":null,\"category\":null,\"amount\":\"99.0000\"}"
+        );
+    }
+}
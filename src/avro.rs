@@ -0,0 +1,460 @@
+//! Avro (de)serialization of `Command` and `Event` records for interop with Kafka ecosystems.
+//!
+//! Records are converted using their serde representation and written using the Avro binary encoding of
+//! `COMMAND_SCHEMA` or `produce::EVENT_SCHEMA` (see `schemas` directory). Union branches of records map to externally
+//! tagged enums (e.g. `{"Credited": {..}}`) matching the serde representation of `Event`.
+//!
+//! Payloads can be framed using the schema registry wire format (magic byte followed by big-endian schema id).
+
+use std::collections::HashMap;
+
+use chrono::{SecondsFormat, TimeZone, Utc};
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Number, Value};
+use simple_error::*;
+
+/// Avro schema of `Command` records.
+pub const COMMAND_SCHEMA: &str = include_str!("../schemas/command.avsc");
+
+/// First byte of payloads framed using the schema registry wire format.
+const MAGIC_BYTE: u8 = 0;
+
+/// Avro schema types used by `COMMAND_SCHEMA` and `produce::EVENT_SCHEMA`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Schema {
+    Null,
+    Boolean,
+    Int,
+    Long,
+    String,
+    TimestampMicros,
+    Fixed { name: String, size: usize },
+    Enum { name: String, symbols: Vec<String> },
+    Record { name: String, fields: Vec<(String, Schema)> },
+    Union(Vec<Schema>),
+}
+
+impl Schema {
+    /// Returns `Schema` parsed from Avro schema `json`.
+    pub fn parse(json: &str) -> Result<Self, SimpleError> {
+        let value: Value = try_with!(serde_json::from_str(json), "invalid avro schema");
+        Schema::from_value(&value, &mut HashMap::new())
+    }
+
+    /// Returns `Schema` of `value` registering named types in `names` (referenced by later types).
+    fn from_value(value: &Value, names: &mut HashMap<String, Schema>) -> Result<Self, SimpleError> {
+        let schema = match value {
+            Value::String(name) => match name.as_str() {
+                "null" => Schema::Null,
+                "boolean" => Schema::Boolean,
+                "int" => Schema::Int,
+                "long" => Schema::Long,
+                "string" => Schema::String,
+                _ => match names.get(short_name(name)) {
+                    Some(schema) => schema.clone(),
+                    None => bail!("unsupported avro type({})", name),
+                },
+            },
+            Value::Array(branches) => Schema::Union(
+                branches.iter()
+                    .map(|branch| { Schema::from_value(branch, names) })
+                    .collect::<Result<_, _>>()?
+            ),
+            Value::Object(object) => {
+                let kind = object.get("type").unwrap_or(&Value::Null);
+                let name = object.get("name").and_then(Value::as_str).map(short_name);
+                let schema = match (kind.as_str(), name) {
+                    (Some("long"), _) if object.get("logicalType").and_then(Value::as_str) == Some("timestamp-micros") => {
+                        Schema::TimestampMicros
+                    }
+                    (Some("fixed"), Some(name)) => Schema::Fixed {
+                        name: name.to_string(),
+                        size: require_field(object, "size")?.as_u64().unwrap_or(0) as usize
+                    },
+                    (Some("enum"), Some(name)) => Schema::Enum {
+                        name: name.to_string(),
+                        symbols: require_field(object, "symbols")?.as_array()
+                            .map(|symbols| { symbols.iter().filter_map(Value::as_str).map(String::from).collect() })
+                            .unwrap_or_default()
+                    },
+                    (Some("record"), Some(name)) => {
+                        let mut fields = vec![];
+                        for field in require_field(object, "fields")?.as_array().unwrap_or(&vec![]) {
+                            let field_name = match field.get("name").and_then(Value::as_str) {
+                                Some(field_name) => field_name,
+                                None => bail!("avro record({}) field name is none", name),
+                            };
+                            let field_schema = match field.get("type") {
+                                Some(field_type) => Schema::from_value(field_type, names)?,
+                                None => bail!("avro record({}) field({}) type is none", name, field_name),
+                            };
+                            fields.push((field_name.to_string(), field_schema));
+                        }
+                        Schema::Record { name: name.to_string(), fields }
+                    }
+                    _ => Schema::from_value(kind, names)?,
+                };
+                if let Some(name) = name {
+                    names.insert(name.to_string(), schema.clone());
+                }
+                schema
+            }
+            _ => bail!("unsupported avro schema({})", value),
+        };
+        Ok(schema)
+    }
+}
+
+/// Returns name without namespace.
+fn short_name(name: &str) -> &str {
+    name.rsplit('.').next().unwrap_or(name)
+}
+
+/// Returns `field` of schema `object` or error when missing.
+fn require_field<'a>(object: &'a Map<String, Value>, field: &str) -> Result<&'a Value, SimpleError> {
+    match object.get(field) {
+        Some(value) => Ok(value),
+        None => bail!("avro schema({}) is missing {}", object.get("name").unwrap_or(&Value::Null), field),
+    }
+}
+
+/// Returns record decoded from Avro binary `bytes` using `schema`.
+pub fn from_avro<T: DeserializeOwned>(schema: &Schema, bytes: &[u8]) -> Result<T, SimpleError> {
+    let mut reader = Reader { bytes, position: 0 };
+    let value = read_value(schema, &mut reader)?;
+    if reader.position != bytes.len() {
+        bail!("avro payload has {} trailing bytes", bytes.len() - reader.position);
+    }
+    Ok(try_with!(serde_json::from_value(value), "avro record does not match type"))
+}
+
+/// Returns schema id and payload of `frame` using the schema registry wire format.
+pub fn from_registry_frame(frame: &[u8]) -> Result<(u32, &[u8]), SimpleError> {
+    if frame.len() < 5 || frame[0] != MAGIC_BYTE {
+        bail!("avro payload is not framed with a schema id");
+    }
+    let schema_id = u32::from_be_bytes([frame[1], frame[2], frame[3], frame[4]]);
+    Ok((schema_id, &frame[5..]))
+}
+
+/// Cursor over Avro binary encoded bytes.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    /// Returns next `length` bytes.
+    fn take(&mut self, length: usize) -> Result<&'a [u8], SimpleError> {
+        if self.bytes.len() - self.position < length {
+            bail!("avro payload ended after {} bytes", self.bytes.len());
+        }
+        let bytes = &self.bytes[self.position..self.position + length];
+        self.position += length;
+        Ok(bytes)
+    }
+
+    /// Returns next zig-zag variable length encoded long.
+    fn long(&mut self) -> Result<i64, SimpleError> {
+        let mut n: u64 = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = self.take(1)?[0];
+            n |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok((n >> 1) as i64 ^ -((n & 1) as i64));
+            }
+        }
+        bail!("avro long exceeds 64 bits")
+    }
+
+    /// Returns next length prefixed string.
+    fn string(&mut self) -> Result<String, SimpleError> {
+        let length = self.long()?;
+        if length < 0 {
+            bail!("avro string length is negative({})", length);
+        }
+        let bytes = self.take(length as usize)?;
+        Ok(try_with!(String::from_utf8(bytes.to_vec()), "avro string is not utf-8"))
+    }
+}
+
+/// Returns value read from `reader` using `schema`.
+fn read_value(schema: &Schema, reader: &mut Reader) -> Result<Value, SimpleError> {
+    let value = match schema {
+        Schema::Null => Value::Null,
+        Schema::Boolean => Value::Bool(reader.take(1)?[0] != 0),
+        Schema::Int | Schema::Long => Value::Number(Number::from(reader.long()?)),
+        Schema::String => Value::String(reader.string()?),
+        Schema::TimestampMicros => {
+            let micros = reader.long()?;
+            let timestamp = Utc.timestamp_opt(micros.div_euclid(1_000_000), (micros.rem_euclid(1_000_000) * 1_000) as u32);
+            match timestamp.single() {
+                Some(timestamp) => Value::String(timestamp.to_rfc3339_opts(SecondsFormat::AutoSi, true)),
+                None => bail!("avro timestamp out of range({})", micros),
+            }
+        }
+        Schema::Fixed { size, .. } => Value::Array(
+            reader.take(*size)?.iter().map(|b| { Value::from(*b) }).collect()
+        ),
+        Schema::Enum { name, symbols } => {
+            let index = reader.long()?;
+            match symbols.get(index as usize) {
+                Some(symbol) => Value::String(symbol.clone()),
+                None => bail!("avro enum({}) has no symbol index({})", name, index),
+            }
+        }
+        Schema::Record { fields, .. } => {
+            let mut object = Map::new();
+            for (name, field) in fields {
+                object.insert(name.clone(), read_value(field, reader)?);
+            }
+            Value::Object(object)
+        }
+        Schema::Union(branches) => {
+            let index = reader.long()?;
+            match branches.get(index as usize) {
+                Some(branch @ Schema::Record { name, .. }) => {
+                    let mut object = Map::new();
+                    object.insert(name.clone(), read_value(branch, reader)?);
+                    Value::Object(object)
+                }
+                Some(branch) => read_value(branch, reader)?,
+                None => bail!("avro union has no branch index({})", index),
+            }
+        }
+    };
+    Ok(value)
+}
+
+/// Encoding of records produced for Kafka ecosystems (`Event` schema, Avro binary encoding and registry frames).
+///
+/// Sources of the cli only decode command messages, so encoding is used by tests (and producers) alone.
+#[cfg_attr(not(test), allow(dead_code))]
+pub mod produce {
+    use std::convert::TryFrom;
+
+    use chrono::DateTime;
+    use serde::Serialize;
+    use serde_json::Value;
+    use simple_error::*;
+
+    use super::{Schema, MAGIC_BYTE};
+
+    /// Avro schema of `Event` records (union of event types).
+    pub const EVENT_SCHEMA: &str = include_str!("../schemas/event.avsc");
+
+    /// Returns Avro binary encoding of `record` using `schema`.
+    pub fn to_avro<T: Serialize>(schema: &Schema, record: &T) -> Result<Vec<u8>, SimpleError> {
+        let value = try_with!(serde_json::to_value(record), "record cannot be represented as avro");
+        let mut buffer = vec![];
+        write_value(schema, &value, &mut buffer)?;
+        Ok(buffer)
+    }
+
+    /// Returns `payload` framed using the schema registry wire format for `schema_id`.
+    pub fn to_registry_frame(schema_id: u32, payload: &[u8]) -> Vec<u8> {
+        let mut frame = Vec::with_capacity(payload.len() + 5);
+        frame.push(MAGIC_BYTE);
+        frame.extend_from_slice(&schema_id.to_be_bytes());
+        frame.extend_from_slice(payload);
+        frame
+    }
+
+    /// Writes `value` to `buffer` using `schema`.
+    fn write_value(schema: &Schema, value: &Value, buffer: &mut Vec<u8>) -> Result<(), SimpleError> {
+        match (schema, value) {
+            (Schema::Null, Value::Null) => {}
+            (Schema::Boolean, Value::Bool(b)) => buffer.push(*b as u8),
+            (Schema::Int, Value::Number(n)) => match n.as_i64().filter(|n| { i32::try_from(*n).is_ok() }) {
+                Some(n) => write_long(n, buffer),
+                None => bail!("avro int out of range({})", n),
+            },
+            (Schema::Long, Value::Number(n)) => match n.as_i64() {
+                Some(n) => write_long(n, buffer),
+                None => bail!("avro long out of range({})", n),
+            },
+            (Schema::String, Value::String(s)) => write_bytes(s.as_bytes(), buffer),
+            (Schema::TimestampMicros, Value::String(s)) => {
+                let timestamp = try_with!(DateTime::parse_from_rfc3339(s), "invalid timestamp({})", s);
+                write_long(timestamp.timestamp() * 1_000_000 + timestamp.timestamp_subsec_micros() as i64, buffer);
+            }
+            (Schema::Fixed { name, size }, Value::Array(bytes)) => {
+                let bytes: Vec<u8> = bytes.iter()
+                    .filter_map(|b| { b.as_u64().and_then(|b| { u8::try_from(b).ok() }) })
+                    .collect();
+                if bytes.len() != *size {
+                    bail!("avro fixed({}) requires {} bytes", name, size);
+                }
+                buffer.extend_from_slice(&bytes);
+            }
+            (Schema::Enum { name, symbols }, Value::String(s)) => match symbols.iter().position(|symbol| { symbol == s }) {
+                Some(index) => write_long(index as i64, buffer),
+                None => bail!("avro enum({}) has no symbol({})", name, s),
+            },
+            (Schema::Record { fields, .. }, Value::Object(object)) => {
+                for (name, field) in fields {
+                    write_value(field, object.get(name).unwrap_or(&Value::Null), buffer)?;
+                }
+            }
+            (Schema::Union(branches), value) => {
+                let (index, branch, value) = match select_branch(branches, value) {
+                    Some(selected) => selected,
+                    None => bail!("avro union has no branch for value({})", value),
+                };
+                write_long(index as i64, buffer);
+                write_value(branch, value, buffer)?;
+            }
+            _ => bail!("avro value({}) does not match schema({:?})", value, schema),
+        }
+        Ok(())
+    }
+
+    /// Returns index, schema and value of union branch used to write `value`.
+    fn select_branch<'a>(branches: &'a [Schema], value: &'a Value) -> Option<(usize, &'a Schema, &'a Value)> {
+        if let Some((tag, inner)) = value.as_object().filter(|object| { object.len() == 1 }).and_then(|object| { object.iter().next() }) {
+            let index = branches.iter().position(|branch| {
+                matches!(branch, Schema::Record { name, .. } if name == tag)
+            });
+            if let Some(index) = index {
+                return Some((index, &branches[index], inner));
+            }
+        }
+        branches.iter()
+            .position(|branch| { accepts(branch, value) })
+            .map(|index| { (index, &branches[index], value) })
+    }
+
+    /// Writes zig-zag variable length encoded `n` to `buffer`.
+    pub(super) fn write_long(n: i64, buffer: &mut Vec<u8>) {
+        let mut n = ((n << 1) ^ (n >> 63)) as u64;
+        while n > 0x7f {
+            buffer.push((n & 0x7f) as u8 | 0x80);
+            n >>= 7;
+        }
+        buffer.push(n as u8);
+    }
+
+    /// Writes length prefixed `bytes` to `buffer`.
+    fn write_bytes(bytes: &[u8], buffer: &mut Vec<u8>) {
+        write_long(bytes.len() as i64, buffer);
+        buffer.extend_from_slice(bytes);
+    }
+
+    /// Returns true when `value` can be written using schema (as a union branch).
+    ///
+    /// Record branches are only selected using externally tagged values.
+    fn accepts(schema: &Schema, value: &Value) -> bool {
+        matches!(
+            (schema, value),
+            (Schema::Null, Value::Null) |
+            (Schema::Boolean, Value::Bool(_)) |
+            (Schema::Int, Value::Number(_)) |
+            (Schema::Long, Value::Number(_)) |
+            (Schema::String, Value::String(_)) |
+            (Schema::TimestampMicros, Value::String(_)) |
+            (Schema::Enum {..}, Value::String(_)) |
+            (Schema::Fixed {..}, Value::Array(_))
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::produce::*;
+    use rust_decimal::prelude::Decimal;
+
+    use crate::models::{Command, Event};
+
+    /// Binary encoding of `Command` deposit (client 1, tx 10, amount "99.0000", merchant 7, 2024-01-31T10:00:00Z).
+    ///
+    /// Bytes follow the binary encoding of the Avro specification (1.11) for `schemas/command.avsc`.
+    const COMMAND_BYTES: &[u8] = &[
+        0x00, // type: enum index 0 (deposit)
+        0x02, // client: int 1
+        0x14, // tx: long 10
+        0x02, 0x0e, b'9', b'9', b'.', b'0', b'0', b'0', b'0', // amount: union index 1 (string) of length 7
+        0x00, // wallet: union index 0 (null)
+        0x02, 0x0e, // merchant: union index 1 (int) 7
+        0x02, 0x80, 0xa0, 0x96, 0x92, 0xde, 0x8e, 0x88, 0x06, // timestamp: union index 1 (micros 1706695200000000)
+        0x00, // category: union index 0 (null)
+    ];
+
+    /// Binary encoding of `Event::Locked` (version 4, key of 1s, no timestamp) for `schemas/event.avsc`.
+    const LOCKED_BYTES: &[u8] = &[
+        0x0a, // union index 5 (Locked)
+        0x08, // version: long 4
+        0x01, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01, // key: fixed(16)
+        0x00, // timestamp: union index 0 (null)
+    ];
+
+    /// Binary encoding of `Event::Credited` (version 1, key of 7s, tx 10, wallet "main", amount "99.0000").
+    const CREDITED_BYTES: &[u8] = &[
+        0x00, // union index 0 (Credited)
+        0x02, // version: long 1
+        0x07, 0x07, 0x07, 0x07, 0x07, 0x07, 0x07, 0x07, 0x07, 0x07, 0x07, 0x07, 0x07, 0x07, 0x07, 0x07, // key: fixed(16)
+        0x02, 0xc0, 0xa4, 0xd3, 0x92, 0xde, 0x8e, 0x88, 0x06, // timestamp: union index 1 (micros 1706695200500000)
+        0x14, // tx: long 10
+        0x08, b'm', b'a', b'i', b'n', // wallet: string of length 4
+        0x02, 0x0e, // merchant: union index 1 (int) 7
+        0x00, // category: union index 0 (null)
+        0x0e, b'9', b'9', b'.', b'0', b'0', b'0', b'0', // amount: string of length 7
+    ];
+
+    #[test]
+    fn longs_zig_zag_encoded() {
+        let encoded = |n| { let mut buffer = vec![]; write_long(n, &mut buffer); buffer };
+
+        assert_eq!(encoded(0), vec![0x00]);
+        assert_eq!(encoded(-1), vec![0x01]);
+        assert_eq!(encoded(1), vec![0x02]);
+        assert_eq!(encoded(-64), vec![0x7f]);
+        assert_eq!(encoded(64), vec![0x80, 0x01]);
+        for n in [0, -1, 1, 64, i64::MIN, i64::MAX] {
+            assert_eq!(Reader { bytes: &encoded(n), position: 0 }.long().unwrap(), n);
+        }
+    }
+
+    #[test]
+    fn command_matches_specified_encoding() {
+        let schema = Schema::parse(COMMAND_SCHEMA).unwrap();
+        let command: Command = serde_json::from_str(
+            r#"{"type":"deposit","client":1,"tx":10,"amount":"99.0000","merchant":7,"timestamp":"2024-01-31T10:00:00Z"}"#
+        ).unwrap();
+
+        assert_eq!(to_avro(&schema, &command).unwrap(), COMMAND_BYTES);
+        assert_eq!(from_avro::<Command>(&schema, COMMAND_BYTES).unwrap(), command);
+    }
+
+    #[test]
+    fn events_match_specified_encoding() {
+        let schema = Schema::parse(EVENT_SCHEMA).unwrap();
+        let events = [
+            (Event::Credited {
+                version: 1,
+                key: [7; 16],
+                timestamp: Some(Utc.timestamp_opt(1706695200, 500_000_000).unwrap()),
+                tx: 10,
+                wallet: String::from("main"),
+                merchant: Some(7),
+                category: None,
+                amount: Decimal::new(990000, 4)
+            }, CREDITED_BYTES),
+            (Event::Locked { version: 4, key: [1; 16], timestamp: None }, LOCKED_BYTES),
+        ];
+
+        for (event, bytes) in events {
+            assert_eq!(to_avro(&schema, &event).unwrap(), bytes);
+            assert_eq!(from_avro::<Event>(&schema, bytes).unwrap(), event);
+        }
+    }
+
+    #[test]
+    fn registry_frame_round_trip() {
+        let frame = to_registry_frame(42, &[1, 2, 3]);
+
+        assert_eq!(frame, vec![0, 0, 0, 0, 42, 1, 2, 3]);
+        assert_eq!(from_registry_frame(&frame).unwrap(), (42, &[1u8, 2, 3][..]));
+        assert!(from_registry_frame(&[1, 0, 0, 0, 42]).is_err());
+    }
+}
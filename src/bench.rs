@@ -0,0 +1,168 @@
+//! Built-in benchmark (`bench` subcommand) of processing transactions generated in memory.
+//!
+//! Transactions of `clients` accounts (deposits, withdrawals, disputes later resolved and a few chargebacks) are
+//! generated as a csv source then parsed, handled and written in stages timed separately, so performance regressions
+//! are measurable without external scripts or data files.
+
+use std::fmt;
+use std::fs;
+use std::io::Write;
+use std::time::Duration;
+
+use crate::models::ClientId;
+
+/// Timed stage of a benchmark.
+struct Stage {
+    name: &'static str,
+    rows: usize,
+    elapsed: Duration,
+}
+
+/// Stage timings and peak memory of a benchmark run (generating transactions excluded from throughput).
+pub struct BenchReport {
+    transactions: usize,
+    clients: ClientId,
+    generated: Duration,
+    rejected: usize,
+    stages: Vec<Stage>,
+    peak_rss: Option<u64>,
+}
+
+impl BenchReport {
+    /// Returns new `BenchReport` of `transactions` generated for `clients` accounts.
+    pub fn new(transactions: usize, clients: ClientId) -> Self {
+        BenchReport { transactions, clients, generated: Duration::default(), rejected: 0, stages: vec![], peak_rss: None }
+    }
+
+    /// Sets time taken to generate transactions.
+    pub fn generated(&mut self, elapsed: Duration) {
+        self.generated = elapsed;
+    }
+
+    /// Records stage `name` processing `rows` in `elapsed` time.
+    pub fn stage(&mut self, name: &'static str, rows: usize, elapsed: Duration) {
+        self.stages.push(Stage { name, rows, elapsed });
+    }
+
+    /// Counts transactions rejected while handled.
+    pub fn rejected(&mut self, rejected: usize) {
+        self.rejected += rejected;
+    }
+
+    /// Completes report measuring peak resident memory of the process.
+    pub fn finish(&mut self) {
+        self.peak_rss = peak_rss();
+    }
+}
+
+impl fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "bench: {} transactions, {} clients (generated: {:.3}s)",
+            self.transactions,
+            self.clients,
+            self.generated.as_secs_f64()
+        )?;
+        let mut elapsed = Duration::default();
+        for stage in self.stages.iter() {
+            writeln!(f, "  {}: {:.3}s ({:.0} rows/s)", stage.name, stage.elapsed.as_secs_f64(), throughput(stage.rows, stage.elapsed))?;
+            elapsed += stage.elapsed;
+        }
+        writeln!(f, "rejected: {}", self.rejected)?;
+        writeln!(f, "elapsed: {:.3}s ({:.0} transactions/s)", elapsed.as_secs_f64(), throughput(self.transactions, elapsed))?;
+        match self.peak_rss {
+            Some(bytes) => writeln!(f, "peak rss: {:.1} MB", bytes as f64 / 1e6),
+            None => writeln!(f, "peak rss: unknown"),
+        }
+    }
+}
+
+/// Returns `rows` processed per second of `elapsed`.
+fn throughput(rows: usize, elapsed: Duration) -> f64 {
+    let seconds = elapsed.as_secs_f64();
+    if seconds > 0.0 { rows as f64 / seconds } else { 0.0 }
+}
+
+/// Returns csv source (`type,client,tx,amount`) of `transactions` spread across `clients` accounts.
+///
+/// Every client cycles through deposits, withdrawals, a dispute and its resolution (one in a hundred clients has a
+/// dispute charged back locking its account) so transactions are mostly accepted.
+pub fn generate(transactions: usize, clients: ClientId) -> Vec<u8> {
+    let clients = clients.max(1) as usize;
+    let mut source = Vec::with_capacity(transactions * 32);
+    source.extend_from_slice(b"type,client,tx,amount\n");
+    for row in 0..transactions {
+        let (client, round) = (row % clients + 1, row / clients);
+        let tx = row + 1;
+        // deposit of current cycle (disputed then resolved)
+        let disputed = tx - (round % 10) * clients;
+        match round % 10 {
+            0..=4 | 9 => writeln!(source, "deposit,{},{},{}.{:04}", client, tx, row % 1_000 + 1, row % 10_000),
+            5 | 6 => writeln!(source, "withdraw,{},{},{}.{:04}", client, tx, row % 3, row % 10_000),
+            7 => writeln!(source, "dispute,{},{},", client, disputed),
+            _ if client % 100 == 0 && round / 10 == 5 => writeln!(source, "chargeback,{},{},", client, disputed),
+            _ => writeln!(source, "resolve,{},{},", client, disputed),
+        }.unwrap();
+    }
+    source
+}
+
+/// Returns peak resident memory (bytes) of the process (none when unavailable, e.g. other than linux).
+fn peak_rss() -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| { line.starts_with("VmHWM:") })?;
+    let kilobytes: u64 = line.trim_start_matches("VmHWM:").trim().trim_end_matches("kB").trim().parse().ok()?;
+    Some(kilobytes * 1024)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    use crate::events::{Actor, Cause};
+    use crate::input::csv_reader;
+    use crate::models::{Account, Command};
+
+    #[test]
+    fn generated_transactions_mostly_accepted() {
+        let (transactions, clients) = (12_000, 200);
+        let source = generate(transactions, clients);
+        let commands: Vec<Command> = csv_reader(source.as_slice(), b',').unwrap()
+            .deserialize()
+            .map(|result| { result.unwrap() })
+            .collect();
+        assert_eq!(commands.len(), transactions);
+
+        let mut accounts: HashMap<ClientId, Account> = HashMap::new();
+        let mut rejected = 0;
+        for command in commands {
+            let account = accounts.entry(command.actor_id()).or_insert_with(|| { Account::new(command.actor_id()) });
+            match account.handle(command) {
+                Ok(events) => account.apply(events),
+                Err(_) => rejected += 1,
+            }
+        }
+        assert_eq!(accounts.len(), clients as usize);
+        // accounts charged back (clients 100 and 200) reject their remaining transactions
+        assert_eq!(accounts.values().filter(|account| { account.locked() }).count(), 2);
+        assert!(rejected < transactions / 100);
+    }
+
+    #[test]
+    fn report_throughput_of_stages() {
+        let mut report = BenchReport::new(1_000, 10);
+        report.generated(Duration::from_millis(100));
+        report.stage("parse", 1_000, Duration::from_millis(250));
+        report.stage("handle", 1_000, Duration::from_millis(250));
+        report.rejected(3);
+        report.finish();
+        let report = report.to_string();
+
+        assert!(report.starts_with("bench: 1000 transactions, 10 clients (generated: 0.100s)\n  parse: 0.250s (4000 rows/s)\n"));
+        assert!(report.contains("rejected: 3\nelapsed: 0.500s (2000 transactions/s)\n"));
+        #[cfg(target_os = "linux")]
+        assert!(!report.contains("peak rss: unknown"));
+    }
+}
@@ -0,0 +1,122 @@
+//! Memory budget of accounts (and their event history) kept in memory.
+//!
+//! Memory used is approximate (see `Account::memory_bytes`). Exceeding the budget spills accounts to a persistent
+//! store, compacts event history or aborts the run with a resource failure (exit code 71) rather than exhausting
+//! memory.
+
+use std::str::FromStr;
+
+use simple_error::*;
+
+use crate::failure::{Failure, Kind, OrFail};
+use crate::store::ProjectionStore;
+
+/// Bytes of a megabyte (MiB).
+const MEGABYTE: usize = 1024 * 1024;
+
+/// Action taken when accounts exceed memory budget.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BudgetAction {
+    /// Evict least recently used accounts to persistent store.
+    Spill,
+    /// Release unused capacity of event history.
+    Compact,
+    /// Abort run.
+    Abort,
+}
+
+impl BudgetAction {
+    /// Returns names of budget actions used for cli arguments.
+    pub fn names() -> Vec<&'static str> {
+        vec!["spill", "compact", "abort"]
+    }
+}
+
+impl FromStr for BudgetAction {
+    type Err = SimpleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "spill" => Ok(BudgetAction::Spill),
+            "compact" => Ok(BudgetAction::Compact),
+            "abort" => Ok(BudgetAction::Abort),
+            _ => Err(SimpleError::new(format!("unsupported memory budget action({})", s))),
+        }
+    }
+}
+
+/// Bytes accounts kept in memory are limited to and action taken when exceeded.
+#[derive(Debug, Clone, Copy)]
+pub struct MemoryBudget {
+    bytes: usize,
+    action: BudgetAction,
+}
+
+impl MemoryBudget {
+    /// Returns budget of `megabytes` enforced using `action`.
+    pub fn new(megabytes: usize, action: BudgetAction) -> Self {
+        MemoryBudget { bytes: megabytes.saturating_mul(MEGABYTE), action }
+    }
+
+    /// Returns action taken when budget is exceeded.
+    pub fn action(&self) -> BudgetAction { self.action }
+
+    /// Enforces budget on `accounts` failing when memory used exceeds budget after action is taken (resource
+    /// failure) or accounts can't be spilled (I/O failure).
+    pub fn enforce(&self, accounts: &mut dyn ProjectionStore) -> Result<(), Failure> {
+        if accounts.memory_bytes() <= self.bytes {
+            return Ok(());
+        }
+        match self.action {
+            BudgetAction::Spill => accounts.evict(self.bytes).or_fail(Kind::Io)?,
+            BudgetAction::Compact => accounts.compact(),
+            BudgetAction::Abort => {}
+        }
+        let used = accounts.memory_bytes();
+        if used > self.bytes {
+            let message = format!("memory budget({} MiB) exceeded by accounts({} bytes)", self.bytes / MEGABYTE, used);
+            return Err(Failure::new(Kind::Resource, message));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::prelude::Decimal;
+
+    use crate::events::Actor;
+    use crate::models::{Account, Command, CommandType};
+    use crate::store::{MemoryStore, SpillStore};
+
+    fn deposits(accounts: &mut dyn ProjectionStore, budget: &MemoryBudget) -> Result<(), Failure> {
+        for tx in 1..=20_000 {
+            let client = (tx % 100) as u16;
+            accounts.update(client, || { Account::new(client) }, |account| {
+                let events = account.handle(Command::new(CommandType::Deposit, client, tx, Some(Decimal::new(15, 1))))?;
+                account.apply(events);
+                Ok(())
+            }).or_fail(Kind::Io)?;
+            budget.enforce(accounts)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn budget_exceeded_aborts() {
+        let budget = MemoryBudget::new(1, BudgetAction::Abort);
+        let failure = deposits(&mut MemoryStore::default(), &budget).unwrap_err();
+        assert_eq!(failure.code(), Kind::Resource.code());
+        assert!(failure.to_string().starts_with("memory budget(1 MiB) exceeded"));
+    }
+
+    #[test]
+    fn budget_exceeded_spills() {
+        let budget = MemoryBudget::new(1, BudgetAction::Spill);
+        let mut accounts = SpillStore::new(Box::new(MemoryStore::default()), usize::MAX);
+        deposits(&mut accounts, &budget).unwrap();
+        assert!(accounts.memory_bytes() <= MEGABYTE);
+        assert_eq!(accounts.iter().unwrap().count(), 100);
+    }
+}
@@ -0,0 +1,93 @@
+//! Checkpoints of source records processed used to resume interrupted runs.
+//!
+//! Checkpoints are saved as JSON every `CHECKPOINT_RECORDS` and at the end of each source (written to a partial file
+//! synced to disk then renamed so an interrupted save or a crash never leaves a truncated checkpoint).
+
+use std::fs::{self, File};
+use std::io::{self, Write};
+
+use serde::{Serialize, Deserialize};
+use simple_error::*;
+
+use crate::output::partial_path;
+
+/// Records between checkpoints saved while processing a source.
+pub const CHECKPOINT_RECORDS: u64 = 10_000;
+
+/// Source and number of its records (rows) fully processed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub source: String,
+    pub records: u64,
+}
+
+impl Checkpoint {
+    /// Returns new `Checkpoint` of `records` processed of `source`.
+    pub fn new(source: &str, records: u64) -> Self {
+        Checkpoint { source: source.to_string(), records }
+    }
+
+    /// Returns checkpoint loaded from `path` (none when no checkpoint was saved).
+    pub fn load(path: &str) -> Result<Option<Self>, SimpleError> {
+        let json = match fs::read_to_string(path) {
+            Ok(json) => json,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => bail!("unable to read checkpoint({}), {}", path, e),
+        };
+        Ok(Some(try_with!(serde_json::from_str(&json), "unable to parse checkpoint({})", path)))
+    }
+
+    /// Saves checkpoint to `path` atomically (synced to disk before replacing the previous checkpoint).
+    pub fn save(&self, path: &str) -> Result<(), SimpleError> {
+        let json = try_with!(serde_json::to_string(self), "unable to encode checkpoint({})", path);
+        let partial = partial_path(path);
+        let mut file = try_with!(File::create(&partial), "unable to write checkpoint({})", path);
+        try_with!(file.write_all(json.as_bytes()), "unable to write checkpoint({})", path);
+        try_with!(file.sync_all(), "unable to sync checkpoint({})", path);
+        try_with!(fs::rename(&partial, path), "unable to write checkpoint({})", path);
+        Ok(())
+    }
+
+    /// Returns records of `source` (at `index` of `sources`) processed before checkpoint.
+    ///
+    /// Sources ordered before the checkpoint source were processed entirely.
+    pub fn skip(&self, sources: &[String], index: usize) -> Result<u64, SimpleError> {
+        let position = require_with!(
+            sources.iter().position(|source| { *source == self.source }),
+            "checkpoint source({}) is none of sources",
+            self.source
+        );
+        Ok(match index {
+            index if index < position => u64::MAX,
+            index if index == position => self.records,
+            _ => 0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::testing::TempDir;
+
+    #[test]
+    fn checkpoint_round_trip_and_skip() {
+        let directory = TempDir::new("checkpoint");
+        let path = directory.join("run.checkpoint");
+        let path = path.to_str().unwrap();
+        assert_eq!(Checkpoint::load(path).unwrap(), None);
+
+        Checkpoint::new("a.csv", 10).save(path).unwrap();
+        Checkpoint::new("b.csv", 42).save(path).unwrap();
+        let checkpoint = Checkpoint::load(path).unwrap().unwrap();
+        assert_eq!(checkpoint, Checkpoint::new("b.csv", 42));
+        assert!(!std::path::Path::new(&partial_path(path)).exists());
+
+        let sources = vec![String::from("a.csv"), String::from("b.csv"), String::from("c.csv")];
+        assert_eq!(checkpoint.skip(&sources, 0).unwrap(), u64::MAX);
+        assert_eq!(checkpoint.skip(&sources, 1).unwrap(), 42);
+        assert_eq!(checkpoint.skip(&sources, 2).unwrap(), 0);
+        assert!(checkpoint.skip(&sources[..1], 0).is_err());
+    }
+}
@@ -0,0 +1,159 @@
+//! Conversion of serializable records into Arrow record batches used by columnar output formats.
+//!
+//! Column types are inferred from serialized record values:
+//! - booleans become `Boolean`
+//! - integers become `Int64` and other numbers `Float64`
+//! - strings become `Decimal128` when every value is a decimal (e.g. currency) otherwise `Utf8`
+//! - columns having only nulls become `Utf8`
+
+use std::io::{self, Write};
+use std::sync::Arc;
+
+use arrow_array::{ArrayRef, BooleanArray, Decimal128Array, Float64Array, Int64Array, RecordBatch, StringArray};
+use arrow_schema::{ArrowError, Field, Schema};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+/// Maximum precision of `Decimal128` columns.
+const DECIMAL_PRECISION: u8 = 38;
+
+/// Buffers serializable records as rows to be converted into a `RecordBatch`.
+#[derive(Debug, Clone, Default)]
+pub struct Rows {
+    columns: Vec<String>,
+    rows: Vec<Map<String, Value>>,
+}
+
+impl Rows {
+    /// Returns new empty `Rows`.
+    pub fn new() -> Self {
+        Rows { columns: vec![], rows: vec![] }
+    }
+
+    /// Buffers `record` as a row (columns are named after first record fields).
+    pub fn push<T: Serialize>(&mut self, record: T) -> io::Result<()> {
+        match serde_json::to_value(record)? {
+            Value::Object(row) => {
+                if self.columns.is_empty() {
+                    self.columns = row.keys().cloned().collect();
+                }
+                self.rows.push(row);
+                Ok(())
+            }
+            _ => Err(io::Error::new(io::ErrorKind::InvalidData, "columnar records must be structs")),
+        }
+    }
+
+    /// Returns `RecordBatch` having a column per record field.
+    pub fn to_batch(&self) -> Result<RecordBatch, ArrowError> {
+        let mut fields = Vec::with_capacity(self.columns.len());
+        let mut arrays: Vec<ArrayRef> = Vec::with_capacity(self.columns.len());
+        for name in &self.columns {
+            let values: Vec<&Value> = self.rows.iter()
+                .map(|row| { row.get(name).unwrap_or(&Value::Null) })
+                .collect();
+            let array = to_array(&values);
+            fields.push(Field::new(name, array.data_type().clone(), true));
+            arrays.push(array);
+        }
+        RecordBatch::try_new(Arc::new(Schema::new(fields)), arrays)
+    }
+
+    /// Writes rows to `writer` as an Apache Parquet file.
+    #[cfg(feature = "parquet")]
+    pub fn write_parquet<W: Write + Send>(&self, writer: W) -> io::Result<()> {
+        let batch = self.to_batch().map_err(invalid_data)?;
+        let mut writer = parquet::arrow::ArrowWriter::try_new(writer, batch.schema(), None)?;
+        writer.write(&batch)?;
+        writer.into_inner()?.flush()
+    }
+
+    /// Writes rows to `writer` as an Apache Arrow IPC stream.
+    #[cfg(feature = "arrow")]
+    pub fn write_ipc<W: Write>(&self, writer: W) -> io::Result<()> {
+        let batch = self.to_batch().map_err(invalid_data)?;
+        let mut writer = arrow_ipc::writer::StreamWriter::try_new(writer, &batch.schema()).map_err(invalid_data)?;
+        writer.write(&batch).map_err(invalid_data)?;
+        writer.finish().map_err(invalid_data)?;
+        writer.into_inner().map_err(invalid_data)?.flush()
+    }
+}
+
+/// Returns `io::Error` for Arrow `error`.
+fn invalid_data(error: ArrowError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+/// Returns Arrow array of `values` using type inferred from first non-null value.
+fn to_array(values: &[&Value]) -> ArrayRef {
+    let sample = values.iter().find(|v| { !v.is_null() });
+    match sample {
+        Some(Value::Bool(_)) => {
+            Arc::new(values.iter().map(|v| { v.as_bool() }).collect::<BooleanArray>())
+        }
+        Some(Value::Number(_)) if values.iter().all(|v| { v.is_null() || v.is_i64() || v.is_u64() }) => {
+            Arc::new(values.iter().map(|v| { v.as_i64() }).collect::<Int64Array>())
+        }
+        Some(Value::Number(_)) => {
+            Arc::new(values.iter().map(|v| { v.as_f64() }).collect::<Float64Array>())
+        }
+        Some(Value::String(_)) => match to_decimals(values) {
+            Some(array) => Arc::new(array),
+            None => Arc::new(values.iter().map(|v| { v.as_str() }).collect::<StringArray>()),
+        },
+        // column of nulls (or nested values) defaults to strings
+        _ => Arc::new(values.iter().map(|v| { v.as_str() }).collect::<StringArray>()),
+    }
+}
+
+/// Returns `Decimal128Array` when every non-null value is a decimal string (none otherwise).
+fn to_decimals(values: &[&Value]) -> Option<Decimal128Array> {
+    let decimals: Vec<Option<Decimal>> = values.iter()
+        .map(|v| { match v {
+            Value::Null => Some(None),
+            Value::String(s) => s.parse::<Decimal>().ok().map(Some),
+            _ => None,
+        } })
+        .collect::<Option<_>>()?;
+    let scale = decimals.iter().flatten().map(|d| { d.scale() }).max().unwrap_or(0);
+    let array = decimals.iter()
+        .map(|d| { d.map(|mut d| { d.rescale(scale); d.mantissa() }) })
+        .collect::<Decimal128Array>()
+        .with_precision_and_scale(DECIMAL_PRECISION, scale as i8)
+        .ok()?;
+    Some(array)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_schema::DataType;
+
+    #[derive(Serialize)]
+    struct Record {
+        client: u16,
+        wallet: &'static str,
+        available: Decimal,
+        locked: bool,
+    }
+
+    #[test]
+    fn rows_converted_to_batch() {
+        let mut rows = Rows::new();
+        rows.push(Record { client: 1, wallet: "main", available: Decimal::new(15, 1), locked: false }).unwrap();
+        rows.push(Record { client: 2, wallet: "savings", available: Decimal::new(990000, 4), locked: true }).unwrap();
+        let batch = rows.to_batch().unwrap();
+        let schema = batch.schema();
+
+        assert_eq!(batch.num_rows(), 2);
+        assert_eq!(schema.field(0).name(), "client");
+        assert_eq!(schema.field(0).data_type(), &DataType::Int64);
+        assert_eq!(schema.field(1).data_type(), &DataType::Utf8);
+        assert_eq!(schema.field(2).data_type(), &DataType::Decimal128(DECIMAL_PRECISION, 4));
+        assert_eq!(schema.field(3).data_type(), &DataType::Boolean);
+        let available = batch.column(2).as_any().downcast_ref::<Decimal128Array>().unwrap();
+        assert_eq!(available.value(0), 15000);
+        assert_eq!(available.value(1), 990000);
+    }
+}
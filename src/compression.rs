@@ -0,0 +1,111 @@
+//! Compression of sources and outputs (gzip or zstd).
+
+use std::io::{Read, Write, self};
+use std::str::FromStr;
+
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use simple_error::SimpleError;
+
+/// Compression of a source or output.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    /// Returns names of supported compressions used for cli arguments.
+    pub fn names() -> Vec<&'static str> {
+        vec!["gzip", "zstd"]
+    }
+
+    /// Returns compression of `path` using extension (`.gz` or `.zst`).
+    pub fn from_path(path: &str) -> Self {
+        let path = path.to_lowercase();
+        if path.ends_with(".gz") {
+            Compression::Gzip
+        } else if path.ends_with(".zst") {
+            Compression::Zstd
+        } else {
+            Compression::None
+        }
+    }
+
+    /// Returns reader decompressing `reader`.
+    pub fn reader<R: Read + Send + 'static>(self, reader: R) -> io::Result<Box<dyn Read + Send>> {
+        let reader: Box<dyn Read + Send> = match self {
+            Compression::None => Box::new(reader),
+            Compression::Gzip => Box::new(MultiGzDecoder::new(reader)),
+            Compression::Zstd => Box::new(zstd::Decoder::new(reader)?),
+        };
+        Ok(reader)
+    }
+
+    /// Returns writer compressing to `writer`.
+    ///
+    /// Compressed streams are completed when the returned writer is dropped.
+    pub fn writer<W: Write + Send + 'static>(self, writer: W) -> io::Result<Box<dyn Write + Send>> {
+        let writer: Box<dyn Write + Send> = match self {
+            Compression::None => Box::new(writer),
+            Compression::Gzip => Box::new(GzEncoder::new(writer, flate2::Compression::default())),
+            Compression::Zstd => Box::new(zstd::Encoder::new(writer, 0)?.auto_finish()),
+        };
+        Ok(writer)
+    }
+}
+
+impl FromStr for Compression {
+    type Err = SimpleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Compression::None),
+            "gzip" => Ok(Compression::Gzip),
+            "zstd" => Ok(Compression::Zstd),
+            _ => Err(SimpleError::new(format!("unsupported compression({})", s))),
+        }
+    }
+}
+
+/// Returns `path` without compression extension (e.g. `data.tsv` of `data.tsv.gz`).
+pub fn strip_compression(path: &str) -> &str {
+    match Compression::from_path(path) {
+        Compression::Gzip => &path[..path.len() - ".gz".len()],
+        Compression::Zstd => &path[..path.len() - ".zst".len()],
+        Compression::None => path,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::testing::TempDir;
+
+    #[test]
+    fn compression_detected() {
+        assert_eq!(Compression::from_path("tx.csv"), Compression::None);
+        assert_eq!(Compression::from_path("tx.csv.gz"), Compression::Gzip);
+        assert_eq!(Compression::from_path("tx.CSV.ZST"), Compression::Zstd);
+        assert_eq!(strip_compression("tx.tsv.gz"), "tx.tsv");
+        assert_eq!(strip_compression("tx.tsv.zst"), "tx.tsv");
+    }
+
+    #[test]
+    fn compressed_round_trip() {
+        let directory = TempDir::new("compression");
+        let path = directory.join("accounts.csv");
+        for compression in [Compression::None, Compression::Gzip, Compression::Zstd] {
+            {
+                let mut writer = compression.writer(std::fs::File::create(&path).unwrap()).unwrap();
+                writer.write_all(b"client,available\n1,1.0\n").unwrap();
+            }
+            let mut content = String::new();
+            compression.reader(std::fs::File::open(&path).unwrap()).unwrap().read_to_string(&mut content).unwrap();
+
+            assert_eq!(content, "client,available\n1,1.0\n");
+        }
+    }
+}
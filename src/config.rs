@@ -0,0 +1,317 @@
+//! Configuration file (TOML) of processing settings supplied using `--config`.
+//!
+//! Settings are grouped in tables (`input`, `output`, `reports`, `events`, `store`, `checkpoint`, `stream`,
+//! `webhook` and `rules`) each setting equivalent to a cli argument. Unknown settings and values of the wrong type are
+//! rejected when loaded. Arguments supplied on the command line override settings of the file.
+//!
+//! ```toml
+//! [input]
+//! delimiter = ";"
+//!
+//! [output]
+//! destination = "accounts.json"
+//! format = "json"
+//! sort = "available"
+//!
+//! [reports]
+//! rejects = "rejects.csv"
+//! summary = "-"
+//!
+//! [store]
+//! kind = "sled"
+//! path = "accounts.sled"
+//! max-memory = 100000
+//! ```
+
+use std::fs;
+
+use clap::ArgMatches;
+use serde::Deserialize;
+use simple_error::*;
+
+/// Settings of transaction sources.
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct InputConfig {
+    pub accounts: Option<String>,
+    pub initial_balances: Option<String>,
+    pub delimiter: Option<String>,
+    pub layout: Option<String>,
+    pub sheet: Option<String>,
+    pub client: Option<u16>,
+    #[serde(default)]
+    pub external_sort: bool,
+    pub run_records: Option<usize>,
+    #[serde(default)]
+    pub merge_sources: bool,
+    #[serde(default)]
+    pub follow: bool,
+    pub follow_interval: Option<u64>,
+    pub watch: Option<String>,
+    pub max_unparseable: Option<usize>,
+}
+
+/// Settings of account snapshots output.
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct OutputConfig {
+    pub destination: Option<String>,
+    pub format: Option<String>,
+    pub sort: Option<String>,
+    pub flush_rows: Option<usize>,
+    pub compress: Option<String>,
+    #[serde(default)]
+    pub extended: bool,
+}
+
+/// Settings of reports.
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct ReportsConfig {
+    pub rejects: Option<String>,
+    pub summary: Option<String>,
+    pub metrics_json: Option<String>,
+    pub settlements: Option<String>,
+    pub history: Option<String>,
+    pub snapshots: Option<String>,
+    pub window: Option<String>,
+    pub monthly: Option<String>,
+    pub categories: Option<String>,
+}
+
+/// Settings of event logs, audit trails and event stores.
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct EventsConfig {
+    pub log: Option<String>,
+    pub log_format: Option<String>,
+    pub rehydrate: Option<String>,
+    pub export: Option<String>,
+    pub import: Option<String>,
+    pub store: Option<String>,
+}
+
+/// Settings of account stores.
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct StoreConfig {
+    pub kind: Option<String>,
+    pub path: Option<String>,
+    pub outbox: Option<String>,
+    pub max_memory: Option<u64>,
+    pub max_memory_mb: Option<u64>,
+    pub memory_budget_action: Option<String>,
+    pub load_state: Option<String>,
+    pub save_state: Option<String>,
+    #[serde(default)]
+    pub retain_events: bool,
+}
+
+/// Settings of checkpoints.
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct CheckpointConfig {
+    pub path: Option<String>,
+    #[serde(default)]
+    pub resume: bool,
+}
+
+/// Settings of streaming sources (source is supplied using `--source`).
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct StreamConfig {
+    pub brokers: Option<String>,
+    pub topic: Option<String>,
+    pub group: Option<String>,
+    pub message_format: Option<String>,
+    pub idle_timeout: Option<u64>,
+    pub max_rate: Option<String>,
+    pub health_bind: Option<String>,
+    pub max_lag: Option<u64>,
+    #[serde(default)]
+    pub exactly_once: bool,
+}
+
+/// Settings of webhook notifications.
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct WebhookConfig {
+    pub url: Option<String>,
+    pub retries: Option<u32>,
+}
+
+/// Settings of scripted validation rules.
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct RulesConfig {
+    pub script: Option<String>,
+}
+
+/// Processing settings of a configuration file.
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub input: InputConfig,
+    pub output: OutputConfig,
+    pub reports: ReportsConfig,
+    pub events: EventsConfig,
+    pub store: StoreConfig,
+    pub checkpoint: CheckpointConfig,
+    pub stream: StreamConfig,
+    pub webhook: WebhookConfig,
+    pub rules: RulesConfig,
+}
+
+impl Config {
+    /// Returns `Config` of TOML file at `path`.
+    pub fn load(path: &str) -> Result<Self, SimpleError> {
+        let config = try_with!(fs::read_to_string(path), "unable to read config({})", path);
+        Ok(try_with!(toml::from_str(&config), "invalid config({})", path))
+    }
+
+    /// Returns cli arguments (names and values, none for flags) of settings supplied.
+    pub fn args(&self) -> Vec<(&'static str, Option<String>)> {
+        let values = vec![
+            ("accounts", self.input.accounts.clone()),
+            ("initial-balances", self.input.initial_balances.clone()),
+            ("delimiter", self.input.delimiter.clone()),
+            ("layout", self.input.layout.clone()),
+            ("sheet", self.input.sheet.clone()),
+            ("client", self.input.client.map(|client| { client.to_string() })),
+            ("run-records", self.input.run_records.map(|records| { records.to_string() })),
+            ("follow-interval", self.input.follow_interval.map(|seconds| { seconds.to_string() })),
+            ("watch", self.input.watch.clone()),
+            ("max-unparseable", self.input.max_unparseable.map(|max| { max.to_string() })),
+            ("output", self.output.destination.clone()),
+            ("output-format", self.output.format.clone()),
+            ("sort", self.output.sort.clone()),
+            ("flush-rows", self.output.flush_rows.map(|rows| { rows.to_string() })),
+            ("compress", self.output.compress.clone()),
+            ("rejects", self.reports.rejects.clone()),
+            ("summary", self.reports.summary.clone()),
+            ("metrics-json", self.reports.metrics_json.clone()),
+            ("settlements", self.reports.settlements.clone()),
+            ("history", self.reports.history.clone()),
+            ("snapshots", self.reports.snapshots.clone()),
+            ("window", self.reports.window.clone()),
+            ("monthly", self.reports.monthly.clone()),
+            ("categories", self.reports.categories.clone()),
+            ("event-log", self.events.log.clone()),
+            ("event-log-format", self.events.log_format.clone()),
+            ("rehydrate", self.events.rehydrate.clone()),
+            ("export-events", self.events.export.clone()),
+            ("import-events", self.events.import.clone()),
+            ("event-store", self.events.store.clone()),
+            ("store", self.store.kind.clone()),
+            ("store-path", self.store.path.clone()),
+            ("outbox", self.store.outbox.clone()),
+            ("max-memory", self.store.max_memory.map(|max| { max.to_string() })),
+            ("max-memory-mb", self.store.max_memory_mb.map(|max| { max.to_string() })),
+            ("memory-budget-action", self.store.memory_budget_action.clone()),
+            ("load-state", self.store.load_state.clone()),
+            ("save-state", self.store.save_state.clone()),
+            ("checkpoint", self.checkpoint.path.clone()),
+            ("brokers", self.stream.brokers.clone()),
+            ("topic", self.stream.topic.clone()),
+            ("group", self.stream.group.clone()),
+            ("message-format", self.stream.message_format.clone()),
+            ("idle-timeout", self.stream.idle_timeout.map(|seconds| { seconds.to_string() })),
+            ("max-rate", self.stream.max_rate.clone()),
+            ("health-bind", self.stream.health_bind.clone()),
+            ("max-lag", self.stream.max_lag.map(|lag| { lag.to_string() })),
+            ("webhook", self.webhook.url.clone()),
+            ("webhook-retries", self.webhook.retries.map(|retries| { retries.to_string() })),
+            ("rules", self.rules.script.clone()),
+        ];
+        let flags = vec![
+            ("external-sort", self.input.external_sort),
+            ("merge-sources", self.input.merge_sources),
+            ("follow", self.input.follow),
+            ("exactly-once", self.stream.exactly_once),
+            ("extended", self.output.extended),
+            ("retain-events", self.store.retain_events),
+            ("resume", self.checkpoint.resume),
+        ];
+        values.into_iter()
+            .filter_map(|(name, value)| { value.map(|value| { (name, Some(value)) }) })
+            .chain(flags.into_iter().filter(|(_, set)| { *set }).map(|(name, _)| { (name, None) }))
+            .collect()
+    }
+}
+
+/// Returns path of `--config` argument of command line `args` (none when not supplied).
+pub fn path(args: &[String]) -> Option<String> {
+    args.iter().enumerate().skip(1).find_map(|(index, arg)| {
+        match arg.strip_prefix("--config") {
+            Some("") => args.get(index + 1).cloned(),
+            Some(value) => value.strip_prefix('=').map(String::from),
+            None => None,
+        }
+    })
+}
+
+/// Returns command line `args` with arguments of `config` not supplied by `args` (parsed as `matches`) inserted after
+/// the program name.
+pub fn merge(args: Vec<String>, matches: &ArgMatches, config: &Config) -> Vec<String> {
+    let mut merged: Vec<String> = args.iter().take(1).cloned().collect();
+    for (name, value) in config.args() {
+        if matches.occurrences_of(name) == 0 {
+            merged.push(match value {
+                Some(value) => format!("--{}={}", name, value),
+                None => format!("--{}", name),
+            });
+        }
+    }
+    merged.extend(args.into_iter().skip(1));
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{App, Arg};
+
+    const CONFIG: &str = r#"
+        [output]
+        format = "json"
+        sort = "available"
+        extended = true
+
+        [reports]
+        rejects = "rejects.csv"
+
+        [store]
+        max-memory = 1000
+    "#;
+
+    #[test]
+    fn settings_merged_under_cli_arguments() {
+        let config: Config = toml::from_str(CONFIG).unwrap();
+        assert_eq!(config.output.format.as_deref(), Some("json"));
+        assert_eq!(config.store.max_memory, Some(1000));
+        assert!(toml::from_str::<Config>("[output]\nformats = \"json\"").is_err());
+        assert!(toml::from_str::<Config>("[store]\nmax-memory = \"lots\"").is_err());
+
+        let app = App::new("test")
+            .arg(Arg::with_name("source").required(true))
+            .arg(Arg::with_name("config").long("config").takes_value(true))
+            .arg(Arg::with_name("output-format").long("output-format").default_value("csv").takes_value(true))
+            .arg(Arg::with_name("sort").short("s").long("sort").takes_value(true))
+            .arg(Arg::with_name("extended").long("extended"))
+            .arg(Arg::with_name("rejects").long("rejects").takes_value(true))
+            .arg(Arg::with_name("max-memory").long("max-memory").takes_value(true));
+        let args: Vec<String> = ["test", "tx.csv", "--config", "config.toml", "-s", "client"]
+            .iter()
+            .map(|arg| { arg.to_string() })
+            .collect();
+        assert_eq!(path(&args).as_deref(), Some("config.toml"));
+        let matches = app.clone().get_matches_from(&args);
+        let matches = app.get_matches_from(merge(args, &matches, &config));
+        assert_eq!(matches.value_of("output-format"), Some("json"));
+        assert_eq!(matches.value_of("sort"), Some("client"));
+        assert!(matches.is_present("extended"));
+        assert_eq!(matches.value_of("rejects"), Some("rejects.csv"));
+        assert_eq!(matches.value_of("max-memory"), Some("1000"));
+        assert_eq!(matches.value_of("source"), Some("tx.csv"));
+    }
+}
@@ -0,0 +1,198 @@
+//! Comparison of account snapshots written by runs (`diff` subcommand) for day-over-day reconciliation.
+//!
+//! Snapshots are csv of account balances (extra columns ignored) with rows keyed by client, or by client and wallet
+//! when snapshots have a wallet column. Rows of accounts changed are reported with balance deltas (new less old) and
+//! accounts unchanged are omitted. Snapshots compared must both have (or both lack) a wallet column.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::Read;
+
+use csv::Reader;
+use serde::{Deserialize, Serialize};
+use simple_error::*;
+
+use crate::models::{ClientId, Currency, WalletId};
+
+/// Key of a snapshot row.
+pub type RowKey = (ClientId, Option<WalletId>);
+
+/// Row of an account snapshot.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct SnapshotRow {
+    client: ClientId,
+    #[serde(default)]
+    wallet: Option<WalletId>,
+    available: Currency,
+    held: Currency,
+    total: Currency,
+    locked: bool,
+}
+
+/// Rows of an account snapshot keyed by client and wallet.
+#[derive(Debug)]
+pub struct Snapshot {
+    /// Snapshot has a wallet column.
+    wallets: bool,
+    rows: BTreeMap<RowKey, SnapshotRow>,
+}
+
+/// Change of an account between snapshots.
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Change {
+    /// Account is only in the new snapshot.
+    Appeared,
+    /// Account is only in the old snapshot.
+    Disappeared,
+    /// Account is locked in the new snapshot only.
+    Locked,
+    /// Account is locked in the old snapshot only.
+    Unlocked,
+    /// Balances of account changed.
+    Changed,
+}
+
+/// Row of the differences between snapshots (deltas are new less old, missing balances being zero).
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct AccountDiff {
+    client: ClientId,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    wallet: Option<WalletId>,
+    change: Change,
+    available: Currency,
+    held: Currency,
+    total: Currency,
+    locked: bool,
+}
+
+/// Counts of changes between snapshots.
+#[derive(Debug, Default, PartialEq)]
+pub struct DiffSummary {
+    changed: usize,
+    locked: usize,
+    unlocked: usize,
+    appeared: usize,
+    disappeared: usize,
+}
+
+impl DiffSummary {
+    /// Returns summary of `diffs`.
+    pub fn of(diffs: &[AccountDiff]) -> Self {
+        let mut summary = DiffSummary::default();
+        for diff in diffs {
+            match diff.change {
+                Change::Changed => summary.changed += 1,
+                Change::Locked => summary.locked += 1,
+                Change::Unlocked => summary.unlocked += 1,
+                Change::Appeared => summary.appeared += 1,
+                Change::Disappeared => summary.disappeared += 1,
+            }
+        }
+        summary
+    }
+}
+
+impl fmt::Display for DiffSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} changed, {} newly locked, {} unlocked, {} appeared, {} disappeared",
+            self.changed, self.locked, self.unlocked, self.appeared, self.disappeared
+        )
+    }
+}
+
+/// Returns snapshot read by csv `reader` (see `input::csv_reader`).
+pub fn read_snapshot<R: Read>(mut reader: Reader<R>) -> Result<Snapshot, SimpleError> {
+    let headers = try_with!(reader.headers(), "invalid snapshot header");
+    let mut snapshot = Snapshot { wallets: headers.iter().any(|header| { header == "wallet" }), rows: BTreeMap::new() };
+    for result in reader.deserialize() {
+        let row: SnapshotRow = try_with!(result, "invalid snapshot row");
+        snapshot.rows.insert((row.client, row.wallet.clone()), row);
+    }
+    Ok(snapshot)
+}
+
+/// Returns differences of accounts changed from `old` to `new` snapshot ordered by client (then wallet).
+///
+/// Snapshots with and without a wallet column are rejected (rows keyed differently would never match).
+pub fn diff(old: &Snapshot, new: &Snapshot) -> Result<Vec<AccountDiff>, SimpleError> {
+    if old.wallets != new.wallets {
+        bail!("snapshot columns differ, wallet column in {} snapshot only", if old.wallets { "old" } else { "new" });
+    }
+    let (old, new) = (&old.rows, &new.rows);
+    let zero = Currency::new(0, 4);
+    let mut keys: Vec<&RowKey> = old.keys().chain(new.keys()).collect();
+    keys.sort();
+    keys.dedup();
+    Ok(keys.into_iter().filter_map(|key| {
+        let (old, new) = (old.get(key), new.get(key));
+        let change = match (old, new) {
+            (None, Some(_)) => Change::Appeared,
+            (Some(_), None) => Change::Disappeared,
+            (Some(old), Some(new)) if new.locked && !old.locked => Change::Locked,
+            (Some(old), Some(new)) if old.locked && !new.locked => Change::Unlocked,
+            (Some(old), Some(new)) if (old.available, old.held, old.total) != (new.available, new.held, new.total) => {
+                Change::Changed
+            }
+            _ => return None,
+        };
+        let delta = |balance: fn(&SnapshotRow) -> Currency| {
+            new.map_or(zero, balance) - old.map_or(zero, balance)
+        };
+        Some(AccountDiff {
+            client: key.0,
+            wallet: key.1.clone(),
+            change,
+            available: delta(|row| { row.available }),
+            held: delta(|row| { row.held }),
+            total: delta(|row| { row.total }),
+            locked: new.or(old).is_some_and(|row| { row.locked }),
+        })
+    }).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::prelude::Decimal;
+
+    use crate::input::csv_reader;
+
+    fn snapshot(csv: &str) -> Snapshot {
+        read_snapshot(csv_reader(csv.as_bytes(), b',').unwrap()).unwrap()
+    }
+
+    #[test]
+    fn accounts_changed_reported_with_deltas() {
+        let old = snapshot("client,available,held,total,locked\n1,1.0,0.0,1.0,false\n2,2.0,0.0,2.0,false\n3,1.0,0.0,1.0,false\n4,5,0,5,false\n");
+        let new = snapshot("client,available,held,total,locked\n1,1.5,0.5,2.0,false\n2,0.0,0.0,0.0,true\n4,5.0,0.0,5.0,false\n5,3.0,0.0,3.0,false\n");
+        let diffs = diff(&old, &new).unwrap();
+        let rows: Vec<_> = diffs.iter().map(|diff| { (diff.client, diff.change, diff.available, diff.total, diff.locked) }).collect();
+        assert_eq!(rows, vec![
+            (1, Change::Changed, Decimal::new(5, 1), Decimal::new(10, 1), false),
+            (2, Change::Locked, Decimal::new(-20, 1), Decimal::new(-20, 1), true),
+            (3, Change::Disappeared, Decimal::new(-10, 1), Decimal::new(-10, 1), false),
+            (5, Change::Appeared, Decimal::new(30, 1), Decimal::new(30, 1), false),
+        ]);
+        assert_eq!(DiffSummary::of(&diffs).to_string(), "1 changed, 1 newly locked, 0 unlocked, 1 appeared, 1 disappeared");
+    }
+
+    #[test]
+    fn wallet_rows_keyed_by_client_and_wallet() {
+        let old = snapshot("client,wallet,available,held,total,locked\n1,main,1.0,0.0,1.0,false\n1,savings,1.0,0.0,1.0,false\n");
+        let new = snapshot("client,wallet,available,held,total,locked\n1,main,1.0,0.0,1.0,false\n1,savings,2.0,0.0,2.0,false\n");
+        let diffs = diff(&old, &new).unwrap();
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].wallet.as_deref(), Some("savings"));
+    }
+
+    #[test]
+    fn wallet_column_mismatch_rejected() {
+        let old = snapshot("client,available,held,total,locked\n1,1.0,0.0,1.0,false\n");
+        let new = snapshot("client,wallet,available,held,total,locked\n1,main,1.0,0.0,1.0,false\n");
+        assert_eq!(diff(&old, &new).unwrap_err().as_str(), "snapshot columns differ, wallet column in new snapshot only");
+        assert_eq!(diff(&new, &old).unwrap_err().as_str(), "snapshot columns differ, wallet column in old snapshot only");
+    }
+}
@@ -0,0 +1,157 @@
+//! Sink of account snapshots and events into DuckDB tables and ad-hoc queries over them.
+//!
+//! Records are staged in a csv file alongside the database then loaded by the `duckdb` command line (replacing the
+//! table within a transaction) when finished, so no DuckDB library is linked. Queries run read-only using the same
+//! command line. Sinks and queries fail once opened when the command line isn't found on `PATH` (before any record is
+//! staged).
+
+use std::fs::{self, File};
+use std::process::Command;
+
+use serde_json::{Map, Value};
+use simple_error::*;
+
+use crate::output::partial_path;
+
+/// Scheme of destinations written to tables of a DuckDB database (e.g. `duckdb://results.duckdb`).
+const SCHEME: &str = "duckdb://";
+
+/// Command line used to load and query databases.
+const CLI: &str = "duckdb";
+
+/// Returns database path of DuckDB `destination` (none when destination is not a duckdb url).
+pub fn database_path(destination: &str) -> Option<&str> {
+    destination.strip_prefix(SCHEME)
+}
+
+/// Writes records to a DuckDB table replaced when committed.
+pub struct DuckdbSink {
+    path: String,
+    table: String,
+    staging: String,
+    writer: csv::Writer<File>,
+    columns: Vec<String>,
+}
+
+impl DuckdbSink {
+    /// Returns new `DuckdbSink` replacing `table` of database at `path` (records staged until committed).
+    pub fn open(path: &str, table: &str) -> Result<Self, SimpleError> {
+        installed(CLI)?;
+        Self::staged(path, table)
+    }
+
+    /// Returns new `DuckdbSink` staging records of `table` of database at `path` alongside it.
+    fn staged(path: &str, table: &str) -> Result<Self, SimpleError> {
+        let staging = partial_path(&format!("{}.{}.csv", path, table));
+        let writer = try_with!(csv::Writer::from_path(&staging), "unable to create duckdb staging({})", staging);
+        Ok(DuckdbSink { path: path.to_string(), table: table.to_string(), staging, writer, columns: vec![] })
+    }
+
+    /// Writes `record` (columns named after fields of first record) to staging.
+    pub fn write(&mut self, record: &Map<String, Value>) -> Result<(), SimpleError> {
+        if self.columns.is_empty() {
+            self.columns = record.keys().cloned().collect();
+            try_with!(self.writer.write_record(&self.columns), "unable to write duckdb staging({})", self.staging);
+        }
+        let values = self.columns.iter().map(|column| { text(record.get(column).unwrap_or(&Value::Null)) });
+        try_with!(self.writer.write_record(values), "unable to write duckdb staging({})", self.staging);
+        Ok(())
+    }
+
+    /// Replaces table with records staged.
+    pub fn commit(&mut self) -> Result<(), SimpleError> {
+        try_with!(self.writer.flush(), "unable to write duckdb staging({})", self.staging);
+        let sql = replace_table_sql(&self.table, (!self.columns.is_empty()).then_some(self.staging.as_str()));
+        let result = run(&[&self.path, "-c", &sql]);
+        try_with!(fs::remove_file(&self.staging), "unable to remove duckdb staging({})", self.staging);
+        result
+    }
+}
+
+/// Runs `sql` against database at `path` (read-only) writing results to stdout in `format`.
+pub fn query(path: &str, sql: &str, format: &str) -> Result<(), SimpleError> {
+    let mode = match format {
+        "table" => "-box",
+        "csv" => "-csv",
+        "json" => "-json",
+        "jsonl" => "-jsonlines",
+        format => bail!("unsupported query format({})", format),
+    };
+    installed(CLI)?;
+    run(&["-readonly", mode, path, "-c", sql])
+}
+
+/// Returns error unless command line `cli` is found on `PATH` (answering its version).
+fn installed(cli: &str) -> Result<(), SimpleError> {
+    match Command::new(cli).arg("-version").output() {
+        Ok(output) if output.status.success() => Ok(()),
+        Ok(output) => bail!("{} command line failed({})", cli, output.status),
+        Err(e) => bail!("{} command line not found on PATH (required by duckdb feature), {}", cli, e),
+    }
+}
+
+/// Runs duckdb command line using `args`.
+fn run(args: &[&str]) -> Result<(), SimpleError> {
+    let status = try_with!(Command::new(CLI).args(args).status(), "unable to run {} command line", CLI);
+    if !status.success() {
+        bail!("{} command line failed({})", CLI, status);
+    }
+    Ok(())
+}
+
+/// Returns statement replacing `table` with records of csv `staging` (dropping table when nothing was staged).
+fn replace_table_sql(table: &str, staging: Option<&str>) -> String {
+    match staging {
+        Some(staging) => format!(
+            "BEGIN; CREATE OR REPLACE TABLE \"{}\" AS SELECT * FROM read_csv('{}', header = true); COMMIT;",
+            table,
+            staging.replace('\'', "''")
+        ),
+        None => format!("DROP TABLE IF EXISTS \"{}\";", table),
+    }
+}
+
+/// Returns csv field of record `value` (nulls empty).
+fn text(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(value) => value.clone(),
+        value => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::testing::TempDir;
+
+    #[test]
+    fn database_paths_matched() {
+        assert_eq!(database_path("duckdb://results.duckdb"), Some("results.duckdb"));
+        assert_eq!(database_path("accounts.csv"), None);
+    }
+
+    #[test]
+    fn missing_command_line_fails_when_opened() {
+        let e = installed("accounts-aggregate-missing-duckdb").unwrap_err();
+        assert!(e.to_string().starts_with("accounts-aggregate-missing-duckdb command line not found on PATH"));
+    }
+
+    #[test]
+    fn records_staged_before_replacing_table() {
+        let directory = TempDir::new("duckdb");
+        let path = directory.join("accounts.duckdb");
+        let mut sink = DuckdbSink::staged(path.to_str().unwrap(), "accounts").unwrap();
+        let record = serde_json::json!({ "client": 1, "available": "1.5", "held": null, "locked": false });
+        sink.write(record.as_object().unwrap()).unwrap();
+        sink.writer.flush().unwrap();
+        assert_eq!(fs::read_to_string(&sink.staging).unwrap(), "client,available,held,locked\n1,1.5,,false\n");
+
+        assert_eq!(
+            replace_table_sql("accounts", Some("it's.csv")),
+            "BEGIN; CREATE OR REPLACE TABLE \"accounts\" AS SELECT * FROM read_csv('it''s.csv', header = true); COMMIT;"
+        );
+        assert_eq!(replace_table_sql("events", None), "DROP TABLE IF EXISTS \"events\";");
+    }
+}
@@ -0,0 +1,158 @@
+//! Accounts owned by a single thread applying commands requested by API servers (`serve` subcommand).
+//!
+//! Servers (REST and gRPC) queue requests through an `Engine` handle to the thread owning the store and await its
+//! reply, so commands are applied one request at a time in the order received (as in batch runs) without locking
+//! accounts. Commands of a request are flushed to the store before it is answered and events applied are broadcast to
+//! subscribers (server-sent events and gRPC event streams).
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+
+use simple_error::*;
+use tokio::sync::{broadcast, oneshot};
+
+use crate::events::{Cause, Effects};
+use crate::models::{Account, AccountMetadata, ClientId, Command, Event};
+use crate::shards::Handler;
+use crate::store::ProjectionStore;
+
+/// Events buffered per subscriber before it lags (and is disconnected).
+pub const SUBSCRIBER_CAPACITY: usize = 1_024;
+
+/// Sender of reply to a request.
+type Reply<T> = oneshot::Sender<Result<T, SimpleError>>;
+
+/// Request queued to the thread owning accounts.
+enum Request {
+    Apply(Vec<Command>, Reply<Vec<Applied>>),
+    Account(ClientId, Reply<Option<Account>>),
+    #[cfg(feature = "server")]
+    Accounts(Reply<Vec<Account>>),
+    #[cfg(feature = "server")]
+    Probe(Reply<()>),
+}
+
+/// Command handled with events applied or reason rejected.
+pub struct Applied {
+    pub command: Command,
+    pub result: Result<Effects<Event>, String>,
+}
+
+/// Handle of servers queueing requests to the thread owning accounts.
+#[derive(Clone)]
+pub struct Engine {
+    requests: Sender<Request>,
+    events: broadcast::Sender<(ClientId, Event)>,
+}
+
+/// Requests queued by `Engine` handles (handled by the thread owning accounts).
+pub struct Requests {
+    requests: Receiver<Request>,
+    events: broadcast::Sender<(ClientId, Event)>,
+}
+
+/// Returns new `Engine` handle and the `Requests` it queues.
+pub fn channel() -> (Engine, Requests) {
+    let (sender, requests) = mpsc::channel();
+    let (events, _) = broadcast::channel(SUBSCRIBER_CAPACITY);
+    (Engine { requests: sender, events: events.clone() }, Requests { requests, events })
+}
+
+impl Engine {
+    /// Returns outcomes of `commands` once applied (and flushed) in order.
+    pub async fn apply(&self, commands: Vec<Command>) -> Result<Vec<Applied>, SimpleError> {
+        self.queue(|reply| { Request::Apply(commands, reply) }).await
+    }
+
+    /// Returns account of `client` (if any).
+    pub async fn account(&self, client: ClientId) -> Result<Option<Account>, SimpleError> {
+        self.queue(|reply| { Request::Account(client, reply) }).await
+    }
+
+    /// Returns every account ordered by client.
+    #[cfg(feature = "server")]
+    pub async fn accounts(&self) -> Result<Vec<Account>, SimpleError> {
+        self.queue(Request::Accounts).await
+    }
+
+    /// Returns once the store answered a probe (reading an account).
+    #[cfg(feature = "server")]
+    pub async fn probe(&self) -> Result<(), SimpleError> {
+        self.queue(Request::Probe).await
+    }
+
+    /// Returns receiver of events (and their client) applied once subscribed.
+    pub fn subscribe(&self) -> broadcast::Receiver<(ClientId, Event)> {
+        self.events.subscribe()
+    }
+
+    /// Returns reply of `request` queued to the thread owning accounts.
+    async fn queue<T, R>(&self, request: R) -> Result<T, SimpleError>
+    where
+        R: FnOnce(Reply<T>) -> Request,
+    {
+        let (reply, replied) = oneshot::channel();
+        if self.requests.send(request(reply)).is_err() {
+            bail!("accounts unavailable");
+        }
+        match replied.await {
+            Ok(reply) => reply,
+            Err(_) => bail!("accounts unavailable"),
+        }
+    }
+}
+
+impl Requests {
+    /// Handles requests applying commands to `accounts` using `handler` (accounts opened with `metadata`).
+    ///
+    /// Requests are handled by the calling thread until every `Engine` handle is dropped (servers stop).
+    pub fn run(self, accounts: &mut dyn ProjectionStore, metadata: &HashMap<ClientId, AccountMetadata>, handler: Handler) {
+        for request in self.requests {
+            match request {
+                Request::Apply(commands, reply) => {
+                    let applied: Vec<Applied> = commands.into_iter().map(|command| {
+                        let result = handler(accounts, metadata, command.clone()).map_err(|e| { e.to_string() });
+                        Applied { command, result }
+                    }).collect();
+                    let flushed = accounts.flush();
+                    for applied in applied.iter() {
+                        for event in applied.result.iter().flatten() {
+                            // events are dropped when nobody subscribed
+                            self.events.send((applied.command.actor_id(), event.clone())).ok();
+                        }
+                    }
+                    reply.send(flushed.map(|_| { applied })).ok();
+                }
+                Request::Account(client, reply) => {
+                    reply.send(accounts.get(client).map(|account| { account.map(|account| { account.into_owned() }) })).ok();
+                }
+                #[cfg(feature = "server")]
+                Request::Accounts(reply) => {
+                    reply.send(accounts.iter().map(|accounts| { accounts.map(|account| { account.into_owned() }).collect() })).ok();
+                }
+                #[cfg(feature = "server")]
+                Request::Probe(reply) => {
+                    reply.send(accounts.get(0).map(|_| {})).ok();
+                }
+            }
+        }
+    }
+}
+
+/// Returns thread named `name` running `server` on its own runtime until it stops.
+pub fn spawn<F, S>(name: &str, server: S) -> Result<JoinHandle<Result<(), SimpleError>>, SimpleError>
+where
+    F: Future<Output = Result<(), SimpleError>>,
+    S: FnOnce() -> F + Send + 'static,
+{
+    let thread = thread::Builder::new().name(name.to_string()).spawn(move || {
+        let runtime = try_with!(
+            tokio::runtime::Builder::new_current_thread().enable_all().build(),
+            "unable to start server runtime"
+        );
+        runtime.block_on(server())
+    });
+    Ok(try_with!(thread, "unable to spawn {}", name))
+}
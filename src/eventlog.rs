@@ -0,0 +1,217 @@
+//! Event logs persisting the event stream of `Account` aggregates as length-prefixed frames.
+//!
+//! Supported formats:
+//! - `protobuf` length-delimited (varint prefixed) `AccountEvent` messages for consumers in other languages
+//! - `bincode` frames prefixed by a little-endian `u32` length for compact archival and replay
+
+use std::io::{self, Read, Write};
+use std::str::FromStr;
+use std::convert::TryFrom;
+
+use prost::Message;
+use simple_error::SimpleError;
+
+use crate::models::{ClientId, Event};
+use crate::proto::{self, AccountEvent};
+
+/// Maximum number of bytes of a varint encoded frame length.
+const MAX_VARINT_BYTES: usize = 10;
+
+/// Format of event log frames.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EventLogFormat {
+    /// Length-delimited protobuf `AccountEvent` messages.
+    Protobuf,
+    /// Bincode encoded client and event prefixed by frame length.
+    Bincode,
+}
+
+impl EventLogFormat {
+    /// Returns names of supported formats used for cli arguments.
+    pub fn names() -> Vec<&'static str> {
+        vec!["protobuf", "bincode"]
+    }
+}
+
+impl FromStr for EventLogFormat {
+    type Err = SimpleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "protobuf" => Ok(EventLogFormat::Protobuf),
+            "bincode" => Ok(EventLogFormat::Bincode),
+            _ => Err(SimpleError::new(format!("unsupported event log format({})", s))),
+        }
+    }
+}
+
+/// Writes `Account` events to `W` as frames of an `EventLogFormat`.
+pub struct EventLogWriter<W: Write> {
+    format: EventLogFormat,
+    writer: W,
+}
+
+impl<W: Write> EventLogWriter<W> {
+    /// Returns new `EventLogWriter` writing `format` frames to `writer`.
+    pub fn new(format: EventLogFormat, writer: W) -> Self {
+        EventLogWriter { format, writer }
+    }
+
+    /// Writes `event` of `client` account to event log.
+    pub fn write(&mut self, client: ClientId, event: &Event) -> io::Result<()> {
+        match self.format {
+            EventLogFormat::Protobuf => {
+                let message = AccountEvent { client: client as u32, event: Some(proto::Event::from(event)) };
+                self.writer.write_all(&message.encode_length_delimited_to_vec())
+            }
+            EventLogFormat::Bincode => {
+                let frame = bincode::serialize(&(client, event)).map_err(|e| { invalid_data(e) })?;
+                self.writer.write_all(&(frame.len() as u32).to_le_bytes())?;
+                self.writer.write_all(&frame)
+            }
+        }
+    }
+
+    /// Flushes underlying writer.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Reads `Account` events from `R` having frames of an `EventLogFormat`.
+///
+/// Iterates client and event of each frame until the end of the log.
+pub struct EventLogReader<R: Read> {
+    format: EventLogFormat,
+    reader: R,
+}
+
+impl<R: Read> EventLogReader<R> {
+    /// Returns new `EventLogReader` reading `format` frames from `reader`.
+    pub fn new(format: EventLogFormat, reader: R) -> Self {
+        EventLogReader { format, reader }
+    }
+
+    /// Returns length of next frame or none at end of log.
+    fn frame_length(&mut self) -> io::Result<Option<usize>> {
+        match self.format {
+            EventLogFormat::Protobuf => {
+                let mut length: u64 = 0;
+                for index in 0..MAX_VARINT_BYTES {
+                    let mut byte = [0u8; 1];
+                    if self.reader.read(&mut byte)? == 0 {
+                        if index == 0 {
+                            return Ok(None);
+                        }
+                        return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+                    }
+                    length |= ((byte[0] & 0x7f) as u64) << (index * 7);
+                    if byte[0] & 0x80 == 0 {
+                        return Ok(Some(length as usize));
+                    }
+                }
+                Err(invalid_data("event log frame length exceeds 64 bits"))
+            }
+            EventLogFormat::Bincode => {
+                let mut length = [0u8; 4];
+                let read = self.reader.read(&mut length)?;
+                if read == 0 {
+                    return Ok(None);
+                }
+                self.reader.read_exact(&mut length[read..])?;
+                Ok(Some(u32::from_le_bytes(length) as usize))
+            }
+        }
+    }
+
+    /// Returns client and event of next frame or none at end of log.
+    fn read_frame(&mut self) -> io::Result<Option<(ClientId, Event)>> {
+        let length = match self.frame_length()? {
+            Some(length) => length,
+            None => return Ok(None),
+        };
+        let mut frame = vec![0u8; length];
+        self.reader.read_exact(&mut frame)?;
+        let record = match self.format {
+            EventLogFormat::Protobuf => {
+                let message = AccountEvent::decode(frame.as_slice()).map_err(|e| { invalid_data(e) })?;
+                let event = match message.event {
+                    Some(event) => Event::try_from(event).map_err(|e| { invalid_data(e) })?,
+                    None => return Err(invalid_data(format!("event is none for account({})", message.client))),
+                };
+                let client = ClientId::try_from(message.client).map_err(|e| { invalid_data(e) })?;
+                (client, event)
+            }
+            EventLogFormat::Bincode => bincode::deserialize(&frame).map_err(|e| { invalid_data(e) })?,
+        };
+        Ok(Some(record))
+    }
+}
+
+impl<R: Read> Iterator for EventLogReader<R> {
+    type Item = io::Result<(ClientId, Event)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_frame().transpose()
+    }
+}
+
+/// Returns `io::Error` for invalid event log data.
+fn invalid_data<E: Into<Box<dyn std::error::Error + Send + Sync>>>(error: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::prelude::Decimal;
+
+    fn events() -> Vec<(ClientId, Event)> {
+        vec![
+            (1, Event::Credited {
+                version: 1,
+                key: [1; 16],
+                timestamp: None,
+                tx: 10,
+                wallet: String::from("main"),
+                merchant: Some(7),
+                category: None,
+                amount: Decimal::new(990000, 4)
+            }),
+            (2, Event::Locked { version: 2, key: [2; 16], timestamp: None }),
+        ]
+    }
+
+    fn round_trip(format: EventLogFormat) -> Vec<(ClientId, Event)> {
+        let mut buffer = vec![];
+        {
+            let mut writer = EventLogWriter::new(format, &mut buffer);
+            for (client, event) in events() {
+                writer.write(client, &event).unwrap();
+            }
+            writer.flush().unwrap();
+        }
+        EventLogReader::new(format, buffer.as_slice()).map(|record| { record.unwrap() }).collect()
+    }
+
+    #[test]
+    fn protobuf_event_log_round_trip() {
+        assert_eq!(round_trip(EventLogFormat::Protobuf), events());
+    }
+
+    #[test]
+    fn bincode_event_log_round_trip() {
+        assert_eq!(round_trip(EventLogFormat::Bincode), events());
+    }
+
+    #[test]
+    fn truncated_event_log_errors() {
+        let mut buffer = vec![];
+        EventLogWriter::new(EventLogFormat::Bincode, &mut buffer).write(1, &events()[0].1).unwrap();
+        buffer.pop();
+
+        let mut reader = EventLogReader::new(EventLogFormat::Bincode, buffer.as_slice());
+
+        assert!(reader.next().unwrap().is_err());
+    }
+}
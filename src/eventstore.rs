@@ -0,0 +1,302 @@
+//! Append-only stores of account event streams kept apart from `Account` projections.
+//!
+//! `SegmentEventStore` appends events to numbered segment files of a directory as frames of a little-endian `u32`
+//! length, `u32` CRC-32 checksum and bincode encoded client and event. Segments roll over once they reach
+//! `SEGMENT_BYTES` and frames are indexed by client when the store is opened.
+
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use simple_error::*;
+
+use crate::models::{ClientId, Event};
+
+/// Bytes of frame headers (length and checksum).
+const HEADER_BYTES: usize = 8;
+
+/// Bytes segments roll over at.
+const SEGMENT_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Extension of segment files.
+const SEGMENT_EXTENSION: &str = "seg";
+
+/// Store of account event streams.
+pub trait EventStore {
+    /// Appends `events` to stream of `client`.
+    fn append(&mut self, client: ClientId, events: &[Event]) -> Result<(), SimpleError>;
+
+    /// Returns events of `client` in order appended.
+    #[allow(dead_code)]
+    fn read(&self, client: ClientId) -> Result<Vec<Event>, SimpleError>;
+
+    /// Returns client and event of every event in order appended.
+    fn read_all(&self) -> Result<Vec<Record>, SimpleError>;
+
+    /// Writes appended events to disk.
+    fn flush(&mut self) -> Result<(), SimpleError>;
+}
+
+/// Client and event of a frame.
+type Record = (ClientId, Event);
+
+/// Position of a frame in a segment.
+#[derive(Debug, Clone, Copy)]
+struct Position {
+    segment: u32,
+    offset: u64,
+}
+
+/// Events appended to CRC-checked frames of segment files in a directory.
+pub struct SegmentEventStore {
+    directory: PathBuf,
+    /// Segment appended to (segments are numbered from zero).
+    segment: u32,
+    file: File,
+    size: u64,
+    segment_bytes: u64,
+    index: HashMap<ClientId, Vec<Position>>,
+}
+
+impl SegmentEventStore {
+    /// Returns event store opened (created when missing) at `directory`.
+    ///
+    /// Frames of every segment are verified and indexed. A frame torn at the end of the last segment (interrupted
+    /// append) is truncated, any other invalid frame is an error.
+    pub fn open<P: AsRef<Path>>(directory: P) -> Result<Self, SimpleError> {
+        let directory = directory.as_ref().to_path_buf();
+        try_with!(fs::create_dir_all(&directory), "unable to create event store({})", directory.display());
+        let mut segments = vec![];
+        for entry in try_with!(fs::read_dir(&directory), "unable to read event store({})", directory.display()) {
+            let path = try_with!(entry, "unable to read event store({})", directory.display()).path();
+            if path.extension().is_some_and(|extension| { extension == SEGMENT_EXTENSION }) {
+                let stem = path.file_stem().and_then(|stem| { stem.to_str() }).unwrap_or_default();
+                segments.push(try_with!(stem.parse::<u32>(), "invalid event store segment({})", path.display()));
+            }
+        }
+        segments.sort_unstable();
+        if segments.iter().enumerate().any(|(index, segment)| { index as u32 != *segment }) {
+            bail!("event store({}) segments are not contiguous", directory.display());
+        }
+
+        let mut index: HashMap<ClientId, Vec<Position>> = HashMap::new();
+        let segment = segments.last().copied().unwrap_or(0);
+        let mut size = 0;
+        for segment in segments.iter().copied() {
+            let path = segment_path(&directory, segment);
+            let bytes = try_with!(fs::read(&path), "unable to read event store segment({})", path.display());
+            let mut offset = 0;
+            while let Some(((client, _), length)) = decode_frame::<Record>(&bytes[offset..])
+                .map_err(|e| { SimpleError::new(format!("event store segment({}) frame({}) {}", path.display(), offset, e)) })?
+            {
+                index.entry(client).or_default().push(Position { segment, offset: offset as u64 });
+                offset += length;
+            }
+            if offset < bytes.len() && segment != *segments.last().unwrap() {
+                bail!("event store segment({}) frame({}) is incomplete", path.display(), offset);
+            }
+            size = offset as u64;
+        }
+
+        let path = segment_path(&directory, segment);
+        let file = try_with!(
+            OpenOptions::new().create(true).append(true).open(&path),
+            "unable to open event store segment({})",
+            path.display()
+        );
+        // drop frame torn by an interrupted append
+        try_with!(file.set_len(size), "unable to truncate event store segment({})", path.display());
+        Ok(SegmentEventStore { directory, segment, file, size, segment_bytes: SEGMENT_BYTES, index })
+    }
+
+    /// Opens next segment to append to.
+    fn roll(&mut self) -> Result<(), SimpleError> {
+        self.flush()?;
+        let path = segment_path(&self.directory, self.segment + 1);
+        self.file = try_with!(
+            OpenOptions::new().create_new(true).append(true).open(&path),
+            "unable to create event store segment({})",
+            path.display()
+        );
+        self.segment += 1;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl EventStore for SegmentEventStore {
+    /// Appends `events` of `client` as frames of one write (rolling over to a new segment when full).
+    fn append(&mut self, client: ClientId, events: &[Event]) -> Result<(), SimpleError> {
+        let mut frames = vec![];
+        let mut offsets = vec![];
+        for event in events {
+            offsets.push(frames.len() as u64);
+            frames.extend(try_with!(encode_frame(&(client, event)), "unable to encode account({}) event", client));
+        }
+        if self.size > 0 && self.size + frames.len() as u64 > self.segment_bytes {
+            self.roll()?;
+        }
+        try_with!(self.file.write_all(&frames), "unable to append account({}) events", client);
+        let positions = self.index.entry(client).or_default();
+        for offset in offsets {
+            positions.push(Position { segment: self.segment, offset: self.size + offset });
+        }
+        self.size += frames.len() as u64;
+        Ok(())
+    }
+
+    fn read(&self, client: ClientId) -> Result<Vec<Event>, SimpleError> {
+        let mut events = vec![];
+        let mut file: Option<(u32, File)> = None;
+        for position in self.index.get(&client).into_iter().flatten() {
+            if file.as_ref().is_none_or(|(segment, _)| { *segment != position.segment }) {
+                let path = segment_path(&self.directory, position.segment);
+                let segment = try_with!(File::open(&path), "unable to open event store segment({})", path.display());
+                file = Some((position.segment, segment));
+            }
+            let (_, segment) = file.as_mut().unwrap();
+            try_with!(segment.seek(SeekFrom::Start(position.offset)), "unable to read account({}) events", client);
+            let mut frame = vec![0u8; HEADER_BYTES];
+            try_with!(segment.read_exact(&mut frame), "unable to read account({}) events", client);
+            let length = u32::from_le_bytes(frame[0..4].try_into().unwrap()) as usize;
+            frame.resize(HEADER_BYTES + length, 0);
+            try_with!(segment.read_exact(&mut frame[HEADER_BYTES..]), "unable to read account({}) events", client);
+            match try_with!(decode_frame::<Record>(&frame), "unable to read account({}) events", client) {
+                Some(((_, event), _)) => events.push(event),
+                None => bail!("account({}) event frame is incomplete", client),
+            }
+        }
+        Ok(events)
+    }
+
+    fn read_all(&self) -> Result<Vec<Record>, SimpleError> {
+        let mut records = vec![];
+        for segment in 0..=self.segment {
+            let path = segment_path(&self.directory, segment);
+            let bytes = try_with!(fs::read(&path), "unable to read event store segment({})", path.display());
+            let mut offset = 0;
+            while let Some((record, length)) = try_with!(decode_frame(&bytes[offset..]), "unable to read event store segment({})", path.display()) {
+                records.push(record);
+                offset += length;
+            }
+        }
+        Ok(records)
+    }
+
+    fn flush(&mut self) -> Result<(), SimpleError> {
+        try_with!(self.file.sync_data(), "unable to flush event store({})", self.directory.display());
+        Ok(())
+    }
+}
+
+/// Returns path of `segment` file in `directory`.
+fn segment_path(directory: &Path, segment: u32) -> PathBuf {
+    directory.join(format!("{:08}.{}", segment, SEGMENT_EXTENSION))
+}
+
+/// Returns frame of `record` (length, checksum and bincode encoded record).
+pub fn encode_frame<T: Serialize>(record: &T) -> Result<Vec<u8>, SimpleError> {
+    let payload = try_with!(bincode::serialize(record), "unable to encode frame");
+    let mut frame = Vec::with_capacity(HEADER_BYTES + payload.len());
+    frame.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    frame.extend_from_slice(&crc32fast::hash(&payload).to_le_bytes());
+    frame.extend_from_slice(&payload);
+    Ok(frame)
+}
+
+/// Returns record and length of frame at start of `bytes` (none when `bytes` ends before the frame).
+pub fn decode_frame<T: DeserializeOwned>(bytes: &[u8]) -> Result<Option<(T, usize)>, SimpleError> {
+    if bytes.len() < HEADER_BYTES {
+        return Ok(None);
+    }
+    let length = u32::from_le_bytes(bytes[0..4].try_into().unwrap()) as usize;
+    let checksum = u32::from_le_bytes(bytes[4..8].try_into().unwrap());
+    let end = HEADER_BYTES + length;
+    if bytes.len() < end {
+        return Ok(None);
+    }
+    let payload = &bytes[HEADER_BYTES..end];
+    if crc32fast::hash(payload) != checksum {
+        bail!("checksum mismatch");
+    }
+    Ok(Some((try_with!(bincode::deserialize(payload), "unable to decode frame"), end)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::prelude::Decimal;
+
+    use crate::testing::TempDir;
+
+    fn credited(version: u32) -> Event {
+        Event::Credited {
+            version,
+            key: [version as u8; 16],
+            timestamp: None,
+            tx: version,
+            wallet: String::from("main"),
+            merchant: None,
+            category: None,
+            amount: Decimal::new(15, 1)
+        }
+    }
+
+    #[test]
+    fn segment_event_store_reads_streams_across_segments() {
+        let directory = TempDir::new("eventstore");
+        {
+            let mut store = SegmentEventStore::open(directory.path()).unwrap();
+            store.segment_bytes = 64;
+            store.append(1, &[credited(1), credited(2)]).unwrap();
+            store.append(2, &[credited(1)]).unwrap();
+            store.append(1, &[credited(3)]).unwrap();
+            assert!(store.segment > 0);
+            assert_eq!(store.read(1).unwrap(), vec![credited(1), credited(2), credited(3)]);
+            store.flush().unwrap();
+        }
+        // streams are indexed when reopened
+        let store = SegmentEventStore::open(directory.path()).unwrap();
+        assert_eq!(store.read(2).unwrap(), vec![credited(1)]);
+        assert!(store.read(3).unwrap().is_empty());
+        let clients: Vec<ClientId> = store.read_all().unwrap().iter().map(|(client, _)| { *client }).collect();
+        assert_eq!(clients, vec![1, 1, 2, 1]);
+    }
+
+    #[test]
+    fn segment_event_store_truncates_torn_frame() {
+        let directory = TempDir::new("eventstore-torn");
+        {
+            let mut store = SegmentEventStore::open(directory.path()).unwrap();
+            store.append(1, &[credited(1)]).unwrap();
+        }
+        let path = segment_path(directory.path(), 0);
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&[42, 0, 0, 0, 1]).unwrap();
+        drop(file);
+
+        let mut store = SegmentEventStore::open(directory.path()).unwrap();
+        store.append(1, &[credited(2)]).unwrap();
+        assert_eq!(store.read(1).unwrap(), vec![credited(1), credited(2)]);
+    }
+
+    #[test]
+    fn segment_event_store_rejects_corrupt_frame() {
+        let directory = TempDir::new("eventstore-corrupt");
+        {
+            let mut store = SegmentEventStore::open(directory.path()).unwrap();
+            store.append(1, &[credited(1)]).unwrap();
+        }
+        let path = segment_path(directory.path(), 0);
+        let mut bytes = fs::read(&path).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+        fs::write(&path, bytes).unwrap();
+
+        assert!(SegmentEventStore::open(directory.path()).is_err());
+    }
+}
@@ -0,0 +1,326 @@
+//! Exporters rendering client `Statement`s in formats imported by accounting and banking software.
+//!
+//! Statements are exported per client using the total balance (held funds are not itemized).
+
+use std::fmt::Write;
+
+use chrono::Duration;
+
+use crate::models::{Currency, Timestamp};
+use crate::projections::Statement;
+
+/// Currency of exported statements.
+const CURRENCY: &str = "USD";
+
+/// Returns OFX (2.2) bank statement document of `statement` generated at `generated`.
+///
+/// Events changing the total balance become `CREDIT` or `DEBIT` transactions using transaction id as `FITID`.
+/// Undated events are posted at `generated`.
+pub fn ofx(statement: &Statement, generated: Timestamp) -> String {
+    let mut document = String::new();
+    let start = period_start(statement, generated);
+    let end = statement.to().unwrap_or(generated);
+    // writing to a string cannot fail
+    let _ = write_ofx(&mut document, statement, generated, start, end);
+    document
+}
+
+fn write_ofx(
+    document: &mut String,
+    statement: &Statement,
+    generated: Timestamp,
+    start: Timestamp,
+    end: Timestamp
+) -> std::fmt::Result {
+    writeln!(document, "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"no\"?>")?;
+    writeln!(document, "<?OFX OFXHEADER=\"200\" VERSION=\"220\" SECURITY=\"NONE\" OLDFILEUID=\"NONE\" NEWFILEUID=\"NONE\"?>")?;
+    writeln!(document, "<OFX>")?;
+    writeln!(document, "  <SIGNONMSGSRSV1>")?;
+    writeln!(document, "    <SONRS>")?;
+    writeln!(document, "      <STATUS><CODE>0</CODE><SEVERITY>INFO</SEVERITY></STATUS>")?;
+    writeln!(document, "      <DTSERVER>{}</DTSERVER>", ofx_date(generated))?;
+    writeln!(document, "      <LANGUAGE>ENG</LANGUAGE>")?;
+    writeln!(document, "    </SONRS>")?;
+    writeln!(document, "  </SIGNONMSGSRSV1>")?;
+    writeln!(document, "  <BANKMSGSRSV1>")?;
+    writeln!(document, "    <STMTTRNRS>")?;
+    writeln!(document, "      <TRNUID>{}</TRNUID>", statement.client())?;
+    writeln!(document, "      <STATUS><CODE>0</CODE><SEVERITY>INFO</SEVERITY></STATUS>")?;
+    writeln!(document, "      <STMTRS>")?;
+    writeln!(document, "        <CURDEF>{}</CURDEF>", CURRENCY)?;
+    writeln!(document, "        <BANKACCTFROM>")?;
+    writeln!(document, "          <BANKID>000000000</BANKID>")?;
+    writeln!(document, "          <ACCTID>{}</ACCTID>", statement.client())?;
+    writeln!(document, "          <ACCTTYPE>CHECKING</ACCTTYPE>")?;
+    writeln!(document, "        </BANKACCTFROM>")?;
+    writeln!(document, "        <BANKTRANLIST>")?;
+    writeln!(document, "          <DTSTART>{}</DTSTART>", ofx_date(start))?;
+    writeln!(document, "          <DTEND>{}</DTEND>", ofx_date(end))?;
+    for line in statement.transactions() {
+        let change = line.change();
+        if change.is_zero() {
+            continue;
+        }
+        writeln!(document, "          <STMTTRN>")?;
+        writeln!(document, "            <TRNTYPE>{}</TRNTYPE>", if change.is_sign_negative() { "DEBIT" } else { "CREDIT" })?;
+        writeln!(document, "            <DTPOSTED>{}</DTPOSTED>", ofx_date(line.timestamp().unwrap_or(generated)))?;
+        writeln!(document, "            <TRNAMT>{}</TRNAMT>", amount(change))?;
+        writeln!(document, "            <FITID>{}</FITID>", line.tx().unwrap_or_default())?;
+        writeln!(document, "            <NAME>{}</NAME>", line.event())?;
+        writeln!(document, "          </STMTTRN>")?;
+    }
+    writeln!(document, "        </BANKTRANLIST>")?;
+    writeln!(document, "        <LEDGERBAL>")?;
+    writeln!(document, "          <BALAMT>{}</BALAMT>", amount(statement.closing()))?;
+    writeln!(document, "          <DTASOF>{}</DTASOF>", ofx_date(end))?;
+    writeln!(document, "        </LEDGERBAL>")?;
+    writeln!(document, "      </STMTRS>")?;
+    writeln!(document, "    </STMTTRNRS>")?;
+    writeln!(document, "  </BANKMSGSRSV1>")?;
+    writeln!(document, "</OFX>")
+}
+
+/// Returns ISO 20022 camt.053 (bank to customer statement) XML document of `statement` generated at `generated`.
+///
+/// Events changing the total balance become booked `Ntry` entries using transaction id as `NtryRef`.
+/// Opening (`OPBD`) and closing (`CLBD`) balances are booked on the first and last day of the period.
+pub fn camt053(statement: &Statement, generated: Timestamp) -> String {
+    let mut document = String::new();
+    let start = period_start(statement, generated);
+    let end = statement.to().unwrap_or(generated);
+    // writing to a string cannot fail
+    let _ = write_camt053(&mut document, statement, generated, start, end);
+    document
+}
+
+fn write_camt053(
+    document: &mut String,
+    statement: &Statement,
+    generated: Timestamp,
+    start: Timestamp,
+    end: Timestamp
+) -> std::fmt::Result {
+    writeln!(document, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+    writeln!(document, "<Document xmlns=\"urn:iso:std:iso:20022:tech:xsd:camt.053.001.02\">")?;
+    writeln!(document, "  <BkToCstmrStmt>")?;
+    writeln!(document, "    <GrpHdr>")?;
+    writeln!(document, "      <MsgId>{}{}</MsgId>", generated.format("%Y%m%d%H%M%S"), statement.client())?;
+    writeln!(document, "      <CreDtTm>{}</CreDtTm>", iso_date_time(generated))?;
+    writeln!(document, "    </GrpHdr>")?;
+    writeln!(document, "    <Stmt>")?;
+    writeln!(document, "      <Id>{}</Id>", statement.client())?;
+    writeln!(document, "      <CreDtTm>{}</CreDtTm>", iso_date_time(generated))?;
+    writeln!(document, "      <FrToDt>")?;
+    writeln!(document, "        <FrDtTm>{}</FrDtTm>", iso_date_time(start))?;
+    writeln!(document, "        <ToDtTm>{}</ToDtTm>", iso_date_time(end))?;
+    writeln!(document, "      </FrToDt>")?;
+    writeln!(document, "      <Acct>")?;
+    writeln!(document, "        <Id><Othr><Id>{}</Id></Othr></Id>", statement.client())?;
+    writeln!(document, "        <Ccy>{}</Ccy>", CURRENCY)?;
+    writeln!(document, "      </Acct>")?;
+    // period end is excluded so closing balance is booked on the previous second (last day)
+    write_camt053_balance(document, "OPBD", statement.opening(), start)?;
+    write_camt053_balance(document, "CLBD", statement.closing(), end - Duration::seconds(1))?;
+    for line in statement.transactions() {
+        let change = line.change();
+        if change.is_zero() {
+            continue;
+        }
+        let date = line.timestamp().unwrap_or(generated);
+        writeln!(document, "      <Ntry>")?;
+        writeln!(document, "        <NtryRef>{}</NtryRef>", line.tx().unwrap_or_default())?;
+        writeln!(document, "        <Amt Ccy=\"{}\">{}</Amt>", CURRENCY, amount(change.abs()))?;
+        writeln!(document, "        <CdtDbtInd>{}</CdtDbtInd>", credit_debit(change))?;
+        writeln!(document, "        <Sts>BOOK</Sts>")?;
+        writeln!(document, "        <BookgDt><DtTm>{}</DtTm></BookgDt>", iso_date_time(date))?;
+        writeln!(document, "        <ValDt><Dt>{}</Dt></ValDt>", date.format("%Y-%m-%d"))?;
+        writeln!(document, "        <BkTxCd><Prtry><Cd>{}</Cd></Prtry></BkTxCd>", line.event())?;
+        writeln!(document, "      </Ntry>")?;
+    }
+    writeln!(document, "    </Stmt>")?;
+    writeln!(document, "  </BkToCstmrStmt>")?;
+    writeln!(document, "</Document>")
+}
+
+fn write_camt053_balance(document: &mut String, code: &str, balance: Currency, timestamp: Timestamp) -> std::fmt::Result {
+    writeln!(document, "      <Bal>")?;
+    writeln!(document, "        <Tp><CdOrPrtry><Cd>{}</Cd></CdOrPrtry></Tp>", code)?;
+    writeln!(document, "        <Amt Ccy=\"{}\">{}</Amt>", CURRENCY, amount(balance.abs()))?;
+    writeln!(document, "        <CdtDbtInd>{}</CdtDbtInd>", credit_debit(balance))?;
+    writeln!(document, "        <Dt><Dt>{}</Dt></Dt>", timestamp.format("%Y-%m-%d"))?;
+    writeln!(document, "      </Bal>")
+}
+
+/// Returns ISO 20022 credit (`CRDT`) or debit (`DBIT`) indicator of `amount`.
+fn credit_debit(amount: Currency) -> &'static str {
+    if amount.is_sign_negative() { "DBIT" } else { "CRDT" }
+}
+
+/// Returns ISO 8601 datetime (UTC) of `timestamp`.
+fn iso_date_time(timestamp: Timestamp) -> String {
+    timestamp.format("%Y-%m-%dT%H:%M:%SZ").to_string()
+}
+
+/// Returns SWIFT MT940 customer statement message text (block 4) of `statement` generated at `generated`.
+///
+/// Events changing the total balance become `:61:` statement lines using transaction id as reference.
+/// Fields are separated by CRLF and the message is terminated by `-`.
+pub fn mt940(statement: &Statement, generated: Timestamp) -> String {
+    let mut message = String::new();
+    let start = period_start(statement, generated);
+    // period end is excluded so closing balance is booked on the previous second (last day)
+    let end = statement.to().unwrap_or(generated) - Duration::seconds(1);
+    // writing to a string cannot fail
+    let _ = write_mt940(&mut message, statement, generated, start, end);
+    message
+}
+
+fn write_mt940(
+    message: &mut String,
+    statement: &Statement,
+    generated: Timestamp,
+    start: Timestamp,
+    end: Timestamp
+) -> std::fmt::Result {
+    write!(message, ":20:{}{}\r\n", generated.format("%y%m%d"), statement.client())?;
+    write!(message, ":25:{}\r\n", statement.client())?;
+    write!(message, ":28C:1\r\n")?;
+    write!(message, ":60F:{}\r\n", mt940_balance(statement.opening(), start))?;
+    for line in statement.transactions() {
+        let change = line.change();
+        if change.is_zero() {
+            continue;
+        }
+        let date = line.timestamp().unwrap_or(generated);
+        write!(
+            message,
+            ":61:{}{}{}{}NTRF{}\r\n",
+            date.format("%y%m%d"),
+            date.format("%m%d"),
+            if change.is_sign_negative() { "D" } else { "C" },
+            mt940_amount(change),
+            line.tx().unwrap_or_default()
+        )?;
+        write!(message, ":86:{}\r\n", line.event())?;
+    }
+    write!(message, ":62F:{}\r\n", mt940_balance(statement.closing(), end))?;
+    write!(message, "-\r\n")
+}
+
+/// Returns MT940 balance (mark, date, currency and amount) of `balance` at `timestamp`.
+fn mt940_balance(balance: Currency, timestamp: Timestamp) -> String {
+    let mark = if balance.is_sign_negative() { "D" } else { "C" };
+    format!("{}{}{}{}", mark, timestamp.format("%y%m%d"), CURRENCY, mt940_amount(balance))
+}
+
+/// Returns unsigned MT940 `amount` rounded to cents using a decimal comma.
+fn mt940_amount(amount: Currency) -> String {
+    format!("{:.2}", amount.abs()).replace('.', ",")
+}
+
+/// Returns start of `statement` period (first event when unbounded) or `generated` without events.
+fn period_start(statement: &Statement, generated: Timestamp) -> Timestamp {
+    statement.from()
+        .or_else(|| { statement.transactions().iter().find_map(|line| { line.timestamp() }) })
+        .unwrap_or(generated)
+}
+
+/// Returns OFX datetime (UTC) of `timestamp`.
+fn ofx_date(timestamp: Timestamp) -> String {
+    timestamp.format("%Y%m%d%H%M%S.%3f[0:GMT]").to_string()
+}
+
+/// Returns `amount` rounded to cents.
+fn amount(amount: Currency) -> String {
+    format!("{:.2}", amount)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    use crate::events::Actor;
+    use crate::models::{Account, Event};
+
+    fn statement() -> Statement {
+        let timestamp = Utc.with_ymd_and_hms(2024, 1, 15, 10, 0, 0).unwrap();
+        let mut account = Account::new(1);
+        account.apply([
+            Event::Credited {
+                version: 1,
+                key: [1; 16],
+                timestamp: Some(timestamp),
+                tx: 10,
+                wallet: String::from("main"),
+                merchant: None,
+                category: None,
+                amount: Currency::new(1000000, 4)
+            },
+            Event::Held { version: 1, key: [2; 16], timestamp: Some(timestamp), tx: 10, wallet: String::from("main"), amount: Currency::new(1000000, 4) },
+            Event::Debited {
+                version: 1,
+                key: [3; 16],
+                timestamp: Some(timestamp),
+                tx: 11,
+                wallet: String::from("main"),
+                merchant: None,
+                category: None,
+                amount: Currency::new(10000, 4)
+            },
+        ]);
+        let from = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let to = Utc.with_ymd_and_hms(2024, 2, 1, 0, 0, 0).unwrap();
+        Statement::from_account(&account, Some(from), Some(to))
+    }
+
+    #[test]
+    fn ofx_statement_exported() {
+        let generated = Utc.with_ymd_and_hms(2024, 2, 1, 12, 0, 0).unwrap();
+        let document = ofx(&statement(), generated);
+
+        assert!(document.contains("<DTSERVER>20240201120000.000[0:GMT]</DTSERVER>"));
+        assert!(document.contains("<ACCTID>1</ACCTID>"));
+        assert!(document.contains("<DTSTART>20240101000000.000[0:GMT]</DTSTART>"));
+        assert_eq!(document.matches("<STMTTRN>").count(), 2);
+        assert!(document.contains("<TRNTYPE>CREDIT</TRNTYPE>"));
+        assert!(document.contains("<TRNAMT>100.00</TRNAMT>"));
+        assert!(document.contains("<TRNTYPE>DEBIT</TRNTYPE>"));
+        assert!(document.contains("<TRNAMT>-1.00</TRNAMT>"));
+        assert!(document.contains("<BALAMT>99.00</BALAMT>"));
+    }
+
+    #[test]
+    fn camt053_statement_exported() {
+        let generated = Utc.with_ymd_and_hms(2024, 2, 1, 12, 0, 0).unwrap();
+        let document = camt053(&statement(), generated);
+
+        assert!(document.contains("<MsgId>202402011200001</MsgId>"));
+        assert!(document.contains("<FrDtTm>2024-01-01T00:00:00Z</FrDtTm>"));
+        assert!(document.contains("<Cd>OPBD</Cd>"));
+        assert!(document.contains("<Amt Ccy=\"USD\">99.00</Amt>\n        <CdtDbtInd>CRDT</CdtDbtInd>\n        <Dt><Dt>2024-01-31</Dt></Dt>"));
+        assert_eq!(document.matches("<Ntry>").count(), 2);
+        assert!(document.contains("<NtryRef>11</NtryRef>\n        <Amt Ccy=\"USD\">1.00</Amt>\n        <CdtDbtInd>DBIT</CdtDbtInd>"));
+        assert!(document.contains("<ValDt><Dt>2024-01-15</Dt></ValDt>"));
+    }
+
+    #[test]
+    fn mt940_statement_exported() {
+        let generated = Utc.with_ymd_and_hms(2024, 2, 1, 12, 0, 0).unwrap();
+        let message = mt940(&statement(), generated);
+
+        assert_eq!(
+            message,
+            ":20:2402011\r\n\
+            :25:1\r\n\
+            :28C:1\r\n\
+            :60F:C240101USD0,00\r\n\
+            :61:2401150115C100,00NTRF10\r\n\
+            :86:credited\r\n\
+            :61:2401150115D1,00NTRF11\r\n\
+            :86:debited\r\n\
+            :62F:C240131USD99,00\r\n\
+            -\r\n"
+        );
+    }
+}
@@ -0,0 +1,250 @@
+//! External sort of source commands by client (`--external-sort`).
+//!
+//! Commands read from every source are buffered in runs of up to `run_records` commands, each run sorted by client
+//! (commands of a client keep the order read) and spilled to a temporary file of frames. Runs are then merged so the
+//! commands of each client are handled contiguously: an account is only active while its commands are handled, thus
+//! memory used processing arbitrarily large sources is bounded by the run size (and accounts kept).
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::convert::TryInto;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::vec;
+
+use serde::{Deserialize, Serialize};
+use simple_error::{SimpleError, try_with};
+
+use crate::events::Cause;
+use crate::eventstore::{decode_frame, encode_frame};
+use crate::models::{ClientId, Command};
+
+/// Default commands buffered in memory before a sorted run is spilled.
+pub const RUN_RECORDS: usize = 1_000_000;
+
+/// Bytes of frame header (length and checksum) preceding payload.
+const HEADER_BYTES: usize = 8;
+
+/// Command read at `line` of source `source` (`sequence` orders commands of a client as read).
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SortedCommand {
+    pub source: usize,
+    pub line: usize,
+    sequence: u64,
+    pub command: Command,
+}
+
+impl SortedCommand {
+    /// Returns key commands are sorted by (client then order read).
+    fn key(&self) -> (ClientId, u64) {
+        (self.command.actor_id(), self.sequence)
+    }
+}
+
+/// Sorts commands by client spilling sorted runs to temporary files of `directory`.
+pub struct ExternalSorter {
+    run_records: usize,
+    directory: PathBuf,
+    buffer: Vec<SortedCommand>,
+    runs: Vec<PathBuf>,
+    sequence: u64,
+}
+
+impl ExternalSorter {
+    /// Returns new `ExternalSorter` buffering up to `run_records` commands per run.
+    pub fn new<P: AsRef<Path>>(run_records: usize, directory: P) -> Self {
+        ExternalSorter {
+            run_records: run_records.max(1),
+            directory: directory.as_ref().to_path_buf(),
+            buffer: vec![],
+            runs: vec![],
+            sequence: 0,
+        }
+    }
+
+    /// Adds `command` read at `line` of source `source` (spilling a sorted run once buffer is full).
+    pub fn push(&mut self, source: usize, line: usize, command: Command) -> Result<(), SimpleError> {
+        self.buffer.push(SortedCommand { source, line, sequence: self.sequence, command });
+        self.sequence += 1;
+        if self.buffer.len() >= self.run_records {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    /// Writes buffered commands sorted by client to a new run file.
+    fn spill(&mut self) -> Result<(), SimpleError> {
+        let path = self.directory.join(format!("accounts-aggregate-{}-run-{}.bin", process::id(), self.runs.len()));
+        let file = try_with!(File::create(&path), "unable to create run({})", path.display());
+        // run is removed (once created) whether or not written
+        self.runs.push(path.clone());
+        self.buffer.sort_unstable_by_key(SortedCommand::key);
+        let mut writer = BufWriter::new(file);
+        for command in self.buffer.drain(..) {
+            try_with!(writer.write_all(&encode_frame(&command)?), "unable to write run({})", path.display());
+        }
+        try_with!(writer.flush(), "unable to write run({})", path.display());
+        Ok(())
+    }
+
+    /// Returns commands pushed ordered by client (commands of a client in order pushed).
+    ///
+    /// Commands are read from runs as merged; runs are removed once merged (or merge is dropped).
+    pub fn finish(mut self) -> Result<SortedCommands, SimpleError> {
+        self.buffer.sort_unstable_by_key(SortedCommand::key);
+        let mut runs = vec![Run::Memory(std::mem::take(&mut self.buffer).into_iter())];
+        for path in std::mem::take(&mut self.runs) {
+            let file = try_with!(File::open(&path), "unable to open run({})", path.display());
+            runs.push(Run::File(BufReader::new(file), RunFile(path)));
+        }
+        let mut merge = SortedCommands { runs, heads: vec![], heap: BinaryHeap::new() };
+        for index in 0..merge.runs.len() {
+            let head = merge.runs[index].next()?;
+            if let Some(command) = head.as_ref() {
+                merge.heap.push(Reverse((command.key(), index)));
+            }
+            merge.heads.push(head);
+        }
+        Ok(merge)
+    }
+}
+
+impl Drop for ExternalSorter {
+    fn drop(&mut self) {
+        for path in self.runs.iter() {
+            fs::remove_file(path).ok();
+        }
+    }
+}
+
+/// Path of run file removed when dropped.
+struct RunFile(PathBuf);
+
+impl Drop for RunFile {
+    fn drop(&mut self) {
+        fs::remove_file(&self.0).ok();
+    }
+}
+
+/// Sorted run of commands (last run is kept in memory).
+enum Run {
+    Memory(vec::IntoIter<SortedCommand>),
+    File(BufReader<File>, RunFile),
+}
+
+impl Run {
+    /// Returns next command of run (none once every command is read).
+    fn next(&mut self) -> Result<Option<SortedCommand>, SimpleError> {
+        let (reader, path) = match self {
+            Run::Memory(commands) => return Ok(commands.next()),
+            Run::File(reader, RunFile(path)) => (reader, path),
+        };
+        let mut frame = vec![0; HEADER_BYTES];
+        match reader.read_exact(&mut frame) {
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            result => try_with!(result, "unable to read run({})", path.display()),
+        }
+        let length = u32::from_le_bytes(frame[0..4].try_into().unwrap()) as usize;
+        frame.resize(HEADER_BYTES + length, 0);
+        try_with!(reader.read_exact(&mut frame[HEADER_BYTES..]), "unable to read run({})", path.display());
+        let decoded = try_with!(decode_frame(&frame), "unable to read run({})", path.display());
+        Ok(decoded.map(|(command, _)| { command }))
+    }
+}
+
+/// K-way merge of sorted runs yielding commands ordered by client.
+pub struct SortedCommands {
+    runs: Vec<Run>,
+    heads: Vec<Option<SortedCommand>>,
+    heap: BinaryHeap<Reverse<((ClientId, u64), usize)>>,
+}
+
+impl Iterator for SortedCommands {
+    type Item = Result<SortedCommand, SimpleError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let Reverse((_, index)) = self.heap.pop()?;
+        let command = self.heads[index].take();
+        match self.runs[index].next() {
+            Ok(head) => {
+                if let Some(next) = head.as_ref() {
+                    self.heap.push(Reverse((next.key(), index)));
+                }
+                self.heads[index] = head;
+            }
+            Err(e) => {
+                // merge ends once a run is unreadable
+                self.heap.clear();
+                return Some(Err(e));
+            }
+        }
+        command.map(Ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::input::csv_reader;
+    use crate::testing::TempDir;
+
+    /// Returns commands of `clients` cycling through clients (transaction ids ascending).
+    fn commands(transactions: usize, clients: usize) -> Vec<Command> {
+        let mut source = String::from("type,client,tx,amount\n");
+        for tx in 1..=transactions {
+            source.push_str(&format!("deposit,{},{},1.0\n", (tx * 7) % clients + 1, tx));
+        }
+        csv_reader(source.as_bytes(), b',').unwrap().deserialize().map(|result| { result.unwrap() }).collect()
+    }
+
+    fn runs(directory: &Path) -> usize {
+        fs::read_dir(directory).unwrap().filter(|entry| {
+            entry.as_ref().unwrap().file_name().to_string_lossy().contains(&format!("{}-run-", process::id()))
+        }).count()
+    }
+
+    #[test]
+    fn commands_merged_by_client_in_order_read() {
+        let directory = TempDir::new("extsort-merged");
+        for run_records in [3, 10, 1_000] {
+            let mut sorter = ExternalSorter::new(run_records, directory.path());
+            for (line, command) in commands(100, 6).into_iter().enumerate() {
+                sorter.push(line / 50, line + 2, command).unwrap();
+            }
+            let spilled = runs(directory.path());
+            assert_eq!(spilled, 100 / run_records);
+
+            let sorted: Vec<SortedCommand> = sorter.finish().unwrap().map(|result| { result.unwrap() }).collect();
+            assert_eq!(sorted.len(), 100);
+            for pair in sorted.windows(2) {
+                let (previous, next) = (&pair[0], &pair[1]);
+                assert!(previous.command.actor_id() <= next.command.actor_id());
+                if previous.command.actor_id() == next.command.actor_id() {
+                    assert!(previous.line < next.line);
+                    assert!(previous.source <= next.source);
+                }
+            }
+            assert_eq!(sorted[0].command.actor_id(), 1);
+            assert_eq!(sorted[99].command.actor_id(), 6);
+            // runs are removed once merged
+            assert_eq!(runs(directory.path()), 0);
+        }
+    }
+
+    #[test]
+    fn runs_removed_when_dropped() {
+        let directory = TempDir::new("extsort-dropped");
+        let mut sorter = ExternalSorter::new(4, directory.path());
+        for (line, command) in commands(20, 3).into_iter().enumerate() {
+            sorter.push(0, line, command).unwrap();
+        }
+        assert_eq!(runs(directory.path()), 5);
+        let mut merge = sorter.finish().unwrap();
+        assert!(merge.next().is_some());
+        drop(merge);
+        assert_eq!(runs(directory.path()), 0);
+    }
+}
@@ -0,0 +1,106 @@
+//! Failures of runs reported to stderr with exit codes telling I/O, data and internal errors apart.
+//!
+//! Exit codes follow sysexits(3) so schedulers can retry I/O failures while alerting on data failures:
+//! - `74` (`EX_IOERR`) sources, destinations or stores can't be opened, read or written
+//! - `65` (`EX_DATAERR`) inputs are invalid (e.g. metadata rows, saved state or too many unparseable records)
+//! - `70` (`EX_SOFTWARE`) processing failed otherwise (including broken invariants panicking)
+//! - `71` (`EX_OSERR`) resources are exhausted (e.g. accounts exceeding the `--max-memory-mb` budget)
+//! - `64` (`EX_USAGE`) arguments are missing for sources (e.g. `--client` of qif sources), have values unable to be
+//!   parsed (e.g. `--threads abc`) or are invalid glob patterns
+//!
+//! Arguments unknown or failing validators are reported by clap (exit code 1).
+
+use std::fmt;
+use std::panic;
+use std::process;
+
+/// Kind of failure determining exit code.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    Io,
+    Data,
+    Internal,
+    Usage,
+    Resource,
+}
+
+impl Kind {
+    /// Returns exit code of failures of kind.
+    pub fn code(&self) -> i32 {
+        match self {
+            Kind::Io => 74,
+            Kind::Data => 65,
+            Kind::Internal => 70,
+            Kind::Usage => 64,
+            Kind::Resource => 71,
+        }
+    }
+}
+
+/// Failure of a run (error message with context, e.g. source and line).
+#[derive(Debug)]
+pub struct Failure {
+    kind: Kind,
+    message: String,
+}
+
+impl Failure {
+    /// Returns new `Failure` of `kind` described by `error`.
+    pub fn new<E: fmt::Display>(kind: Kind, error: E) -> Self {
+        Failure { kind, message: error.to_string() }
+    }
+
+    /// Returns exit code of failure.
+    pub fn code(&self) -> i32 {
+        self.kind.code()
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// Conversion of results into results failing with a `Kind` of failure.
+pub trait OrFail<T> {
+    /// Returns value of result or failure of `kind` described by its error.
+    fn or_fail(self, kind: Kind) -> Result<T, Failure>;
+}
+
+impl<T, E: fmt::Display> OrFail<T> for Result<T, E> {
+    fn or_fail(self, kind: Kind) -> Result<T, Failure> {
+        self.map_err(|e| { Failure::new(kind, e) })
+    }
+}
+
+/// Reports `failure` to stderr then exits with its code.
+pub fn exit(failure: Failure) -> ! {
+    eprintln!("error: {}", failure);
+    process::exit(failure.code())
+}
+
+/// Reports panics (broken invariants of any thread) as internal failures exiting the process.
+pub fn exit_on_panic() {
+    panic::set_hook(Box::new(|info| {
+        eprintln!("error: internal, {}", info);
+        process::exit(Kind::Internal.code());
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_error::SimpleError;
+
+    #[test]
+    fn failures_coded_by_kind() {
+        let result: Result<(), SimpleError> = Err(SimpleError::new("unable to open source(a.csv)"));
+        let failure = result.or_fail(Kind::Io).unwrap_err();
+        assert_eq!((failure.code(), failure.to_string()), (74, "unable to open source(a.csv)".to_string()));
+        assert_eq!(Failure::new(Kind::Data, "invalid state").code(), 65);
+        assert_eq!(Failure::new(Kind::Usage, "client argument is required").code(), 64);
+        assert_eq!(Failure::new(Kind::Resource, "memory budget(1 MiB) exceeded").code(), 71);
+        assert_eq!(Ok::<u8, String>(1).or_fail(Kind::Internal).unwrap(), 1);
+    }
+}
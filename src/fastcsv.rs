@@ -0,0 +1,400 @@
+//! SIMD accelerated reader of csv transaction sources (`simd` feature).
+//!
+//! Records are located by scanning for record terminators and delimiters using SIMD (`memchr`) rather than the
+//! byte-at-a-time state machine of `csv_core`, and fields of the transaction schema (`type,client,tx,amount`) are
+//! validated (`simdutf8`) and parsed directly rather than deserialized using serde. Records quoting fields (or
+//! terminated by a lone carriage return) and unterminated records ending a source are read by `csv_core`, while records unable
+//! to be parsed directly (e.g. errors or other schemas) are deserialized, so commands, lines and errors read are those
+//! of `CsvCommandReader`.
+
+use std::io::{self, Read};
+use std::str::FromStr;
+
+use csv::{ByteRecord, Position, StringRecord};
+use csv_core::ReadRecordResult;
+use rust_decimal::prelude::Decimal;
+use simple_error::*;
+
+use crate::input::SourceReader;
+use crate::models::{Command, CommandType, Currency};
+
+/// Initial capacity (bytes) of buffer sources are read into (grown for records longer than the buffer).
+const BUFFER_CAPACITY: usize = 256 * 1024;
+
+/// Initial capacity (bytes) of fields of a csv record.
+const FIELDS_CAPACITY: usize = 256;
+
+/// Initial capacity of field ends of a csv record.
+const ENDS_CAPACITY: usize = 16;
+
+/// Headers of sources whose records are parsed without deserializing.
+const SCHEMA: [&str; 4] = ["type", "client", "tx", "amount"];
+
+/// Record read from source.
+enum Record {
+    /// Fields located within buffer (`spans`).
+    Scanned,
+    /// Fields parsed by `csv_core` (`record`).
+    Parsed,
+    End,
+}
+
+/// Reads records of a csv source as `Command`s (as lenient as `input::csv_reader`).
+pub struct FastCsvReader<R> {
+    source: R,
+    delimiter: u8,
+    parser: csv_core::Reader,
+    /// Bytes read from source (`start..end` not yet read as records).
+    buffer: Vec<u8>,
+    start: usize,
+    end: usize,
+    eof: bool,
+    /// Offsets of line feeds within buffer (located once filled) and index of the next line feed.
+    newlines: Vec<usize>,
+    newline: usize,
+    /// Offsets (start and end) of fields of record scanned within buffer.
+    spans: Vec<(usize, usize)>,
+    fields: Vec<u8>,
+    ends: Vec<usize>,
+    position: Position,
+    record: ByteRecord,
+    headers: StringRecord,
+    byte_headers: ByteRecord,
+    /// True when headers are the transaction schema.
+    schema: bool,
+    line: usize,
+    done: bool,
+}
+
+impl<R: Read> FastCsvReader<R> {
+    /// Returns new `FastCsvReader` reading records of `source` using field `delimiter` (headers read).
+    pub fn new(source: R, delimiter: u8) -> Result<Self, SimpleError> {
+        Self::with_capacity(source, delimiter, BUFFER_CAPACITY)
+    }
+
+    /// Returns new `FastCsvReader` reading `source` into a buffer of `capacity` bytes (headers read).
+    fn with_capacity(source: R, delimiter: u8, capacity: usize) -> Result<Self, SimpleError> {
+        let mut position = Position::new();
+        position.set_line(1);
+        let mut reader = FastCsvReader {
+            source,
+            delimiter,
+            parser: csv_core::ReaderBuilder::new().delimiter(delimiter).build(),
+            buffer: vec![0; capacity.max(1)],
+            start: 0,
+            end: 0,
+            eof: false,
+            newlines: vec![],
+            newline: 0,
+            spans: vec![],
+            fields: vec![0; FIELDS_CAPACITY],
+            ends: vec![0; ENDS_CAPACITY],
+            position,
+            record: ByteRecord::new(),
+            headers: StringRecord::new(),
+            byte_headers: ByteRecord::new(),
+            schema: false,
+            line: 1,
+            done: false,
+        };
+        // headers are read by `csv_core` (stripping byte order mark)
+        if let Record::Parsed = reader.read_record()? {
+            let headers = try_with!(StringRecord::from_byte_record(reader.record.clone()), "invalid utf-8 in headers");
+            reader.headers = headers.iter().map(|header| { header.to_lowercase() }).collect();
+            reader.byte_headers = reader.headers.as_byte_record().clone();
+            reader.schema = reader.headers.iter().eq(SCHEMA.iter().copied());
+        }
+        Ok(reader)
+    }
+
+    /// Returns lowercased headers of source.
+    pub fn headers(&self) -> &StringRecord {
+        &self.headers
+    }
+
+    /// Reads next record of source (scanned unless it must be parsed by `csv_core`).
+    fn read_record(&mut self) -> Result<Record, SimpleError> {
+        if self.done {
+            return Ok(Record::End);
+        }
+        let position = self.position.clone();
+        let scanned = self.position.record() > 0 && self.scan_record().is_ok_and(|scanned| { scanned });
+        if !scanned && !self.parse_record()? {
+            return Ok(Record::End);
+        }
+        self.line = position.line() as usize;
+        let record = self.position.record();
+        self.position.set_record(record + 1);
+        self.record.set_position(Some(position));
+        Ok(if scanned { Record::Scanned } else { Record::Parsed })
+    }
+
+    /// Locates fields of next record within buffer (false when record must be parsed by `csv_core`, e.g. quoted
+    /// fields, lone carriage returns or unterminated records at end of source).
+    fn scan_record(&mut self) -> io::Result<bool> {
+        loop {
+            // terminators preceding a record are skipped (empty lines)
+            while self.start < self.end {
+                match self.buffer[self.start] {
+                    b'\n' => self.advance(1, 1),
+                    b'\r' => self.advance(1, 0),
+                    _ => break,
+                }
+            }
+            while self.newlines.get(self.newline).is_some_and(|newline| { *newline < self.start }) {
+                self.newline += 1;
+            }
+            if self.start < self.end && self.newline < self.newlines.len() {
+                break;
+            }
+            if !self.fill()? {
+                return Ok(false);
+            }
+        }
+        let newline = self.newlines[self.newline];
+        // records terminated by CRLF end at the carriage return (line feed is skipped by the next record)
+        let (end, lines) = match self.buffer[newline - 1] {
+            b'\r' => (newline - 1, 0),
+            _ => (newline, 1),
+        };
+        if memchr::memchr2(b'"', b'\r', &self.buffer[self.start..end]).is_some() {
+            return Ok(false);
+        }
+        self.spans.clear();
+        let mut start = self.start;
+        for delimiter in memchr::memchr_iter(self.delimiter, &self.buffer[self.start..end]) {
+            self.spans.push((start, self.start + delimiter));
+            start = self.start + delimiter + 1;
+        }
+        self.spans.push((start, end));
+        self.advance(end + 1 - self.start, lines);
+        Ok(true)
+    }
+
+    /// Reads next record of source into `record` using `csv_core` returning false once read.
+    fn parse_record(&mut self) -> Result<bool, SimpleError> {
+        let (mut fields, mut ends) = (0, 0);
+        loop {
+            if self.start == self.end {
+                if let Err(e) = self.fill() {
+                    // sources are not read past errors reading them
+                    self.done = true;
+                    bail!("unable to read csv source, {}", e);
+                }
+            }
+            let (result, read, written, ended) = self.parser.read_record(
+                &self.buffer[self.start..self.end],
+                &mut self.fields[fields..],
+                &mut self.ends[ends..]
+            );
+            self.start += read;
+            let byte = self.position.byte();
+            self.position.set_byte(byte + read as u64).set_line(self.parser.line());
+            fields += written;
+            ends += ended;
+            match result {
+                ReadRecordResult::InputEmpty => {}
+                ReadRecordResult::OutputFull => self.fields.resize(self.fields.len() * 2, 0),
+                ReadRecordResult::OutputEndsFull => self.ends.resize(self.ends.len() * 2, 0),
+                ReadRecordResult::Record => break,
+                ReadRecordResult::End => {
+                    self.done = true;
+                    return Ok(false);
+                }
+            }
+        }
+        self.record.clear();
+        let mut start = 0;
+        for end in self.ends[..ends].iter() {
+            self.record.push_field(&self.fields[start..*end]);
+            start = *end;
+        }
+        self.record.trim();
+        Ok(true)
+    }
+
+    /// Reads more of source into buffer (keeping bytes not yet read as records) returning false at end of source.
+    fn fill(&mut self) -> io::Result<bool> {
+        if self.eof {
+            return Ok(false);
+        }
+        self.buffer.copy_within(self.start..self.end, 0);
+        self.end -= self.start;
+        self.start = 0;
+        if self.end == self.buffer.len() {
+            self.buffer.resize(self.buffer.len() * 2, 0);
+        }
+        let read = loop {
+            match self.source.read(&mut self.buffer[self.end..]) {
+                Ok(read) => break read,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        };
+        self.end += read;
+        self.eof = read == 0;
+        self.newlines.clear();
+        self.newlines.extend(memchr::memchr_iter(b'\n', &self.buffer[..self.end]));
+        self.newline = 0;
+        Ok(!self.eof)
+    }
+
+    /// Advances past `read` bytes of buffer having `lines` line feeds (position and line of `csv_core`).
+    fn advance(&mut self, read: usize, lines: u64) {
+        self.start += read;
+        let (byte, line) = (self.position.byte(), self.position.line());
+        self.position.set_byte(byte + read as u64).set_line(line + lines);
+        self.parser.set_line(line + lines);
+    }
+
+    /// Returns field (trimmed) of record scanned at `index` (if any).
+    fn field(&self, index: usize) -> Option<&[u8]> {
+        self.spans.get(index).map(|(start, end)| { self.buffer[*start..*end].trim_ascii() })
+    }
+
+    /// Returns command of record scanned having the transaction schema (none when record must be deserialized, e.g.
+    /// to report errors).
+    ///
+    /// Fields are parsed as deserialized (integers using `str::parse` and amounts as decimal or scientific notation).
+    fn parse(&self) -> Option<Command> {
+        if !(3..=4).contains(&self.spans.len()) {
+            return None;
+        }
+        let name = match self.field(0)? {
+            b"deposit" => CommandType::Deposit,
+            b"withdraw" => CommandType::Withdraw,
+            b"dispute" => CommandType::Dispute,
+            b"resolve" => CommandType::Resolve,
+            b"chargeback" => CommandType::Chargeback,
+            _ => return None,
+        };
+        let client = utf8(self.field(1)?)?.parse().ok()?;
+        let tx = utf8(self.field(2)?)?.parse().ok()?;
+        let amount: Option<Currency> = match self.field(3) {
+            None | Some(b"") => None,
+            Some(amount) => {
+                let amount = utf8(amount)?;
+                Some(Decimal::from_str(amount).or_else(|_| { Decimal::from_scientific(amount) }).ok()?)
+            }
+        };
+        Some(Command::new(name, client, tx, amount))
+    }
+}
+
+impl<R: Read> SourceReader for FastCsvReader<R> {
+    fn line(&self) -> usize {
+        self.line
+    }
+}
+
+impl<R: Read> Iterator for FastCsvReader<R> {
+    type Item = Result<Command, SimpleError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.read_record() {
+            Ok(Record::End) => return None,
+            Ok(Record::Scanned) => {
+                if let Some(command) = self.schema.then(|| { self.parse() }).flatten() {
+                    return Some(Ok(command));
+                }
+                let position = self.record.position().cloned();
+                self.record.clear();
+                for index in 0..self.spans.len() {
+                    let (start, end) = self.spans[index];
+                    self.record.push_field(self.buffer[start..end].trim_ascii());
+                }
+                self.record.set_position(position);
+            }
+            Ok(Record::Parsed) => {}
+            Err(e) => return Some(Err(e)),
+        }
+        Some(self.record.deserialize(Some(&self.byte_headers)).map_err(|e| { SimpleError::new(e.to_string()) }))
+    }
+}
+
+/// Returns `field` validated as utf-8 (none when invalid).
+fn utf8(field: &[u8]) -> Option<&str> {
+    simdutf8::basic::from_utf8(field).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    use crate::input::{CsvCommandReader, csv_reader};
+
+    /// Asserts commands, lines and errors read from `source` (into a buffer of `capacity` bytes) are those of
+    /// `CsvCommandReader`.
+    fn assert_read_as_csv_reader(source: &[u8], capacity: usize) {
+        let mut reader = FastCsvReader::with_capacity(source, b',', capacity).unwrap();
+        let mut expected = CsvCommandReader::new(csv_reader(Cursor::new(source), b',').unwrap()).unwrap();
+        assert_eq!(reader.headers(), expected.headers());
+        while let Some(result) = reader.next() {
+            let other = expected.next().unwrap();
+            assert_eq!(reader.line(), expected.line());
+            match (result, other) {
+                (Ok(command), Ok(other)) => assert_eq!(command, other),
+                (Err(e), Err(other)) => assert_eq!(e.to_string(), other.to_string()),
+                (result, other) => panic!("{:?} read as {:?}", other, result),
+            }
+        }
+        assert!(expected.next().is_none());
+    }
+
+    #[test]
+    fn records_read_as_csv_reader() {
+        let mut source = b"\xef\xbb\xbfType, Client, TX, Amount\r\n\
+            deposit, 1, 1, 10.0\r\n\
+            \"withdraw\",1,2,\"2.5\"\n\
+            \n\
+            unknown,1,3,1.0\n\
+            withdraw,two,4,1.0\n\
+            withdraw,2,5,1e2\rdispute,1,1\n\
+            deposit,1,6,abc\n\
+            deposit,1,7,1.0,extra\n\
+            deposit,1,8,"
+            .to_vec();
+        source.extend_from_slice(b"\xff\nresolve,1,1,\n\r\n  \ndeposit,3,9,5.5");
+        for capacity in [8, 64, BUFFER_CAPACITY] {
+            assert_read_as_csv_reader(&source, capacity);
+        }
+    }
+
+    #[test]
+    fn other_schemas_deserialized() {
+        let source = b"type;client;tx;amount;wallet\ndeposit;1;1;1.0;savings\nwithdraw;1;2;0.5\n";
+        let mut reader = FastCsvReader::new(&source[..], b';').unwrap();
+        assert!(!reader.schema);
+
+        let command = reader.next().unwrap().unwrap();
+        assert_eq!(command.wallet().map(String::as_str), Some("savings"));
+        let command = reader.next().unwrap().unwrap();
+        assert_eq!((command.tx(), command.amount(), command.wallet()), (2, Some(Decimal::new(5, 1)), None));
+        assert_eq!(reader.line(), 3);
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    #[ignore = "benchmark"]
+    fn parse_throughput() {
+        use std::time::Instant;
+
+        let mut source = String::from("type,client,tx,amount\n");
+        for tx in 0..2_000_000 {
+            match tx % 10 {
+                0 => source.push_str(&format!("dispute,{},{},\n", tx % 5_000, tx / 2)),
+                1..=3 => source.push_str(&format!("withdraw,{},{},{}.{:04}\n", tx % 5_000, tx, tx % 100, tx % 10_000)),
+                _ => source.push_str(&format!("deposit,{},{},{}.{:04}\n", tx % 5_000, tx, tx % 1_000, tx % 10_000)),
+            }
+        }
+        let run = |name: &str, reader: &mut dyn SourceReader| {
+            let started = Instant::now();
+            assert_eq!(reader.filter(Result::is_ok).count(), 2_000_000);
+            let elapsed = started.elapsed().as_secs_f64();
+            println!("{}: {:.3}s ({:.0} MB/s)", name, elapsed, source.len() as f64 / elapsed / 1e6);
+        };
+        run("csv", &mut CsvCommandReader::new(csv_reader(source.as_bytes(), b',').unwrap()).unwrap());
+        run("simd", &mut FastCsvReader::new(source.as_bytes(), b',').unwrap());
+    }
+}
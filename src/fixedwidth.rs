@@ -0,0 +1,237 @@
+//! Reader of fixed-width records (e.g. mainframe exports) mapping columns of a layout to `Command`s.
+//!
+//! Layouts are TOML documents listing columns named after transaction fields (type, client, tx, amount etc.):
+//!
+//! ```toml
+//! skip = 1 # header lines
+//!
+//! [[columns]]
+//! name = "type"
+//! offset = 0
+//! width = 1
+//! values = { D = "deposit", W = "withdraw" }
+//!
+//! [[columns]]
+//! name = "amount"
+//! offset = 1
+//! width = 9
+//! type = "decimal"
+//! scale = 2 # implied decimal places (000012345 is 123.45)
+//! ```
+//!
+//! Column values are trimmed and records shorter than the layout have empty trailing columns (none).
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufRead, Lines};
+
+use csv::StringRecord;
+use serde::Deserialize;
+use simple_error::*;
+
+use crate::input::SourceReader;
+use crate::models::{Command, Currency};
+
+/// Type of a fixed-width column.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColumnType {
+    #[default]
+    String,
+    Integer,
+    Decimal,
+}
+
+/// Column of a fixed-width layout.
+#[derive(Debug, Deserialize)]
+pub struct Column {
+    name: String,
+    offset: usize,
+    width: usize,
+    #[serde(rename = "type", default)]
+    column_type: ColumnType,
+    /// Implied decimal places of decimal columns without a decimal point.
+    #[serde(default)]
+    scale: u32,
+    /// Values replacing column codes (e.g. `D` as `deposit`).
+    #[serde(default)]
+    values: HashMap<String, String>,
+}
+
+/// Layout of fixed-width records.
+#[derive(Debug, Deserialize)]
+pub struct Layout {
+    /// Number of lines skipped at start of sources (e.g. headers).
+    #[serde(default)]
+    skip: usize,
+    columns: Vec<Column>,
+}
+
+impl Layout {
+    /// Returns layout read from TOML source `path`.
+    pub fn from_path(path: &str) -> Result<Self, SimpleError> {
+        let layout = try_with!(fs::read_to_string(path), "unable to read layout({})", path);
+        Self::from_toml(&layout)
+    }
+
+    /// Returns layout of TOML `layout`.
+    pub fn from_toml(layout: &str) -> Result<Self, SimpleError> {
+        Ok(try_with!(toml::from_str(layout), "invalid layout"))
+    }
+
+    /// Returns trimmed values of layout columns in `line` at `position`.
+    fn record(&self, line: &str, position: usize) -> Result<StringRecord, SimpleError> {
+        let mut record = StringRecord::new();
+        for column in self.columns.iter() {
+            let end = line.len().min(column.offset + column.width);
+            let value = match line.get(column.offset.min(end)..end) {
+                Some(value) => value.trim(),
+                None => bail!("invalid fixed-width record({}) column({})", position, column.name),
+            };
+            let value = match column.values.get(value) {
+                Some(value) => value.clone(),
+                None => column.parse(value, position)?,
+            };
+            record.push_field(&value);
+        }
+        Ok(record)
+    }
+}
+
+impl Column {
+    /// Returns `value` of column at `position` validated and normalized by column type.
+    fn parse(&self, value: &str, position: usize) -> Result<String, SimpleError> {
+        if value.is_empty() {
+            return Ok(String::new());
+        }
+        match self.column_type {
+            ColumnType::String => Ok(value.to_string()),
+            ColumnType::Integer => {
+                let value: u64 = try_with!(value.parse(), "invalid fixed-width record({}) {}({})", position, self.name, value);
+                Ok(value.to_string())
+            }
+            ColumnType::Decimal => {
+                let parsed = if value.contains('.') {
+                    value.parse::<Currency>().ok()
+                } else {
+                    value.parse::<i64>().ok().map(|value| { Currency::new(value, self.scale) })
+                };
+                match parsed {
+                    Some(value) => Ok(value.to_string()),
+                    None => bail!("invalid fixed-width record({}) {}({})", position, self.name, value),
+                }
+            }
+        }
+    }
+}
+
+/// Reads fixed-width records from `R` as `Command`s using a `Layout`.
+pub struct FixedWidthReader<R: BufRead> {
+    lines: Lines<R>,
+    layout: Layout,
+    headers: StringRecord,
+    position: usize,
+}
+
+impl<R: BufRead> FixedWidthReader<R> {
+    /// Returns new `FixedWidthReader` reading records of `layout` from `reader`.
+    pub fn new(reader: R, layout: Layout) -> Self {
+        let headers = layout.columns.iter().map(|column| { column.name.to_lowercase() }).collect();
+        FixedWidthReader { lines: reader.lines(), layout, headers, position: 0 }
+    }
+
+    /// Returns command of next record or none at end of source.
+    fn read_record(&mut self) -> Result<Option<Command>, SimpleError> {
+        for line in self.lines.by_ref() {
+            self.position += 1;
+            let line = try_with!(line, "unable to read fixed-width record({})", self.position);
+            if self.position <= self.layout.skip || line.trim().is_empty() {
+                continue;
+            }
+            let record = self.layout.record(line.trim_end_matches('\r'), self.position)?;
+            let command = try_with!(record.deserialize(Some(&self.headers)), "invalid fixed-width record({})", self.position);
+            return Ok(Some(command));
+        }
+        Ok(None)
+    }
+}
+
+impl<R: BufRead> SourceReader for FixedWidthReader<R> {
+    fn line(&self) -> usize {
+        self.position
+    }
+}
+
+impl<R: BufRead> Iterator for FixedWidthReader<R> {
+    type Item = Result<Command, SimpleError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_record().transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::prelude::Decimal;
+
+    use crate::events::Cause;
+    use crate::models::CommandType;
+
+    const LAYOUT: &str = r#"
+        skip = 1
+
+        [[columns]]
+        name = "type"
+        offset = 0
+        width = 1
+        values = { D = "deposit", W = "withdraw", X = "dispute" }
+
+        [[columns]]
+        name = "client"
+        offset = 1
+        width = 5
+        type = "integer"
+
+        [[columns]]
+        name = "tx"
+        offset = 6
+        width = 8
+        type = "integer"
+
+        [[columns]]
+        name = "amount"
+        offset = 14
+        width = 9
+        type = "decimal"
+        scale = 2
+    "#;
+
+    fn commands(source: &str) -> Vec<Command> {
+        let layout = Layout::from_toml(LAYOUT).unwrap();
+        FixedWidthReader::new(source.as_bytes(), layout).map(|command| { command.unwrap() }).collect()
+    }
+
+    #[test]
+    fn records_read_as_commands() {
+        let commands = commands("TCLNT TX      AMOUNT\nD0000700000001000012345\nW0000700000002   10.50\nX0000700000001\n");
+
+        assert_eq!(commands.len(), 3);
+        assert_eq!(commands[0].name(), &CommandType::Deposit);
+        assert_eq!(commands[0].actor_id(), 7);
+        assert_eq!(commands[0].tx(), 1);
+        assert_eq!(commands[0].amount(), Some(Decimal::new(12345, 2)));
+        assert_eq!(commands[1].name(), &CommandType::Withdraw);
+        assert_eq!(commands[1].amount(), Some(Decimal::new(1050, 2)));
+        assert_eq!(commands[2].name(), &CommandType::Dispute);
+        assert_eq!(commands[2].amount(), None);
+    }
+
+    #[test]
+    fn invalid_column_errors() {
+        let layout = Layout::from_toml(LAYOUT).unwrap();
+        let mut reader = FixedWidthReader::new("header\nD00007ABCDEFGH000012345\n".as_bytes(), layout);
+
+        assert!(reader.next().unwrap().is_err());
+    }
+}
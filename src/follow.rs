@@ -0,0 +1,160 @@
+//! Following a csv source as rows are appended (`--follow`), like `tail -f`.
+//!
+//! The followed file is kept open and reads wait at its end until a producer (e.g. a log shipper) appends more rows,
+//! so a row being written is only parsed once complete. Accounts updated by rows handled are re-emitted every
+//! interval by `FollowSnapshots` (on its own thread, so rows handled before the producer went quiet are emitted too).
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::mem;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use csv::Writer;
+use simple_error::*;
+use tracing::warn;
+
+use crate::models::{Account, ClientId, Event};
+use crate::store::ProjectionStore;
+use crate::subscribers::EventSubscriber;
+
+/// Seconds between emissions of accounts updated unless supplied.
+pub const FOLLOW_INTERVAL: u64 = 5;
+
+/// Duration waited at end of file before reading again.
+pub const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// File read as rows are appended (reads wait at end of file instead of ending).
+pub struct FollowFile {
+    file: File,
+}
+
+impl FollowFile {
+    /// Returns new `FollowFile` reading file at `path` from its start.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, SimpleError> {
+        let path = path.as_ref();
+        let file = try_with!(File::open(path), "unable to follow source({})", path.display());
+        Ok(FollowFile { file })
+    }
+}
+
+impl Read for FollowFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            match self.file.read(buf)? {
+                0 if !buf.is_empty() => thread::sleep(POLL_INTERVAL),
+                read => return Ok(read),
+            }
+        }
+    }
+}
+
+/// Subscriber re-emitting accounts updated since last emitted every interval (as csv rows of account balances).
+pub struct FollowSnapshots {
+    updated: Arc<Mutex<BTreeMap<ClientId, Account>>>,
+}
+
+impl FollowSnapshots {
+    /// Returns new `FollowSnapshots` writing accounts updated to `writer` every `interval` (on a new thread).
+    pub fn spawn<W: Write + Send + 'static>(mut writer: Writer<W>, interval: Duration) -> Self {
+        let updated = Arc::new(Mutex::new(BTreeMap::new()));
+        let emitted = updated.clone();
+        thread::spawn(move || {
+            loop {
+                thread::sleep(interval);
+                let accounts = mem::take(&mut *emitted.lock().unwrap());
+                if let Err(e) = emit(&mut writer, accounts.values()) {
+                    // destination closed (e.g. reader of stdout exited)
+                    warn!("unable to emit snapshots, {}", e);
+                    return;
+                }
+            }
+        });
+        FollowSnapshots { updated }
+    }
+}
+
+impl EventSubscriber for FollowSnapshots {
+    fn on_events(&mut self, client: ClientId, _events: &[Event], accounts: &dyn ProjectionStore) -> Result<(), SimpleError> {
+        if let Some(account) = accounts.get(client)? {
+            self.updated.lock().unwrap().insert(client, account.into_owned());
+        }
+        Ok(())
+    }
+}
+
+/// Writes `accounts` to `writer` (flushed so followers of the destination read them).
+fn emit<'a, W: Write, I: Iterator<Item = &'a Account>>(writer: &mut Writer<W>, accounts: I) -> Result<(), SimpleError> {
+    for account in accounts {
+        try_with!(writer.serialize(account), "unable to write account({})", account.client());
+    }
+    try_with!(writer.flush(), "unable to flush snapshots");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{self, OpenOptions};
+    use std::sync::mpsc;
+
+    use rust_decimal::prelude::Decimal;
+
+    use crate::events::Actor;
+    use crate::models::{Command, CommandType};
+    use crate::store::MemoryStore;
+    use crate::testing::TempDir;
+
+    /// Writer of rows sent to a channel.
+    struct Sent(mpsc::Sender<String>);
+
+    impl Write for Sent {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.send(String::from_utf8_lossy(buf).into_owned()).ok();
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn rows_appended_are_read() {
+        let directory = TempDir::new("follow");
+        let path = directory.join("transactions.csv");
+        fs::write(&path, "type,client,tx,amount\ndeposit,1,1,1.0\ndepo").unwrap();
+        let mut file = FollowFile::open(&path).unwrap();
+        let appended = path.clone();
+        let appender = thread::spawn(move || {
+            thread::sleep(POLL_INTERVAL * 2);
+            OpenOptions::new().append(true).open(appended).unwrap().write_all(b"sit,2,2,2.0\n").unwrap();
+        });
+        let mut read = String::new();
+        while !read.ends_with('\n') || read.lines().count() < 3 {
+            let mut buf = [0; 64];
+            let count = file.read(&mut buf).unwrap();
+            read.push_str(&String::from_utf8_lossy(&buf[..count]));
+        }
+        appender.join().unwrap();
+        assert_eq!(read, "type,client,tx,amount\ndeposit,1,1,1.0\ndeposit,2,2,2.0\n");
+    }
+
+    #[test]
+    fn updated_accounts_emitted() {
+        let (sender, rows) = mpsc::channel();
+        let writer = csv::WriterBuilder::new().has_headers(false).from_writer(Sent(sender));
+        let mut snapshots = FollowSnapshots::spawn(writer, Duration::from_millis(10));
+        let mut accounts = MemoryStore::default();
+        let mut account = Account::new(1);
+        let events = account.handle(Command::new(CommandType::Deposit, 1, 1, Some(Decimal::new(15, 1)))).unwrap();
+        account.apply(events.clone());
+        accounts.put(account, 0).unwrap();
+        snapshots.on_events(1, &events, &accounts).unwrap();
+        snapshots.on_events(2, &[], &accounts).unwrap();
+        assert_eq!(rows.recv().unwrap(), "1,1.5,0.0000,1.5,false\n");
+    }
+}
@@ -0,0 +1,161 @@
+//! GraphQL queries of accounts and their events (`POST /graphql` of `serve` subcommand, `graphql` feature).
+//!
+//! Queries resolve accounts through the thread owning them (see `Engine`), e.g. for ops tooling:
+//!
+//! ```graphql
+//! { accounts(locked: true) { client available held total events(last: 5) { kind tx amount } } }
+//! ```
+//!
+//! Events are those kept by accounts: persistent stores always keep events while memory stores only keep them using
+//! `--retain-events`. Amounts are decimal strings (as in REST responses) and timestamps are RFC 3339.
+
+use async_graphql::{Context, EmptyMutation, EmptySubscription, Object, Result, SimpleObject};
+
+use crate::engine::Engine;
+use crate::events::Effect;
+use crate::models::{self, ClientId, MerchantId, TransactionId, Version};
+
+/// Schema of queries resolved using `Engine` (supplied as data).
+pub type Schema = async_graphql::Schema<Query, EmptyMutation, EmptySubscription>;
+
+/// Returns schema of queries resolved by `engine`.
+pub fn schema(engine: Engine) -> Schema {
+    Schema::build(Query, EmptyMutation, EmptySubscription).data(engine).finish()
+}
+
+/// Root of queries.
+pub struct Query;
+
+#[Object]
+impl Query {
+    /// Account of client (if any).
+    async fn account(&self, ctx: &Context<'_>, client: ClientId) -> Result<Option<Account>> {
+        let engine = ctx.data::<Engine>()?;
+        Ok(engine.account(client).await?.map(Account))
+    }
+
+    /// Accounts ordered by client (only those locked or unlocked when `locked` is supplied).
+    async fn accounts(&self, ctx: &Context<'_>, locked: Option<bool>) -> Result<Vec<Account>> {
+        let engine = ctx.data::<Engine>()?;
+        Ok(engine.accounts().await?.into_iter()
+            .filter(|account| { locked.is_none_or(|locked| { account.locked() == locked }) })
+            .map(Account)
+            .collect())
+    }
+}
+
+/// Balances and event history of an account.
+pub struct Account(models::Account);
+
+#[Object]
+impl Account {
+    async fn client(&self) -> ClientId { self.0.client() }
+
+    async fn available(&self) -> String { self.0.available().to_string() }
+
+    async fn held(&self) -> String { self.0.held().to_string() }
+
+    async fn total(&self) -> String { self.0.total().to_string() }
+
+    async fn locked(&self) -> bool { self.0.locked() }
+
+    async fn version(&self) -> Version { self.0.version() }
+
+    /// Events applied to account in order (only the `last` events when supplied).
+    async fn events(&self, last: Option<usize>) -> Vec<Event> {
+        let events = self.0.events();
+        let skipped = last.map_or(0, |last| { events.len().saturating_sub(last) });
+        events[skipped..].iter().map(Event::from).collect()
+    }
+}
+
+/// Event applied to an account.
+#[derive(SimpleObject)]
+pub struct Event {
+    /// Lowercase name of event type (e.g. `credited`).
+    kind: &'static str,
+    version: Version,
+    timestamp: Option<String>,
+    tx: Option<TransactionId>,
+    wallet: Option<String>,
+    merchant: Option<MerchantId>,
+    category: Option<String>,
+    amount: Option<String>,
+}
+
+impl From<&models::Event> for Event {
+    fn from(event: &models::Event) -> Self {
+        let (wallet, merchant) = match event {
+            models::Event::Credited { wallet, merchant, .. } |
+            models::Event::Debited { wallet, merchant, .. } |
+            models::Event::Reversed { wallet, merchant, .. } => (Some(wallet.clone()), *merchant),
+            models::Event::Held { wallet, .. } |
+            models::Event::Released { wallet, .. } => (Some(wallet.clone()), None),
+            models::Event::Locked { .. } => (None, None),
+        };
+        let transaction = event.transaction();
+        Event {
+            kind: event.name(),
+            version: event.version(),
+            timestamp: event.timestamp().map(|timestamp| { timestamp.to_rfc3339() }),
+            tx: transaction.map(|(tx, _)| { tx }),
+            wallet,
+            merchant,
+            category: event.category().cloned(),
+            amount: transaction.map(|(_, amount)| { amount.to_string() }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::thread;
+
+    use rust_decimal::prelude::Decimal;
+    use serde_json::json;
+
+    use crate::engine;
+    use crate::models::{Command, CommandType};
+    use crate::store::{EventHistory, MemoryStore};
+    use crate::handle_command;
+
+    #[test]
+    fn accounts_queried_with_events() {
+        let (engine, requests) = engine::channel();
+        thread::spawn(move || {
+            requests.run(&mut MemoryStore::new(EventHistory::Retained), &HashMap::new(), handle_command);
+        });
+        let schema = schema(engine.clone());
+
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        runtime.block_on(async {
+            engine.apply(vec![
+                Command::new(CommandType::Deposit, 1, 1, Some(Decimal::new(25, 1))),
+                Command::new(CommandType::Deposit, 2, 2, Some(Decimal::new(1, 0))),
+                Command::new(CommandType::Dispute, 2, 2, None),
+                Command::new(CommandType::Chargeback, 2, 2, None),
+            ]).await.unwrap();
+
+            let response = schema.execute("{ accounts(locked: true) { client total events(last: 2) { kind tx amount } } }").await;
+            assert!(response.errors.is_empty(), "{:?}", response.errors);
+            assert_eq!(response.data.into_json().unwrap(), json!({
+                "accounts": [{
+                    "client": 2,
+                    "total": "0",
+                    "events": [
+                        { "kind": "reversed", "tx": 2, "amount": "1" },
+                        { "kind": "locked", "tx": null, "amount": null },
+                    ],
+                }],
+            }));
+
+            let response = schema.execute("{ account(client: 1) { available locked } missing: account(client: 3) { client } }").await;
+            assert_eq!(response.data.into_json().unwrap(), json!({
+                "account": { "available": "2.5", "locked": false },
+                "missing": null,
+            }));
+        });
+    }
+}
@@ -0,0 +1,304 @@
+//! gRPC service applying transactions to accounts in-process (`serve` subcommand, `grpc` feature).
+//!
+//! The `Accounts` service of `proto/accounts.proto` is implemented using tonic, with messages and method routing
+//! declared by hand mirroring the `.proto` definitions (no `protoc` needed to build):
+//! - `SubmitTransaction` applies a command returning whether applied (reason when rejected) and events applied
+//! - `GetAccount` returns balances of an account (`NOT_FOUND` when none)
+//! - `StreamEvents` streams events applied (of a client or every client) once subscribed
+//!
+//! Requests are queued to the thread owning accounts (see `Engine`). Subscribers lagging by more than
+//! `SUBSCRIBER_CAPACITY` events are disconnected (`RESOURCE_EXHAUSTED`) rather than slowing down commands.
+
+use std::convert::{Infallible, TryFrom};
+use std::net::{SocketAddr, TcpListener};
+use std::thread::JoinHandle;
+
+use prost::Message;
+use simple_error::*;
+use tokio::sync::broadcast::error::RecvError;
+use tonic::body::BoxBody;
+use tonic::codec::ProstCodec;
+use tonic::codegen::{http, BoxFuture, BoxStream, Context, Poll, Service};
+use tonic::server::{Grpc, NamedService, ServerStreamingService, UnaryService};
+use tonic::Status;
+
+use crate::engine::{self, Engine};
+use crate::models::{self, Account, ClientId};
+use crate::proto::{AccountEvent, Command, Event};
+
+/// Package and name of service.
+const SERVICE: &str = "accounts.aggregate.Accounts";
+
+#[derive(Clone, PartialEq, Message)]
+pub struct SubmitTransactionResponse {
+    #[prost(bool, tag = "1")]
+    pub applied: bool,
+    #[prost(string, optional, tag = "2")]
+    pub reason: Option<String>,
+    #[prost(message, repeated, tag = "3")]
+    pub events: Vec<Event>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct GetAccountRequest {
+    #[prost(uint32, tag = "1")]
+    pub client: u32,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct AccountBalances {
+    #[prost(uint32, tag = "1")]
+    pub client: u32,
+    #[prost(string, tag = "2")]
+    pub available: String,
+    #[prost(string, tag = "3")]
+    pub held: String,
+    #[prost(string, tag = "4")]
+    pub total: String,
+    #[prost(bool, tag = "5")]
+    pub locked: bool,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct StreamEventsRequest {
+    #[prost(uint32, optional, tag = "1")]
+    pub client: Option<u32>,
+}
+
+impl From<&Account> for AccountBalances {
+    fn from(account: &Account) -> Self {
+        AccountBalances {
+            client: account.client() as u32,
+            available: account.available().to_string(),
+            held: account.held().to_string(),
+            total: account.total().to_string(),
+            locked: account.locked(),
+        }
+    }
+}
+
+/// gRPC server bound to an address.
+pub struct GrpcServer {
+    listener: TcpListener,
+}
+
+impl GrpcServer {
+    /// Returns new `GrpcServer` bound to `address` (host:port).
+    pub fn bind(address: &str) -> Result<Self, SimpleError> {
+        let listener = try_with!(TcpListener::bind(address), "unable to bind grpc server({})", address);
+        try_with!(listener.set_nonblocking(true), "unable to bind grpc server({})", address);
+        Ok(GrpcServer { listener })
+    }
+
+    /// Returns address server is bound to.
+    pub fn local_addr(&self) -> Result<SocketAddr, SimpleError> {
+        Ok(try_with!(self.listener.local_addr(), "unable to read grpc server address"))
+    }
+
+    /// Returns thread serving requests queued to `engine` (runs until the process is stopped).
+    pub fn spawn(self, engine: Engine) -> Result<JoinHandle<Result<(), SimpleError>>, SimpleError> {
+        let listener = self.listener;
+        engine::spawn("grpc", move || {
+            async move {
+                let listener = try_with!(tokio::net::TcpListener::from_std(listener), "unable to listen");
+                let incoming = futures::stream::unfold(listener, |listener| {
+                    async move {
+                        let accepted = listener.accept().await.map(|(stream, _)| { stream });
+                        Some((accepted, listener))
+                    }
+                });
+                let server = tonic::transport::Server::builder().add_service(AccountsService { engine });
+                try_with!(server.serve_with_incoming(incoming).await, "grpc server stopped");
+                Ok(())
+            }
+        })
+    }
+}
+
+/// `Accounts` service routing calls to methods.
+#[derive(Clone)]
+pub struct AccountsService {
+    engine: Engine,
+}
+
+impl NamedService for AccountsService {
+    const NAME: &'static str = SERVICE;
+}
+
+impl Service<http::Request<BoxBody>> for AccountsService {
+    type Response = http::Response<BoxBody>;
+    type Error = Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: http::Request<BoxBody>) -> Self::Future {
+        let engine = self.engine.clone();
+        let method = request.uri().path().strip_prefix(&format!("/{}/", SERVICE)).map(String::from);
+        Box::pin(async move {
+            let response = match method.as_deref() {
+                Some("SubmitTransaction") => Grpc::new(ProstCodec::default()).unary(SubmitTransaction(engine), request).await,
+                Some("GetAccount") => Grpc::new(ProstCodec::default()).unary(GetAccount(engine), request).await,
+                Some("StreamEvents") => Grpc::new(ProstCodec::default()).server_streaming(StreamEvents(engine), request).await,
+                _ => Status::unimplemented("unsupported method").into_http(),
+            };
+            Ok(response)
+        })
+    }
+}
+
+/// `SubmitTransaction` method.
+struct SubmitTransaction(Engine);
+
+impl UnaryService<Command> for SubmitTransaction {
+    type Response = SubmitTransactionResponse;
+    type Future = BoxFuture<tonic::Response<Self::Response>, Status>;
+
+    fn call(&mut self, request: tonic::Request<Command>) -> Self::Future {
+        let engine = self.0.clone();
+        Box::pin(async move {
+            let command = models::Command::try_from(request.into_inner()).map_err(|e| { Status::invalid_argument(e.to_string()) })?;
+            let applied = engine.apply(vec![command]).await.map_err(|e| { Status::unavailable(e.to_string()) })?;
+            let response = match applied.into_iter().next().map(|applied| { applied.result }) {
+                Some(Ok(events)) => SubmitTransactionResponse { applied: true, reason: None, events: events.iter().map(Event::from).collect() },
+                Some(Err(reason)) => SubmitTransactionResponse { applied: false, reason: Some(reason), events: vec![] },
+                None => return Err(Status::internal("command not applied")),
+            };
+            Ok(tonic::Response::new(response))
+        })
+    }
+}
+
+/// `GetAccount` method.
+struct GetAccount(Engine);
+
+impl UnaryService<GetAccountRequest> for GetAccount {
+    type Response = AccountBalances;
+    type Future = BoxFuture<tonic::Response<Self::Response>, Status>;
+
+    fn call(&mut self, request: tonic::Request<GetAccountRequest>) -> Self::Future {
+        let engine = self.0.clone();
+        Box::pin(async move {
+            let client = request.into_inner().client;
+            let id = ClientId::try_from(client).map_err(|_| { Status::invalid_argument(format!("invalid client({})", client)) })?;
+            match engine.account(id).await {
+                Ok(Some(account)) => Ok(tonic::Response::new(AccountBalances::from(&account))),
+                Ok(None) => Err(Status::not_found(format!("account({}) not found", client))),
+                Err(e) => Err(Status::unavailable(e.to_string())),
+            }
+        })
+    }
+}
+
+/// `StreamEvents` method.
+struct StreamEvents(Engine);
+
+impl ServerStreamingService<StreamEventsRequest> for StreamEvents {
+    type Response = AccountEvent;
+    type ResponseStream = BoxStream<AccountEvent>;
+    type Future = BoxFuture<tonic::Response<Self::ResponseStream>, Status>;
+
+    fn call(&mut self, request: tonic::Request<StreamEventsRequest>) -> Self::Future {
+        // events applied once the call is received are streamed
+        let events = self.0.subscribe();
+        let client = request.into_inner().client;
+        Box::pin(async move {
+            let stream = futures::stream::unfold(Some(events), move |events| {
+                async move {
+                    let mut events = events?;
+                    loop {
+                        match events.recv().await {
+                            Ok((id, event)) if client.is_none_or(|client| { client == id as u32 }) => {
+                                let event = AccountEvent { client: id as u32, event: Some(Event::from(&event)) };
+                                return Some((Ok(event), Some(events)));
+                            }
+                            Ok(_) => {}
+                            Err(RecvError::Lagged(skipped)) => {
+                                let status = Status::resource_exhausted(format!("subscriber lagged by {} events", skipped));
+                                return Some((Err(status), None));
+                            }
+                            Err(RecvError::Closed) => return None,
+                        }
+                    }
+                }
+            });
+            Ok(tonic::Response::new(Box::pin(stream) as Self::ResponseStream))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::thread;
+
+    use tonic::codegen::http::uri::PathAndQuery;
+    use tonic::transport::{Channel, Endpoint};
+    use tonic::Code;
+
+    use crate::models::CommandType;
+    use crate::proto::Kind;
+    use crate::store::MemoryStore;
+    use crate::handle_command;
+
+    /// Returns response of unary `method` called with `request`.
+    async fn call<M1, M2>(channel: &Channel, method: &'static str, request: M1) -> Result<M2, Status>
+    where
+        M1: Message + Send + Sync + 'static,
+        M2: Message + Default + Send + Sync + 'static,
+    {
+        let mut grpc = tonic::client::Grpc::new(channel.clone());
+        grpc.ready().await.unwrap();
+        let path = PathAndQuery::from_static(method);
+        grpc.unary(tonic::Request::new(request), path, ProstCodec::default()).await.map(|response| { response.into_inner() })
+    }
+
+    fn deposit(client: ClientId, tx: u32) -> Command {
+        Command::from(&models::Command::new(CommandType::Deposit, client, tx, Some(models::Currency::new(25, 1))))
+    }
+
+    #[test]
+    fn transactions_submitted_and_events_streamed() {
+        let server = GrpcServer::bind("127.0.0.1:0").unwrap();
+        let address = server.local_addr().unwrap();
+        let (engine, requests) = engine::channel();
+        server.spawn(engine).unwrap();
+        thread::spawn(move || {
+            requests.run(&mut MemoryStore::default(), &HashMap::new(), handle_command);
+        });
+
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        runtime.block_on(async {
+            let channel = Endpoint::from_shared(format!("http://{}", address)).unwrap().connect().await.unwrap();
+            let mut grpc = tonic::client::Grpc::new(channel.clone());
+            grpc.ready().await.unwrap();
+            let path = PathAndQuery::from_static("/accounts.aggregate.Accounts/StreamEvents");
+            let mut events = grpc.server_streaming::<_, AccountEvent, _>(tonic::Request::new(StreamEventsRequest { client: Some(1) }), path, ProstCodec::default())
+                .await
+                .unwrap()
+                .into_inner();
+
+            let submit = "/accounts.aggregate.Accounts/SubmitTransaction";
+            let response: SubmitTransactionResponse = call(&channel, submit, deposit(1, 1)).await.unwrap();
+            assert!(response.applied && response.events.len() == 1);
+            let response: SubmitTransactionResponse = call(&channel, submit, deposit(1, 1)).await.unwrap();
+            assert!(!response.applied && response.reason.is_some());
+            call::<_, SubmitTransactionResponse>(&channel, submit, deposit(2, 2)).await.unwrap();
+            let invalid = Command { r#type: 9, ..deposit(1, 3) };
+            assert_eq!(call::<_, SubmitTransactionResponse>(&channel, submit, invalid).await.unwrap_err().code(), Code::InvalidArgument);
+
+            let get = "/accounts.aggregate.Accounts/GetAccount";
+            let account: AccountBalances = call(&channel, get, GetAccountRequest { client: 1 }).await.unwrap();
+            assert_eq!((account.client, account.available.as_str(), account.locked), (1, "2.5", false));
+            assert_eq!(call::<_, AccountBalances>(&channel, get, GetAccountRequest { client: 3 }).await.unwrap_err().code(), Code::NotFound);
+
+            // events of other clients aren't streamed
+            let event = events.message().await.unwrap().unwrap();
+            assert_eq!(event.client, 1);
+            assert!(matches!(event.event.and_then(|event| { event.kind }), Some(Kind::Credited(_))));
+        });
+    }
+}
@@ -0,0 +1,249 @@
+//! Liveness and readiness of long-running processes (`--health-bind` of stream consumers and `serve`, `server` feature).
+//!
+//! Orchestrators probe:
+//! - `GET /healthz` answering `200` while the process serves requests (liveness)
+//! - `GET /readyz` answering `200` when every check passes or `503` otherwise, with checks as JSON (readiness)
+//!
+//! Checks are reported by the processing thread as it consumes: accounts loaded and recovered (`started`), the account
+//! store answering probes (`store`, probed at most every `PROBE_INTERVAL`), consumer lag of streaming sources within
+//! `--max-lag` (`lag`) and commands logged by the write-ahead log since its checkpoint (`wal`, persistent stores).
+
+use std::net::{SocketAddr, TcpListener};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use serde::Serialize;
+use simple_error::*;
+
+use crate::engine;
+use crate::store::ProjectionStore;
+
+/// Duration between probes of the account store (and reads of consumer lag).
+pub const PROBE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Value of counters not yet reported.
+const UNREPORTED: u64 = u64::MAX;
+
+/// Outcome of a readiness check.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct Check {
+    pub name: &'static str,
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+impl Check {
+    /// Returns check `name` passing (`ok`) with `detail` (if any).
+    pub fn new(name: &'static str, ok: bool, detail: Option<String>) -> Self {
+        Check { name, ok, detail }
+    }
+}
+
+/// Readiness of process (ready when every check passes).
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct Readiness {
+    pub ready: bool,
+    pub checks: Vec<Check>,
+}
+
+impl Readiness {
+    /// Returns readiness of `checks`.
+    pub fn new(checks: Vec<Check>) -> Self {
+        Readiness { ready: checks.iter().all(|check| { check.ok }), checks }
+    }
+}
+
+impl IntoResponse for Readiness {
+    fn into_response(self) -> Response {
+        let status = if self.ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+        (status, Json(self)).into_response()
+    }
+}
+
+/// Health reported by the processing thread (cloned handles share reports).
+#[derive(Clone)]
+pub struct Health {
+    reports: Arc<Reports>,
+}
+
+struct Reports {
+    max_lag: Option<u64>,
+    wal: bool,
+    started: AtomicBool,
+    lag: AtomicU64,
+    pending: AtomicU64,
+    store: Mutex<Option<Result<(), String>>>,
+    probed: Mutex<Option<Instant>>,
+}
+
+impl Health {
+    /// Returns new `Health` not yet started checking consumer lag within `max_lag` (when supplied) and the
+    /// write-ahead log (when `wal`).
+    pub fn new(max_lag: Option<u64>, wal: bool) -> Self {
+        Health {
+            reports: Arc::new(Reports {
+                max_lag,
+                wal,
+                started: AtomicBool::new(false),
+                lag: AtomicU64::new(UNREPORTED),
+                pending: AtomicU64::new(0),
+                store: Mutex::new(None),
+                probed: Mutex::new(None),
+            }),
+        }
+    }
+
+    /// Marks accounts loaded and recovered (processing started).
+    pub fn start(&self) {
+        self.reports.started.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns true when probes are due (and restarts the interval).
+    pub fn due(&self) -> bool {
+        let mut probed = self.reports.probed.lock().unwrap_or_else(|poisoned| { poisoned.into_inner() });
+        if probed.is_some_and(|probed| { probed.elapsed() < PROBE_INTERVAL }) {
+            return false;
+        }
+        *probed = Some(Instant::now());
+        true
+    }
+
+    /// Probes `accounts` store (reading an account).
+    pub fn probe(&self, accounts: &dyn ProjectionStore) {
+        let result = accounts.get(0).map(|_| {}).map_err(|e| { e.to_string() });
+        *self.reports.store.lock().unwrap_or_else(|poisoned| { poisoned.into_inner() }) = Some(result);
+    }
+
+    /// Records consumer `lag` (messages not yet consumed) of streaming source.
+    pub fn lag(&self, lag: u64) {
+        self.reports.lag.store(lag, Ordering::Relaxed);
+    }
+
+    /// Records commands logged by the write-ahead log since its checkpoint.
+    pub fn pending(&self, commands: usize) {
+        self.reports.pending.store(commands as u64, Ordering::Relaxed);
+    }
+
+    /// Returns readiness of checks reported.
+    pub fn readiness(&self) -> Readiness {
+        let reports = &self.reports;
+        let mut checks = vec![Check::new("started", reports.started.load(Ordering::Relaxed), None)];
+        checks.push(match reports.store.lock().unwrap_or_else(|poisoned| { poisoned.into_inner() }).as_ref() {
+            Some(Ok(())) => Check::new("store", true, None),
+            Some(Err(e)) => Check::new("store", false, Some(e.clone())),
+            None => Check::new("store", false, Some("not yet probed".to_string())),
+        });
+        match (reports.lag.load(Ordering::Relaxed), reports.max_lag) {
+            (UNREPORTED, _) => {}
+            (lag, Some(max_lag)) if lag > max_lag => {
+                checks.push(Check::new("lag", false, Some(format!("lag({}) exceeds max lag({})", lag, max_lag))));
+            }
+            (lag, _) => checks.push(Check::new("lag", true, Some(format!("lag({})", lag)))),
+        }
+        if reports.wal {
+            let pending = reports.pending.load(Ordering::Relaxed);
+            checks.push(Check::new("wal", true, Some(format!("{} commands since checkpoint", pending))));
+        }
+        Readiness::new(checks)
+    }
+}
+
+/// Health endpoints server bound to an address.
+pub struct HealthServer {
+    listener: TcpListener,
+}
+
+impl HealthServer {
+    /// Returns new `HealthServer` bound to `address` (host:port).
+    pub fn bind(address: &str) -> Result<Self, SimpleError> {
+        let listener = try_with!(TcpListener::bind(address), "unable to bind health server({})", address);
+        try_with!(listener.set_nonblocking(true), "unable to bind health server({})", address);
+        Ok(HealthServer { listener })
+    }
+
+    /// Returns address server is bound to.
+    pub fn local_addr(&self) -> Result<SocketAddr, SimpleError> {
+        Ok(try_with!(self.listener.local_addr(), "unable to read health server address"))
+    }
+
+    /// Returns thread serving health reported to `health` (runs until the process exits).
+    pub fn spawn(self, health: Health) -> Result<JoinHandle<Result<(), SimpleError>>, SimpleError> {
+        let listener = self.listener;
+        engine::spawn("health", move || {
+            async move {
+                let listener = try_with!(tokio::net::TcpListener::from_std(listener), "unable to listen");
+                let router = Router::new()
+                    .route("/healthz", get(healthz))
+                    .route("/readyz", get(readyz))
+                    .with_state(health);
+                try_with!(axum::serve(listener, router).await, "health server stopped");
+                Ok(())
+            }
+        })
+    }
+}
+
+/// Answers liveness (process serving requests).
+pub async fn healthz() -> &'static str {
+    "ok"
+}
+
+async fn readyz(State(health): State<Health>) -> Readiness {
+    health.readiness()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    use crate::store::MemoryStore;
+
+    /// Returns response (status line and body) of GET `path`.
+    fn get(address: SocketAddr, path: &str) -> String {
+        let mut stream = TcpStream::connect(address).unwrap();
+        write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n", path).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn readiness_reflects_checks() {
+        let health = Health::new(Some(100), true);
+        let server = HealthServer::bind("127.0.0.1:0").unwrap();
+        let address = server.local_addr().unwrap();
+        server.spawn(health.clone()).unwrap();
+
+        assert!(get(address, "/healthz").starts_with("HTTP/1.1 200"));
+        let response = get(address, "/readyz");
+        assert!(response.starts_with("HTTP/1.1 503") && response.contains(r#""name":"started","ok":false"#), "{}", response);
+
+        health.start();
+        assert!(health.due() && !health.due());
+        health.probe(&MemoryStore::default());
+        health.lag(250);
+        health.pending(3);
+        let readiness = health.readiness();
+        assert!(!readiness.ready);
+        assert_eq!(readiness.checks, vec![
+            Check::new("started", true, None),
+            Check::new("store", true, None),
+            Check::new("lag", false, Some("lag(250) exceeds max lag(100)".to_string())),
+            Check::new("wal", true, Some("3 commands since checkpoint".to_string())),
+        ]);
+
+        health.lag(20);
+        let response = get(address, "/readyz");
+        assert!(response.starts_with("HTTP/1.1 200") && response.contains(r#""ready":true"#), "{}", response);
+    }
+}
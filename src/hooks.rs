@@ -0,0 +1,61 @@
+//! Plugins of the cli registered into lifecycle hooks of processing (see `hooks` of the core crate).
+//!
+//! Hooks of the cli are run with accounts of its projection store.
+
+use std::fs::File;
+
+use csv::Writer;
+use simple_error::*;
+use tracing::debug;
+
+pub use accounts_aggregate_core::hooks::{Plugin, Reject};
+
+use crate::store::ProjectionStore;
+
+/// Accounts hooks of the cli are run with (its projection store).
+pub type Accounts = dyn ProjectionStore;
+
+/// Plugins (and closures) registered into the processing lifecycle of the cli.
+pub type Hooks = accounts_aggregate_core::hooks::Hooks<Accounts>;
+
+/// Rejects (dead-letter) report written as csv.
+pub struct RejectsReport {
+    writer: Writer<File>,
+}
+
+impl RejectsReport {
+    /// Returns new `RejectsReport` writing rejects to `writer`.
+    pub fn new(writer: Writer<File>) -> Self {
+        RejectsReport { writer }
+    }
+}
+
+impl Plugin<Accounts> for RejectsReport {
+    fn on_reject(&mut self, reject: &Reject) -> Result<(), SimpleError> {
+        try_with!(self.writer.serialize(reject), "unable to write reject");
+        Ok(())
+    }
+
+    fn on_complete(&mut self, _accounts: &Accounts) -> Result<(), SimpleError> {
+        try_with!(self.writer.flush(), "unable to flush rejects");
+        Ok(())
+    }
+}
+
+/// Logs rejects (with client, transaction and code) as `rejects` target at debug level.
+pub struct RejectsLog;
+
+impl Plugin<Accounts> for RejectsLog {
+    fn on_reject(&mut self, reject: &Reject) -> Result<(), SimpleError> {
+        debug!(
+            target: "rejects",
+            source = reject.source(),
+            line = reject.line(),
+            client = reject.client(),
+            tx = reject.tx(),
+            code = reject.code().name(),
+            "{}", reject.reason()
+        );
+        Ok(())
+    }
+}
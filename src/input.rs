@@ -0,0 +1,268 @@
+//! Readers used to input transaction and account metadata sources.
+//!
+//! Sources having a `.gz` (gzip) or `.zst` (zstd) extension are decompressed transparently. Remote sources
+//! (e.g. `s3://bucket/key`) are streamed from their object store.
+//! Sources having a `.qif` extension are read using `QifReader`, a `.ach` extension using `NachaReader`,
+//! a `.dat` extension using `FixedWidthReader`, a `.xlsx` extension using `XlsxReader`, a `.iso8583` extension
+//! using `Iso8583Reader` (`iso8583` feature) and all others as csv (`CsvCommandReader`).
+
+use std::fs::File;
+use std::io::{self, Read};
+
+use csv::{ByteRecord, Reader, ReaderBuilder, StringRecord, Trim};
+use simple_error::SimpleError;
+
+use crate::compression::{Compression, strip_compression};
+use crate::models::Command;
+use crate::remote;
+
+/// Format of a transactions source detected using its file extension (ignoring compression).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SourceFormat {
+    Csv,
+    Qif,
+    Nacha,
+    FixedWidth,
+    Xlsx,
+    #[cfg(feature = "iso8583")]
+    Iso8583,
+}
+
+impl SourceFormat {
+    /// Returns format of `path` using extension (`.qif`, `.ach`, `.dat`, `.xlsx`, `.iso8583` otherwise csv).
+    pub fn from_path(path: &str) -> Self {
+        let path = strip_compression(path).to_lowercase();
+        if path.ends_with(".qif") {
+            return SourceFormat::Qif;
+        }
+        if path.ends_with(".ach") {
+            return SourceFormat::Nacha;
+        }
+        if path.ends_with(".dat") {
+            return SourceFormat::FixedWidth;
+        }
+        if path.ends_with(".xlsx") {
+            return SourceFormat::Xlsx;
+        }
+        #[cfg(feature = "iso8583")]
+        if path.ends_with(".iso8583") {
+            return SourceFormat::Iso8583;
+        }
+        SourceFormat::Csv
+    }
+}
+
+/// Reader of transaction sources yielding `Command`s (or errors of unparseable records).
+pub trait SourceReader: Iterator<Item = Result<Command, SimpleError>> {
+    /// Returns line (or record number) in source of last record read.
+    fn line(&self) -> usize;
+
+    /// Returns partition and offset of last message read (streaming sources only).
+    fn offset(&self) -> Option<(String, i64)> {
+        None
+    }
+
+    /// Returns messages not yet consumed once the last message was read (streaming sources only).
+    #[cfg_attr(not(feature = "server"), allow(dead_code))]
+    fn lag(&self) -> Option<u64> {
+        None
+    }
+}
+
+/// Reads records of a lenient csv `Reader` as `Command`s.
+///
+/// Records are read into a single `ByteRecord` reused for every record and deserialized from its fields (borrowed),
+/// so reading records allocates only for text fields of commands (e.g. wallet or category).
+#[cfg_attr(feature = "simd", allow(dead_code))]
+pub struct CsvCommandReader<R: Read> {
+    reader: Reader<R>,
+    record: ByteRecord,
+    headers: StringRecord,
+    byte_headers: ByteRecord,
+    position: usize,
+}
+
+#[cfg_attr(feature = "simd", allow(dead_code))]
+impl<R: Read> CsvCommandReader<R> {
+    /// Returns new `CsvCommandReader` reading records of `reader` (see `csv_reader`).
+    pub fn new(mut reader: Reader<R>) -> csv::Result<Self> {
+        let headers = reader.headers()?.clone();
+        let byte_headers = reader.byte_headers()?.clone();
+        Ok(CsvCommandReader { reader, record: ByteRecord::new(), headers, byte_headers, position: 1 })
+    }
+
+    /// Returns lowercased headers of source.
+    pub fn headers(&self) -> &StringRecord {
+        &self.headers
+    }
+}
+
+impl<R: Read> SourceReader for CsvCommandReader<R> {
+    fn line(&self) -> usize {
+        self.position
+    }
+}
+
+impl<R: Read> Iterator for CsvCommandReader<R> {
+    type Item = Result<Command, SimpleError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let command = match self.reader.read_byte_record(&mut self.record) {
+            Ok(false) => return None,
+            Ok(true) => {
+                let record = &self.record;
+                self.position = record.position().map_or(self.position + 1, |position| { position.line() as usize });
+                record.deserialize(Some(&self.byte_headers))
+            }
+            Err(e) => {
+                self.position = e.position().map_or(self.position + 1, |position| { position.line() as usize });
+                Err(e)
+            }
+        };
+        Some(command.map_err(|e| { SimpleError::new(e.to_string()) }))
+    }
+}
+
+/// Returns reader of `path` (local or remote url) decompressing sources having a compressed extension.
+pub fn open(path: &str) -> io::Result<Box<dyn Read + Send>> {
+    if remote::is_remote(path) {
+        return Compression::from_path(path).reader(remote::open(path)?);
+    }
+    Compression::from_path(path).reader(File::open(path)?)
+}
+
+/// Returns lenient csv `Reader` of `source` using field `delimiter`.
+///
+/// Real-world exports are tolerated by:
+/// - trimming whitespace surrounding headers and fields
+/// - matching headers case-insensitively (headers are lowercased)
+/// - permitting rows missing trailing fields (deserialized as none)
+pub fn csv_reader<R: Read>(source: R, delimiter: u8) -> csv::Result<Reader<R>> {
+    let mut reader = ReaderBuilder::new()
+        .delimiter(delimiter)
+        .trim(Trim::All)
+        .flexible(true)
+        .from_reader(source);
+    let headers: StringRecord = reader.headers()?
+        .iter()
+        .map(|header| { header.to_lowercase() })
+        .collect();
+    reader.set_headers(headers);
+    Ok(reader)
+}
+
+/// Columns of csv transaction sources (sources without them aren't transaction sources).
+pub const REQUIRED_HEADERS: [&str; 3] = ["type", "client", "tx"];
+
+/// Returns first of `REQUIRED_HEADERS` missing from (lowercased) `headers`, if any.
+pub fn missing_header(headers: &StringRecord) -> Option<&'static str> {
+    REQUIRED_HEADERS.iter().find(|header| { !headers.iter().any(|h| { h == **header }) }).copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::prelude::Decimal;
+
+    use crate::events::Cause;
+    use crate::hooks::Reject;
+    use crate::models::CommandType;
+    use crate::testing::TempDir;
+
+
+    fn commands(source: &str) -> Vec<Command> {
+        csv_reader(source.as_bytes(), b',').unwrap()
+            .deserialize()
+            .map(|result| { result.unwrap() })
+            .collect()
+    }
+
+    #[test]
+    fn padded_fields_and_headers_tolerated() {
+        let commands = commands("Type, Client, TX, Amount\n deposit, 1, 1, 1.0\n");
+
+        assert_eq!(commands.len(), 1);
+        assert_eq!(commands[0].name(), &CommandType::Deposit);
+        assert_eq!(commands[0].actor_id(), 1);
+        assert_eq!(commands[0].amount(), Some(Decimal::new(10, 1)));
+    }
+
+    #[test]
+    fn missing_amount_tolerated() {
+        let commands = commands("type,client,tx,amount\ndeposit,1,1,1.0\ndispute,1,1,\ndispute,1,1\n");
+
+        assert_eq!(commands.len(), 3);
+        assert_eq!(commands[1].amount(), None);
+        assert_eq!(commands[2].amount(), None);
+    }
+
+    #[test]
+    fn compressed_sources_opened() {
+        use std::io::Write;
+
+        let source = "type,client,tx,amount\ndeposit,1,1,1.0\n";
+        let directory = TempDir::new("input-compressed");
+        let gzip = directory.join("transactions.csv.gz");
+        let mut encoder = flate2::write::GzEncoder::new(File::create(&gzip).unwrap(), flate2::Compression::default());
+        encoder.write_all(source.as_bytes()).unwrap();
+        encoder.finish().unwrap();
+        let zstd = directory.join("transactions.csv.zst");
+        std::fs::write(&zstd, zstd::encode_all(source.as_bytes(), 0).unwrap()).unwrap();
+
+        for path in [gzip, zstd] {
+            let mut content = String::new();
+            open(path.to_str().unwrap()).unwrap().read_to_string(&mut content).unwrap();
+
+            assert_eq!(content, source);
+        }
+    }
+
+    #[test]
+    fn unparseable_records_read_with_line() {
+        let source = "type,client,tx,amount\ndeposit,1,1,1.0\ndeposit,one,2,1.0\n";
+        let mut reader = CsvCommandReader::new(csv_reader(source.as_bytes(), b',').unwrap()).unwrap();
+
+        assert!(reader.next().unwrap().is_ok());
+        assert_eq!(reader.line(), 2);
+        let error = reader.next().unwrap().unwrap_err();
+        assert_eq!(reader.line(), 3);
+
+        let mut writer = csv::Writer::from_writer(vec![]);
+        writer.serialize(Reject::new("tx.csv", reader.line(), None, &error)).unwrap();
+        let report = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+
+        assert!(report.starts_with("source,line,type,client,tx,amount,reason\ntx.csv,3,,,,,"));
+    }
+
+    #[test]
+    fn missing_amount_column_tolerated() {
+        let commands = commands("type,client,tx\nresolve,2,5\n");
+
+        assert_eq!(commands[0].name(), &CommandType::Resolve);
+        assert_eq!(commands[0].tx(), 5);
+        assert_eq!(commands[0].amount(), None);
+    }
+
+    #[test]
+    fn records_deserialized_from_reused_record() {
+        let mut source = b"type,client,tx,amount,wallet,category\n\
+            deposit,1,1,1.0,savings,groceries\n\
+            deposit,1,2,2.0,,\n\
+            deposit,1,3,3.0,main,"
+            .to_vec();
+        source.extend_from_slice(b"\xff\n deposit , 2 , 4 , 4.0\n");
+        let mut reader = CsvCommandReader::new(csv_reader(source.as_slice(), b',').unwrap()).unwrap();
+
+        let command = reader.next().unwrap().unwrap();
+        assert_eq!((command.wallet().map(String::as_str), command.category().map(String::as_str)), (Some("savings"), Some("groceries")));
+        let command = reader.next().unwrap().unwrap();
+        assert_eq!((command.tx(), command.wallet(), command.category()), (2, None, None));
+        // invalid utf-8 fields reject their record only
+        assert!(reader.next().unwrap().is_err());
+        assert_eq!(reader.line(), 4);
+        let command = reader.next().unwrap().unwrap();
+        assert_eq!((command.actor_id(), command.tx(), command.amount()), (2, 4, Some(Decimal::new(40, 1))));
+        assert_eq!(reader.line(), 5);
+        assert!(reader.next().is_none());
+    }
+}
@@ -0,0 +1,230 @@
+//! Reader of ISO 8583 (1987) financial messages mapping card-switch traffic to `Command`s.
+//!
+//! Messages are ASCII encoded (one per line) having a hex bitmap and numeric length prefixes:
+//! - financial requests and advices (`0200`, `0220`, `0221`) are withdrawals or deposits by processing code
+//! - reversals (`0400`, `0420`, `0421`) are mapped to the inverse command of their processing code
+//! - other messages (e.g. authorizations, responses and network management) are skipped
+//!
+//! Fields used are the processing code (3), amount in minor units (4), system trace audit number (11) as
+//! transaction id and account identification (102) as client (otherwise the reader client).
+
+use std::io::{BufRead, Lines};
+
+use simple_error::*;
+
+use crate::input::SourceReader;
+use crate::models::{ClientId, Command, CommandType, Currency};
+
+/// Length of a data element.
+#[derive(Clone, Copy)]
+enum Length {
+    /// Fixed number of characters.
+    Fixed(usize),
+    /// Variable number of characters prefixed by a length of given digits.
+    Variable(usize),
+}
+
+use Length::{Fixed, Variable};
+
+/// Lengths of data elements 1 to 128 (binary elements are hex encoded).
+const FIELDS: [Length; 128] = [
+    Fixed(16), Variable(2), Fixed(6), Fixed(12), Fixed(12), Fixed(12), Fixed(10), Fixed(8),
+    Fixed(8), Fixed(8), Fixed(6), Fixed(6), Fixed(4), Fixed(4), Fixed(4), Fixed(4),
+    Fixed(4), Fixed(4), Fixed(3), Fixed(3), Fixed(3), Fixed(3), Fixed(3), Fixed(3),
+    Fixed(2), Fixed(2), Fixed(1), Fixed(9), Fixed(9), Fixed(9), Fixed(9), Variable(2),
+    Variable(2), Variable(2), Variable(2), Variable(3), Fixed(12), Fixed(6), Fixed(2), Fixed(3),
+    Fixed(8), Fixed(15), Fixed(40), Variable(2), Variable(2), Variable(3), Variable(3), Variable(3),
+    Fixed(3), Fixed(3), Fixed(3), Fixed(16), Fixed(16), Variable(3), Variable(3), Variable(3),
+    Variable(3), Variable(3), Variable(3), Variable(3), Variable(3), Variable(3), Variable(3), Fixed(16),
+    Fixed(2), Fixed(1), Fixed(2), Fixed(3), Fixed(3), Fixed(3), Fixed(4), Fixed(4),
+    Fixed(6), Fixed(10), Fixed(10), Fixed(10), Fixed(10), Fixed(10), Fixed(10), Fixed(10),
+    Fixed(10), Fixed(12), Fixed(12), Fixed(12), Fixed(12), Fixed(16), Fixed(16), Fixed(16),
+    Fixed(16), Fixed(42), Fixed(1), Fixed(2), Fixed(5), Fixed(7), Fixed(42), Fixed(16),
+    Fixed(17), Fixed(25), Variable(2), Variable(2), Variable(2), Variable(2), Variable(2), Variable(3),
+    Variable(3), Variable(3), Variable(3), Variable(3), Variable(3), Variable(3), Variable(3), Variable(3),
+    Variable(3), Variable(3), Variable(3), Variable(3), Variable(3), Variable(3), Variable(3), Variable(3),
+    Variable(3), Variable(3), Variable(3), Variable(3), Variable(3), Variable(3), Variable(3), Fixed(16),
+];
+
+/// Reads ISO 8583 messages from `R` as deposit or withdraw `Command`s.
+pub struct Iso8583Reader<R: BufRead> {
+    lines: Lines<R>,
+    client: Option<ClientId>,
+    messages: usize,
+}
+
+/// Message type indicator and data elements of an ISO 8583 message.
+struct Message {
+    mti: String,
+    fields: Vec<Option<String>>,
+}
+
+impl Message {
+    /// Returns value of data element `number` (1-based).
+    fn field(&self, number: usize) -> Option<&str> {
+        self.fields[number - 1].as_deref()
+    }
+}
+
+impl<R: BufRead> Iso8583Reader<R> {
+    /// Returns new `Iso8583Reader` reading messages from `reader` of `client` when without account field.
+    pub fn new(reader: R, client: Option<ClientId>) -> Self {
+        Iso8583Reader { lines: reader.lines(), client, messages: 0 }
+    }
+
+    /// Returns command of next financial message or none at end of source.
+    fn read_message(&mut self) -> Result<Option<Command>, SimpleError> {
+        for line in self.lines.by_ref() {
+            self.messages += 1;
+            let line = try_with!(line, "unable to read iso 8583 message({})", self.messages);
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let message = parse_message(line, self.messages)?;
+            if let Some(command) = command(self.client, self.messages, &message)? {
+                return Ok(Some(command));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl<R: BufRead> SourceReader for Iso8583Reader<R> {
+    fn line(&self) -> usize {
+        self.messages
+    }
+}
+
+impl<R: BufRead> Iterator for Iso8583Reader<R> {
+    type Item = Result<Command, SimpleError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_message().transpose()
+    }
+}
+
+/// Returns message type indicator and data elements of ASCII encoded message at `position`.
+fn parse_message(line: &str, position: usize) -> Result<Message, SimpleError> {
+    if !line.is_ascii() || line.len() < 20 {
+        bail!("invalid iso 8583 message({})", position);
+    }
+    let mti = line[..4].to_string();
+    let mut bitmap = try_with!(u128::from_str_radix(&line[4..20], 16), "invalid iso 8583 message({}) bitmap", position) << 64;
+    let mut offset = 20;
+    // secondary bitmap present when first bit set
+    if bitmap & (1 << 127) != 0 {
+        let secondary = match line.get(20..36) {
+            Some(secondary) => secondary,
+            None => bail!("invalid iso 8583 message({}) secondary bitmap", position),
+        };
+        bitmap |= try_with!(u128::from_str_radix(secondary, 16), "invalid iso 8583 message({}) secondary bitmap", position);
+        offset = 36;
+    }
+
+    let mut fields = vec![None; FIELDS.len()];
+    for (index, length) in FIELDS.iter().enumerate().skip(1) {
+        if bitmap & (1 << (127 - index)) == 0 {
+            continue;
+        }
+        let length = match length {
+            Fixed(length) => *length,
+            Variable(digits) => {
+                let prefix = line.get(offset..offset + digits).and_then(|prefix| { prefix.parse::<usize>().ok() });
+                match prefix {
+                    Some(length) => {
+                        offset += digits;
+                        length
+                    }
+                    None => bail!("invalid iso 8583 message({}) field({}) length", position, index + 1),
+                }
+            }
+        };
+        match line.get(offset..offset + length) {
+            Some(value) => fields[index] = Some(value.to_string()),
+            None => bail!("iso 8583 message({}) truncated at field({})", position, index + 1),
+        }
+        offset += length;
+    }
+    Ok(Message { mti, fields })
+}
+
+/// Returns deposit or withdraw command of financial `message` at `position` (none for other messages).
+fn command(client: Option<ClientId>, position: usize, message: &Message) -> Result<Option<Command>, SimpleError> {
+    let reversal = match message.mti.as_str() {
+        "0200" | "0220" | "0221" => false,
+        "0400" | "0420" | "0421" => true,
+        _ => return Ok(None),
+    };
+    let processing = match message.field(3) {
+        Some(processing) => processing,
+        None => bail!("processing code is none for iso 8583 message({})", position),
+    };
+    // purchases and cash withdrawals debit while refunds, deposits and payments credit the account
+    let debit = match &processing[..2] {
+        "00" | "01" | "09" | "10" | "11" | "12" => true,
+        "20" | "21" | "22" | "28" => false,
+        code => bail!("unsupported processing code({}) of iso 8583 message({})", code, position),
+    };
+    let name = if debit != reversal { CommandType::Withdraw } else { CommandType::Deposit };
+    let amount = match message.field(4).and_then(|amount| { amount.parse::<i64>().ok() }) {
+        Some(amount) => Currency::new(amount, 2),
+        None => bail!("amount is none for iso 8583 message({})", position),
+    };
+    let tx = match message.field(11).and_then(|stan| { stan.parse().ok() }) {
+        Some(tx) => tx,
+        None => bail!("system trace audit number is none for iso 8583 message({})", position),
+    };
+    let client = match message.field(102).and_then(|account| { account.trim().parse().ok() }).or(client) {
+        Some(client) => client,
+        None => bail!("client is none for iso 8583 message({})", position),
+    };
+    Ok(Some(Command::new(name, client, tx, Some(amount))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::prelude::Decimal;
+
+    use crate::events::Cause;
+
+    // 0200 purchase of 12.34 (trace 000101) for account 7 having fields 3, 4, 11 and 102
+    const PURCHASE: &str = "0200B0200000000000000000000004000000000000000000001234000101017";
+    // 0420 reversal of 12.34 (trace 000102) having fields 3, 4 and 11
+    const REVERSAL: &str = "04203020000000000000000000000000001234000102";
+    // 0800 network management echo having field 11
+    const ECHO: &str = "08000020000000000000000103";
+
+    fn commands(source: &str, client: Option<ClientId>) -> Vec<Command> {
+        Iso8583Reader::new(source.as_bytes(), client).map(|command| { command.unwrap() }).collect()
+    }
+
+    #[test]
+    fn financial_messages_read_as_commands() {
+        let commands = commands(&format!("{}\n{}\n{}\n", PURCHASE, ECHO, REVERSAL), Some(3));
+
+        assert_eq!(commands.len(), 2);
+        assert_eq!(commands[0].name(), &CommandType::Withdraw);
+        assert_eq!(commands[0].actor_id(), 7);
+        assert_eq!(commands[0].tx(), 101);
+        assert_eq!(commands[0].amount(), Some(Decimal::new(1234, 2)));
+        assert_eq!(commands[1].name(), &CommandType::Deposit);
+        assert_eq!(commands[1].actor_id(), 3);
+        assert_eq!(commands[1].tx(), 102);
+    }
+
+    #[test]
+    fn truncated_message_errors() {
+        let mut reader = Iso8583Reader::new(&PURCHASE.as_bytes()[..40], Some(3));
+
+        assert!(reader.next().unwrap().is_err());
+    }
+
+    #[test]
+    fn message_without_client_errors() {
+        let mut reader = Iso8583Reader::new(REVERSAL.as_bytes(), None);
+
+        assert!(reader.next().unwrap().is_err());
+    }
+}
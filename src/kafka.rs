@@ -0,0 +1,260 @@
+//! Transactions consumed continuously from a Kafka topic (streaming rather than batch sources).
+//!
+//! Offsets of the consumer group are stored once the main loop has applied (or rejected) a message to accounts, i.e.
+//! when the next message is requested, and committed periodically (and when consumption stops). Offsets aren't tied
+//! to outputs or stores being flushed: messages consumed after the last commit are consumed again by a restarted run,
+//! but messages committed before an interrupted run wrote its outputs (or flushed its store) are lost to them, so
+//! delivery is at-least-once only for commands logged by persistent stores (write-ahead log). `AsyncKafkaReader`
+//! (`async` feature) awaits messages rather than polling them.
+//!
+//! Exactly-once processing instead assigns every partition of the topic from the offset following the one kept by the
+//! account store (see `KafkaReader::resume_from`), as offsets are written within the same commit as accounts.
+
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use rdkafka::ClientConfig;
+use rdkafka::consumer::{BaseConsumer, CommitMode, Consumer};
+#[cfg(feature = "async")]
+use rdkafka::consumer::StreamConsumer;
+use rdkafka::config::FromClientConfig;
+use rdkafka::error::KafkaError;
+use rdkafka::message::Message;
+use rdkafka::topic_partition_list::{Offset, TopicPartitionList};
+use rdkafka::types::RDKafkaErrorCode;
+use simple_error::*;
+
+#[cfg(feature = "async")]
+use crate::asynchronous::AsyncSourceReader;
+use crate::input::SourceReader;
+use crate::messages::MessageDecoder;
+use crate::models::Command;
+use crate::store::Offsets;
+
+/// Duration waited for each poll of messages.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Duration waited for metadata of topic partitions.
+const METADATA_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Duration lag of partitions consumed is reported for before watermarks are fetched again.
+const LAG_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Reads messages of a Kafka topic as `Command`s until idle (forever unless an idle timeout is supplied).
+pub struct KafkaReader {
+    consumer: BaseConsumer,
+    decoder: MessageDecoder,
+    idle: Option<Duration>,
+    consumed: Option<(String, i32, i64)>,
+    partitions: HashMap<i32, i64>,
+    lag: Cell<Option<(Instant, u64)>>,
+    received: Instant,
+    position: usize,
+}
+
+impl KafkaReader {
+    /// Returns new `KafkaReader` of `topic` consumed from `brokers` by consumer `group`.
+    ///
+    /// Topics are consumed from their earliest offset when the group has committed none.
+    pub fn connect(
+        brokers: &str,
+        topic: &str,
+        group: &str,
+        decoder: MessageDecoder,
+        idle: Option<Duration>,
+    ) -> Result<Self, SimpleError> {
+        let consumer: BaseConsumer = subscribe(brokers, topic, group)?;
+        Ok(KafkaReader { consumer, decoder, idle, consumed: None, partitions: HashMap::new(), lag: Cell::new(None), received: Instant::now(), position: 0 })
+    }
+
+    /// Consumes every partition of `topic` from the message following its offset of `offsets` (earliest when none)
+    /// rather than offsets committed by the consumer group.
+    pub fn resume_from(self, topic: &str, offsets: &Offsets) -> Result<Self, SimpleError> {
+        self.consumer.unsubscribe();
+        let metadata = try_with!(
+            self.consumer.fetch_metadata(Some(topic), METADATA_TIMEOUT),
+            "unable to fetch kafka topic({}) metadata",
+            topic
+        );
+        let mut assignment = TopicPartitionList::new();
+        for partition in metadata.topics().iter().flat_map(|metadata| { metadata.partitions() }) {
+            let offset = offsets.get(&partition_key(topic, partition.id()))
+                .map_or(Offset::Beginning, |offset| { Offset::Offset(offset + 1) });
+            try_with!(
+                assignment.add_partition_offset(topic, partition.id(), offset),
+                "unable to assign kafka partition({}/{})",
+                topic,
+                partition.id()
+            );
+        }
+        try_with!(self.consumer.assign(&assignment), "unable to assign kafka topic({}) partitions", topic);
+        Ok(self)
+    }
+
+    /// Stores offset of message consumed (and processed) previously.
+    fn store(&mut self) -> Result<(), SimpleError> {
+        store(&self.consumer, self.consumed.take())
+    }
+}
+
+impl SourceReader for KafkaReader {
+    fn line(&self) -> usize {
+        self.position
+    }
+
+    fn offset(&self) -> Option<(String, i64)> {
+        self.consumed.as_ref().map(|(topic, partition, offset)| { (partition_key(topic, *partition), *offset) })
+    }
+
+    /// Returns messages of partitions consumed following their last message consumed (high watermarks fetched from
+    /// brokers at most every `LAG_INTERVAL`).
+    fn lag(&self) -> Option<u64> {
+        if let Some((_, lag)) = self.lag.get().filter(|(fetched, _)| { fetched.elapsed() < LAG_INTERVAL }) {
+            return Some(lag);
+        }
+        let (topic, _, _) = self.consumed.as_ref()?;
+        let lag = self.partitions.iter().map(|(partition, offset)| {
+            self.consumer.fetch_watermarks(topic, *partition, METADATA_TIMEOUT)
+                .map_or(0, |(_, high)| { (high - offset - 1).max(0) as u64 })
+        }).sum();
+        self.lag.set(Some((Instant::now(), lag)));
+        Some(lag)
+    }
+}
+
+impl Iterator for KafkaReader {
+    type Item = Result<Command, SimpleError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Err(e) = self.store() {
+            return Some(Err(e));
+        }
+        loop {
+            match self.consumer.poll(POLL_INTERVAL) {
+                Some(Ok(message)) => {
+                    self.position += 1;
+                    self.received = Instant::now();
+                    self.partitions.insert(message.partition(), message.offset());
+                    self.consumed = Some((message.topic().to_string(), message.partition(), message.offset()));
+                    return Some(self.decoder.decode(message.payload().unwrap_or_default()));
+                }
+                // consumer errors (e.g. brokers down) are retried (and logged) by the client until idle
+                Some(Err(_)) | None if self.idle.is_some_and(|idle| { self.received.elapsed() >= idle }) => {
+                    return commit(&self.consumer).err().map(Err);
+                }
+                Some(Err(_)) | None => {}
+            }
+        }
+    }
+}
+
+/// Reads messages of a Kafka topic as `Command`s awaiting messages until idle (forever unless an idle timeout is
+/// supplied).
+#[cfg(feature = "async")]
+pub struct AsyncKafkaReader {
+    consumer: StreamConsumer,
+    decoder: MessageDecoder,
+    idle: Option<Duration>,
+    consumed: Option<(String, i32, i64)>,
+    received: Instant,
+    position: usize,
+}
+
+#[cfg(feature = "async")]
+impl AsyncKafkaReader {
+    /// Returns new `AsyncKafkaReader` of `topic` consumed from `brokers` by consumer `group` (within a tokio runtime).
+    ///
+    /// Topics are consumed from their earliest offset when the group has committed none.
+    pub fn connect(
+        brokers: &str,
+        topic: &str,
+        group: &str,
+        decoder: MessageDecoder,
+        idle: Option<Duration>,
+    ) -> Result<Self, SimpleError> {
+        let consumer: StreamConsumer = subscribe(brokers, topic, group)?;
+        Ok(AsyncKafkaReader { consumer, decoder, idle, consumed: None, received: Instant::now(), position: 0 })
+    }
+}
+
+#[cfg(feature = "async")]
+impl AsyncSourceReader for AsyncKafkaReader {
+    fn line(&self) -> usize {
+        self.position
+    }
+
+    async fn next(&mut self) -> Option<Result<Command, SimpleError>> {
+        if let Err(e) = store(&self.consumer, self.consumed.take()) {
+            return Some(Err(e));
+        }
+        loop {
+            let received = match self.idle {
+                Some(idle) => {
+                    let remaining = idle.saturating_sub(self.received.elapsed());
+                    match tokio::time::timeout(remaining, self.consumer.recv()).await {
+                        Ok(received) => received,
+                        Err(_) => return commit(&self.consumer).err().map(Err),
+                    }
+                }
+                None => self.consumer.recv().await,
+            };
+            // consumer errors (e.g. brokers down) are retried (and logged) by the client until idle
+            if let Ok(message) = received {
+                self.position += 1;
+                self.received = Instant::now();
+                self.consumed = Some((message.topic().to_string(), message.partition(), message.offset()));
+                return Some(self.decoder.decode(message.payload().unwrap_or_default()));
+            }
+        }
+    }
+}
+
+/// Returns consumer of `topic` (consumer `group`) connected to `brokers` storing offsets explicitly.
+fn subscribe<C>(brokers: &str, topic: &str, group: &str) -> Result<C, SimpleError>
+where
+    C: Consumer + FromClientConfig
+{
+    let consumer: C = try_with!(
+        ClientConfig::new()
+            .set("bootstrap.servers", brokers)
+            .set("group.id", group)
+            .set("auto.offset.reset", "earliest")
+            .set("enable.auto.offset.store", "false")
+            .create(),
+        "unable to connect to kafka brokers({})",
+        brokers
+    );
+    try_with!(consumer.subscribe(&[topic]), "unable to subscribe to kafka topic({})", topic);
+    Ok(consumer)
+}
+
+/// Returns key of `topic` partition offsets are kept by.
+fn partition_key(topic: &str, partition: i32) -> String {
+    format!("{}/{}", topic, partition)
+}
+
+/// Stores offset of message `consumed` (and processed) previously.
+fn store<C: Consumer>(consumer: &C, consumed: Option<(String, i32, i64)>) -> Result<(), SimpleError> {
+    if let Some((topic, partition, offset)) = consumed {
+        try_with!(
+            consumer.store_offset(&topic, partition, offset),
+            "unable to store kafka offset({}/{}/{})",
+            topic,
+            partition,
+            offset
+        );
+    }
+    Ok(())
+}
+
+/// Commits offsets stored (none stored is not an error).
+fn commit<C: Consumer>(consumer: &C) -> Result<(), SimpleError> {
+    match consumer.commit_consumer_state(CommitMode::Sync) {
+        Err(KafkaError::ConsumerCommit(RDKafkaErrorCode::NoOffset)) => Ok(()),
+        result => {
+            try_with!(result, "unable to commit kafka offsets");
+            Ok(())
+        }
+    }
+}
@@ -2,7 +2,7 @@
 //!
 //! Included:
 //! - main executable controlling workflow of application
-//! - domain models used to build `Account` aggregate
+//! - domain models used to build `Account` aggregate (`core` crate shared by wasm and ffi builds)
 //! - data generator subpackage
 //!
 //! For help:
@@ -10,74 +10,2197 @@
 //! cargo run -- -h
 //! ```
 
-mod events;
-mod models;
+mod merchants;
+mod projections;
+mod exports;
+mod compression;
+mod input;
+mod qif;
+mod nacha;
+mod fixedwidth;
+mod xlsx;
+#[cfg(feature = "iso8583")]
+mod iso8583;
+mod output;
+mod summary;
+mod bench;
+mod audit;
+mod budget;
+mod checkpoint;
+mod config;
+mod manifest;
+mod outbox;
+mod store;
+mod state;
+mod metrics;
+mod telemetry;
+mod pipeline;
+mod shards;
+mod wal;
+mod extsort;
+mod merge;
+mod follow;
+mod watch;
+mod verify;
+mod diff;
+mod stats;
+mod ratelimit;
+mod failure;
+mod progress;
+// serializers for integrations exchanging commands and events (e.g. kafka)
+#[cfg(any(feature = "kafka", feature = "nats", test))]
+mod avro;
+mod proto;
+mod eventlog;
+mod eventstore;
+mod subscribers;
+mod hooks;
+#[cfg(any(feature = "parquet", feature = "arrow"))]
+mod columnar;
+#[cfg(feature = "postgres")]
+mod postgres;
+#[cfg(feature = "duckdb")]
+mod duckdb;
+mod remote;
+#[cfg(feature = "s3")]
+mod s3;
+#[cfg(any(feature = "gcs", feature = "azure"))]
+mod objectstore;
+#[cfg(any(feature = "kafka", feature = "nats"))]
+mod messages;
+#[cfg(feature = "kafka")]
+mod kafka;
+#[cfg(feature = "nats")]
+mod nats;
+#[cfg(feature = "webhook")]
+mod webhook;
+#[cfg(feature = "rhai")]
+mod rules;
+#[cfg(feature = "async")]
+mod asynchronous;
+#[cfg(feature = "simd")]
+mod fastcsv;
+#[cfg(any(feature = "server", feature = "grpc"))]
+mod engine;
+#[cfg(feature = "server")]
+mod server;
+#[cfg(feature = "server")]
+mod health;
+#[cfg(feature = "grpc")]
+mod grpc;
+#[cfg(feature = "graphql")]
+mod graphql;
+#[cfg(test)]
+mod testing;
 
-use std::io;
-use std::fs::File;
+use std::borrow::Cow;
+use std::env;
+use std::fmt;
+use std::str::FromStr;
+use std::io::{self, BufReader, BufWriter};
+use std::fs::{self, File};
+use std::path::Path;
 use std::collections::HashMap;
+use std::time::{Duration, Instant};
 
-use clap::{Arg, App};
-use csv::{Reader, Writer};
+use clap::{Arg, App, AppSettings, ArgMatches, SubCommand};
+use csv::{Writer, WriterBuilder};
+use simple_error::SimpleError;
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveTime, TimeZone, Utc};
 
-use events::{Actor, Cause};
-use models::{Command, Account};
+use accounts_aggregate_core::{events, models};
+use events::{Actor, Cause, Effects};
+use models::{Command, Event, Account, AccountMetadata, ClientId, OpeningBalance, Timestamp, Version};
+use compression::Compression;
+use summary::Summary;
+use budget::{BudgetAction, MemoryBudget};
+use checkpoint::{Checkpoint, CHECKPOINT_RECORDS};
+use config::Config;
+use manifest::{Manifest, MANIFEST_NAME};
+use outbox::FilePublisher;
+use store::{EventHistory, MemoryStore, Offsets, ProjectionStore, StoreKind};
+use shards::{Outcome, ShardPool, QUEUE_CAPACITY as SHARD_QUEUE_CAPACITY};
+use wal::WriteAheadLog;
+use extsort::{ExternalSorter, RUN_RECORDS};
+use merge::SourceMerge;
+use follow::{FollowFile, FollowSnapshots, FOLLOW_INTERVAL};
+use watch::WatchReader;
+use verify::Verification;
+use diff::DiffSummary;
+use stats::Stats;
+use ratelimit::{MaxRate, RateLimiter};
+use failure::{Failure, Kind, OrFail};
+use progress::Progress;
+use input::{SourceFormat, SourceReader, csv_reader};
+#[cfg(not(feature = "simd"))]
+use input::CsvCommandReader;
+use metrics::Metrics;
+use telemetry::{LogFormat, Verbosity, PROGRESS_RECORDS};
+use bench::BenchReport;
+use pipeline::{PipelineReader, QUEUE_CAPACITY};
+use qif::QifReader;
+use nacha::NachaReader;
+use fixedwidth::{FixedWidthReader, Layout};
+use xlsx::XlsxReader;
+#[cfg(feature = "iso8583")]
+use iso8583::Iso8583Reader;
+use output::{FLUSH_ROWS, OutputFormat, RecordWriter, SortKey, partial_path};
+use audit::AuditRecord;
+use eventlog::{EventLogFormat, EventLogReader, EventLogWriter};
+use eventstore::{EventStore, SegmentEventStore};
+use hooks::{Hooks, Reject, RejectsLog, RejectsReport};
+use subscribers::{AuditTrail, EventBus, History, SettlementReport, TotalsReport};
+#[cfg(feature = "kafka")]
+use kafka::KafkaReader;
+#[cfg(any(feature = "kafka", feature = "nats"))]
+use messages::{MessageDecoder, MessageFormat, StreamKind};
+#[cfg(feature = "nats")]
+use nats::NatsReader;
+#[cfg(feature = "webhook")]
+use webhook::Webhook;
+#[cfg(feature = "rhai")]
+use rules::{RulesWatcher, ScriptRules};
+#[cfg(feature = "async")]
+use asynchronous::{AsyncCsvReader, AsyncPipelineReader};
+#[cfg(feature = "simd")]
+use fastcsv::FastCsvReader;
+#[cfg(feature = "server")]
+use server::Server;
+#[cfg(feature = "server")]
+use health::{Health, HealthServer};
+#[cfg(feature = "grpc")]
+use grpc::GrpcServer;
+#[cfg(all(feature = "async", feature = "kafka"))]
+use asynchronous::BlockingReader;
+#[cfg(all(feature = "async", feature = "kafka"))]
+use kafka::AsyncKafkaReader;
+use projections::{CategoryTotals, ExtendedSnapshot, MonthlyTotals, Statement, WindowSnapshot, window_start};
+
+#[cfg(all(feature = "jemalloc", feature = "mimalloc"))]
+compile_error!("features jemalloc and mimalloc each replace the global allocator so only one can be enabled");
+
+/// Global allocator replaced by jemalloc (`jemalloc` feature).
+#[cfg(feature = "jemalloc")]
+#[global_allocator]
+static ALLOCATOR: tikv_jemallocator::Jemalloc = tikv_jemallocator::Jemalloc;
+
+/// Global allocator replaced by mimalloc (`mimalloc` feature).
+#[cfg(all(feature = "mimalloc", not(feature = "jemalloc")))]
+#[global_allocator]
+static ALLOCATOR: mimalloc::MiMalloc = mimalloc::MiMalloc;
 
 /// Procedural execution of application workflow.
 ///
 /// **Steps:**
 /// 1. Bootstrap clap cli argument parser.
-/// 2. Get file handle for data source.
-/// 3. Stream transaction records using csv + serde to deserialize models.
-/// 4. For each transaction record build aggregate and apply events to projection.
-/// 5. For each aggregate account serialize using csv + serde and write to stdout.
+/// 2. Load account metadata (types) when provided and rehydrate accounts from event log or audit trail when provided.
+/// 3. Get file handle for each data source (in lexicographic order).
+/// 4. Stream transaction records using csv + serde to deserialize models (on a separate thread).
+///    Records are sorted by client (spilling sorted runs to temporary files) before handled when requested.
+/// 5. For each transaction record build aggregate and apply events to projection (kept in account store).
+///    Plugin hooks run before commands are handled, once events are applied and for rejected (or unparseable)
+///    records (e.g. written to rejects report with reason when requested).
+/// 6. Publish applied account events to subscribers (balance history, event store, event log, audit trail, reports and
+///    webhook when requested).
+///    When transaction timestamps cross into a new window write snapshots of all accounts (when requested).
+/// 7. For each aggregate account (or wallet) ordered by sort key serialize using output format + serde and write to stdout
+///    (or output file).
+/// 8. Finish subscribers writing settlements (ordered by merchant id), monthly and category totals reports.
+/// 9. Write summary of transactions, accounts and throughput to stderr or summary report and metrics (JSON) when
+///    requested.
+///
+/// Subcommands (see `statement`) run their own workflow.
+///
+/// Logs and spans of processing stages are traced (see `telemetry`).
 ///
-/// Desperately needs a logger w/log levels.
+/// Failures are reported to stderr exiting with a code telling I/O, data and internal errors apart (see `failure`).
 fn main() {
+    // panics (broken invariants) exit as internal failures rather than aborting with a backtrace
+    failure::exit_on_panic();
     // bootstrap clap thus getting source filepath
-    let arg_matches = App::new("account-aggregate")
+    let accounts_arg = Arg::with_name("accounts")
+        .short("a")
+        .long("accounts")
+        .value_name("accounts")
+        .help("source of account metadata (filepath) with client, type and limit columns")
+        .takes_value(true);
+    let delimiter_arg = Arg::with_name("delimiter")
+        .short("d")
+        .long("delimiter")
+        .value_name("delimiter")
+        .help("field delimiter of csv sources and reports (single character or tab) [default: tab for .tsv otherwise comma]")
+        .validator(|value| { parse_delimiter(&value).map(|_| {}) })
+        .takes_value(true);
+    let layout_arg = Arg::with_name("layout")
+        .long("layout")
+        .value_name("layout")
+        .help("layout of fixed-width (.dat) sources (TOML filepath) with column names, offsets, widths and types")
+        .takes_value(true);
+    let sheet_arg = Arg::with_name("sheet")
+        .long("sheet")
+        .value_name("sheet")
+        .help("worksheet of xlsx sources [default: first sheet]")
+        .takes_value(true);
+    let source_arg = Arg::with_name("source")
+        .help("sources of transactions (filepaths, glob patterns or remote s3://, gs:// or az:// urls) processed in lexicographic order")
+        .required(true)
+        .multiple(true)
+        .index(1);
+    let app = App::new("account-aggregate")
         .version("0.1.0")
+        .setting(AppSettings::SubcommandsNegateReqs)
+        .setting(AppSettings::ArgsNegateSubcommands)
+        .arg(source_arg.clone().required_unless_one(&["incremental", "stream", "watch"]))
+        .arg(Arg::with_name("config")
+            .long("config")
+            .value_name("config")
+            .help("configuration file (TOML filepath) of settings applied unless supplied as arguments (see README)")
+            .takes_value(true))
+        .arg(Arg::with_name("incremental")
+            .long("incremental")
+            .value_name("incremental")
+            .help("directory of dated batch files processed in lexicographic order (only files and rows not recorded by its manifest)")
+            .conflicts_with("source")
+            .takes_value(true))
+        .arg(Arg::with_name("watch")
+            .long("watch")
+            .value_name("watch")
+            .help("drop directory of csv files processed as they appear (moved to processed/ or failed/ subdirectories) keeping accounts across files, emitting accounts updated to stdout periodically")
+            .conflicts_with_all(&["source", "incremental", "stream", "follow", "checkpoint", "external-sort", "merge-sources", "threads"])
+            .takes_value(true))
+        .arg(Arg::with_name("manifest")
+            .long("manifest")
+            .value_name("manifest")
+            .help("destination of manifest (filepath) recording rows processed per file of incremental directory (defaults to .manifest.json within directory)")
+            .requires("incremental")
+            .takes_value(true))
+        .arg(accounts_arg.clone())
+        .arg(Arg::with_name("initial-balances")
+            .long("initial-balances")
+            .value_name("initial-balances")
+            .help("source of account balances (filepath) output by a previous run accounts are opened with (without event history)")
+            .takes_value(true))
+        .arg(delimiter_arg.clone())
+        .arg(layout_arg.clone())
+        .arg(sheet_arg.clone())
+        .arg(Arg::with_name("client")
+            .long("client")
+            .value_name("client")
+            .help("client of transactions read from sources without a client column (qif) or account (iso8583)")
+            .takes_value(true))
+        .arg(Arg::with_name("output-format")
+            .long("output-format")
+            .value_name("output-format")
+            .help("format of account snapshots written to stdout")
+            .possible_values(&OutputFormat::names())
+            .default_value("csv")
+            .takes_value(true))
+        .arg(Arg::with_name("output")
+            .short("o")
+            .long("output")
+            .value_name("output")
+            .help("destination of account snapshots (filepath) written atomically instead of stdout, sqlite://<database>, postgres://<url> or duckdb://<database> accounts table or redis://<url> hashes (sqlite, postgres, duckdb or redis feature, duckdb loaded by its command line on PATH)")
+            .takes_value(true))
+        .arg(Arg::with_name("rejects")
+            .long("rejects")
+            .value_name("rejects")
+            .help("destination of rejected and unparseable transactions report (filepath) with source, line and reason")
+            .takes_value(true))
+        .arg(Arg::with_name("max-unparseable")
+            .long("max-unparseable")
+            .value_name("max-unparseable")
+            .help("unparseable transaction records skipped at most before the run fails (exit code 65) [default: unlimited]")
+            .validator(|value| { value.parse::<usize>().map(|_| {}).map_err(|e| { e.to_string() }) })
+            .takes_value(true))
+        .arg(Arg::with_name("summary")
+            .long("summary")
+            .value_name("summary")
+            .help("destination of end-of-run summary (filepath or - for stderr) with transaction counts, balances and throughput")
+            .takes_value(true))
+        .arg(Arg::with_name("metrics-json")
+            .long("metrics-json")
+            .value_name("metrics-json")
+            .help("destination of end-of-run metrics as JSON (filepath or - for stderr) with records read, commands applied and rejected, elapsed time and rows/sec")
+            .takes_value(true))
+        .arg(Arg::with_name("checkpoint")
+            .long("checkpoint")
+            .value_name("checkpoint")
+            .help("destination of checkpoint (filepath) recording source records processed")
+            .takes_value(true))
+        .arg(Arg::with_name("resume")
+            .long("resume")
+            .help("resumes an interrupted run after the source records recorded by its checkpoint (persistent store required)")
+            .requires("checkpoint"))
+        .arg(Arg::with_name("extended")
+            .long("extended")
+            .help("appends transaction, dispute and chargeback counts, last transaction id and version to account snapshots (not wallets)"))
+        .arg(Arg::with_name("sort")
+            .long("sort")
+            .value_name("sort")
+            .help("key accounts written to stdout are ordered by (ties ordered by client)")
+            .possible_values(&SortKey::names())
+            .default_value("client")
+            .takes_value(true))
+        .arg(Arg::with_name("flush-rows")
+            .long("flush-rows")
+            .value_name("flush-rows")
+            .help("account rows written between flushes of output (accounts ordered by client are streamed from store) [default: 8192]")
+            .takes_value(true))
+        .arg(Arg::with_name("compress")
+            .long("compress")
+            .value_name("compress")
+            .help("compression of account snapshots written to stdout and event log")
+            .possible_values(&Compression::names())
+            .takes_value(true))
+        .arg(Arg::with_name("settlements")
+            .short("s")
+            .long("settlements")
+            .value_name("settlements")
+            .help("destination of merchant settlement report (filepath)")
+            .takes_value(true))
+        .arg(Arg::with_name("history")
+            .long("history")
+            .value_name("history")
+            .help("destination of running balance history for every balance-affecting event (filepath)")
+            .takes_value(true))
+        .arg(Arg::with_name("event-log")
+            .long("event-log")
+            .value_name("event-log")
+            .help("destination of account event log (filepath)")
+            .takes_value(true))
+        .arg(Arg::with_name("export-events")
+            .long("export-events")
+            .value_name("export-events")
+            .help("destination of applied account events audit trail (JSON Lines filepath, sqlite://<database>, postgres://<url> or duckdb://<database> events table loaded by its command line on PATH) with client, version, key and amounts")
+            .takes_value(true))
+        .arg(Arg::with_name("import-events")
+            .long("import-events")
+            .value_name("import-events")
+            .help("source of exported audit trail (JSON Lines filepath) used to rehydrate accounts before processing transactions")
+            .takes_value(true))
+        .arg(Arg::with_name("event-log-format")
+            .long("event-log-format")
+            .value_name("event-log-format")
+            .help("format of account event log frames")
+            .possible_values(&EventLogFormat::names())
+            .default_value("protobuf")
+            .takes_value(true))
+        .arg(Arg::with_name("rehydrate")
+            .long("rehydrate")
+            .value_name("rehydrate")
+            .help("source of account event log used to rehydrate accounts before processing transactions (filepath)")
+            .takes_value(true))
+        .arg(Arg::with_name("event-store")
+            .long("event-store")
+            .value_name("event-store")
+            .help("directory of append-only event store segments applied account events are appended to (accounts kept in memory are rehydrated from events already appended)")
+            .takes_value(true))
+        .arg(Arg::with_name("load-state")
+            .long("load-state")
+            .value_name("load-state")
+            .help("source of state saved by a previous run (filepath) accounts are loaded from before processing transactions")
+            .takes_value(true))
+        .arg(Arg::with_name("save-state")
+            .long("save-state")
+            .value_name("save-state")
+            .help("destination of state (filepath) every account (including transactions and disputes) is saved to")
+            .takes_value(true))
+        .arg(Arg::with_name("store")
+            .long("store")
+            .value_name("store")
+            .help("store accounts are kept in (persistent stores keep accounts across runs)")
+            .possible_values(&StoreKind::names())
+            .default_value("memory")
+            .takes_value(true))
+        .arg(Arg::with_name("store-path")
+            .long("store-path")
+            .value_name("store-path")
+            .help("directory (or sqlite database file) of persistent account store")
+            .takes_value(true))
+        .arg(Arg::with_name("retain-events")
+            .long("retain-events")
+            .help("keeps event history of accounts kept in memory (implied by --extended and --save-state) otherwise only balances and transactions referenced by disputes"))
+        .arg(Arg::with_name("outbox")
+            .long("outbox")
+            .value_name("outbox")
+            .help("destination of account events (JSON Lines filepath appended to) written to the outbox of persistent store with their account and relayed once the store is flushed")
+            .takes_value(true))
+        .arg(Arg::with_name("max-memory")
+            .long("max-memory")
+            .value_name("accounts")
+            .help("maximum accounts kept in memory by persistent stores (least recently used accounts are evicted to the store and reloaded when referenced)")
+            .takes_value(true))
+        .arg(Arg::with_name("max-memory-mb")
+            .long("max-memory-mb")
+            .value_name("max-memory-mb")
+            .help("memory budget (megabytes) of accounts and their event history kept in memory")
+            .takes_value(true))
+        .arg(Arg::with_name("memory-budget-action")
+            .long("memory-budget-action")
+            .value_name("memory-budget-action")
+            .help("action taken when memory budget is exceeded (spill requires a persistent store)")
+            .possible_values(&BudgetAction::names())
+            .default_value("abort")
+            .takes_value(true))
+        .arg(Arg::with_name("snapshots")
+            .long("snapshots")
+            .value_name("snapshots")
+            .help("destination of account snapshots at the end of each window (filepath)")
+            .takes_value(true))
+        .arg(Arg::with_name("window")
+            .short("w")
+            .long("window")
+            .value_name("window")
+            .help("length of snapshot windows keyed by transaction timestamp (day, hour or seconds)")
+            .default_value("day")
+            .takes_value(true))
+        .arg(Arg::with_name("monthly")
+            .long("monthly")
+            .value_name("monthly")
+            .help("destination of per-client monthly totals report (filepath)")
+            .takes_value(true))
+        .arg(Arg::with_name("categories")
+            .long("categories")
+            .value_name("categories")
+            .help("destination of per-client category totals report (filepath)")
+            .takes_value(true))
+        .arg(Arg::with_name("threads")
+            .long("threads")
+            .value_name("threads")
+            .help("worker threads handling transactions sharded by client (client % threads) with accounts merged for outputs [default: 1]")
+            .conflicts_with_all(&["history", "snapshots", "checkpoint", "store-path", "max-memory", "max-memory-mb"])
+            .takes_value(true))
+        .arg(Arg::with_name("queue-capacity")
+            .long("queue-capacity")
+            .value_name("queue-capacity")
+            .help("records parsed and queued per source before parsing waits for transactions to be handled [default: 16384]")
+            .takes_value(true))
+        .arg(Arg::with_name("shard-queue-capacity")
+            .long("shard-queue-capacity")
+            .value_name("shard-queue-capacity")
+            .help("transactions queued per worker thread before routing waits (with --threads) [default: 1024]")
+            .requires("threads")
+            .takes_value(true))
+        .arg(Arg::with_name("max-rate")
+            .long("max-rate")
+            .value_name("max-rate")
+            .help("transactions applied per second at most (e.g. 500/s) shared by worker threads [default: unlimited]")
+            .takes_value(true))
+        .arg(Arg::with_name("external-sort")
+            .long("external-sort")
+            .help("sorts transactions of sources by client (runs spilled to temporary files) before handled so each account is handled contiguously")
+            .conflicts_with_all(&["threads", "snapshots", "checkpoint", "incremental", "stream"]))
+        .arg(Arg::with_name("run-records")
+            .long("run-records")
+            .value_name("run-records")
+            .help("transactions sorted in memory per run spilled (with --external-sort) [default: 1000000]")
+            .requires("external-sort")
+            .takes_value(true))
+        .arg(Arg::with_name("follow")
+            .long("follow")
+            .help("keeps the last source (csv file) open handling rows as they are appended (like tail -f), emitting accounts updated to stdout periodically")
+            .conflicts_with_all(&["incremental", "stream", "external-sort", "merge-sources", "threads"]))
+        .arg(Arg::with_name("follow-interval")
+            .long("follow-interval")
+            .value_name("follow-interval")
+            .help("seconds between emissions of accounts updated while following or watching [default: 5]")
+            .takes_value(true))
+        .arg(Arg::with_name("merge-sources")
+            .long("merge-sources")
+            .help("parses sources in parallel handling transactions merged by timestamp (then source order) of sources ordered by timestamp")
+            .conflicts_with_all(&["checkpoint", "incremental", "stream", "external-sort"]))
+        .arg(Arg::with_name("log-format")
+            .long("log-format")
+            .value_name("log-format")
+            .help("format of logs (stderr) filtered by RUST_LOG, json logs have fields at the top level (e.g. client, tx and code of rejects)")
+            .possible_values(&LogFormat::names())
+            .default_value("text")
+            .takes_value(true))
+        .arg(Arg::with_name("verbose")
+            .short("v")
+            .long("verbose")
+            .help("logs rejects, progress and timing of stages (-v), every command handled (-vv) or everything (-vvv) instead of RUST_LOG filter")
+            .multiple(true)
+            .global(true))
+        .arg(Arg::with_name("quiet")
+            .short("q")
+            .long("quiet")
+            .help("logs errors only (and hides progress bar)")
+            .conflicts_with("verbose")
+            .global(true))
+        .arg(Arg::with_name("metrics-interval")
+            .long("metrics-interval")
+            .value_name("metrics-interval")
+            .help("seconds between reports (logged) of queue occupancy and time stages waited on each other")
+            .takes_value(true))
+        .subcommand(SubCommand::with_name("statement")
+            .about("Renders per-client statements (opening balance, transactions, closing balance) of account event logs")
+            .arg(Arg::with_name("source")
+                .help("account event logs (filepaths) written using --event-log (audit trails written using --export-events or sources of transactions using --transactions)")
+                .required(true)
+                .multiple(true)
+                .index(1))
+            .arg(Arg::with_name("audit")
+                .long("audit")
+                .help("reads sources as audit trails (JSON Lines) written using --export-events")
+                .conflicts_with("transactions"))
+            .arg(Arg::with_name("transactions")
+                .long("transactions")
+                .help("reads sources as transactions handled (rows rejected are reported to stderr) instead of event logs"))
+            .arg(Arg::with_name("event-log-format")
+                .long("event-log-format")
+                .value_name("event-log-format")
+                .help("format of account event log frames")
+                .possible_values(&EventLogFormat::names())
+                .default_value("protobuf")
+                .takes_value(true))
+            .arg(accounts_arg.clone())
+            .arg(delimiter_arg.clone())
+            .arg(layout_arg.clone())
+            .arg(sheet_arg.clone())
+            .arg(Arg::with_name("client")
+                .short("c")
+                .long("client")
+                .value_name("client")
+                .help("client to render statement for (all clients when omitted) and of qif sources")
+                .takes_value(true))
+            .arg(Arg::with_name("from")
+                .long("from")
+                .value_name("from")
+                .help("first date (YYYY-MM-DD) of statement period")
+                .takes_value(true))
+            .arg(Arg::with_name("to")
+                .long("to")
+                .value_name("to")
+                .help("last date (YYYY-MM-DD) of statement period")
+                .takes_value(true))
+            .arg(Arg::with_name("format")
+                .short("f")
+                .long("format")
+                .value_name("format")
+                .help("statement output format")
+                .possible_values(&["text", "csv", "ofx", "mt940", "camt053"])
+                .default_value("text")
+                .takes_value(true))
+            .arg(Arg::with_name("output-dir")
+                .short("o")
+                .long("output-dir")
+                .value_name("output-dir")
+                .help("directory to write a statement file per client (e.g. 1.ofx) instead of stdout")
+                .takes_value(true)))
+        .subcommand(SubCommand::with_name("verify")
+            .about("Validates sources (columns, rows parsed and amounts) reporting invalid rows by line without processing them")
+            .arg(source_arg.clone())
+            .arg(delimiter_arg.clone())
+            .arg(layout_arg.clone())
+            .arg(sheet_arg.clone())
+            .arg(Arg::with_name("client")
+                .short("c")
+                .long("client")
+                .value_name("client")
+                .help("client of qif sources")
+                .takes_value(true)))
+        .subcommand(SubCommand::with_name("stats")
+            .about("Profiles sources (counts by type, clients, amount percentiles, duplicate transactions and date range) without processing them")
+            .arg(source_arg)
+            .arg(delimiter_arg.clone())
+            .arg(layout_arg.clone())
+            .arg(sheet_arg.clone())
+            .arg(Arg::with_name("client")
+                .short("c")
+                .long("client")
+                .value_name("client")
+                .help("client of qif sources")
+                .takes_value(true))
+            .arg(Arg::with_name("format")
+                .short("f")
+                .long("format")
+                .value_name("format")
+                .help("profile output format")
+                .possible_values(&["text", "json"])
+                .default_value("text")
+                .takes_value(true)))
+        .subcommand(SubCommand::with_name("diff")
+            .about("Compares account snapshots (csv) reporting balance deltas, newly locked, appeared and disappeared accounts")
+            .arg(Arg::with_name("old")
+                .help("snapshot of accounts (filepath) compared against")
+                .required(true)
+                .index(1))
+            .arg(Arg::with_name("new")
+                .help("snapshot of accounts (filepath) compared")
+                .required(true)
+                .index(2))
+            .arg(delimiter_arg.clone())
+            .arg(Arg::with_name("format")
+                .short("f")
+                .long("format")
+                .value_name("format")
+                .help("differences output format")
+                .possible_values(&OutputFormat::names())
+                .default_value("table")
+                .takes_value(true)))
+        .subcommand(SubCommand::with_name("bench")
+            .about("Processes transactions generated in memory reporting throughput, stage timings and peak memory")
+            .arg(Arg::with_name("transactions")
+                .short("n")
+                .long("transactions")
+                .value_name("transactions")
+                .help("transactions generated")
+                .validator(|value| { value.parse::<usize>().map(|_| {}).map_err(|e| { e.to_string() }) })
+                .default_value("1000000")
+                .takes_value(true))
+            .arg(Arg::with_name("clients")
+                .short("m")
+                .long("clients")
+                .value_name("clients")
+                .help("accounts transactions are spread across (1 to 65535)")
+                .validator(|value| {
+                    match value.parse::<u16>() {
+                        Ok(0) => Err(String::from("clients must be at least 1")),
+                        Ok(_) => Ok(()),
+                        Err(e) => Err(e.to_string()),
+                    }
+                })
+                .default_value("1000")
+                .takes_value(true)));
+    #[cfg(any(feature = "kafka", feature = "nats"))]
+    let app = app
+        .arg(Arg::with_name("stream")
+            .long("source")
+            .value_name("source")
+            .help("streaming source of transactions consumed continuously instead of source files")
+            .possible_values(&StreamKind::names())
+            .conflicts_with_all(&["source", "incremental"])
+            .takes_value(true))
+        .arg(Arg::with_name("brokers")
+            .long("brokers")
+            .value_name("brokers")
+            .help("bootstrap servers (comma separated host:port) of kafka source or server urls of nats source")
+            .required_if("stream", "kafka")
+            .required_if("stream", "nats")
+            .takes_value(true))
+        .arg(Arg::with_name("topic")
+            .long("topic")
+            .value_name("topic")
+            .help("topic of kafka source or jetstream stream of nats source holding transaction messages")
+            .required_if("stream", "kafka")
+            .required_if("stream", "nats")
+            .takes_value(true))
+        .arg(Arg::with_name("group")
+            .long("group")
+            .value_name("group")
+            .help("consumer group committing offsets of kafka source or durable consumer of nats source")
+            .default_value("accounts-aggregate")
+            .takes_value(true))
+        .arg(Arg::with_name("message-format")
+            .long("message-format")
+            .value_name("message-format")
+            .help("format of transaction messages (csv rows without headers, json objects or schema registry framed avro)")
+            .possible_values(&MessageFormat::names())
+            .default_value("csv")
+            .takes_value(true))
+        .arg(Arg::with_name("idle-timeout")
+            .long("idle-timeout")
+            .value_name("idle-timeout")
+            .help("seconds without messages after which consumption stops and outputs are written [default: consume forever]")
+            .takes_value(true))
+        .arg(Arg::with_name("exactly-once")
+            .long("exactly-once")
+            .help("writes offsets of messages within the same commit as accounts of persistent store consuming from offsets kept by store")
+            .requires_all(&["stream", "store-path"])
+            .conflicts_with_all(&["max-memory", "max-memory-mb"]));
+    #[cfg(feature = "webhook")]
+    let app = app
+        .arg(Arg::with_name("webhook")
+            .long("webhook")
+            .value_name("webhook")
+            .help("url receiving a JSON POST (event and account) whenever an account is locked")
+            .conflicts_with("threads")
+            .takes_value(true))
+        .arg(Arg::with_name("webhook-retries")
+            .long("webhook-retries")
+            .value_name("webhook-retries")
+            .help("retries (with exponential backoff) of webhook requests failing or answered by 429 or 5xx")
+            .default_value("3")
+            .takes_value(true));
+    #[cfg(feature = "otlp")]
+    let app = app.arg(Arg::with_name("otlp-endpoint")
+        .long("otlp-endpoint")
+        .value_name("otlp-endpoint")
+        .help("OTLP/HTTP traces endpoint spans of processing stages are exported to (e.g. http://localhost:4318/v1/traces)")
+        .takes_value(true));
+    #[cfg(feature = "server")]
+    let app = app
+        .arg(Arg::with_name("health-bind")
+            .long("health-bind")
+            .value_name("health-bind")
+            .help("address (host:port) answering /healthz and /readyz while processing (e.g. consuming streaming sources)")
+            .takes_value(true))
+        .arg(Arg::with_name("max-lag")
+            .long("max-lag")
+            .value_name("max-lag")
+            .help("messages of streaming source not yet consumed above which /readyz answers unavailable")
+            .requires("health-bind")
+            .takes_value(true));
+    #[cfg(feature = "rhai")]
+    let app = app.arg(Arg::with_name("rules")
+        .long("rules")
+        .value_name("rules")
+        .help("Rhai script (filepath) defining validate(command, account) returning accept() or reject(reason) evaluated before each transaction is handled")
+        .conflicts_with("threads")
+        .takes_value(true));
+    #[cfg(feature = "async")]
+    let app = app.arg(Arg::with_name("async")
+        .long("async")
+        .help("reads csv sources (local and uncompressed) and kafka streams using async readers of a shared tokio runtime"));
+    let query_command = SubCommand::with_name("query")
+        .about("Prints balances (and optionally event history) of a client account read from sources, saved state or a persistent store")
+        .arg(Arg::with_name("client")
+            .short("c")
+            .long("client")
+            .value_name("client")
+            .help("client of account printed")
+            .required_unless("database")
+            .takes_value(true))
         .arg(Arg::with_name("source")
-            .help("source of transactions (filepath)")
+            .long("source")
+            .value_name("source")
+            .help("sources of transactions (filepaths, glob patterns or remote urls) of which only transactions of client are handled")
+            .multiple(true)
+            .number_of_values(1)
+            .conflicts_with("store-path")
+            .takes_value(true))
+        .arg(accounts_arg.clone())
+        .arg(delimiter_arg)
+        .arg(layout_arg)
+        .arg(sheet_arg)
+        .arg(Arg::with_name("load-state")
+            .long("load-state")
+            .value_name("load-state")
+            .help("state (filepath) saved using --save-state accounts are loaded from before sources are handled")
+            .takes_value(true))
+        .arg(Arg::with_name("store")
+            .long("store")
+            .value_name("store")
+            .help("store account is read from (persistent stores hold accounts of previous runs)")
+            .possible_values(&StoreKind::names())
+            .default_value("memory")
+            .takes_value(true))
+        .arg(Arg::with_name("store-path")
+            .long("store-path")
+            .value_name("store-path")
+            .help("directory (or sqlite database file) of persistent account store")
+            .takes_value(true))
+        .arg(Arg::with_name("events")
+            .long("events")
+            .help("prints event history of account after its balances"))
+        .arg(Arg::with_name("format")
+            .short("f")
+            .long("format")
+            .value_name("format")
+            .help("query output format")
+            .possible_values(&["table", "csv", "json", "jsonl"])
+            .default_value("table")
+            .takes_value(true));
+    #[cfg(feature = "duckdb")]
+    let query_command = query_command
+        .about("Prints balances (and optionally event history) of a client account or runs ad-hoc SQL over account snapshots and events written to a DuckDB database")
+        .arg(Arg::with_name("database")
+            .help("DuckDB database (filepath) written using duckdb://<database> destinations (queried by the duckdb command line on PATH)")
+            .conflicts_with("client")
+            .requires("sql")
+            .index(1))
+        .arg(Arg::with_name("sql")
+            .help("SQL query (e.g. select * from accounts where locked)")
+            .index(2));
+    let app = app.subcommand(query_command);
+    let app = app.subcommand(SubCommand::with_name("replay")
+        .about("Rebuilds an account from an exported event log printing its balances at a version (or time)")
+        .arg(Arg::with_name("source")
+            .help("account event log (filepath) written using --event-log (or audit trail written using --export-events)")
             .required(true)
             .index(1))
-        .get_matches();
-    let source = arg_matches.value_of("source").unwrap();
+        .arg(Arg::with_name("client")
+            .short("c")
+            .long("client")
+            .value_name("client")
+            .help("client of account rebuilt")
+            .required(true)
+            .takes_value(true))
+        .arg(Arg::with_name("to-version")
+            .long("to-version")
+            .value_name("to-version")
+            .help("version of account (number of events applied) rebuilt [default: every event]")
+            .validator(|value| { value.parse::<Version>().map(|_| {}).map_err(|e| { e.to_string() }) })
+            .takes_value(true))
+        .arg(Arg::with_name("until")
+            .long("until")
+            .value_name("until")
+            .help("time (RFC 3339) after which events aren't applied (events without timestamps are applied)")
+            .validator(|value| { DateTime::parse_from_rfc3339(&value).map(|_| {}).map_err(|e| { e.to_string() }) })
+            .takes_value(true))
+        .arg(Arg::with_name("audit")
+            .long("audit")
+            .help("reads source as an audit trail (JSON Lines) written using --export-events"))
+        .arg(Arg::with_name("event-log-format")
+            .long("event-log-format")
+            .value_name("event-log-format")
+            .help("format of account event log frames")
+            .possible_values(&EventLogFormat::names())
+            .default_value("protobuf")
+            .takes_value(true))
+        .arg(accounts_arg.clone())
+        .arg(Arg::with_name("events")
+            .long("events")
+            .help("prints events applied after balances of account"))
+        .arg(Arg::with_name("format")
+            .short("f")
+            .long("format")
+            .value_name("format")
+            .help("replay output format")
+            .possible_values(&["table", "csv", "json", "jsonl"])
+            .default_value("table")
+            .takes_value(true)));
+    #[cfg(any(feature = "server", feature = "grpc"))]
+    let serve_command = SubCommand::with_name("serve")
+        .about("Serves APIs applying transactions and reading accounts (REST using server feature and gRPC using grpc feature)")
+        .arg(accounts_arg)
+        .arg(Arg::with_name("store")
+            .long("store")
+            .value_name("store")
+            .help("store accounts are kept in (persistent stores keep accounts across restarts)")
+            .possible_values(&StoreKind::names())
+            .default_value("memory")
+            .takes_value(true))
+        .arg(Arg::with_name("store-path")
+            .long("store-path")
+            .value_name("store-path")
+            .help("directory (or sqlite database file) of persistent account store")
+            .takes_value(true));
+    #[cfg(feature = "server")]
+    let serve_command = serve_command.arg(Arg::with_name("bind")
+        .long("bind")
+        .value_name("bind")
+        .help("address (host:port) REST API listens on")
+        .default_value("127.0.0.1:8080")
+        .takes_value(true));
+    #[cfg(feature = "graphql")]
+    let serve_command = serve_command.arg(Arg::with_name("retain-events")
+        .long("retain-events")
+        .help("keeps event history of accounts kept in memory (queried by GraphQL, persistent stores always keep events)"));
+    #[cfg(feature = "grpc")]
+    let serve_command = serve_command.arg(Arg::with_name("grpc-bind")
+        .long("grpc-bind")
+        .value_name("grpc-bind")
+        .help("address (host:port) gRPC service listens on")
+        .default_value("127.0.0.1:50051")
+        .takes_value(true));
+    #[cfg(any(feature = "server", feature = "grpc"))]
+    let app = app.subcommand(serve_command);
+    // settings of config file are supplied as arguments unless already supplied on the command line
+    let args: Vec<String> = env::args().collect();
+    let config_path = config::path(&args);
+    let arg_matches = match config_path.as_ref() {
+        Some(path) => {
+            let matches = app.clone().get_matches_from(&args);
+            let config = readable(path).and_then(|_| { Config::load(path).or_fail(Kind::Data) })
+                .unwrap_or_else(|failure| { failure::exit(failure) });
+            app.get_matches_from(config::merge(args, &matches, &config))
+        }
+        None => app.get_matches_from(args),
+    };
+    // logs and spans are traced until the process exits (spans not yet exported are flushed once dropped)
+    let log_format: LogFormat = required_arg(&arg_matches, "log-format").unwrap_or_else(|failure| { failure::exit(failure) });
+    let verbosity = Verbosity::new(arg_matches.is_present("quiet"), arg_matches.occurrences_of("verbose"));
+    let telemetry = telemetry::init(log_format, verbosity, arg_matches.value_of("otlp-endpoint"))
+        .unwrap_or_else(|e| { failure::exit(Failure::new(Kind::Io, e)) });
 
-    // todo - sanity check file / input
+    let result = match arg_matches.subcommand() {
+        ("statement", Some(matches)) => statement(matches),
+        ("verify", Some(matches)) => verify(matches),
+        ("stats", Some(matches)) => stats(matches),
+        ("diff", Some(matches)) => diff(matches),
+        ("bench", Some(matches)) => bench(matches),
+        ("replay", Some(matches)) => replay(matches),
+        ("query", Some(matches)) => query(matches),
+        #[cfg(any(feature = "server", feature = "grpc"))]
+        ("serve", Some(matches)) => serve(matches),
+        _ => run(&arg_matches, config_path.as_deref()),
+    };
+    if let Err(failure) = result {
+        // spans not yet exported are flushed before exiting
+        drop(telemetry);
+        failure::exit(failure);
+    }
+}
+
+/// Processing workflow (see `main`) returning failure of run (malformed records are skipped and counted).
+#[cfg_attr(not(feature = "rhai"), allow(unused_variables))]
+fn run(arg_matches: &ArgMatches, config_path: Option<&str>) -> Result<(), Failure> {
+    let started = Instant::now();
+    let _run = tracing::info_span!("run").entered();
+    let sources = sources(arg_matches)?;
 
-    // todo - custom errors in domain model
+    // load account metadata used to open accounts having type-specific rules
+    let metadata = load_metadata(arg_matches)?;
 
-    // todo - replace in-memory projection with disk-backed solution for scale... or get moar memories
-    // todo - sled(beta) embedded vs external db
-    let mut accounts: HashMap<u16, Account> = HashMap::new();
+    // domain rejections are `SimpleError` (reasons reported per record rather than failing runs)
 
-    // read source file while handling aggregate commands / transactions
-    let file = File::open(source).unwrap();
-    let mut reader = Reader::from_reader(file);
-    // fixme - error handling / logging for failed transactions
-    for result in reader.deserialize() {
-        let record: Command = result.unwrap();
+    // accounts are kept in memory unless a persistent store (state kept across runs) is requested
+    let store: StoreKind = required_arg(arg_matches, "store")?;
+    // records skipped by resumed runs were applied to accounts of the interrupted run (lost unless kept by store)
+    if arg_matches.is_present("resume") && store == StoreKind::Memory {
+        return Err(Failure::new(Kind::Usage, "resume requires a persistent store (accounts of interrupted runs kept in memory are lost)"));
+    }
+    // event history of accounts kept in memory is discarded unless needed by outputs
+    let history = if ["retain-events", "extended", "save-state"].iter().any(|name| { arg_matches.is_present(name) }) {
+        EventHistory::Retained
+    } else {
+        EventHistory::Discarded
+    };
+    let mut accounts = store::open(store, arg_matches.value_of("store-path"), history).or_fail(Kind::Io)?;
+    // events are written to the outbox of persistent store within the same commit as their account
+    if arg_matches.is_present("outbox") {
+        accounts.enable_outbox().or_fail(Kind::Io)?;
+    }
+    let budget = parse_arg(arg_matches, "max-memory-mb")?.map(|megabytes| {
+        Ok(MemoryBudget::new(megabytes, required_arg(arg_matches, "memory-budget-action")?))
+    }).transpose()?;
+    // accounts spilled by memory budget are only limited in number by max-memory (when supplied)
+    let capacity = parse_arg(arg_matches, "max-memory")?
+        .or_else(|| { budget.filter(|budget| { budget.action() == BudgetAction::Spill }).map(|_| { usize::MAX }) });
+    if let Some(capacity) = capacity {
+        accounts = store::spill(store, accounts, capacity).or_fail(Kind::Io)?;
+    }
+    // persistent stores log commands before applying them (commands logged by an interrupted run are replayed)
+    // offsets of streamed messages written with accounts make logging commands redundant (exactly-once)
+    let exactly_once = arg_matches.is_present("exactly-once");
+    let wal_path = arg_matches.value_of("store-path").filter(|_| { store != StoreKind::Memory && !exactly_once });
+    // liveness is answered while accounts are recovered and loaded (readiness once processing started)
+    #[cfg(feature = "server")]
+    let health = arg_matches.value_of("health-bind").map(|address| {
+        let health = Health::new(parse_arg(arg_matches, "max-lag")?, wal_path.is_some());
+        let server = HealthServer::bind(address).or_fail(Kind::Io)?;
+        tracing::info!("health listening on http://{}", server.local_addr().or_fail(Kind::Io)?);
+        server.spawn(health.clone()).or_fail(Kind::Io)?;
+        Ok(health)
+    }).transpose()?;
+    let mut wal = wal_path.map(|path| {
+        let (mut wal, tail) = WriteAheadLog::open(format!("{}.wal", path)).or_fail(Kind::Io)?;
+        if !tail.is_empty() {
+            for command in tail {
+                handle_command(accounts.as_mut(), &metadata, command).ok();
+            }
+            accounts.flush().or_fail(Kind::Io)?;
+            wal.checkpoint().or_fail(Kind::Io)?;
+        }
+        Ok(wal)
+    }).transpose()?;
+    if let Some(source) = arg_matches.value_of("load-state") {
+        readable(source)?;
+        for account in state::load(source).or_fail(Kind::Data)? {
+            accounts.put(account, 0).or_fail(Kind::Io)?;
+        }
+    }
+    // accounts seeded with initial balances are opened unless already kept (by store or state)
+    for record in metadata.values().filter(|record| { !record.opening.is_empty() }) {
+        if accounts.get(record.client).or_fail(Kind::Io)?.is_none() {
+            accounts.put(Account::with_metadata(record), 0).or_fail(Kind::Io)?;
+        }
+    }
+    let compression: Compression = parse_arg(arg_matches, "compress")?.unwrap_or(Compression::None);
+    let event_log_format: EventLogFormat = required_arg(arg_matches, "event-log-format")?;
+    if let Some(source) = arg_matches.value_of("rehydrate") {
+        let reader = EventLogReader::new(event_log_format, BufReader::new(open_source(source)?));
+        let events = reader.map(|result| { result.map_err(|e| { format!("invalid event log({}), {}", source, e) }).or_fail(Kind::Data) });
+        rehydrate(accounts.as_mut(), events, &metadata)?;
+    }
+    if let Some(source) = arg_matches.value_of("import-events") {
+        let events = audit::read_events(BufReader::new(open_source(source)?));
+        rehydrate(accounts.as_mut(), events.map(|result| { result.or_fail(Kind::Data) }), &metadata)?;
+    }
+    let event_store = arg_matches.value_of("event-store").map(SegmentEventStore::open).transpose().or_fail(Kind::Io)?;
+    if let Some(event_store) = event_store.as_ref().filter(|_| { store == StoreKind::Memory }) {
+        // persistent stores already hold events appended by previous runs
+        rehydrate(accounts.as_mut(), event_store.read_all().or_fail(Kind::Io)?.into_iter().map(Ok), &metadata)?;
+    }
+    // subscribers observe applied account events (in order subscribed)
+    let mut bus = EventBus::new();
+    if let Some(destination) = arg_matches.value_of("history") {
+        bus.subscribe(Box::new(History::new(csv_writer(arg_matches, destination)?)));
+    }
+    #[cfg(feature = "webhook")]
+    if let Some(url) = arg_matches.value_of("webhook") {
+        bus.subscribe(Box::new(Webhook::new(url, required_arg(arg_matches, "webhook-retries")?)));
+    }
+    if let Some(event_store) = event_store {
+        bus.subscribe(Box::new(event_store));
+    }
+    if let Some(destination) = arg_matches.value_of("event-log") {
+        let writer = compression.writer(BufWriter::new(create(destination)?)).or_fail(Kind::Io)?;
+        bus.subscribe(Box::new(EventLogWriter::new(event_log_format, writer)));
+    }
+    if let Some(destination) = arg_matches.value_of("export-events") {
+        bus.subscribe(Box::new(AuditTrail::new(audit_writer(destination, compression)?)));
+    }
+    if let Some(destination) = arg_matches.value_of("monthly") {
+        bus.subscribe(Box::new(TotalsReport::new(MonthlyTotals::new(), csv_writer(arg_matches, destination)?)));
+    }
+    if let Some(destination) = arg_matches.value_of("categories") {
+        bus.subscribe(Box::new(TotalsReport::new(CategoryTotals::new(), csv_writer(arg_matches, destination)?)));
+    }
+    if let Some(destination) = arg_matches.value_of("settlements") {
+        bus.subscribe(Box::new(SettlementReport::new(csv_writer(arg_matches, destination)?)));
+    }
+    // followed source (or watched directory) is never exhausted so accounts updated are emitted as rows are handled
+    let follow = arg_matches.is_present("follow");
+    if follow || arg_matches.is_present("watch") {
+        let writer = WriterBuilder::new().delimiter(delimiter(arg_matches, None)).from_writer(io::stdout());
+        let interval = Duration::from_secs(parse_arg(arg_matches, "follow-interval")?.unwrap_or(FOLLOW_INTERVAL));
+        bus.subscribe(Box::new(FollowSnapshots::spawn(writer, interval)));
+    }
+    // plugins run by lifecycle hooks of processing (in order registered)
+    let mut hooks = Hooks::new();
+    if let Some(destination) = arg_matches.value_of("rejects") {
+        hooks.register(Box::new(RejectsReport::new(csv_writer(arg_matches, destination)?)));
+    }
+    // rejects are logged when enabled (e.g. RUST_LOG=info,rejects=debug)
+    if tracing::enabled!(target: "rejects", tracing::Level::DEBUG) {
+        hooks.register(Box::new(RejectsLog));
+    }
+    #[cfg(feature = "rhai")]
+    if let Some(path) = arg_matches.value_of("rules") {
+        readable(path)?;
+        let rules = ScriptRules::load(path).or_fail(Kind::Data)?;
+        // daemon (streaming) runs reload rules modified while running
+        if arg_matches.is_present("stream") {
+            hooks.register(Box::new(RulesWatcher::new(rules, config_path)));
+        } else {
+            hooks.register(Box::new(rules));
+        }
+    }
+    let mut snapshots = arg_matches.value_of("snapshots").map(|destination| { csv_writer(arg_matches, destination) }).transpose()?;
+    let window_length = window_length(arg_matches.value_of("window").unwrap())?;
+    let mut window: Option<Timestamp> = None;
+
+    // accounts loaded before processing (state, balances or events) are within memory budget
+    if let Some(budget) = budget.as_ref() {
+        budget.enforce(accounts.as_mut())?;
+    }
+
+    // read source files while handling aggregate commands / transactions
+    // wallet column present (in any source) means balances are output per wallet
+    let mut has_wallets = metadata.values().any(|record| {
+        record.opening.iter().any(|balance| { balance.wallet.is_some() })
+    });
+    let mut summary = Summary::new();
+    let checkpoint_path = arg_matches.value_of("checkpoint");
+    let resume = checkpoint_path
+        .filter(|_| { arg_matches.is_present("resume") })
+        .map(Checkpoint::load)
+        .transpose()
+        .or_fail(Kind::Data)?
+        .flatten();
+    // incremental directory manifest records rows of files processed by previous invocations
+    let manifest_path = arg_matches.value_of("incremental").map(|directory| {
+        arg_matches.value_of("manifest").map_or_else(|| { format!("{}/{}", directory, MANIFEST_NAME) }, String::from)
+    });
+    let mut manifest = manifest_path.as_deref().map(Manifest::load).transpose().or_fail(Kind::Data)?;
+    // accounts are moved to shards of worker threads handling transactions (merged once every transaction is handled)
+    let threads: usize = parse_arg(arg_matches, "threads")?.unwrap_or(1);
+    // queues between stages are measured (and reported periodically when requested)
+    let metrics = Metrics::new();
+    if let Some(seconds) = parse_arg(arg_matches, "metrics-interval")? {
+        metrics.report_every(Duration::from_secs(seconds));
+    }
+    // transactions are paced (by worker threads sharing tokens when sharded) so remote sinks aren't overwhelmed
+    let limiter = parse_arg::<MaxRate>(arg_matches, "max-rate")?.map(RateLimiter::new);
+    let shards = (threads > 1).then(|| {
+        let opened: Vec<Account> = accounts.iter().or_fail(Kind::Io)?.map(Cow::into_owned).collect();
+        accounts = Box::new(MemoryStore::new(history));
+        let capacity = parse_arg(arg_matches, "shard-queue-capacity")?.unwrap_or(SHARD_QUEUE_CAPACITY);
+        ShardPool::spawn(threads, opened, metadata.clone(), handle_command, capacity, history, limiter.as_ref(), &metrics)
+            .or_fail(Kind::Internal)
+    }).transpose()?;
+    // sources are sorted by client (once every source is read) when handled contiguously per account
+    let mut sorted = arg_matches.is_present("external-sort").then(|| {
+        let run_records = parse_arg(arg_matches, "run-records")?.unwrap_or(RUN_RECORDS);
+        Ok(ExternalSorter::new(run_records, env::temp_dir()))
+    }).transpose()?;
+    let contiguous = sorted.is_some();
+    let mut previous: Option<ClientId> = None;
+    // streaming sources consume from offsets kept by store (written with accounts) when processing exactly-once
+    let offsets = if exactly_once { accounts.offsets().or_fail(Kind::Io)? } else { Offsets::new() };
+    // progress of local files is drawn to terminals unless logs are quiet or verbose (logs would break its line)
+    let verbosity = Verbosity::new(arg_matches.is_present("quiet"), arg_matches.occurrences_of("verbose"));
+    let unsized_sources = ["follow", "watch", "stream", "async"].iter().any(|name| { arg_matches.is_present(name) });
+    let progress = (verbosity == Verbosity::Default && !unsized_sources).then(|| { Progress::start(&sources) }).flatten();
+    #[cfg(feature = "server")]
+    if let Some(health) = health.as_ref() {
+        health.probe(accounts.as_ref());
+        health.start();
+    }
+    // runs fail once records unable to be parsed exceed maximum (skipped and counted otherwise)
+    let max_unparseable: Option<usize> = parse_arg(arg_matches, "max-unparseable")?;
+    let mut unparseable = 0;
+    // handles record read at `line` (message `offset`) of source `index` (records rejected are reported to hooks)
+    let mut process = |index: usize, line: usize, offset: Option<(String, i64)>, result: Result<Command, SimpleError>| {
+        let source = &sources[index];
+        #[cfg(feature = "server")]
+        if let Some(health) = health.as_ref().filter(|health| { health.due() }) {
+            health.probe(accounts.as_ref());
+            health.pending(wal.as_ref().map_or(0, WriteAheadLog::pending));
+        }
+        if let Some((partition, offset)) = offset {
+            accounts.stage_offset(&partition, offset).or_fail(Kind::Io)?;
+        }
+        let mut record = match result {
+            Ok(record) => record,
+            Err(e) => {
+                summary.count_unparseable();
+                hooks.run_reject(&Reject::new(source, line, None, &e)).or_fail(Kind::Io)?;
+                unparseable += 1;
+                if let Some(max) = max_unparseable.filter(|max| { unparseable > *max }) {
+                    let reason = format!("more than {} unparseable records, source({}) line({}) {}", max, source, line, e);
+                    return Err(Failure::new(Kind::Data, reason));
+                }
+                return Ok(());
+            }
+        };
+        // plugins enrich (or reject) commands before handled
+        if let Err(e) = hooks.run_command(&mut record, accounts.as_ref()) {
+            summary.count(record.name(), false);
+            hooks.run_reject(&Reject::new(source, line, Some(&record), &e)).or_fail(Kind::Io)?;
+            return Ok(());
+        }
+        // sharded transactions are completed as workers handle them
+        if let Some(shards) = shards.as_ref() {
+            shards.route(index, line, record).or_fail(Kind::Internal)?;
+            for outcome in shards.outcomes() {
+                complete(outcome, &sources, &mut summary, &mut hooks, &mut bus, accounts.as_ref())?;
+            }
+            return Ok(());
+        }
         let client = record.actor_id();
-        // check for existing account
-        if let Some(account) = accounts.get_mut(&client) {
-            if let Ok(events) = account.handle(record) {
-                account.apply(events);
+        // accounts sorted by client are handled contiguously (transaction indexes released once handled)
+        if contiguous && previous != Some(client) {
+            if let Some(previous) = previous {
+                release_indexes(accounts.as_mut(), previous)?;
+            }
+            previous = Some(client);
+        }
+        // snapshot accounts at end of window when transaction starts a new window
+        if let (Some(writer), Some(timestamp)) = (snapshots.as_mut(), record.timestamp()) {
+            let start = window_start(timestamp, window_length);
+            if let Some(previous) = window.filter(|previous| { *previous != start }) {
+                write_snapshots(writer, previous, accounts.as_ref())?;
+            }
+            window = Some(start);
+        }
+        let name = record.name().clone();
+        let rejected = (!hooks.is_empty()).then(|| { record.clone() });
+        if let Some(limiter) = limiter.as_ref() {
+            limiter.acquire();
+        }
+        if let Some(wal) = wal.as_mut() {
+            wal.append(&record).or_fail(Kind::Io)?;
+        }
+        let applied = match handle_command(accounts.as_mut(), &metadata, record) {
+            Ok(applied) => {
+                summary.count(&name, true);
+                applied
+            }
+            Err(e) => {
+                summary.count(&name, false);
+                hooks.run_reject(&Reject::new(source, line, rejected.as_ref(), &e)).or_fail(Kind::Io)?;
+                return Ok(());
+            }
+        };
+        if let Some(budget) = budget.as_ref() {
+            budget.enforce(accounts.as_mut())?;
+        }
+        bus.publish(client, &applied, accounts.as_ref()).or_fail(Kind::Io)?;
+        hooks.run_events(client, &applied, accounts.as_ref()).or_fail(Kind::Io)?;
+        Ok(())
+    };
+    if arg_matches.is_present("merge-sources") {
+        // every source is parsed on its own thread while commands are handled merged by timestamp
+        let _handle = tracing::info_span!("handle", sources = sources.len()).entered();
+        let readers = sources.iter()
+            .map(|source| { source_reader(arg_matches, source, &mut has_wallets, &metrics, &offsets, progress.as_ref()) })
+            .collect::<Result<_, _>>()?;
+        for (index, line, result) in SourceMerge::new(readers) {
+            if let Some(progress) = progress.as_ref() {
+                progress.row();
+            }
+            process(index, line, None, result)?;
+        }
+    } else {
+        for (index, source) in sources.iter().enumerate() {
+            let _handle = tracing::info_span!("handle", source = %source).entered();
+            let mut reader = if follow && index + 1 == sources.len() {
+                follow_reader(arg_matches, source, &mut has_wallets)?
+            } else {
+                source_reader(arg_matches, source, &mut has_wallets, &metrics, &offsets, progress.as_ref())?
+            };
+            // records processed by the interrupted run are skipped (sources before the checkpoint source entirely)
+            let skip = resume.as_ref().map_or(Ok(0), |checkpoint| { checkpoint.skip(&sources, index) }).or_fail(Kind::Data)?;
+            if skip == u64::MAX {
+                continue;
+            }
+            let skip = manifest.as_ref().map_or(skip, |manifest| { skip.max(manifest.processed(source)) });
+            let source_started = Instant::now();
+            tracing::debug!(skip, "handling source");
+            let mut records: u64 = 0;
+            while let Some(result) = reader.next() {
+                // records before this record are fully processed
+                if let Some(path) = checkpoint_path.filter(|_| { records > skip && records.is_multiple_of(CHECKPOINT_RECORDS) }) {
+                    Checkpoint::new(source, records).save(path).or_fail(Kind::Io)?;
+                }
+                records += 1;
+                if let Some(progress) = progress.as_ref() {
+                    progress.row();
+                }
+                if records.is_multiple_of(PROGRESS_RECORDS) {
+                    tracing::debug!(records, elapsed_ms = source_started.elapsed().as_millis() as u64, "progress");
+                }
+                if records <= skip {
+                    continue;
+                }
+                #[cfg(feature = "server")]
+                if let Some(health) = health.as_ref() {
+                    if let Some(lag) = reader.lag() {
+                        health.lag(lag);
+                    }
+                }
+                match (sorted.as_mut(), result) {
+                    (Some(sorter), Ok(record)) => sorter.push(index, reader.line(), record).or_fail(Kind::Io)?,
+                    (_, result) => process(index, reader.line(), reader.offset().filter(|_| { exactly_once }), result)?,
+                }
+            }
+            if let Some(path) = checkpoint_path {
+                Checkpoint::new(source, records).save(path).or_fail(Kind::Io)?;
+            }
+            if let Some(manifest) = manifest.as_mut() {
+                manifest.record(source, records);
+            }
+            tracing::debug!(records, elapsed_ms = source_started.elapsed().as_millis() as u64, "handled source");
+        }
+    }
+    if let Some(sorter) = sorted.take() {
+        let _handle = tracing::info_span!("handle", sorted = true).entered();
+        for sorted in sorter.finish().or_fail(Kind::Io)? {
+            let sorted = sorted.or_fail(Kind::Io)?;
+            process(sorted.source, sorted.line, None, Ok(sorted.command))?;
+        }
+    }
+    // accounts of every shard are merged once every transaction routed is handled
+    if let Some(shards) = shards {
+        let _handle = tracing::info_span!("handle", shards = threads).entered();
+        let (outcomes, opened) = shards.join().or_fail(Kind::Internal)?;
+        for outcome in outcomes {
+            complete(outcome, &sources, &mut summary, &mut hooks, &mut bus, accounts.as_ref())?;
+        }
+        for account in opened {
+            accounts.put(account, 0).or_fail(Kind::Io)?;
+        }
+    }
+    // bar is cleared once every source is handled
+    drop(progress);
+    tracing::debug!(elapsed_ms = started.elapsed().as_millis() as u64, "handled sources");
+    let stage = Instant::now();
+    let apply = tracing::info_span!("apply").entered();
+    bus.finish().or_fail(Kind::Io)?;
+    hooks.run_complete(accounts.as_ref()).or_fail(Kind::Io)?;
+    if let Some(mut writer) = snapshots {
+        if let Some(window) = window {
+            write_snapshots(&mut writer, window, accounts.as_ref())?;
+        }
+        writer.flush().or_fail(Kind::Io)?;
+    }
+
+    accounts.flush().or_fail(Kind::Io)?;
+    if let Some(wal) = wal.as_mut() {
+        wal.checkpoint().or_fail(Kind::Io)?;
+    }
+    // relay events persisted to outbox (including events left pending by an interrupted run)
+    if let Some(destination) = arg_matches.value_of("outbox") {
+        outbox::relay(accounts.as_mut(), &mut FilePublisher::open(destination).or_fail(Kind::Io)?).or_fail(Kind::Io)?;
+    }
+    drop(apply);
+    tracing::debug!(elapsed_ms = stage.elapsed().as_millis() as u64, "applied accounts");
+
+    // write aggregates to stdout, sqlite table or output file (written to temporary file then renamed)
+    let stage = Instant::now();
+    let write = tracing::info_span!("write").entered();
+    let mut writer = account_writer(arg_matches, compression)?;
+    let sort: SortKey = required_arg(arg_matches, "sort")?;
+    let extended = arg_matches.is_present("extended");
+    let flush_rows = parse_arg(arg_matches, "flush-rows")?.unwrap_or(FLUSH_ROWS);
+    if let Some(destination) = arg_matches.value_of("save-state") {
+        let accounts: Vec<_> = accounts.iter().or_fail(Kind::Io)?.collect();
+        state::save(destination, accounts.iter().map(|account| { account.as_ref() })).or_fail(Kind::Io)?;
+    }
+    // manifest is saved once accounts are kept (by store or state) so rows are never recorded before applied
+    if let (Some(manifest), Some(path)) = (manifest.as_ref(), manifest_path.as_ref()) {
+        manifest.save(path).or_fail(Kind::Io)?;
+    }
+    // accounts ordered by client are streamed from store (flushed every `flush_rows`) so output memory is bounded
+    let ordered = match sort {
+        SortKey::Client => accounts.iter().or_fail(Kind::Io)?,
+        sort => Box::new(sort.sort(accounts.iter().or_fail(Kind::Io)?).into_iter()),
+    };
+    let mut rows = 0;
+    for account in ordered {
+        if has_wallets {
+            for wallet in account.wallets() {
+                writer.serialize(wallet).or_fail(Kind::Io)?;
+                rows += 1;
+            }
+        } else if extended {
+            writer.serialize(ExtendedSnapshot::from_account(&account)).or_fail(Kind::Io)?;
+            rows += 1;
+        } else {
+            writer.serialize(account.as_ref()).or_fail(Kind::Io)?;
+            rows += 1;
+        }
+        if rows >= flush_rows {
+            writer.flush().or_fail(Kind::Io)?;
+            rows = 0;
+        }
+    }
+    writer.finish().or_fail(Kind::Io)?;
+    // dropping writer completes compressed output
+    drop(writer);
+    if let Some(destination) = arg_matches.value_of("output").filter(|d| { !output::is_database(d) }) {
+        fs::rename(partial_path(destination), destination).or_fail(Kind::Io)?;
+    }
+    drop(write);
+    tracing::debug!(elapsed_ms = stage.elapsed().as_millis() as u64, "wrote accounts");
+
+    // write summary and metrics of run to stderr or files
+    if arg_matches.is_present("summary") || arg_matches.is_present("metrics-json") {
+        summary.finish(accounts.iter().or_fail(Kind::Io)?, started.elapsed());
+        summary.queues(metrics.stats());
+    }
+    if let Some(destination) = arg_matches.value_of("summary") {
+        match destination {
+            "-" => eprint!("{}", summary),
+            destination => fs::write(destination, summary.to_string()).or_fail(Kind::Io)?,
+        }
+    }
+    if let Some(destination) = arg_matches.value_of("metrics-json") {
+        match destination {
+            "-" => eprintln!("{}", summary.to_json()),
+            destination => fs::write(destination, format!("{}\n", summary.to_json())).or_fail(Kind::Io)?,
+        }
+    }
+    tracing::debug!(elapsed_ms = started.elapsed().as_millis() as u64, "run completed");
+    Ok(())
+}
+
+/// Returns filepaths (or remote urls) of `source` argument values (expanding glob patterns and remote prefixes) in lexicographic order.
+///
+/// Streaming sources (e.g. `--source kafka`) are a single source named after their topic.
+fn sources(matches: &ArgMatches) -> Result<Vec<String>, Failure> {
+    #[cfg(any(feature = "kafka", feature = "nats"))]
+    if let (Some(kind), Some(topic)) = (matches.value_of("stream"), matches.value_of("topic")) {
+        return Ok(vec![format!("{}:{}", kind, topic)]);
+    }
+    if let Some(directory) = matches.value_of("incremental") {
+        return manifest::files(directory).or_fail(Kind::Io);
+    }
+    if let Some(directory) = matches.value_of("watch") {
+        return Ok(vec![directory.to_string()]);
+    }
+    let mut sources: Vec<String> = vec![];
+    for value in matches.values_of("source").unwrap() {
+        if remote::is_remote(value) {
+            sources.extend(remote::sources(value).or_fail(Kind::Io)?);
+        } else if value.contains(['*', '?', '[']) {
+            let paths = glob::glob(value).map_err(|e| { format!("invalid glob pattern({}), {}", value, e) }).or_fail(Kind::Usage)?;
+            for path in paths {
+                sources.push(path.or_fail(Kind::Io)?.to_string_lossy().into_owned());
+            }
+        } else {
+            sources.push(value.to_string());
+        }
+    }
+    sources.sort();
+    Ok(sources)
+}
+
+/// Returns reader of transactions `source` using format of its extension (csv, qif, ach, dat, xlsx or iso8583)
+/// or consumer of streaming source (kafka or nats).
+///
+/// Sets `has_wallets` when a csv or xlsx source has a wallet column. Queues of file sources are measured by `metrics`.
+///
+/// Streaming sources consume from `offsets` (kept by store) when processing exactly-once. Bytes read from files are
+/// counted by `progress` (when drawn).
+#[cfg_attr(not(any(feature = "kafka", feature = "nats")), allow(unused_variables))]
+fn source_reader(
+    matches: &ArgMatches,
+    source: &str,
+    has_wallets: &mut bool,
+    metrics: &Metrics,
+    offsets: &Offsets,
+    progress: Option<&Progress>,
+) -> Result<Box<dyn SourceReader>, Failure> {
+    #[cfg(any(feature = "kafka", feature = "nats"))]
+    if matches.is_present("stream") {
+        let format: MessageFormat = required_arg(matches, "message-format")?;
+        let decoder = MessageDecoder::new(format).or_fail(Kind::Data)?;
+        let idle = parse_arg(matches, "idle-timeout")?.map(Duration::from_secs);
+        let (servers, topic, group) = (
+            matches.value_of("brokers").unwrap(),
+            matches.value_of("topic").unwrap(),
+            matches.value_of("group").unwrap(),
+        );
+        let exactly_once = matches.is_present("exactly-once");
+        return Ok(match required_arg(matches, "stream")? {
+            // offsets are read from store rather than consumer group (messages read synchronously)
+            #[cfg(feature = "kafka")]
+            StreamKind::Kafka if exactly_once => {
+                let reader = KafkaReader::connect(servers, topic, group, decoder, idle).or_fail(Kind::Io)?;
+                Box::new(reader.resume_from(topic, offsets).or_fail(Kind::Io)?)
+            }
+            #[cfg(all(feature = "kafka", feature = "async"))]
+            StreamKind::Kafka if matches.is_present("async") => {
+                // consumer tasks are spawned on the runtime
+                let _runtime = asynchronous::runtime().enter();
+                Box::new(BlockingReader::new(AsyncKafkaReader::connect(servers, topic, group, decoder, idle).or_fail(Kind::Io)?))
+            }
+            #[cfg(feature = "kafka")]
+            StreamKind::Kafka => Box::new(KafkaReader::connect(servers, topic, group, decoder, idle).or_fail(Kind::Io)?),
+            #[cfg(feature = "nats")]
+            StreamKind::Nats if exactly_once => {
+                Box::new(NatsReader::connect_from(servers, topic, decoder, idle, offsets).or_fail(Kind::Io)?)
+            }
+            #[cfg(feature = "nats")]
+            StreamKind::Nats => Box::new(NatsReader::connect(servers, topic, group, decoder, idle).or_fail(Kind::Io)?),
+        });
+    }
+    // files dropped are read by the processing loop so they are moved once every row is handled
+    if matches.is_present("watch") {
+        return Ok(Box::new(WatchReader::open(source, delimiter(matches, None)).or_fail(Kind::Io)?));
+    }
+    let capacity = parse_arg(matches, "queue-capacity")?.unwrap_or(QUEUE_CAPACITY);
+    let queue = metrics.queue(&format!("read({})", source), capacity);
+    #[cfg(feature = "async")]
+    if matches.is_present("async") && async_source(source) {
+        let reader = asynchronous::runtime().block_on(async {
+            let file = tokio::fs::File::open(source).await
+                .map_err(|e| { format!("unable to open source({}), {}", source, e) })
+                .or_fail(Kind::Io)?;
+            AsyncCsvReader::new(tokio::io::BufReader::new(file), delimiter(matches, Some(source))).await
+                .map_err(|e| { format!("invalid headers of source({}), {}", source, e) })
+                .or_fail(Kind::Data)
+        })?;
+        *has_wallets |= reader.headers().iter().any(|h| { h == "wallet" });
+        return Ok(Box::new(AsyncPipelineReader::spawn(reader, queue)));
+    }
+    // files are counted as read before decompressed (measured against their size)
+    let read_source = || {
+        match progress {
+            Some(progress) => {
+                let file = File::open(source).map_err(|e| { Failure::new(Kind::Io, format!("unable to open source({}), {}", source, e)) })?;
+                Compression::from_path(source).reader(progress.reader(file)).or_fail(Kind::Io)
+            }
+            None => open_source(source),
+        }
+    };
+    let open = || { read_source().map(BufReader::new) };
+    let reader: Box<dyn SourceReader + Send> = match SourceFormat::from_path(source) {
+        SourceFormat::Csv => csv_command_reader(source, read_source()?, delimiter(matches, Some(source)), has_wallets)?,
+        SourceFormat::Qif => {
+            let client: u16 = parse_arg(matches, "client")?
+                .ok_or_else(|| { Failure::new(Kind::Usage, format!("client argument is required for qif source({})", source)) })?;
+            Box::new(QifReader::new(open()?, client))
+        }
+        SourceFormat::Nacha => Box::new(NachaReader::new(open()?)),
+        SourceFormat::FixedWidth => {
+            let layout = matches.value_of("layout")
+                .ok_or_else(|| { Failure::new(Kind::Usage, format!("layout argument is required for dat source({})", source)) })?;
+            readable(layout)?;
+            let layout = Layout::from_path(layout).or_fail(Kind::Data)?;
+            Box::new(FixedWidthReader::new(open()?, layout))
+        }
+        SourceFormat::Xlsx => {
+            readable(source)?;
+            let reader = XlsxReader::open(source, matches.value_of("sheet")).or_fail(Kind::Data)?;
+            *has_wallets |= reader.headers().iter().any(|h| { h == "wallet" });
+            Box::new(reader)
+        }
+        #[cfg(feature = "iso8583")]
+        SourceFormat::Iso8583 => {
+            let client: Option<u16> = parse_arg(matches, "client")?;
+            Box::new(Iso8583Reader::new(open()?, client))
+        }
+    };
+    // records of files are parsed on a separate thread (streaming sources are acknowledged once handled)
+    Ok(Box::new(PipelineReader::spawn(reader, queue)))
+}
+
+/// Returns reader of csv transactions `source` using field `delimiter` (SIMD accelerated using `simd` feature).
+///
+/// Sets `has_wallets` when source has a wallet column.
+fn csv_command_reader<'a, R: io::Read + Send + 'a>(
+    name: &str,
+    source: R,
+    delimiter: u8,
+    has_wallets: &mut bool
+) -> Result<Box<dyn SourceReader + Send + 'a>, Failure> {
+    #[cfg(feature = "simd")]
+    let reader = FastCsvReader::new(source, delimiter);
+    #[cfg(not(feature = "simd"))]
+    let reader = csv_reader(BufReader::new(source), delimiter).and_then(CsvCommandReader::new);
+    let reader = reader.map_err(|e| { format!("invalid headers of source({}), {}", name, e) }).or_fail(Kind::Data)?;
+    *has_wallets |= reader.headers().iter().any(|h| { h == "wallet" });
+    Ok(Box::new(reader))
+}
+
+/// Returns reader of csv `source` (local uncompressed file) handling rows as they are appended.
+///
+/// Rows are read by the processing loop rather than a parsing thread so rows are handled as soon as appended.
+fn follow_reader(matches: &ArgMatches, source: &str, has_wallets: &mut bool) -> Result<Box<dyn SourceReader>, Failure> {
+    let local = !remote::is_remote(source) && Compression::from_path(source) == Compression::None;
+    if !local || SourceFormat::from_path(source) != SourceFormat::Csv {
+        return Err(Failure::new(Kind::Usage, format!("followed source({}) must be a local uncompressed csv file", source)));
+    }
+    let file = FollowFile::open(source).or_fail(Kind::Io)?;
+    Ok(csv_command_reader(source, file, delimiter(matches, Some(source)), has_wallets)?)
+}
+
+/// Returns true when `source` is read by an async reader (local uncompressed csv files).
+#[cfg(feature = "async")]
+fn async_source(source: &str) -> bool {
+    SourceFormat::from_path(source) == SourceFormat::Csv
+        && Compression::from_path(source) == Compression::None
+        && !remote::is_remote(source)
+}
+
+/// Returns account metadata keyed by client read from `accounts` argument source (empty when none).
+///
+/// Balances read from `initial-balances` argument source (account or wallet rows) are added as opening balances.
+fn load_metadata(matches: &ArgMatches) -> Result<HashMap<u16, AccountMetadata>, Failure> {
+    let invalid = |source: &str, e: csv::Error| { Failure::new(Kind::Data, format!("invalid row of source({}), {}", source, e)) };
+    let mut metadata: HashMap<u16, AccountMetadata> = HashMap::new();
+    if let Some(source) = matches.value_of("accounts") {
+        let mut reader = csv_reader(open_source(source)?, delimiter(matches, Some(source))).map_err(|e| { invalid(source, e) })?;
+        for result in reader.deserialize() {
+            let record: AccountMetadata = result.map_err(|e| { invalid(source, e) })?;
+            record.validate().map_err(|e| { Failure::new(Kind::Data, format!("invalid row of source({}), {}", source, e)) })?;
+            metadata.insert(record.client, record);
+        }
+    }
+    if let Some(source) = matches.value_of("initial-balances") {
+        let mut reader = csv_reader(open_source(source)?, delimiter(matches, Some(source))).map_err(|e| { invalid(source, e) })?;
+        for result in reader.deserialize() {
+            let record: OpeningBalance = result.map_err(|e| { invalid(source, e) })?;
+            metadata.entry(record.client).or_insert_with(|| { AccountMetadata::new(record.client) }).opening.push(record);
+        }
+    }
+    Ok(metadata)
+}
+
+/// Returns reader of `source` (local file or remote url) failing with the source in context.
+fn open_source(source: &str) -> Result<Box<dyn io::Read + Send>, Failure> {
+    input::open(source).map_err(|e| { Failure::new(Kind::Io, format!("unable to open source({}), {}", source, e)) })
+}
+
+/// Checks local file `path` can be opened so files missing fail as I/O rather than their contents as invalid data.
+fn readable(path: &str) -> Result<(), Failure> {
+    if !remote::is_remote(path) {
+        File::open(path).map_err(|e| { Failure::new(Kind::Io, format!("unable to open {}, {}", path, e)) })?;
+    }
+    Ok(())
+}
+
+/// Returns new file created at `path` failing with the path in context.
+fn create<P: AsRef<Path>>(path: P) -> Result<File, Failure> {
+    let path = path.as_ref();
+    File::create(path).map_err(|e| { Failure::new(Kind::Io, format!("unable to create {}, {}", path.display(), e)) })
+}
+
+/// Returns usage failure of invalid `value` of argument `name`.
+fn usage<E: fmt::Display>(name: &str, value: &str, error: E) -> Failure {
+    Failure::new(Kind::Usage, format!("invalid value({}) of argument({}), {}", value, name, error))
+}
+
+/// Returns value of argument `name` parsed when supplied (failing with usage errors naming the argument).
+fn parse_arg<T>(matches: &ArgMatches, name: &str) -> Result<Option<T>, Failure>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    matches.value_of(name).map(|value| { value.parse().map_err(|e| { usage(name, value, e) }) }).transpose()
+}
+
+/// Returns value of argument `name` parsed (arguments required or having a default value).
+fn required_arg<T>(matches: &ArgMatches, name: &str) -> Result<T, Failure>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    parse_arg(matches, name)?.ok_or_else(|| { Failure::new(Kind::Usage, format!("{} argument is required", name)) })
+}
+
+/// Returns field delimiter of `value` (single character or tab).
+fn parse_delimiter(value: &str) -> Result<u8, String> {
+    match value {
+        "tab" | "\\t" | "\t" => Ok(b'\t'),
+        value if value.len() == 1 => Ok(value.as_bytes()[0]),
+        value => Err(format!("delimiter({}) must be a single character or tab", value)),
+    }
+}
+
+/// Returns field delimiter of `path` using `delimiter` argument when provided otherwise extension.
+///
+/// Paths having a `.tsv` extension (optionally compressed) are tab separated and all others comma separated.
+fn delimiter(matches: &ArgMatches, path: Option<&str>) -> u8 {
+    match matches.value_of("delimiter") {
+        Some(value) => parse_delimiter(value).unwrap(),
+        None if path.is_some_and(|path| { compression::strip_compression(path).to_lowercase().ends_with(".tsv") }) => b'\t',
+        None => b',',
+    }
+}
+
+/// Returns writer of account snapshots to stdout, `output` destination (partial until renamed) or database table.
+fn account_writer(matches: &ArgMatches, compression: Compression) -> Result<RecordWriter<Box<dyn io::Write + Send>>, Failure> {
+    let destination = matches.value_of("output");
+    #[cfg(feature = "sqlite")]
+    if let Some(path) = destination.and_then(output::sqlite_path) {
+        return RecordWriter::sqlite(path, "accounts").or_fail(Kind::Io);
+    }
+    #[cfg(feature = "postgres")]
+    if let Some(url) = destination.and_then(postgres::database_url) {
+        return RecordWriter::postgres(url, postgres::PostgresTable::Accounts).or_fail(Kind::Io);
+    }
+    #[cfg(feature = "redis")]
+    if let Some(url) = destination.and_then(output::redis_url) {
+        return RecordWriter::redis(url).or_fail(Kind::Io);
+    }
+    #[cfg(feature = "duckdb")]
+    if let Some(path) = destination.and_then(duckdb::database_path) {
+        return RecordWriter::duckdb(path, "accounts").or_fail(Kind::Io);
+    }
+    let format: OutputFormat = required_arg(matches, "output-format")?;
+    let output: Box<dyn io::Write + Send> = match destination {
+        Some(destination) => Box::new(BufWriter::new(create(partial_path(destination))?)),
+        None => Box::new(io::stdout()),
+    };
+    let output = compression.writer(output).or_fail(Kind::Io)?;
+    Ok(RecordWriter::with_delimiter(format, delimiter(matches, None), output))
+}
+
+/// Returns writer of audit trail to JSON Lines `destination` or database table.
+fn audit_writer(destination: &str, compression: Compression) -> Result<RecordWriter<Box<dyn io::Write + Send>>, Failure> {
+    #[cfg(feature = "sqlite")]
+    if let Some(path) = output::sqlite_path(destination) {
+        return RecordWriter::sqlite(path, "events").or_fail(Kind::Io);
+    }
+    #[cfg(feature = "postgres")]
+    if let Some(url) = postgres::database_url(destination) {
+        return RecordWriter::postgres(url, postgres::PostgresTable::Events).or_fail(Kind::Io);
+    }
+    #[cfg(feature = "duckdb")]
+    if let Some(path) = duckdb::database_path(destination) {
+        return RecordWriter::duckdb(path, "events").or_fail(Kind::Io);
+    }
+    let writer = compression.writer(BufWriter::new(create(destination)?)).or_fail(Kind::Io)?;
+    Ok(RecordWriter::with_delimiter(OutputFormat::Jsonl, b',', writer))
+}
+
+/// Returns csv writer of report `destination` using field delimiter of `destination`.
+fn csv_writer(matches: &ArgMatches, destination: &str) -> Result<Writer<File>, Failure> {
+    Ok(WriterBuilder::new().delimiter(delimiter(matches, Some(destination))).from_writer(create(destination)?))
+}
+
+/// Rehydrates `accounts` by applying every client event of `events` in order.
+///
+/// Accounts are opened using `metadata` before applying their first event.
+/// Fails with the first event unable to be read.
+fn rehydrate<I: IntoIterator<Item = Result<(u16, Event), Failure>>>(
+    accounts: &mut dyn ProjectionStore,
+    events: I,
+    metadata: &HashMap<u16, AccountMetadata>
+) -> Result<(), Failure> {
+    for result in events {
+        let (client, event) = result?;
+        let open = || { open_account(metadata, client) };
+        accounts.update(client, open, |account| { account.replay([event]); Ok(()) }).or_fail(Kind::Io)?;
+    }
+    Ok(())
+}
+
+/// Returns new `Account` of `client` opened using `metadata` (when present).
+fn open_account(metadata: &HashMap<u16, AccountMetadata>, client: u16) -> Account {
+    match metadata.get(&client) {
+        Some(record) => Account::with_metadata(record),
+        None => Account::new(client),
+    }
+}
+
+/// Handles `command` using existing or new `Account` returning events applied or reason rejected.
+///
+/// New accounts are opened using `metadata` and only kept when `command` is accepted.
+fn handle_command(
+    accounts: &mut dyn ProjectionStore,
+    metadata: &HashMap<u16, AccountMetadata>,
+    command: Command
+) -> Result<Effects<Event>, SimpleError> {
+    let client = command.actor_id();
+    let _command = tracing::trace_span!("command", client, tx = command.tx()).entered();
+    // existing account or new account (genesis time)
+    accounts.update(client, || { open_account(metadata, client) }, |account| {
+        let events = account.handle(command)?;
+        account.apply(events.clone());
+        tracing::trace!(events = events.len(), version = account.version(), "applied");
+        Ok(events)
+    })
+}
+
+/// Releases transaction indexes of `client` account once its transactions are handled.
+fn release_indexes(accounts: &mut dyn ProjectionStore, client: ClientId) -> Result<(), Failure> {
+    if let Some(mut account) = accounts.take(client).or_fail(Kind::Io)? {
+        let version = account.version();
+        account.release_indexes();
+        accounts.put(account, version).or_fail(Kind::Io)?;
+    }
+    Ok(())
+}
+
+/// Completes `outcome` of transaction handled by a shard (counted then published or rejected).
+///
+/// `accounts` are empty until shards are merged.
+fn complete(
+    outcome: Outcome,
+    sources: &[String],
+    summary: &mut Summary,
+    hooks: &mut Hooks,
+    bus: &mut EventBus,
+    accounts: &hooks::Accounts
+) -> Result<(), Failure> {
+    let client = outcome.command.actor_id();
+    match outcome.result {
+        Ok(applied) => {
+            summary.count(outcome.command.name(), true);
+            bus.publish(client, &applied, accounts).or_fail(Kind::Io)?;
+            hooks.run_events(client, &applied, accounts).or_fail(Kind::Io)
+        }
+        Err(e) => {
+            summary.count(outcome.command.name(), false);
+            hooks.run_reject(&Reject::new(&sources[outcome.source], outcome.line, Some(&outcome.command), &e)).or_fail(Kind::Io)
+        }
+    }
+}
+
+/// Returns window length in seconds for `value` (day, hour or seconds).
+fn window_length(value: &str) -> Result<i64, Failure> {
+    match value {
+        "day" => Ok(86400),
+        "hour" => Ok(3600),
+        seconds => seconds.parse().map_err(|e| { usage("window", seconds, e) }),
+    }
+}
+
+/// Writes snapshot of every account (ordered by client) for `window`.
+fn write_snapshots<W: io::Write>(writer: &mut Writer<W>, window: Timestamp, accounts: &dyn ProjectionStore) -> Result<(), Failure> {
+    for account in accounts.iter().or_fail(Kind::Io)? {
+        writer.serialize(WindowSnapshot::from_account(window, &account)).or_fail(Kind::Io)?;
+    }
+    Ok(())
+}
+
+/// Returns start of day timestamp for date argument `name` (YYYY-MM-DD) shifted by `days`.
+fn date_arg(matches: &ArgMatches, name: &str, days: i64) -> Result<Option<Timestamp>, Failure> {
+    matches.value_of(name).map(|value| {
+        let date = NaiveDate::parse_from_str(value, "%Y-%m-%d").map_err(|e| { usage(name, value, e) })?;
+        Ok(Utc.from_utc_datetime(&(date + chrono::Duration::days(days)).and_time(NaiveTime::MIN)))
+    }).transpose()
+}
+
+/// Statement subcommand workflow.
+///
+/// **Steps:**
+/// 1. Rehydrate `Account` aggregates from events of sources (or handle transactions of sources with `transactions`).
+/// 2. For each (or requested) account render statement of events within period.
+/// 3. Write statements to stdout (or a file per client) as text, csv, ofx, mt940 or camt053.
+fn statement(matches: &ArgMatches) -> Result<(), Failure> {
+    let sources = sources(matches)?;
+    let metadata = load_metadata(matches)?;
+    let client: Option<u16> = parse_arg(matches, "client")?;
+    // period includes entire last day
+    let from = date_arg(matches, "from", 0)?;
+    let to = date_arg(matches, "to", 1)?;
+
+    let mut accounts = MemoryStore::default();
+    for source in sources.iter() {
+        if !matches.is_present("transactions") {
+            rehydrate(&mut accounts, event_reader(matches, source)?, &metadata)?;
+            continue;
+        }
+        let mut reader = source_reader(matches, source, &mut false, &Metrics::new(), &Offsets::new(), None)?;
+        while let Some(record) = reader.next() {
+            let result = record.and_then(|command| { handle_command(&mut accounts, &metadata, command).map(|_| {}) });
+            if let Err(reason) = result {
+                eprintln!("{}:{}: {}", source, reader.line(), reason);
+            }
+        }
+    }
+
+    let statements: Vec<Statement> = accounts.iter().or_fail(Kind::Io)?
+        .filter(|account| { client.is_none_or(|client| { account.client() == client }) })
+        .map(|account| { Statement::from_account(&account, from, to) })
+        .collect();
+
+    let format = matches.value_of("format").unwrap();
+    match matches.value_of("output-dir") {
+        Some(directory) => {
+            let extension = match format {
+                "text" => "txt",
+                "mt940" => "sta",
+                "camt053" => "xml",
+                format => format,
+            };
+            for statement in statements {
+                let path = Path::new(directory).join(format!("{}.{}", statement.client(), extension));
+                write_statements(matches, format, &[statement], create(path)?)?;
+            }
+            Ok(())
+        }
+        None => write_statements(matches, format, &statements, io::stdout()),
+    }
+}
+
+/// Verify subcommand workflow.
+///
+/// **Steps:**
+/// 1. Open each source checking csv sources have columns of transactions.
+/// 2. Parse and check every row of sources writing rows failing to stdout (by line).
+/// 3. Exit with code of verdict (0 valid, 1 rows invalid, 2 sources unreadable) once summarized to stderr.
+fn verify(matches: &ArgMatches) -> Result<(), Failure> {
+    let mut verification = Verification::new(io::stdout());
+    for source in sources(matches)?.iter() {
+        let verified = if SourceFormat::from_path(source) == SourceFormat::Csv {
+            match verify::open_csv(source, delimiter(matches, Some(source))) {
+                Ok(mut reader) => verification.rows(source, &mut reader),
+                Err(e) => verification.unreadable(source, &e),
             }
         } else {
-            // account is new, genesis time
-            let mut account = Account::new(client);
-            if let Ok(events) = account.handle(record) {
-                account.apply(events);
-                accounts.insert(client, account);
+            // readers of other formats open sources themselves
+            match source_reader(matches, source, &mut false, &Metrics::new(), &Offsets::new(), None) {
+                Ok(mut reader) => verification.rows(source, reader.as_mut()),
+                Err(failure) => verification.unreadable(source, &SimpleError::new(failure.to_string())),
+            }
+        };
+        verified.or_fail(Kind::Io)?;
+    }
+    eprintln!("{}", verification);
+    std::process::exit(verification.verdict().code());
+}
+
+/// Stats subcommand workflow.
+///
+/// **Steps:**
+/// 1. Parse every transaction record of sources profiling them (unparseable records counted).
+/// 2. Write profile to stdout as text or JSON.
+fn stats(matches: &ArgMatches) -> Result<(), Failure> {
+    let mut stats = Stats::new();
+    for source in sources(matches)?.iter() {
+        for result in source_reader(matches, source, &mut false, &Metrics::new(), &Offsets::new(), None)? {
+            match result {
+                Ok(command) => stats.record(&command),
+                Err(_) => stats.count_unparseable(),
+            }
+        }
+    }
+    stats.finish();
+    match matches.value_of("format").unwrap() {
+        "json" => println!("{}", serde_json::to_string_pretty(&stats.to_json()).or_fail(Kind::Internal)?),
+        _ => print!("{}", stats),
+    }
+    Ok(())
+}
+
+/// Diff subcommand workflow.
+///
+/// **Steps:**
+/// 1. Read rows of old and new account snapshots.
+/// 2. Write differences of accounts changed (balance deltas, newly locked, appeared or disappeared) to stdout.
+/// 3. Exit with code 1 when accounts changed (like diff) once summarized to stderr.
+fn diff(matches: &ArgMatches) -> Result<(), Failure> {
+    let snapshot = |name: &str| {
+        let path = matches.value_of(name).unwrap();
+        csv_reader(open_source(path)?, delimiter(matches, Some(path)))
+            .map_err(SimpleError::from)
+            .and_then(diff::read_snapshot)
+            .map_err(|e| { Failure::new(Kind::Data, format!("invalid snapshot({}), {}", path, e)) })
+    };
+    let (old, new) = (snapshot("old")?, snapshot("new")?);
+    let diffs = diff::diff(&old, &new).or_fail(Kind::Data)?;
+
+    let format: OutputFormat = required_arg(matches, "format")?;
+    let mut writer = RecordWriter::with_delimiter(format, delimiter(matches, None), io::stdout());
+    for diff in diffs.iter() {
+        writer.serialize(diff).or_fail(Kind::Io)?;
+    }
+    writer.finish().or_fail(Kind::Io)?;
+    eprintln!("{}", DiffSummary::of(&diffs));
+    if !diffs.is_empty() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Query subcommand workflow.
+///
+/// **Steps:**
+/// 1. Open store (persistent stores hold accounts of previous runs) and load saved state of account.
+/// 2. Handle transaction records of sources belonging to requested client.
+/// 3. Write balances (and event history) of account to stdout (exiting with an error when not found).
+fn query(matches: &ArgMatches) -> Result<(), Failure> {
+    #[cfg(feature = "duckdb")]
+    if let (Some(database), Some(sql)) = (matches.value_of("database"), matches.value_of("sql")) {
+        return duckdb::query(database, sql, matches.value_of("format").unwrap()).or_fail(Kind::Io);
+    }
+    let client: ClientId = required_arg(matches, "client")?;
+    let metadata = load_metadata(matches)?;
+    let store: StoreKind = required_arg(matches, "store")?;
+    let history = if matches.is_present("events") { EventHistory::Retained } else { EventHistory::Discarded };
+    let mut accounts = store::open(store, matches.value_of("store-path"), history).or_fail(Kind::Io)?;
+    if let Some(source) = matches.value_of("load-state") {
+        readable(source)?;
+        for account in state::load(source).or_fail(Kind::Data)?.into_iter().filter(|account| { account.client() == client }) {
+            accounts.put(account, 0).or_fail(Kind::Io)?;
+        }
+    }
+    if matches.is_present("source") {
+        for source in sources(matches)?.iter() {
+            // unparseable and rejected records are skipped
+            let records = source_reader(matches, source, &mut false, &Metrics::new(), &Offsets::new(), None)?.flatten();
+            for record in records.filter(|record| { record.actor_id() == client }) {
+                handle_command(accounts.as_mut(), &metadata, record).ok();
             }
         }
     }
+    let account = match accounts.get(client).or_fail(Kind::Io)? {
+        Some(account) => account.into_owned(),
+        None => {
+            eprintln!("account({}) not found", client);
+            std::process::exit(1);
+        }
+    };
+    write_account(matches, &account)
+}
+
+/// Replay subcommand workflow.
+///
+/// **Steps:**
+/// 1. Read events of requested client from exported event log (or audit trail).
+/// 2. Replay events onto a new `Account` until version (or time) requested is reached.
+/// 3. Write balances (and event history) of account at that point to stdout (exiting with an error without events).
+fn replay(matches: &ArgMatches) -> Result<(), Failure> {
+    let client: ClientId = required_arg(matches, "client")?;
+    let metadata = load_metadata(matches)?;
+    let to_version: Option<Version> = parse_arg(matches, "to-version")?;
+    let until = parse_arg::<DateTime<FixedOffset>>(matches, "until")?.map(|timestamp| { timestamp.with_timezone(&Utc) });
+    let source = matches.value_of("source").unwrap();
+
+    let mut account = open_account(&metadata, client);
+    for result in event_reader(matches, source)? {
+        let (actor, event) = result?;
+        if actor != client {
+            continue;
+        }
+        // events of a log are ordered so replay stops at the first event past the point requested
+        let after = until.is_some_and(|until| { event.timestamp().is_some_and(|timestamp| { timestamp > until }) });
+        if after || to_version.is_some_and(|version| { account.version() >= version }) {
+            break;
+        }
+        account.replay([event]);
+    }
+    if account.version() == 0 {
+        eprintln!("events of account({}) not found", client);
+        std::process::exit(1);
+    }
+    eprintln!("account({}) replayed to version({})", client, account.version());
+    write_account(matches, &account)
+}
+
+/// Client events read from an account event log (or audit trail).
+type EventReader<'a> = Box<dyn Iterator<Item = Result<(ClientId, Event), Failure>> + 'a>;
 
-    // write aggregates to stdout
-    let mut writer = Writer::from_writer(io::stdout());
-    for (_, account) in accounts {
-        writer.serialize(account).unwrap();
+/// Returns reader of client events of `source` read as an audit trail (when `audit` argument is present) or an event
+/// log using `event-log-format` argument.
+fn event_reader<'a>(matches: &ArgMatches, source: &'a str) -> Result<EventReader<'a>, Failure> {
+    let reader = BufReader::new(open_source(source)?);
+    Ok(match matches.is_present("audit") {
+        true => Box::new(audit::read_events(reader).map(|result| { result.or_fail(Kind::Data) })),
+        false => {
+            let format: EventLogFormat = required_arg(matches, "event-log-format")?;
+            let invalid = move |e| { Failure::new(Kind::Data, format!("invalid event log({}), {}", source, e)) };
+            Box::new(EventLogReader::new(format, reader).map(move |result| { result.map_err(invalid) }))
+        }
+    })
+}
+
+/// Writes balances of `account` (and event history when `events` argument is present) to stdout using `format`
+/// argument.
+fn write_account(matches: &ArgMatches, account: &Account) -> Result<(), Failure> {
+    let with_events = matches.is_present("events");
+    let events: Vec<AuditRecord> = account.events().iter().map(|event| { AuditRecord::from_event(account.client(), event) }).collect();
+    let format = matches.value_of("format").unwrap();
+    if format == "json" {
+        let value = match with_events {
+            true => serde_json::json!({ "account": account, "events": events }),
+            false => serde_json::json!(account),
+        };
+        println!("{}", serde_json::to_string_pretty(&value).or_fail(Kind::Internal)?);
+        return Ok(());
+    }
+    let format: OutputFormat = format.parse().map_err(|e| { usage("format", format, e) })?;
+    let delimiter = delimiter(matches, None);
+    let mut writer = RecordWriter::with_delimiter(format, delimiter, io::stdout());
+    writer.serialize(account).or_fail(Kind::Io)?;
+    writer.finish().or_fail(Kind::Io)?;
+    if with_events {
+        // sections of tables and csv are separated by a blank line (jsonl records are told apart by fields)
+        if format != OutputFormat::Jsonl {
+            println!();
+        }
+        let mut writer = RecordWriter::with_delimiter(format, delimiter, io::stdout());
+        for event in events {
+            writer.serialize(event).or_fail(Kind::Io)?;
+        }
+        writer.finish().or_fail(Kind::Io)?;
+    }
+    Ok(())
+}
+
+/// Serve subcommand workflow.
+///
+/// **Steps:**
+/// 1. Open store of accounts (accounts opened with metadata when new).
+/// 2. Start REST API and gRPC servers (by features) queueing requests to this thread.
+/// 3. Apply transactions and read accounts requested until every server stops (or the process is stopped).
+#[cfg(any(feature = "server", feature = "grpc"))]
+fn serve(matches: &ArgMatches) -> Result<(), Failure> {
+    let metadata = load_metadata(matches)?;
+    let store: StoreKind = required_arg(matches, "store")?;
+    let history = if matches.is_present("retain-events") { EventHistory::Retained } else { EventHistory::Discarded };
+    let mut accounts = store::open(store, matches.value_of("store-path"), history).or_fail(Kind::Io)?;
+    let (engine, requests) = engine::channel();
+    let mut servers = vec![];
+    #[cfg(feature = "server")]
+    {
+        let server = Server::bind(matches.value_of("bind").unwrap()).or_fail(Kind::Io)?;
+        tracing::info!("serving REST API at http://{}", server.local_addr().or_fail(Kind::Io)?);
+        servers.push(server.spawn(engine.clone()).or_fail(Kind::Io)?);
+    }
+    #[cfg(feature = "grpc")]
+    {
+        let server = GrpcServer::bind(matches.value_of("grpc-bind").unwrap()).or_fail(Kind::Io)?;
+        tracing::info!("serving gRPC at {}", server.local_addr().or_fail(Kind::Io)?);
+        servers.push(server.spawn(engine.clone()).or_fail(Kind::Io)?);
+    }
+    drop(engine);
+    requests.run(accounts.as_mut(), &metadata, handle_command);
+    for server in servers {
+        server.join().map_err(|_| { "server thread panicked" }).or_fail(Kind::Internal)?.or_fail(Kind::Io)?;
+    }
+    Ok(())
+}
+
+/// Bench subcommand workflow.
+///
+/// **Steps:**
+/// 1. Generate csv source of transactions in memory.
+/// 2. Parse and handle transactions in batches (stages timed separately) using accounts kept in memory.
+/// 3. Write accounts (csv) discarding output.
+/// 4. Report throughput of each stage and peak memory to stdout.
+fn bench(matches: &ArgMatches) -> Result<(), Failure> {
+    let transactions: usize = required_arg(matches, "transactions")?;
+    let clients: u16 = required_arg(matches, "clients")?;
+    let mut report = BenchReport::new(transactions, clients);
+
+    let started = Instant::now();
+    let source = bench::generate(transactions, clients);
+    report.generated(started.elapsed());
+
+    // batches of a full parsing queue are handled once parsed
+    let metadata = HashMap::new();
+    let mut accounts = MemoryStore::new(EventHistory::Discarded);
+    let mut reader = csv_command_reader("bench", source.as_slice(), b',', &mut false)?;
+    let mut batch = Vec::with_capacity(QUEUE_CAPACITY);
+    let (mut parsing, mut handling) = (Duration::default(), Duration::default());
+    loop {
+        let started = Instant::now();
+        batch.extend(reader.by_ref().take(QUEUE_CAPACITY).flatten());
+        parsing += started.elapsed();
+        if batch.is_empty() {
+            break;
+        }
+        let started = Instant::now();
+        let rejected = batch.drain(..)
+            .filter_map(|command| { handle_command(&mut accounts, &metadata, command).err() })
+            .count();
+        handling += started.elapsed();
+        report.rejected(rejected);
+    }
+    report.stage("parse", transactions, parsing);
+    report.stage("handle", transactions, handling);
+
+    let started = Instant::now();
+    let mut writer = RecordWriter::with_delimiter(OutputFormat::Csv, b',', io::sink());
+    let accounts: Vec<_> = accounts.iter().or_fail(Kind::Io)?.collect();
+    for account in accounts.iter() {
+        writer.serialize(account.as_ref()).or_fail(Kind::Io)?;
+    }
+    writer.finish().or_fail(Kind::Io)?;
+    report.stage("write", accounts.len(), started.elapsed());
+
+    report.finish();
+    print!("{}", report);
+    Ok(())
+}
+
+/// Writes `statements` to `writer` rendered using `format` (text, csv, ofx, mt940 or camt053).
+fn write_statements<W: io::Write>(matches: &ArgMatches, format: &str, statements: &[Statement], mut writer: W) -> Result<(), Failure> {
+    match format {
+        "csv" => {
+            let mut writer = WriterBuilder::new().delimiter(delimiter(matches, None)).from_writer(&mut writer);
+            for statement in statements {
+                for line in statement.lines() {
+                    writer.serialize(line).or_fail(Kind::Io)?;
+                }
+            }
+            writer.flush().or_fail(Kind::Io)?;
+        }
+        "ofx" => {
+            let generated = Utc::now();
+            for statement in statements {
+                write!(writer, "{}", exports::ofx(statement, generated)).or_fail(Kind::Io)?;
+            }
+        }
+        "mt940" => {
+            let generated = Utc::now();
+            for statement in statements {
+                write!(writer, "{}", exports::mt940(statement, generated)).or_fail(Kind::Io)?;
+            }
+        }
+        "camt053" => {
+            let generated = Utc::now();
+            for statement in statements {
+                write!(writer, "{}", exports::camt053(statement, generated)).or_fail(Kind::Io)?;
+            }
+        }
+        _ => {
+            for statement in statements {
+                writeln!(writer, "{}", statement).or_fail(Kind::Io)?;
+            }
+        }
     }
-    writer.flush().unwrap();
+    writer.flush().or_fail(Kind::Io)?;
+    Ok(())
 }
@@ -0,0 +1,102 @@
+//! Manifest of batch files processed from an incremental directory.
+//!
+//! The manifest is the watermark of every file in the directory: rows (records) processed per file name, so new
+//! files and rows appended to files already processed are the only records processed by the next invocation.
+//! Manifests are saved as JSON (written to a partial file then renamed).
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::{Serialize, Deserialize};
+use simple_error::*;
+
+use crate::output::partial_path;
+
+/// Name of manifest kept in an incremental directory (unless another path is supplied).
+pub const MANIFEST_NAME: &str = ".manifest.json";
+
+/// Records processed keyed by file name.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+    files: BTreeMap<String, u64>,
+}
+
+impl Manifest {
+    /// Returns manifest loaded from `path` (empty when no manifest was saved).
+    pub fn load(path: &str) -> Result<Self, SimpleError> {
+        let json = match fs::read_to_string(path) {
+            Ok(json) => json,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Manifest::default()),
+            Err(e) => bail!("unable to read manifest({}), {}", path, e),
+        };
+        Ok(try_with!(serde_json::from_str(&json), "unable to parse manifest({})", path))
+    }
+
+    /// Saves manifest to `path` atomically.
+    pub fn save(&self, path: &str) -> Result<(), SimpleError> {
+        let json = try_with!(serde_json::to_string_pretty(self), "unable to encode manifest({})", path);
+        try_with!(fs::write(partial_path(path), json), "unable to write manifest({})", path);
+        try_with!(fs::rename(partial_path(path), path), "unable to write manifest({})", path);
+        Ok(())
+    }
+
+    /// Returns records of file at `path` already processed (zero for new arrivals).
+    pub fn processed(&self, path: &str) -> u64 {
+        self.files.get(&file_name(path)).copied().unwrap_or(0)
+    }
+
+    /// Records `records` of file at `path` as processed.
+    pub fn record(&mut self, path: &str, records: u64) {
+        self.files.insert(file_name(path), records);
+    }
+}
+
+/// Returns files of incremental `directory` in lexicographic (dated file name) order.
+///
+/// Hidden files (including the manifest) and subdirectories are ignored.
+pub fn files(directory: &str) -> Result<Vec<String>, SimpleError> {
+    let mut files = vec![];
+    for entry in try_with!(fs::read_dir(directory), "unable to read directory({})", directory) {
+        let entry = try_with!(entry, "unable to read directory({})", directory);
+        let hidden = entry.file_name().to_string_lossy().starts_with('.');
+        if !hidden && entry.path().is_file() {
+            files.push(entry.path().to_string_lossy().into_owned());
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Returns name of file at `path` (manifest entries are independent of directory location).
+fn file_name(path: &str) -> String {
+    Path::new(path).file_name().map_or_else(|| { path.to_string() }, |name| { name.to_string_lossy().into_owned() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::testing::TempDir;
+
+    #[test]
+    fn manifest_tracks_new_arrivals() {
+        let temp = TempDir::new("incremental");
+        fs::write(temp.join("2021-01-02.csv"), "").unwrap();
+        fs::write(temp.join("2021-01-01.csv"), "").unwrap();
+        let directory = temp.path().to_str().unwrap();
+        let path = format!("{}/{}", directory, MANIFEST_NAME);
+        let mut manifest = Manifest::load(&path).unwrap();
+        let files = files(directory).unwrap();
+        assert_eq!(files.len(), 2);
+        assert!(files[0].ends_with("2021-01-01.csv"));
+
+        manifest.record(&files[0], 3);
+        manifest.save(&path).unwrap();
+        let manifest = Manifest::load(&path).unwrap();
+        assert_eq!(manifest.processed(&files[0]), 3);
+        assert_eq!(manifest.processed(&files[1]), 0);
+        assert_eq!(self::files(directory).unwrap(), files);
+    }
+}
@@ -0,0 +1,249 @@
+//! Domain models for event sourcing the `Merchant` aggregate.
+//!
+//! Merchants accumulate the other side of `Account` transactions referencing a merchant.
+
+use std::collections::HashSet;
+
+use simple_error::*;
+use smallvec::smallvec;
+use serde::{Serialize, Deserialize};
+use uuid::Uuid;
+
+use crate::events::{Actor, Cause, Effect, Effects};
+use crate::models::{Event, Version, ClientId, TransactionId, Currency, IdempotencyKey, MerchantId};
+
+/// A settlement to perform for a given `Merchant` aggregate.
+///
+/// Settlements are derived from `Account` events referencing a merchant.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Settlement {
+    name: SettlementType,
+    merchant: MerchantId,
+    client: ClientId,
+    tx: TransactionId,
+    amount: Currency
+}
+
+impl Settlement {
+    /// Returns `Settlement` for `Account` event when funds moved between `client` and a merchant.
+    ///
+    /// - `Credited` client funds are paid by merchant
+    /// - `Debited` client funds are received by merchant
+    /// - `Reversed` client funds are charged back to merchant
+    pub fn from_event(client: ClientId, event: &Event) -> Option<Self> {
+        let (name, merchant, tx, amount) = match event {
+            Event::Credited { merchant: Some(merchant), tx, amount, .. } => (SettlementType::Pay, merchant, tx, amount),
+            Event::Debited { merchant: Some(merchant), tx, amount, .. } => (SettlementType::Receive, merchant, tx, amount),
+            Event::Reversed { merchant: Some(merchant), tx, amount, .. } => (SettlementType::Chargeback, merchant, tx, amount),
+            _ => return None,
+        };
+        Some(Settlement { name, merchant: *merchant, client, tx: *tx, amount: *amount })
+    }
+}
+
+impl Cause for Settlement {
+    type ActorId = MerchantId;
+    fn actor_id(&self) -> Self::ActorId { self.merchant }
+}
+
+/// Type of `Settlements` that can be handled by the `Merchant` aggregate.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum SettlementType {
+    Pay,
+    Receive,
+    Chargeback,
+}
+
+/// Events that can occur from the `Merchant` aggregate.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum MerchantEvent {
+    Paid { version: Version, key: IdempotencyKey, client: ClientId, tx: TransactionId, amount: Currency },
+    Received { version: Version, key: IdempotencyKey, client: ClientId, tx: TransactionId, amount: Currency },
+    ChargedBack { version: Version, key: IdempotencyKey, client: ClientId, tx: TransactionId, amount: Currency },
+}
+
+impl Effect for MerchantEvent {
+    type Version = Version;
+    type Key = IdempotencyKey;
+    fn version(&self) -> Self::Version {
+        match self {
+            MerchantEvent::Paid {version, ..} |
+            MerchantEvent::Received {version, ..} |
+            MerchantEvent::ChargedBack {version, ..} => { *version }
+        }
+    }
+    fn idempotency_key(&self) -> Self::Key {
+        match self {
+            MerchantEvent::Paid {key, ..} |
+            MerchantEvent::Received {key, ..} |
+            MerchantEvent::ChargedBack {key, ..} => { *key }
+        }
+    }
+}
+
+/// Aggregate that summarizes all `merchant` settlements.
+///
+/// `net` is the amount owed to merchant (received plus charged back less paid).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Merchant {
+    #[serde(skip_serializing)]
+    version: Version,
+    merchant: MerchantId,
+    paid: Currency,
+    received: Currency,
+    charged_back: Currency,
+    net: Currency,
+    #[serde(skip_serializing)]
+    events: Vec<MerchantEvent>,
+    /// Transactions settled by type (duplicates declined without scanning events).
+    #[serde(skip)]
+    settled: HashSet<(SettlementType, TransactionId)>
+}
+
+impl Merchant {
+    /// Returns new `Merchant` with `merchant` id set and defaults.
+    pub fn new(merchant: MerchantId) -> Self {
+        Merchant {
+            version: 0,
+            merchant,
+            paid: Currency::new(0, 4),
+            received: Currency::new(0, 4),
+            charged_back: Currency::new(0, 4),
+            net: Currency::new(0, 4),
+            events: vec![],
+            settled: HashSet::new()
+        }
+    }
+}
+
+impl Actor<Settlement, MerchantEvent> for Merchant {
+    type Id = MerchantId;
+
+    fn handle(&self, command: Settlement) -> Result<Effects<MerchantEvent>, SimpleError> {
+        if self.settled.contains(&(command.name, command.tx)) {
+            bail!("duplicate settlement merchant({}) transaction({})", command.merchant, command.tx);
+        }
+
+        let namespace = Uuid::NAMESPACE_OID;
+        let key = *Uuid::new_v3(&namespace, &command.tx.to_le_bytes()).as_bytes();
+
+        let event = match command.name {
+            SettlementType::Pay => MerchantEvent::Paid {
+                version: 1, key, client: command.client, tx: command.tx, amount: command.amount
+            },
+            SettlementType::Receive => MerchantEvent::Received {
+                version: 1, key, client: command.client, tx: command.tx, amount: command.amount
+            },
+            SettlementType::Chargeback => MerchantEvent::ChargedBack {
+                version: 1, key, client: command.client, tx: command.tx, amount: command.amount
+            },
+        };
+
+        Ok(smallvec![event])
+    }
+
+    fn apply<I: IntoIterator<Item = MerchantEvent>>(&mut self, events: I) {
+        for event in events {
+            let (name, tx) = match &event {
+                MerchantEvent::Paid { tx, amount, .. } => {
+                    self.paid += amount;
+                    (SettlementType::Pay, tx)
+                }
+                MerchantEvent::Received { tx, amount, .. } => {
+                    self.received += amount;
+                    (SettlementType::Receive, tx)
+                }
+                MerchantEvent::ChargedBack { tx, amount, .. } => {
+                    self.charged_back += amount;
+                    (SettlementType::Chargeback, tx)
+                }
+            };
+            self.settled.insert((name, *tx));
+            self.net = self.received + self.charged_back - self.paid;
+            self.version += 1;
+            self.events.push(event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::prelude::Decimal;
+
+    fn credited(tx: TransactionId, merchant: Option<MerchantId>, amount: Currency) -> Event {
+        Event::Credited {
+            version: 1,
+            key: [0; 16],
+            timestamp: None,
+            tx,
+            wallet: String::from("main"),
+            merchant,
+            category: None,
+            amount
+        }
+    }
+
+    #[test]
+    fn settlement_without_merchant_ignored() {
+        let event = credited(10, None, Decimal::new(990000, 4));
+
+        assert!(Settlement::from_event(1, &event).is_none());
+    }
+
+    #[test]
+    fn settlements_accepted() {
+        let merchant = 7;
+
+        let mut aggregate = Merchant::new(merchant);
+        let settlement = Settlement::from_event(1, &credited(10, Some(merchant), Decimal::new(100000, 4))).unwrap();
+        let events = aggregate.handle(settlement).unwrap();
+        aggregate.apply(events);
+        let settlement = Settlement {
+            name: SettlementType::Receive,
+            merchant,
+            client: 1,
+            tx: 11,
+            amount: Decimal::new(990000, 4)
+        };
+        let events = aggregate.handle(settlement).unwrap();
+        aggregate.apply(events);
+        let settlement = Settlement {
+            name: SettlementType::Chargeback,
+            merchant,
+            client: 1,
+            tx: 10,
+            amount: Decimal::new(100000, 4)
+        };
+        let events = aggregate.handle(settlement).unwrap();
+        aggregate.apply(events);
+
+        assert_eq!(aggregate.version, 3);
+        assert_eq!(aggregate.paid, Decimal::new(100000, 4));
+        assert_eq!(aggregate.received, Decimal::new(990000, 4));
+        assert_eq!(aggregate.charged_back, Decimal::new(100000, 4));
+        assert_eq!(aggregate.net, Decimal::new(990000, 4));
+        assert_eq!(aggregate.events.len(), 3);
+    }
+
+    #[test]
+    fn settlement_duplicate_declined() {
+        let merchant = 7;
+
+        let mut aggregate = Merchant::new(merchant);
+        let settlement = Settlement::from_event(1, &credited(10, Some(merchant), Decimal::new(100000, 4))).unwrap();
+        let events = aggregate.handle(settlement.clone()).unwrap();
+        aggregate.apply(events);
+        let events = aggregate.handle(settlement.clone());
+        assert!(events.is_err());
+        // transaction repeated with another amount is still a duplicate
+        let events = aggregate.handle(Settlement { amount: Decimal::new(50000, 4), ..settlement });
+        assert!(events.is_err());
+
+        assert_eq!(aggregate.version, 1);
+        assert_eq!(aggregate.paid, Decimal::new(100000, 4));
+        assert_eq!(aggregate.net, Decimal::new(-100000, 4));
+        assert_eq!(aggregate.events.len(), 1);
+    }
+}
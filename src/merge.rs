@@ -0,0 +1,145 @@
+//! Deterministic merge of sources parsed in parallel (`--merge-sources`).
+//!
+//! Every source is opened at once so each is parsed on its own thread (see `PipelineReader`), while the processing
+//! loop handles commands merged by timestamp then source order. Each source is expected ordered by timestamp (e.g.
+//! daily files), records of a source are merged in the order read and records without a timestamp (or unparseable)
+//! are ordered at the timestamp of the record read before them, so commands are handled in the same order whichever
+//! source parses fastest.
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use simple_error::SimpleError;
+
+use crate::input::SourceReader;
+use crate::models::{Command, Timestamp};
+
+/// Record read from a source with its line.
+type Head = (Result<Command, SimpleError>, usize);
+
+/// Key records are merged by (timestamp of source then source index).
+type Key = (Option<Timestamp>, usize);
+
+/// Merges records of sources ordered by timestamp then source (in order of `readers`).
+pub struct SourceMerge {
+    readers: Vec<Box<dyn SourceReader>>,
+    heads: Vec<Option<Head>>,
+    /// Timestamp of record last read of each source (records without timestamp are ordered at it).
+    timestamps: Vec<Option<Timestamp>>,
+    heap: BinaryHeap<Reverse<Key>>,
+}
+
+impl SourceMerge {
+    /// Returns new `SourceMerge` of `readers` (first record of each source read once every source is started).
+    pub fn new(readers: Vec<Box<dyn SourceReader>>) -> Self {
+        let sources = readers.len();
+        let mut merge = SourceMerge {
+            readers,
+            heads: (0..sources).map(|_| { None }).collect(),
+            timestamps: vec![None; sources],
+            heap: BinaryHeap::with_capacity(sources),
+        };
+        for index in 0..sources {
+            merge.advance(index);
+        }
+        merge
+    }
+
+    /// Reads next record of source `index` queueing it for merge (none once source is read).
+    fn advance(&mut self, index: usize) {
+        let reader = &mut self.readers[index];
+        let head = reader.next().map(|result| { (result, reader.line()) });
+        if let Some((Ok(command), _)) = head.as_ref() {
+            if let Some(timestamp) = command.timestamp() {
+                self.timestamps[index] = Some(timestamp);
+            }
+        }
+        if head.is_some() {
+            self.heap.push(Reverse((self.timestamps[index], index)));
+        }
+        self.heads[index] = head;
+    }
+}
+
+impl Iterator for SourceMerge {
+    /// Source index, line and command (or error of unparseable record) read.
+    type Item = (usize, usize, Result<Command, SimpleError>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let Reverse((_, index)) = self.heap.pop()?;
+        let (result, line) = self.heads[index].take()?;
+        self.advance(index);
+        Some((index, line, result))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use simple_error::bail;
+
+    use crate::input::csv_reader;
+
+    /// Reads commands of a csv source (records named `invalid` are unparseable).
+    struct Records {
+        records: std::vec::IntoIter<Result<Command, SimpleError>>,
+        line: usize,
+    }
+
+    impl Records {
+        fn boxed(source: &str) -> Box<dyn SourceReader> {
+            let records = source.lines().map(|record| {
+                if record == "invalid" {
+                    bail!("invalid record");
+                }
+                let source = format!("type,client,tx,amount,timestamp\n{}\n", record);
+                Ok(csv_reader(source.as_bytes(), b',').unwrap().deserialize().next().unwrap().unwrap())
+            }).collect::<Vec<_>>();
+            Box::new(Records { records: records.into_iter(), line: 1 })
+        }
+    }
+
+    impl SourceReader for Records {
+        fn line(&self) -> usize {
+            self.line
+        }
+    }
+
+    impl Iterator for Records {
+        type Item = Result<Command, SimpleError>;
+
+        fn next(&mut self) -> Option<Self::Item> {
+            self.line += 1;
+            self.records.next()
+        }
+    }
+
+    #[test]
+    fn records_merged_by_timestamp_then_source() {
+        let readers = vec![
+            Records::boxed("deposit,1,1,1.0,2021-03-01T10:00:00Z\ndeposit,1,2,1.0,2021-03-02T10:00:00Z\ninvalid\ndeposit,1,3,1.0,"),
+            Records::boxed("deposit,2,4,1.0,\ndeposit,2,5,1.0,2021-03-01T10:00:00Z\ndeposit,2,6,1.0,2021-03-01T12:00:00Z"),
+            Records::boxed(""),
+            Records::boxed("deposit,3,7,1.0,2021-02-28T10:00:00Z\ndeposit,3,8,1.0,2021-03-03T10:00:00Z"),
+        ];
+        let merged: Vec<(usize, usize, Option<u32>)> = SourceMerge::new(readers).map(|(index, line, result)| {
+            (index, line, result.ok().map(|command| { command.tx() }))
+        }).collect();
+
+        assert_eq!(merged, vec![
+            // records without a timestamp before any timestamp read come first
+            (1, 2, Some(4)),
+            (3, 2, Some(7)),
+            // equal timestamps are merged in source order
+            (0, 2, Some(1)),
+            (1, 3, Some(5)),
+            (1, 4, Some(6)),
+            (0, 3, Some(2)),
+            // unparseable and records without timestamp follow the record read before them
+            (0, 4, None),
+            (0, 5, Some(3)),
+            (3, 3, Some(8)),
+        ]);
+    }
+}
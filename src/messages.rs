@@ -0,0 +1,147 @@
+//! Transaction messages consumed from streaming sources (Kafka topics or NATS JetStream streams).
+//!
+//! Each message holds a single transaction as a csv row without headers (type, client, tx, amount, wallet,
+//! merchant, timestamp and category columns, trailing columns optional), a JSON object or an Avro record framed
+//! for a schema registry (see `avro::from_registry_frame`).
+
+use std::str::FromStr;
+
+use csv::{ReaderBuilder, StringRecord, Trim};
+use simple_error::*;
+
+use crate::avro::{self, Schema, COMMAND_SCHEMA};
+use crate::models::Command;
+
+/// Columns of csv messages.
+const CSV_COLUMNS: [&str; 8] = ["type", "client", "tx", "amount", "wallet", "merchant", "timestamp", "category"];
+
+/// Streaming source of transaction messages.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StreamKind {
+    /// Kafka topic (requires `kafka` feature).
+    #[cfg(feature = "kafka")]
+    Kafka,
+    /// NATS JetStream stream (requires `nats` feature).
+    #[cfg(feature = "nats")]
+    Nats,
+}
+
+impl StreamKind {
+    /// Returns names of streaming sources used for cli arguments.
+    pub fn names() -> Vec<&'static str> {
+        vec![
+            #[cfg(feature = "kafka")]
+            "kafka",
+            #[cfg(feature = "nats")]
+            "nats",
+        ]
+    }
+}
+
+impl FromStr for StreamKind {
+    type Err = SimpleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            #[cfg(feature = "kafka")]
+            "kafka" => Ok(StreamKind::Kafka),
+            #[cfg(feature = "nats")]
+            "nats" => Ok(StreamKind::Nats),
+            _ => Err(SimpleError::new(format!("unsupported streaming source({})", s))),
+        }
+    }
+}
+
+/// Encoding of transaction messages.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MessageFormat {
+    Csv,
+    Json,
+    Avro,
+}
+
+impl MessageFormat {
+    /// Returns names of message formats used for cli arguments.
+    pub fn names() -> Vec<&'static str> {
+        vec!["csv", "json", "avro"]
+    }
+}
+
+impl FromStr for MessageFormat {
+    type Err = SimpleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(MessageFormat::Csv),
+            "json" => Ok(MessageFormat::Json),
+            "avro" => Ok(MessageFormat::Avro),
+            _ => Err(SimpleError::new(format!("unsupported message format({})", s))),
+        }
+    }
+}
+
+/// Decodes payloads of transaction messages as `Command`s.
+pub struct MessageDecoder {
+    format: MessageFormat,
+    schema: Option<Schema>,
+}
+
+impl MessageDecoder {
+    /// Returns new `MessageDecoder` of messages encoded using `format`.
+    pub fn new(format: MessageFormat) -> Result<Self, SimpleError> {
+        let schema = match format {
+            MessageFormat::Avro => Some(Schema::parse(COMMAND_SCHEMA)?),
+            _ => None,
+        };
+        Ok(MessageDecoder { format, schema })
+    }
+
+    /// Returns command of message `payload`.
+    pub fn decode(&self, payload: &[u8]) -> Result<Command, SimpleError> {
+        match (self.format, self.schema.as_ref()) {
+            (MessageFormat::Avro, Some(schema)) => {
+                let (_, record) = avro::from_registry_frame(payload)?;
+                avro::from_avro(schema, record)
+            }
+            (MessageFormat::Json, _) => Ok(try_with!(serde_json::from_slice(payload), "unable to parse json message")),
+            _ => {
+                let mut reader = ReaderBuilder::new()
+                    .has_headers(false)
+                    .trim(Trim::All)
+                    .flexible(true)
+                    .from_reader(payload);
+                let mut record = StringRecord::new();
+                if !try_with!(reader.read_record(&mut record), "unable to parse csv message") {
+                    bail!("empty csv message");
+                }
+                let columns = StringRecord::from(&CSV_COLUMNS[..]);
+                Ok(try_with!(record.deserialize(Some(&columns)), "unable to parse csv message"))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::prelude::Decimal;
+
+    use crate::models::CommandType;
+
+    #[test]
+    fn messages_decoded() {
+        let csv = MessageDecoder::new(MessageFormat::Csv).unwrap();
+        let command = csv.decode(b"deposit, 1, 10, 1.5\n").unwrap();
+        assert_eq!(command, Command::new(CommandType::Deposit, 1, 10, Some(Decimal::new(15, 1))));
+        assert_eq!(csv.decode(b"dispute,1,10").unwrap().amount(), None);
+        assert!(csv.decode(b"").is_err());
+
+        let json = MessageDecoder::new(MessageFormat::Json).unwrap();
+        assert_eq!(json.decode(br#"{"type":"deposit","client":1,"tx":10,"amount":"1.5"}"#).unwrap(), command);
+        assert!(json.decode(b"deposit,1,10,1.5").is_err());
+
+        let avro = MessageDecoder::new(MessageFormat::Avro).unwrap();
+        let record = avro::produce::to_avro(avro.schema.as_ref().unwrap(), &command).unwrap();
+        assert_eq!(avro.decode(&avro::produce::to_registry_frame(7, &record)).unwrap(), command);
+    }
+}
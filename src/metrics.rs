@@ -0,0 +1,191 @@
+//! Occupancy and lag metrics of the bounded queues connecting processing stages.
+//!
+//! Each queue (records parsed waiting to be handled, commands routed waiting for a shard worker) counts records
+//! queued (lag) and their peak, and the time its producer was blocked by a full queue (backpressure) or its consumer
+//! waited on an empty queue (stalled upstream). Metrics are reported periodically (`--metrics-interval`) and by the
+//! summary of a run.
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use tracing::info;
+
+/// Metrics of a bounded queue shared by its producer and consumer.
+pub struct QueueMetrics {
+    name: String,
+    capacity: usize,
+    sent: AtomicU64,
+    received: AtomicU64,
+    peak: AtomicU64,
+    blocked: AtomicU64,
+    waited: AtomicU64,
+    closed: AtomicBool,
+}
+
+impl QueueMetrics {
+    /// Returns new `QueueMetrics` of queue `name` holding up to `capacity` records.
+    pub fn new(name: &str, capacity: usize) -> Self {
+        QueueMetrics {
+            name: name.to_string(),
+            capacity,
+            sent: AtomicU64::new(0),
+            received: AtomicU64::new(0),
+            peak: AtomicU64::new(0),
+            blocked: AtomicU64::new(0),
+            waited: AtomicU64::new(0),
+            closed: AtomicBool::new(false),
+        }
+    }
+
+    /// Returns records the queue holds at most.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Counts `records` sent to the queue.
+    pub fn sent(&self, records: usize) {
+        let sent = self.sent.fetch_add(records as u64, Ordering::Relaxed) + records as u64;
+        // records may be received before counted as sent
+        self.peak.fetch_max(sent.saturating_sub(self.received.load(Ordering::Relaxed)), Ordering::Relaxed);
+    }
+
+    /// Counts `records` received from the queue (taken to be handled).
+    pub fn received(&self, records: usize) {
+        self.received.fetch_add(records as u64, Ordering::Relaxed);
+    }
+
+    /// Adds time producer was blocked by a full queue since `started`.
+    pub fn blocked(&self, started: Instant) {
+        self.blocked.fetch_add(started.elapsed().as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    /// Adds time consumer waited on an empty queue since `started`.
+    pub fn waited(&self, started: Instant) {
+        self.waited.fetch_add(started.elapsed().as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    /// Marks queue as closed (every record sent received).
+    pub fn close(&self) {
+        self.closed.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns true once queue is closed.
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::Relaxed)
+    }
+
+    /// Returns current metrics of the queue.
+    pub fn stats(&self) -> QueueStats {
+        let sent = self.sent.load(Ordering::Relaxed);
+        QueueStats {
+            name: self.name.clone(),
+            capacity: self.capacity,
+            queued: sent.saturating_sub(self.received.load(Ordering::Relaxed)),
+            peak: self.peak.load(Ordering::Relaxed),
+            sent,
+            blocked: Duration::from_nanos(self.blocked.load(Ordering::Relaxed)),
+            waited: Duration::from_nanos(self.waited.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+/// Metrics of a queue at a point in time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueueStats {
+    pub name: String,
+    pub capacity: usize,
+    /// Records queued (lag of consumer).
+    pub queued: u64,
+    /// Most records queued at once.
+    pub peak: u64,
+    pub sent: u64,
+    /// Time producer was blocked by a full queue (backpressure).
+    pub blocked: Duration,
+    /// Time consumer waited on an empty queue.
+    pub waited: Duration,
+}
+
+impl fmt::Display for QueueStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {}/{} queued (peak: {}, sent: {}, blocked: {:.3}s, waited: {:.3}s)",
+            self.name,
+            self.queued,
+            self.capacity,
+            self.peak,
+            self.sent,
+            self.blocked.as_secs_f64(),
+            self.waited.as_secs_f64()
+        )
+    }
+}
+
+/// Queues of a run (in order created) whose metrics are reported.
+#[derive(Clone, Default)]
+pub struct Metrics {
+    queues: Arc<Mutex<Vec<Arc<QueueMetrics>>>>,
+}
+
+impl Metrics {
+    /// Returns new `Metrics` without queues.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns metrics of new queue `name` holding up to `capacity` records.
+    pub fn queue(&self, name: &str, capacity: usize) -> Arc<QueueMetrics> {
+        let queue = Arc::new(QueueMetrics::new(name, capacity));
+        self.queues.lock().unwrap().push(queue.clone());
+        queue
+    }
+
+    /// Returns current metrics of every queue.
+    pub fn stats(&self) -> Vec<QueueStats> {
+        self.queues.lock().unwrap().iter().map(|queue| { queue.stats() }).collect()
+    }
+
+    /// Logs metrics of queues not yet closed every `interval` (on a new thread running until the process
+    /// exits).
+    pub fn report_every(&self, interval: Duration) {
+        let queues = self.queues.clone();
+        thread::spawn(move || {
+            loop {
+                thread::sleep(interval);
+                for queue in queues.lock().unwrap().iter().filter(|queue| { !queue.is_closed() }) {
+                    info!("QueueMetrics {}", queue.stats());
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn occupancy_and_waits_measured() {
+        let metrics = Metrics::new();
+        let queue = metrics.queue("read(tx.csv)", 512);
+        queue.sent(256);
+        queue.sent(256);
+        queue.received(256);
+        queue.sent(128);
+        queue.blocked(Instant::now() - Duration::from_millis(20));
+        queue.waited(Instant::now() - Duration::from_millis(5));
+        assert!(!queue.is_closed());
+        queue.close();
+        assert!(queue.is_closed());
+
+        let stats = metrics.stats();
+        assert_eq!(stats.len(), 1);
+        assert_eq!((stats[0].queued, stats[0].peak, stats[0].sent), (384, 512, 640));
+        assert!(stats[0].blocked >= Duration::from_millis(20));
+        assert!(stats[0].waited >= Duration::from_millis(5) && stats[0].waited < stats[0].blocked);
+        assert!(stats[0].to_string().starts_with("read(tx.csv): 384/512 queued (peak: 512, sent: 640, blocked: 0.0"));
+    }
+}
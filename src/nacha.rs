@@ -0,0 +1,261 @@
+//! Reader of NACHA ACH files mapping entries and returns to `Command`s.
+//!
+//! Files are 94 character records (one per line or blocked without line breaks):
+//! - credit entries are deposits and debit entries withdrawals of the individual identification number client
+//! - returns of unauthorized entries (e.g. `R05`, `R10`) are disputes charged back on the original entry
+//! - other returns (e.g. `R01` insufficient funds) are mapped to the inverse command of the returned entry
+//! - prenotes, zero dollar entries and notifications of change are skipped
+//!
+//! Transaction ids are the sequence number (last 7 digits) of entry trace numbers and entries are timestamped
+//! midnight UTC of their batch effective entry date.
+
+use std::collections::VecDeque;
+use std::io::{BufRead, Lines};
+
+use chrono::{NaiveDate, TimeZone, Utc};
+use simple_error::*;
+
+use crate::input::SourceReader;
+use crate::models::{ClientId, Command, CommandType, Currency, Timestamp, TransactionId};
+
+/// Length of NACHA records.
+const RECORD_LENGTH: usize = 94;
+
+/// Return reason codes of entries the receiver did not authorize.
+const UNAUTHORIZED: [&str; 6] = ["R05", "R07", "R10", "R11", "R29", "R51"];
+
+/// Reads NACHA ACH records from `R` as deposit, withdraw, dispute and chargeback `Command`s.
+pub struct NachaReader<R: BufRead> {
+    lines: Lines<R>,
+    records: VecDeque<String>,
+    commands: VecDeque<Command>,
+    position: usize,
+    entry: usize,
+    effective: Option<Timestamp>,
+}
+
+impl<R: BufRead> NachaReader<R> {
+    /// Returns new `NachaReader` reading records from `reader`.
+    pub fn new(reader: R) -> Self {
+        NachaReader {
+            lines: reader.lines(),
+            records: VecDeque::new(),
+            commands: VecDeque::new(),
+            position: 0,
+            entry: 0,
+            effective: None,
+        }
+    }
+
+    /// Returns next record (padded to record length) or none at end of file.
+    fn read_record(&mut self) -> Result<Option<String>, SimpleError> {
+        while self.records.is_empty() {
+            let line = match self.lines.next() {
+                Some(line) => try_with!(line, "unable to read nacha record({})", self.position + 1),
+                None => return Ok(None),
+            };
+            let line = line.trim_end();
+            if !line.is_ascii() {
+                bail!("invalid nacha record({})", self.position + 1);
+            }
+            // blocked files have records without line breaks
+            for start in (0..line.len()).step_by(RECORD_LENGTH) {
+                let end = line.len().min(start + RECORD_LENGTH);
+                self.records.push_back(format!("{:<width$}", &line[start..end], width = RECORD_LENGTH));
+            }
+        }
+        self.position += 1;
+        Ok(self.records.pop_front())
+    }
+
+    /// Returns addenda records following an entry detail record.
+    fn read_addenda(&mut self) -> Result<Vec<String>, SimpleError> {
+        let mut addenda = vec![];
+        while let Some(record) = self.read_record()? {
+            if !record.starts_with('7') {
+                self.position -= 1;
+                self.records.push_front(record);
+                break;
+            }
+            addenda.push(record);
+        }
+        Ok(addenda)
+    }
+
+    /// Queues commands of records until a command is read or end of file.
+    fn read_commands(&mut self) -> Result<(), SimpleError> {
+        while self.commands.is_empty() {
+            let record = match self.read_record()? {
+                Some(record) => record,
+                None => return Ok(()),
+            };
+            match &record[..1] {
+                "5" => self.effective = Some(parse_date(field(&record, 70, 75), self.position)?),
+                "6" => {
+                    self.entry = self.position;
+                    let addenda = if field(&record, 79, 79) == "1" { self.read_addenda()? } else { vec![] };
+                    let commands = commands(&record, &addenda, self.effective, self.entry)?;
+                    self.commands.extend(commands);
+                }
+                // file header, addenda without entry, controls and block padding
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<R: BufRead> SourceReader for NachaReader<R> {
+    fn line(&self) -> usize {
+        self.entry
+    }
+}
+
+impl<R: BufRead> Iterator for NachaReader<R> {
+    type Item = Result<Command, SimpleError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Err(e) = self.read_commands() {
+            return Some(Err(e));
+        }
+        self.commands.pop_front().map(Ok)
+    }
+}
+
+/// Returns characters of `record` from `start` to `end` (1-based and inclusive as in the NACHA specification).
+fn field(record: &str, start: usize, end: usize) -> &str {
+    &record[start - 1..end]
+}
+
+/// Returns commands of entry detail `record` having `addenda` at `position`.
+fn commands(
+    record: &str,
+    addenda: &[String],
+    effective: Option<Timestamp>,
+    position: usize
+) -> Result<Vec<Command>, SimpleError> {
+    let code = field(record, 2, 3);
+    let client: ClientId = try_with!(
+        field(record, 40, 54).trim().parse(),
+        "invalid nacha entry({}) individual identification number",
+        position
+    );
+    let amount: i64 = try_with!(field(record, 30, 39).parse(), "invalid nacha entry({}) amount", position);
+    let amount = Currency::new(amount, 2);
+    let tx = trace_sequence(field(record, 80, 94), position)?;
+
+    let name = match &code[1..] {
+        "2" => CommandType::Deposit,
+        "7" => CommandType::Withdraw,
+        // returns (or notifications of change) of credit and debit entries
+        "1" | "6" => {
+            let addendum = match addenda.iter().find(|addendum| { field(addendum, 2, 3) == "99" }) {
+                Some(addendum) => addendum,
+                None if addenda.iter().any(|addendum| { field(addendum, 2, 3) == "98" }) => return Ok(vec![]),
+                None => bail!("return addenda is none for nacha entry({})", position),
+            };
+            let reason = field(addendum, 4, 6);
+            if UNAUTHORIZED.contains(&reason) {
+                let original = trace_sequence(field(addendum, 7, 21), position)?;
+                return Ok(vec![
+                    Command::new(CommandType::Dispute, client, original, None).with_timestamp(effective),
+                    Command::new(CommandType::Chargeback, client, original, None).with_timestamp(effective),
+                ]);
+            }
+            // returned credits are withdrawn while returned debits are deposited
+            if &code[1..] == "1" { CommandType::Withdraw } else { CommandType::Deposit }
+        }
+        // prenotes and zero dollar entries
+        "3" | "4" | "8" | "9" => return Ok(vec![]),
+        _ => bail!("unsupported nacha entry({}) transaction code({})", position, code),
+    };
+    Ok(vec![Command::new(name, client, tx, Some(amount)).with_timestamp(effective)])
+}
+
+/// Returns transaction id of entry `trace` number (sequence number excluding ODFI routing number).
+fn trace_sequence(trace: &str, position: usize) -> Result<TransactionId, SimpleError> {
+    match trace.get(8..).and_then(|sequence| { sequence.trim().parse().ok() }) {
+        Some(tx) => Ok(tx),
+        None => bail!("invalid nacha record({}) trace number({})", position, trace),
+    }
+}
+
+/// Returns midnight UTC of batch effective entry `date` (YYMMDD).
+fn parse_date(date: &str, position: usize) -> Result<Timestamp, SimpleError> {
+    match NaiveDate::parse_from_str(date, "%y%m%d").ok().and_then(|date| { date.and_hms_opt(0, 0, 0) }) {
+        Some(date) => Ok(Utc.from_utc_datetime(&date)),
+        None => bail!("invalid nacha batch({}) effective entry date({})", position, date),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::prelude::Decimal;
+
+    use crate::events::Cause;
+
+    const BATCH: &str = "5200ACME                                1234567890PPDPAYROLL         240115   1021000020000001";
+
+    fn entry(code: &str, amount: &str, client: &str, addenda: bool, sequence: &str) -> String {
+        format!(
+            "6{}02100002112345678         {}{:<15}{:<22}  {}02100002{}",
+            code,
+            amount,
+            client,
+            "JANE DOE",
+            if addenda { "1" } else { "0" },
+            sequence
+        )
+    }
+
+    fn commands(source: &str) -> Vec<Command> {
+        NachaReader::new(source.as_bytes()).map(|command| { command.unwrap() }).collect()
+    }
+
+    #[test]
+    fn entries_read_as_commands() {
+        let source = format!(
+            "{}\n{}\n{}\n{}\n",
+            BATCH,
+            entry("22", "0000012345", "7", false, "0000001"),
+            entry("27", "0000000500", "7", false, "0000002"),
+            entry("23", "0000000000", "7", false, "0000003")
+        );
+        let commands = commands(&source);
+
+        assert_eq!(commands.len(), 2);
+        assert_eq!(commands[0].name(), &CommandType::Deposit);
+        assert_eq!(commands[0].actor_id(), 7);
+        assert_eq!(commands[0].tx(), 1);
+        assert_eq!(commands[0].amount(), Some(Decimal::new(12345, 2)));
+        assert_eq!(commands[0].timestamp(), Some(Utc.with_ymd_and_hms(2024, 1, 15, 0, 0, 0).unwrap()));
+        assert_eq!(commands[1].name(), &CommandType::Withdraw);
+        assert_eq!(commands[1].tx(), 2);
+    }
+
+    #[test]
+    fn returns_read_as_chargebacks_or_inverse_commands() {
+        let unauthorized = format!("799R10021000020000002      02100002{:<44}021000020000004", "");
+        let insufficient = format!("799R01021000020000001      02100002{:<44}021000020000005", "");
+        // blocked without line breaks
+        let source = format!(
+            "{}{}{}{}{}",
+            BATCH,
+            entry("26", "0000000500", "7", true, "0000004"),
+            unauthorized,
+            entry("21", "0000012345", "7", true, "0000005"),
+            insufficient
+        );
+        let commands = commands(&source);
+
+        assert_eq!(commands.len(), 3);
+        assert_eq!(commands[0].name(), &CommandType::Dispute);
+        assert_eq!(commands[0].tx(), 2);
+        assert_eq!(commands[1].name(), &CommandType::Chargeback);
+        assert_eq!(commands[1].tx(), 2);
+        assert_eq!(commands[2].name(), &CommandType::Withdraw);
+        assert_eq!(commands[2].tx(), 5);
+        assert_eq!(commands[2].amount(), Some(Decimal::new(12345, 2)));
+    }
+}
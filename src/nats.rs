@@ -0,0 +1,162 @@
+//! Transactions consumed continuously from a NATS JetStream stream using a durable pull consumer.
+//!
+//! Messages are acknowledged explicitly only once the main loop has applied (or rejected) them, i.e. when the next
+//! message is requested. Messages left unacknowledged by an interrupted run are redelivered to the durable consumer
+//! (at-least-once). Exactly-once processing instead consumes the stream from the sequence following the one kept by
+//! the account store (see `connect_from`) using an ephemeral consumer.
+
+use std::time::{Duration, Instant};
+
+use async_nats::jetstream::{self, Message};
+use async_nats::jetstream::consumer::{AckPolicy, DeliverPolicy, pull};
+use futures::StreamExt;
+use simple_error::*;
+use tokio::runtime::Runtime;
+
+use crate::input::SourceReader;
+use crate::messages::MessageDecoder;
+use crate::models::Command;
+use crate::store::Offsets;
+
+/// Reads messages of a JetStream stream as `Command`s until idle (forever unless an idle timeout is supplied).
+pub struct NatsReader {
+    runtime: Runtime,
+    messages: pull::Stream,
+    stream: String,
+    acknowledged: bool,
+    decoder: MessageDecoder,
+    idle: Option<Duration>,
+    consumed: Option<Message>,
+    received: Instant,
+    position: usize,
+}
+
+impl NatsReader {
+    /// Returns new `NatsReader` of `stream` consumed from `servers` by durable consumer `durable`.
+    ///
+    /// Durable consumers are created (delivering all messages of the stream) when missing.
+    pub fn connect(
+        servers: &str,
+        stream: &str,
+        durable: &str,
+        decoder: MessageDecoder,
+        idle: Option<Duration>,
+    ) -> Result<Self, SimpleError> {
+        let config = pull::Config {
+            durable_name: Some(durable.to_string()),
+            ack_policy: AckPolicy::Explicit,
+            ..Default::default()
+        };
+        Self::consume(servers, stream, config, decoder, idle)
+    }
+
+    /// Returns new `NatsReader` of `stream` consumed from `servers` from the message following its sequence of
+    /// `offsets` (every message when none) by an ephemeral consumer (messages aren't acknowledged).
+    pub fn connect_from(
+        servers: &str,
+        stream: &str,
+        decoder: MessageDecoder,
+        idle: Option<Duration>,
+        offsets: &Offsets,
+    ) -> Result<Self, SimpleError> {
+        let deliver_policy = match offsets.get(stream) {
+            Some(sequence) => DeliverPolicy::ByStartSequence { start_sequence: *sequence as u64 + 1 },
+            None => DeliverPolicy::All,
+        };
+        let config = pull::Config { deliver_policy, ack_policy: AckPolicy::None, ..Default::default() };
+        Self::consume(servers, stream, config, decoder, idle)
+    }
+
+    /// Returns new `NatsReader` of `stream` consumed from `servers` by consumer of `config` (created when missing).
+    fn consume(
+        servers: &str,
+        stream: &str,
+        config: pull::Config,
+        decoder: MessageDecoder,
+        idle: Option<Duration>,
+    ) -> Result<Self, SimpleError> {
+        let runtime = try_with!(
+            tokio::runtime::Builder::new_current_thread().enable_all().build(),
+            "unable to start nats runtime"
+        );
+        let acknowledged = config.ack_policy != AckPolicy::None;
+        let messages = runtime.block_on(async {
+            let client = try_with!(async_nats::connect(servers).await, "unable to connect to nats servers({})", servers);
+            let context = jetstream::new(client);
+            let stream = try_with!(context.get_stream(stream).await, "unable to get jetstream stream({})", stream);
+            let durable = config.durable_name.clone();
+            let consumer = match durable.as_deref() {
+                Some(durable) => stream.get_or_create_consumer(durable, config).await,
+                None => stream.create_consumer(config).await,
+            };
+            let name = durable.unwrap_or_else(|| { String::from("ephemeral") });
+            let consumer = try_with!(consumer, "unable to create jetstream consumer({})", name);
+            Ok::<_, SimpleError>(try_with!(consumer.messages().await, "unable to consume jetstream consumer({})", name))
+        })?;
+        Ok(NatsReader {
+            runtime,
+            messages,
+            stream: stream.to_string(),
+            acknowledged,
+            decoder,
+            idle,
+            consumed: None,
+            received: Instant::now(),
+            position: 0,
+        })
+    }
+
+    /// Acknowledges message consumed (and processed) previously.
+    fn ack(&mut self) -> Result<(), SimpleError> {
+        if let Some(message) = self.consumed.take().filter(|_| { self.acknowledged }) {
+            if let Err(e) = self.runtime.block_on(message.ack()) {
+                bail!("unable to acknowledge jetstream message({}), {}", self.position, e);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl SourceReader for NatsReader {
+    fn line(&self) -> usize {
+        self.position
+    }
+
+    fn offset(&self) -> Option<(String, i64)> {
+        let info = self.consumed.as_ref()?.info().ok()?;
+        Some((self.stream.clone(), info.stream_sequence as i64))
+    }
+
+    fn lag(&self) -> Option<u64> {
+        Some(self.consumed.as_ref()?.info().ok()?.pending)
+    }
+}
+
+impl Iterator for NatsReader {
+    type Item = Result<Command, SimpleError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Err(e) = self.ack() {
+            return Some(Err(e));
+        }
+        let message = loop {
+            let next = self.messages.next();
+            let message = match self.idle {
+                Some(idle) => {
+                    let remaining = idle.saturating_sub(self.received.elapsed());
+                    self.runtime.block_on(tokio::time::timeout(remaining, next)).ok()?
+                }
+                None => self.runtime.block_on(next),
+            };
+            // consumer errors (e.g. missed heartbeats) are retried by the client until idle
+            if let Ok(message) = message? {
+                break message;
+            }
+        };
+        self.received = Instant::now();
+        self.position += 1;
+        let command = self.decoder.decode(&message.payload);
+        self.consumed = Some(message);
+        Some(command)
+    }
+}
@@ -0,0 +1,68 @@
+//! Objects of Google Cloud Storage buckets and Azure Blob Storage containers streamed as transaction sources.
+//!
+//! Stores are configured from the environment (e.g. `GOOGLE_APPLICATION_CREDENTIALS`, `AZURE_STORAGE_ACCOUNT_NAME`)
+//! falling back to provider credential chains (application default credentials, managed identity).
+
+use std::io::{self, Read};
+
+use futures::StreamExt;
+use object_store::path::Path;
+use object_store::{ObjectStore, ObjectStoreExt};
+use simple_error::*;
+use tokio::runtime::Runtime;
+
+use crate::remote::ChunkReader;
+
+/// Client of an object store bucket (or container) blocking on requests.
+pub struct ObjectStoreClient {
+    runtime: Runtime,
+    store: Box<dyn ObjectStore>,
+}
+
+impl ObjectStoreClient {
+    /// Returns client of Google Cloud Storage `bucket`.
+    #[cfg(feature = "gcs")]
+    pub fn gcs(bucket: &str) -> Result<Self, SimpleError> {
+        let builder = object_store::gcp::GoogleCloudStorageBuilder::from_env().with_bucket_name(bucket);
+        Self::new(Box::new(try_with!(builder.build(), "unable to configure gcs bucket({})", bucket)))
+    }
+
+    /// Returns client of Azure Blob Storage `container`.
+    #[cfg(feature = "azure")]
+    pub fn azure(container: &str) -> Result<Self, SimpleError> {
+        let builder = object_store::azure::MicrosoftAzureBuilder::from_env().with_container_name(container);
+        Self::new(Box::new(try_with!(builder.build(), "unable to configure azure container({})", container)))
+    }
+
+    /// Returns client of `store`.
+    fn new(store: Box<dyn ObjectStore>) -> Result<Self, SimpleError> {
+        let runtime = try_with!(
+            tokio::runtime::Builder::new_current_thread().enable_all().build(),
+            "unable to start object store runtime"
+        );
+        Ok(ObjectStoreClient { runtime, store })
+    }
+
+    /// Returns reader streaming object `key`.
+    pub fn open(self, key: &str) -> Result<impl Read + Send, SimpleError> {
+        let result = self.runtime.block_on(self.store.get(&Path::from(key)));
+        let mut body = try_with!(result, "unable to get object({}/{})", self.store, key).into_stream();
+        let runtime = self.runtime;
+        Ok(ChunkReader::new(move || {
+            let chunk = runtime.block_on(body.next()).transpose().map_err(io::Error::other)?;
+            Ok(chunk.map(|chunk| { chunk.to_vec() }))
+        }))
+    }
+
+    /// Returns keys of objects starting with `prefix` in lexicographic order.
+    pub fn list(&self, prefix: &str) -> Result<Vec<String>, SimpleError> {
+        let prefix = Path::from(prefix);
+        let objects: Vec<_> = self.runtime.block_on(self.store.list(Some(&prefix)).collect());
+        let mut keys = vec![];
+        for object in objects {
+            keys.push(try_with!(object, "unable to list objects({}/{})", self.store, prefix).location.to_string());
+        }
+        keys.sort();
+        Ok(keys)
+    }
+}
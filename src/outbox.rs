@@ -0,0 +1,114 @@
+//! Transactional outbox of account events relayed to a destination once persisted.
+//!
+//! Persistent stores write events applied by `put` to their outbox within the same commit as the account, so an
+//! event is never published unless its account update persisted (nor persisted without eventually being published).
+//! The relay publishes pending events then marks them sent. Events published by a relay interrupted before marking
+//! them are published again; records carry the event idempotency key (and version) so consumers discard duplicates.
+
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+
+use simple_error::*;
+
+use crate::audit::AuditRecord;
+use crate::models::{ClientId, Event, Version};
+use crate::store::ProjectionStore;
+
+/// Events relayed per batch (published then marked sent).
+const RELAY_BATCH: usize = 1_000;
+
+/// Event of outbox pending publication.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutboxEntry {
+    pub client: ClientId,
+    pub version: Version,
+    pub event: Event,
+}
+
+/// Destination outbox events are published to.
+pub trait Publisher {
+    /// Publishes `entry` (possibly buffered until flushed).
+    fn publish(&mut self, entry: &OutboxEntry) -> Result<(), SimpleError>;
+
+    /// Completes publication of entries published.
+    fn flush(&mut self) -> Result<(), SimpleError>;
+}
+
+/// Publishes events as audit records (JSON Lines) appended to a file (synced when flushed).
+pub struct FilePublisher {
+    path: String,
+    writer: BufWriter<File>,
+}
+
+impl FilePublisher {
+    /// Returns new `FilePublisher` appending to file at `path` (created when missing).
+    pub fn open(path: &str) -> Result<Self, SimpleError> {
+        let file = try_with!(OpenOptions::new().create(true).append(true).open(path), "unable to open outbox destination({})", path);
+        Ok(FilePublisher { path: path.to_string(), writer: BufWriter::new(file) })
+    }
+}
+
+impl Publisher for FilePublisher {
+    fn publish(&mut self, entry: &OutboxEntry) -> Result<(), SimpleError> {
+        let record = AuditRecord::from_event(entry.client, &entry.event);
+        try_with!(serde_json::to_writer(&mut self.writer, &record), "unable to publish event to outbox destination({})", self.path);
+        try_with!(self.writer.write_all(b"\n"), "unable to publish event to outbox destination({})", self.path);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), SimpleError> {
+        try_with!(self.writer.flush(), "unable to flush outbox destination({})", self.path);
+        try_with!(self.writer.get_ref().sync_data(), "unable to sync outbox destination({})", self.path);
+        Ok(())
+    }
+}
+
+/// Relays events pending in outbox of `store` to `publisher` returning events published.
+///
+/// Events are published in batches (ordered by client and version) each marked sent once flushed.
+pub fn relay(store: &mut dyn ProjectionStore, publisher: &mut dyn Publisher) -> Result<usize, SimpleError> {
+    let mut published = 0;
+    loop {
+        let entries = store.outbox(RELAY_BATCH)?;
+        if entries.is_empty() {
+            return Ok(published);
+        }
+        for entry in entries.iter() {
+            publisher.publish(entry)?;
+        }
+        publisher.flush()?;
+        store.mark_sent(&entries)?;
+        store.flush()?;
+        published += entries.len();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use rust_decimal::prelude::Decimal;
+
+    use crate::events::Actor;
+    use crate::models::{Account, Command, CommandType};
+    use crate::testing::TempDir;
+
+    #[test]
+    fn events_appended_to_file() {
+        let events = Account::new(1).handle(Command::new(CommandType::Deposit, 1, 1, Some(Decimal::new(15, 1)))).unwrap();
+        let entry = OutboxEntry { client: 1, version: 1, event: events[0].clone() };
+        let directory = TempDir::new("outbox");
+        let path = directory.join("outbox.jsonl");
+        let path = path.to_str().unwrap();
+        for _ in 0..2 {
+            let mut publisher = FilePublisher::open(path).unwrap();
+            publisher.publish(&entry).unwrap();
+            publisher.flush().unwrap();
+        }
+        let lines: Vec<AuditRecord> = fs::read_to_string(path).unwrap()
+            .lines()
+            .map(|line| { serde_json::from_str(line).unwrap() })
+            .collect();
+        assert_eq!(lines, vec![AuditRecord::from_event(1, &entry.event); 2]);
+    }
+}
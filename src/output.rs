@@ -0,0 +1,609 @@
+//! Writers used to output account snapshots in supported formats.
+
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+use comfy_table::Table;
+use serde::Serialize;
+use serde_json::Value;
+use simple_error::*;
+
+use crate::models::Account;
+
+#[cfg(any(feature = "parquet", feature = "arrow"))]
+use crate::columnar::Rows;
+#[cfg(feature = "postgres")]
+use crate::postgres::{PostgresSink, PostgresTable};
+#[cfg(feature = "duckdb")]
+use crate::duckdb::DuckdbSink;
+
+/// Account rows written between flushes of output by default.
+pub const FLUSH_ROWS: usize = 8192;
+
+/// Format of written records.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    /// Comma separated values having a header row.
+    Csv,
+    /// Single JSON array of records.
+    Json,
+    /// JSON record per line (JSON Lines).
+    Jsonl,
+    /// Aligned table of records for terminals.
+    Table,
+    /// Apache Parquet file (requires `parquet` feature).
+    #[cfg(feature = "parquet")]
+    Parquet,
+    /// Apache Arrow IPC stream (requires `arrow` feature).
+    #[cfg(feature = "arrow")]
+    Arrow,
+}
+
+impl OutputFormat {
+    /// Returns names of supported formats used for cli arguments.
+    pub fn names() -> Vec<&'static str> {
+        #[allow(unused_mut)]
+        let mut names = vec!["csv", "json", "jsonl", "table"];
+        #[cfg(feature = "parquet")]
+        names.push("parquet");
+        #[cfg(feature = "arrow")]
+        names.push("arrow");
+        names
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = SimpleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(OutputFormat::Csv),
+            "json" => Ok(OutputFormat::Json),
+            "jsonl" => Ok(OutputFormat::Jsonl),
+            "table" => Ok(OutputFormat::Table),
+            #[cfg(feature = "parquet")]
+            "parquet" => Ok(OutputFormat::Parquet),
+            #[cfg(feature = "arrow")]
+            "arrow" => Ok(OutputFormat::Arrow),
+            _ => Err(SimpleError::new(format!("unsupported output format({})", s))),
+        }
+    }
+}
+
+/// Scheme of destinations written to tables of a SQLite database (e.g. `sqlite://accounts.db`).
+const SQLITE_SCHEME: &str = "sqlite://";
+
+/// Returns database path of SQLite `destination` (none when destination is a file).
+#[cfg(feature = "sqlite")]
+pub fn sqlite_path(destination: &str) -> Option<&str> {
+    destination.strip_prefix(SQLITE_SCHEME)
+}
+
+/// Scheme of destinations written to hashes of a Redis server (e.g. `redis://localhost:6379`).
+const REDIS_SCHEME: &str = "redis://";
+
+/// Number of records written to Redis per pipeline.
+#[cfg(feature = "redis")]
+const REDIS_PIPELINE_RECORDS: usize = 1000;
+
+/// Returns url of Redis `destination` (none when destination is not a redis url).
+#[cfg(feature = "redis")]
+pub fn redis_url(destination: &str) -> Option<&str> {
+    destination.starts_with(REDIS_SCHEME).then_some(destination)
+}
+
+/// Returns true when `destination` is a database url (e.g. `sqlite://` or `postgres://`) rather than a file.
+pub fn is_database(destination: &str) -> bool {
+    [SQLITE_SCHEME, "postgres://", "postgresql://", REDIS_SCHEME, "duckdb://"].iter().any(|scheme| { destination.starts_with(scheme) })
+}
+
+/// Returns path output `destination` is written to before renamed (atomically replacing `destination`).
+///
+/// Partial output is written alongside destination (same filesystem) so renaming is atomic.
+pub fn partial_path(destination: &str) -> String {
+    format!("{}.{}.partial", destination, std::process::id())
+}
+
+/// Key accounts are ordered by when written (ties ordered by client).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortKey {
+    Client,
+    Available,
+    Held,
+    Total,
+}
+
+impl SortKey {
+    /// Returns names of supported keys used for cli arguments.
+    pub fn names() -> Vec<&'static str> {
+        vec!["client", "available", "held", "total"]
+    }
+
+    /// Returns `accounts` (borrowed or owned) ordered (ascending) by key then client.
+    pub fn sort<A: Borrow<Account>, I: IntoIterator<Item = A>>(self, accounts: I) -> Vec<A> {
+        let mut accounts: Vec<A> = accounts.into_iter().collect();
+        accounts.sort_by(|a, b| {
+            let (a, b) = (a.borrow(), b.borrow());
+            let ordering = match self {
+                SortKey::Client => Ordering::Equal,
+                SortKey::Available => a.available().cmp(&b.available()),
+                SortKey::Held => a.held().cmp(&b.held()),
+                SortKey::Total => a.total().cmp(&b.total()),
+            };
+            ordering.then(a.client().cmp(&b.client()))
+        });
+        accounts
+    }
+}
+
+impl FromStr for SortKey {
+    type Err = SimpleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "client" => Ok(SortKey::Client),
+            "available" => Ok(SortKey::Available),
+            "held" => Ok(SortKey::Held),
+            "total" => Ok(SortKey::Total),
+            _ => Err(SimpleError::new(format!("unsupported sort key({})", s))),
+        }
+    }
+}
+
+/// Writes serializable records to `W` using an `OutputFormat`.
+///
+/// Table and columnar formats buffer records until finished while SQLite tables are replaced
+/// (and PostgreSQL tables upserted) when finished. Redis hashes are written in atomic pipelines. DuckDB tables are
+/// replaced by records staged until finished.
+pub enum RecordWriter<W: Write + Send> {
+    Csv(Box<csv::Writer<W>>),
+    Json { writer: W, records: usize },
+    Jsonl(W),
+    Table { writer: Option<W>, table: Box<Table> },
+    #[cfg(feature = "parquet")]
+    Parquet { writer: Option<W>, rows: Rows },
+    #[cfg(feature = "arrow")]
+    Arrow { writer: Option<W>, rows: Rows },
+    #[cfg(feature = "sqlite")]
+    Sqlite { connection: rusqlite::Connection, table: String, columns: Vec<String> },
+    #[cfg(feature = "postgres")]
+    Postgres { sink: Box<PostgresSink>, table: PostgresTable },
+    #[cfg(feature = "redis")]
+    Redis { connection: Box<redis::Connection>, pipeline: redis::Pipeline, records: usize },
+    #[cfg(feature = "duckdb")]
+    Duckdb(Box<DuckdbSink>),
+}
+
+impl<W: Write + Send> RecordWriter<W> {
+    /// Returns new `RecordWriter` writing `format` records to `writer` using csv field `delimiter`.
+    pub fn with_delimiter(format: OutputFormat, delimiter: u8, writer: W) -> Self {
+        match format {
+            OutputFormat::Csv => RecordWriter::Csv(Box::new(
+                csv::WriterBuilder::new().delimiter(delimiter).from_writer(writer)
+            )),
+            OutputFormat::Json => RecordWriter::Json { writer, records: 0 },
+            OutputFormat::Jsonl => RecordWriter::Jsonl(writer),
+            OutputFormat::Table => RecordWriter::Table { writer: Some(writer), table: Box::new(Table::new()) },
+            #[cfg(feature = "parquet")]
+            OutputFormat::Parquet => RecordWriter::Parquet { writer: Some(writer), rows: Rows::new() },
+            #[cfg(feature = "arrow")]
+            OutputFormat::Arrow => RecordWriter::Arrow { writer: Some(writer), rows: Rows::new() },
+        }
+    }
+
+    /// Returns new `RecordWriter` replacing `table` of SQLite database at `path` (columns named after record fields).
+    #[cfg(feature = "sqlite")]
+    pub fn sqlite(path: &str, table: &str) -> Result<Self, SimpleError> {
+        let connection = try_with!(rusqlite::Connection::open(path), "unable to open sqlite database({})", path);
+        // table is replaced atomically when finished
+        try_with!(
+            connection.execute_batch(&format!("BEGIN; DROP TABLE IF EXISTS \"{}\";", table)),
+            "unable to replace sqlite table({})",
+            table
+        );
+        Ok(RecordWriter::Sqlite { connection, table: table.to_string(), columns: vec![] })
+    }
+
+    /// Returns new `RecordWriter` writing to `table` of PostgreSQL database at `url`.
+    #[cfg(feature = "postgres")]
+    pub fn postgres(url: &str, table: PostgresTable) -> Result<Self, SimpleError> {
+        Ok(RecordWriter::Postgres { sink: Box::new(PostgresSink::connect(url)?), table })
+    }
+
+    /// Returns new `RecordWriter` replacing `table` of DuckDB database at `path` (columns named after record fields).
+    #[cfg(feature = "duckdb")]
+    pub fn duckdb(path: &str, table: &str) -> Result<Self, SimpleError> {
+        Ok(RecordWriter::Duckdb(Box::new(DuckdbSink::open(path, table)?)))
+    }
+
+    /// Returns new `RecordWriter` writing records to hashes (keyed by client) of Redis server at `url`.
+    #[cfg(feature = "redis")]
+    pub fn redis(url: &str) -> Result<Self, SimpleError> {
+        let client = try_with!(redis::Client::open(url), "invalid redis url({})", url);
+        let connection = try_with!(client.get_connection(), "unable to connect to redis({})", url);
+        // readers never see hashes replaced partially
+        let mut pipeline = redis::pipe();
+        pipeline.atomic();
+        Ok(RecordWriter::Redis { connection: Box::new(connection), pipeline, records: 0 })
+    }
+
+    /// Writes `record` to underlying writer.
+    pub fn serialize<T: Serialize>(&mut self, record: T) -> io::Result<()> {
+        match self {
+            RecordWriter::Csv(writer) => writer.serialize(record)?,
+            RecordWriter::Json { writer, records } => {
+                writer.write_all(if *records == 0 { b"[\n" } else { b",\n" })?;
+                serde_json::to_writer(&mut *writer, &record)?;
+                *records += 1;
+            }
+            RecordWriter::Jsonl(writer) => {
+                serde_json::to_writer(&mut *writer, &record)?;
+                writer.write_all(b"\n")?;
+            }
+            RecordWriter::Table { table, .. } => {
+                let record = match serde_json::to_value(record)? {
+                    Value::Object(record) => record,
+                    _ => return Err(io::Error::new(io::ErrorKind::InvalidData, "table records must be structs")),
+                };
+                // header is named after first record fields
+                if table.header().is_none() {
+                    table.set_header(record.keys());
+                }
+                table.add_row(record.values().map(cell));
+            }
+            #[cfg(feature = "parquet")]
+            RecordWriter::Parquet { rows, .. } => rows.push(record)?,
+            #[cfg(feature = "arrow")]
+            RecordWriter::Arrow { rows, .. } => rows.push(record)?,
+            #[cfg(feature = "sqlite")]
+            RecordWriter::Sqlite { connection, table, columns } => {
+                let record = match serde_json::to_value(record)? {
+                    Value::Object(record) => record,
+                    _ => return Err(io::Error::new(io::ErrorKind::InvalidData, "sqlite records must be structs")),
+                };
+                // table is created having columns named after first record fields
+                if columns.is_empty() {
+                    *columns = record.keys().cloned().collect();
+                    let definitions: Vec<String> = columns.iter().map(|column| { format!("\"{}\"", column) }).collect();
+                    let sql = format!("CREATE TABLE \"{}\" ({})", table, definitions.join(", "));
+                    connection.execute(&sql, []).map_err(sqlite_error)?;
+                }
+                let sql = format!("INSERT INTO \"{}\" VALUES ({})", table, vec!["?"; columns.len()].join(", "));
+                let values = columns.iter().map(|column| { sql_value(record.get(column).unwrap_or(&Value::Null)) });
+                let mut statement = connection.prepare_cached(&sql).map_err(sqlite_error)?;
+                statement.execute(rusqlite::params_from_iter(values)).map_err(sqlite_error)?;
+            }
+            #[cfg(feature = "redis")]
+            RecordWriter::Redis { connection, pipeline, records } => {
+                let (key, fields) = match serde_json::to_value(record)? {
+                    Value::Object(record) => redis_hash(&record)?,
+                    _ => return Err(io::Error::new(io::ErrorKind::InvalidData, "redis records must be structs")),
+                };
+                // hash is replaced so fields of previous snapshots are not kept
+                pipeline.del(&key).ignore().cmd("HSET").arg(&key).arg(fields).ignore();
+                *records += 1;
+                if *records % REDIS_PIPELINE_RECORDS == 0 {
+                    pipeline.query::<()>(connection.as_mut()).map_err(io::Error::other)?;
+                    pipeline.clear();
+                }
+            }
+            #[cfg(feature = "postgres")]
+            RecordWriter::Postgres { sink, table } => match serde_json::to_value(record)? {
+                Value::Object(record) => sink.write(*table, &record).map_err(io::Error::other)?,
+                _ => return Err(io::Error::new(io::ErrorKind::InvalidData, "postgres records must be structs")),
+            },
+            #[cfg(feature = "duckdb")]
+            RecordWriter::Duckdb(sink) => match serde_json::to_value(record)? {
+                Value::Object(record) => sink.write(&record).map_err(io::Error::other)?,
+                _ => return Err(io::Error::new(io::ErrorKind::InvalidData, "duckdb records must be structs")),
+            },
+        }
+        Ok(())
+    }
+
+    /// Flushes records written to underlying writer (table, columnar and database records are written once finished).
+    pub fn flush(&mut self) -> io::Result<()> {
+        match self {
+            RecordWriter::Csv(writer) => writer.flush(),
+            RecordWriter::Json { writer, .. } | RecordWriter::Jsonl(writer) => writer.flush(),
+            _ => Ok(()),
+        }
+    }
+
+    /// Completes output (closing JSON array) and flushes underlying writer.
+    pub fn finish(&mut self) -> io::Result<()> {
+        match self {
+            RecordWriter::Csv(writer) => writer.flush(),
+            RecordWriter::Json { writer, records } => {
+                writer.write_all(if *records == 0 { b"[]\n" } else { b"\n]\n" })?;
+                writer.flush()
+            }
+            RecordWriter::Jsonl(writer) => writer.flush(),
+            RecordWriter::Table { writer, table } => match writer.take() {
+                Some(mut writer) => {
+                    if table.header().is_some() {
+                        writeln!(writer, "{}", table)?;
+                    }
+                    writer.flush()
+                }
+                None => Ok(()),
+            },
+            #[cfg(feature = "parquet")]
+            RecordWriter::Parquet { writer, rows } => match writer.take() {
+                Some(writer) => rows.write_parquet(writer),
+                None => Ok(()),
+            },
+            #[cfg(feature = "arrow")]
+            RecordWriter::Arrow { writer, rows } => match writer.take() {
+                Some(writer) => rows.write_ipc(writer),
+                None => Ok(()),
+            },
+            #[cfg(feature = "sqlite")]
+            RecordWriter::Sqlite { connection, .. } => {
+                if !connection.is_autocommit() {
+                    connection.execute_batch("COMMIT").map_err(sqlite_error)?;
+                }
+                Ok(())
+            }
+            #[cfg(feature = "postgres")]
+            RecordWriter::Postgres { sink, .. } => sink.commit().map_err(io::Error::other),
+            #[cfg(feature = "redis")]
+            RecordWriter::Redis { connection, pipeline, .. } => {
+                pipeline.query::<()>(connection.as_mut()).map_err(io::Error::other)?;
+                pipeline.clear();
+                Ok(())
+            }
+            #[cfg(feature = "duckdb")]
+            RecordWriter::Duckdb(sink) => sink.commit().map_err(io::Error::other),
+        }
+    }
+}
+
+/// Returns table cell of record `value` (strings unquoted and nulls empty).
+fn cell(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(value) => value.clone(),
+        value => value.to_string(),
+    }
+}
+
+/// Returns SQLite value of record `value` (booleans as integers).
+#[cfg(feature = "sqlite")]
+fn sql_value(value: &Value) -> rusqlite::types::Value {
+    use rusqlite::types::Value as SqlValue;
+
+    match value {
+        Value::Null => SqlValue::Null,
+        Value::Bool(value) => SqlValue::Integer(*value as i64),
+        Value::Number(value) => match value.as_i64() {
+            Some(value) => SqlValue::Integer(value),
+            None => SqlValue::Real(value.as_f64().unwrap_or_default()),
+        },
+        Value::String(value) => SqlValue::Text(value.clone()),
+        value => SqlValue::Text(value.to_string()),
+    }
+}
+
+/// Returns key (`account:<client>` or `account:<client>:<wallet>`) and fields of Redis hash of `record`.
+///
+/// Null fields are omitted.
+#[cfg(feature = "redis")]
+fn redis_hash(record: &serde_json::Map<String, Value>) -> io::Result<(String, Vec<(String, String)>)> {
+    let client = match record.get("client") {
+        Some(client) => client.to_string(),
+        None => return Err(io::Error::new(io::ErrorKind::InvalidData, "redis records must have a client")),
+    };
+    let key = match record.get("wallet") {
+        Some(Value::String(wallet)) => format!("account:{}:{}", client, wallet),
+        _ => format!("account:{}", client),
+    };
+    let fields = record.iter()
+        .filter(|(_, value)| { !value.is_null() })
+        .map(|(name, value)| { (name.clone(), cell(value)) })
+        .collect();
+    Ok((key, fields))
+}
+
+/// Returns io error of SQLite error `e`.
+#[cfg(feature = "sqlite")]
+fn sqlite_error(e: rusqlite::Error) -> io::Error {
+    io::Error::other(e)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::testing::TempDir;
+
+    #[derive(Serialize)]
+    struct Record {
+        client: u16,
+        locked: bool,
+    }
+
+    fn write(format: OutputFormat, records: Vec<Record>) -> String {
+        let mut buffer = vec![];
+        {
+            let mut writer = RecordWriter::with_delimiter(format, b',', &mut buffer);
+            for record in records {
+                writer.serialize(record).unwrap();
+            }
+            writer.finish().unwrap();
+        }
+        String::from_utf8(buffer).unwrap()
+    }
+
+    #[test]
+    fn accounts_sorted_by_key_then_client() {
+        use crate::events::Actor;
+        use crate::models::{Command, CommandType};
+        use rust_decimal::prelude::Decimal;
+
+        let accounts: Vec<Account> = [(3, 5), (1, 10), (2, 5)].iter().map(|(client, amount)| {
+            let mut account = Account::new(*client);
+            let command = Command::new(CommandType::Deposit, *client, 1, Some(Decimal::new(*amount, 0)));
+            let events = account.handle(command).unwrap();
+            account.apply(events);
+            account
+        }).collect();
+        let clients = |key: SortKey| -> Vec<u16> { key.sort(accounts.iter()).iter().map(|a| { a.client() }).collect() };
+
+        assert_eq!(clients(SortKey::Client), vec![1, 2, 3]);
+        assert_eq!(clients(SortKey::Total), vec![2, 3, 1]);
+        // owned accounts (e.g. read from a persistent store) sorted without borrowing
+        let sorted: Vec<u16> = SortKey::Total.sort(accounts.clone()).iter().map(|a| { a.client() }).collect();
+        assert_eq!(sorted, vec![2, 3, 1]);
+    }
+
+    #[test]
+    fn streamed_records_flushed() {
+        let directory = TempDir::new("output-flush");
+        let path = directory.join("accounts.csv");
+        for (format, flushed) in [(OutputFormat::Csv, "client,locked\n1,false\n"), (OutputFormat::Table, "")] {
+            let file = io::BufWriter::new(std::fs::File::create(&path).unwrap());
+            let mut writer = RecordWriter::with_delimiter(format, b',', file);
+            writer.serialize(Record { client: 1, locked: false }).unwrap();
+            writer.flush().unwrap();
+
+            assert_eq!(std::fs::read_to_string(&path).unwrap(), flushed);
+            writer.finish().unwrap();
+        }
+    }
+
+    #[test]
+    fn csv_records_written() {
+        let output = write(OutputFormat::Csv, vec![Record { client: 1, locked: false }]);
+
+        assert_eq!(output, "client,locked\n1,false\n");
+    }
+
+    #[test]
+    fn tsv_records_written() {
+        let mut buffer = vec![];
+        {
+            let mut writer = RecordWriter::with_delimiter(OutputFormat::Csv, b'\t', &mut buffer);
+            writer.serialize(Record { client: 1, locked: false }).unwrap();
+            writer.finish().unwrap();
+        }
+
+        assert_eq!(String::from_utf8(buffer).unwrap(), "client\tlocked\n1\tfalse\n");
+    }
+
+    #[test]
+    fn json_records_written() {
+        let output = write(OutputFormat::Json, vec![
+            Record { client: 1, locked: false },
+            Record { client: 2, locked: true },
+        ]);
+
+        assert_eq!(output, "[\n{\"client\":1,\"locked\":false},\n{\"client\":2,\"locked\":true}\n]\n");
+        assert_eq!(write(OutputFormat::Json, vec![]), "[]\n");
+    }
+
+    #[test]
+    fn table_records_written() {
+        let output = write(OutputFormat::Table, vec![
+            Record { client: 1, locked: false },
+            Record { client: 22, locked: true },
+        ]);
+        let lines: Vec<&str> = output.lines().collect();
+
+        assert_eq!(lines.len(), 7);
+        assert!(lines[1].contains("client") && lines[1].contains("locked"));
+        assert!(lines[3].contains(" 1 ") && lines[3].contains(" false "));
+        assert!(lines[5].contains(" 22 ") && lines[5].contains(" true "));
+        assert!(lines.iter().all(|line| { line.chars().count() == lines[0].chars().count() }));
+        assert_eq!(write(OutputFormat::Table, vec![]), "");
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn sqlite_records_replace_table() {
+        let directory = TempDir::new("output-sqlite");
+        let path = directory.join("accounts.db");
+        let path = path.to_str().unwrap();
+        for records in [vec![Record { client: 9, locked: true }], vec![Record { client: 1, locked: false }, Record { client: 2, locked: true }]] {
+            let mut writer: RecordWriter<Vec<u8>> = RecordWriter::sqlite(path, "accounts").unwrap();
+            for record in records {
+                writer.serialize(record).unwrap();
+            }
+            writer.finish().unwrap();
+        }
+        let connection = rusqlite::Connection::open(path).unwrap();
+        let mut statement = connection.prepare("SELECT client, locked FROM accounts ORDER BY client").unwrap();
+        let rows: Vec<(i64, bool)> = statement.query_map([], |row| { Ok((row.get(0)?, row.get(1)?)) })
+            .unwrap()
+            .map(|row| { row.unwrap() })
+            .collect();
+
+        assert_eq!(rows, vec![(1, false), (2, true)]);
+    }
+
+    #[cfg(feature = "redis")]
+    #[test]
+    fn redis_hashes_keyed_by_client() {
+        let record = serde_json::json!({ "client": 7, "wallet": "savings", "available": "1.5", "held": null, "locked": false });
+        let (key, fields) = redis_hash(record.as_object().unwrap()).unwrap();
+
+        assert_eq!(key, "account:7:savings");
+        assert_eq!(fields, vec![
+            (String::from("client"), String::from("7")),
+            (String::from("wallet"), String::from("savings")),
+            (String::from("available"), String::from("1.5")),
+            (String::from("locked"), String::from("false")),
+        ]);
+    }
+
+    #[test]
+    fn jsonl_records_written() {
+        let output = write(OutputFormat::Jsonl, vec![
+            Record { client: 1, locked: false },
+            Record { client: 2, locked: true },
+        ]);
+
+        assert_eq!(output, "{\"client\":1,\"locked\":false}\n{\"client\":2,\"locked\":true}\n");
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn parquet_records_written() {
+        use parquet::file::reader::{FileReader, SerializedFileReader};
+
+        let directory = TempDir::new("output-parquet");
+        let path = directory.join("accounts.parquet");
+        {
+            let mut writer = RecordWriter::with_delimiter(OutputFormat::Parquet, b',', std::fs::File::create(&path).unwrap());
+            writer.serialize(Record { client: 1, locked: false }).unwrap();
+            writer.serialize(Record { client: 2, locked: true }).unwrap();
+            writer.finish().unwrap();
+        }
+        let reader = SerializedFileReader::new(std::fs::File::open(&path).unwrap()).unwrap();
+
+        assert_eq!(reader.metadata().file_metadata().num_rows(), 2);
+        assert_eq!(reader.metadata().file_metadata().schema_descr().column(0).name(), "client");
+    }
+
+    #[cfg(feature = "arrow")]
+    #[test]
+    fn arrow_records_written() {
+        use arrow_ipc::reader::StreamReader;
+
+        let mut buffer = vec![];
+        {
+            let mut writer = RecordWriter::with_delimiter(OutputFormat::Arrow, b',', &mut buffer);
+            writer.serialize(Record { client: 1, locked: false }).unwrap();
+            writer.serialize(Record { client: 2, locked: true }).unwrap();
+            writer.finish().unwrap();
+        }
+        let reader = StreamReader::try_new(buffer.as_slice(), None).unwrap();
+        let batches: Vec<_> = reader.map(|batch| { batch.unwrap() }).collect();
+
+        assert_eq!(batches.len(), 1);
+        assert_eq!(batches[0].num_rows(), 2);
+        assert_eq!(batches[0].schema().field(1).name(), "locked");
+    }
+}
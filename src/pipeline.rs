@@ -0,0 +1,170 @@
+//! Sources read (and records parsed) on a separate thread overlapping command handling.
+//!
+//! The parsing stage sends batches of commands (with their lines) over a bounded channel so reading, decompressing
+//! and deserializing records (often the bottleneck) proceeds while the processing loop handles commands. Parsing
+//! stops when the channel is full until the processing loop catches up. Capacity of the channel (records) is
+//! configurable (`--queue-capacity`) and its occupancy measured by `QueueMetrics`.
+
+use std::mem;
+use std::panic;
+use std::sync::Arc;
+use std::sync::mpsc::{self, Receiver, SyncSender, TryRecvError, TrySendError};
+use std::thread::{self, JoinHandle};
+use std::time::Instant;
+use std::vec;
+
+use simple_error::SimpleError;
+
+use crate::input::SourceReader;
+use crate::metrics::QueueMetrics;
+use crate::models::Command;
+
+/// Records parsed per batch sent to the processing loop (at most).
+pub const BATCH_RECORDS: usize = 256;
+
+/// Records queued before parsing waits by default.
+pub const QUEUE_CAPACITY: usize = 16_384;
+
+/// Command (or error of unparseable record) and line of source record.
+type Parsed = (Result<Command, SimpleError>, usize);
+
+/// Reads records of a `SourceReader` parsed on a separate thread.
+pub struct PipelineReader {
+    batches: Receiver<Vec<Parsed>>,
+    batch: vec::IntoIter<Parsed>,
+    line: usize,
+    parser: Option<JoinHandle<()>>,
+    queue: Arc<QueueMetrics>,
+}
+
+impl PipelineReader {
+    /// Returns new `PipelineReader` of `reader` (read on a new thread) queueing records up to capacity of `queue`.
+    pub fn spawn(mut reader: Box<dyn SourceReader + Send>, queue: Arc<QueueMetrics>) -> Self {
+        let (records, batches) = batches(queue.capacity());
+        let (sender, receiver) = mpsc::sync_channel(batches);
+        let metrics = queue.clone();
+        // records are parsed within the span of the stage reading them
+        let span = tracing::info_span!("parse");
+        let parser = thread::spawn(move || {
+            let _parse = span.entered();
+            let mut batch = Vec::with_capacity(records);
+            while let Some(result) = reader.next() {
+                batch.push((result, reader.line()));
+                if batch.len() == records {
+                    // processing loop stopped reading (reader dropped)
+                    if !send(&sender, mem::replace(&mut batch, Vec::with_capacity(records)), &metrics) {
+                        return;
+                    }
+                }
+            }
+            if !batch.is_empty() {
+                send(&sender, batch, &metrics);
+            }
+        });
+        PipelineReader { batches: receiver, batch: vec![].into_iter(), line: 0, parser: Some(parser), queue }
+    }
+}
+
+impl SourceReader for PipelineReader {
+    fn line(&self) -> usize {
+        self.line
+    }
+}
+
+impl Iterator for PipelineReader {
+    type Item = Result<Command, SimpleError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((result, line)) = self.batch.next() {
+                self.line = line;
+                self.queue.received(1);
+                return Some(result);
+            }
+            let received = match self.batches.try_recv() {
+                Err(TryRecvError::Empty) => {
+                    let started = Instant::now();
+                    let received = self.batches.recv().ok();
+                    self.queue.waited(started);
+                    received
+                }
+                received => received.ok(),
+            };
+            match received {
+                Some(batch) => self.batch = batch.into_iter(),
+                None => {
+                    self.queue.close();
+                    // panics of parsing thread (e.g. unreadable source) are raised by the processing loop
+                    if let Some(Err(e)) = self.parser.take().map(JoinHandle::join) {
+                        panic::resume_unwind(e);
+                    }
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+/// Sends `batch` to `sender` waiting while the queue is full (returns false once receiver is dropped).
+fn send(sender: &SyncSender<Vec<Parsed>>, batch: Vec<Parsed>, queue: &QueueMetrics) -> bool {
+    let records = batch.len();
+    let sent = match sender.try_send(batch) {
+        Err(TrySendError::Full(batch)) => {
+            let started = Instant::now();
+            let sent = sender.send(batch).is_ok();
+            queue.blocked(started);
+            sent
+        }
+        result => result.is_ok(),
+    };
+    if sent {
+        queue.sent(records);
+    }
+    sent
+}
+
+/// Returns records per batch and batches queued of a queue of `capacity` records (including the batch read by the
+/// processing loop).
+pub fn batches(capacity: usize) -> (usize, usize) {
+    let records = (capacity / 2).clamp(1, BATCH_RECORDS);
+    (records, (capacity / records).saturating_sub(1).max(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    use crate::input::{CsvCommandReader, csv_reader};
+
+    #[test]
+    fn records_read_in_order() {
+        let mut csv = String::from("type,client,tx,amount\n");
+        for tx in 1..=1000 {
+            match tx % 100 {
+                0 => csv.push_str("unknown,1,1,1.0\n"),
+                _ => csv.push_str(&format!("deposit,{},{},1.0\n", tx % 7, tx)),
+            }
+        }
+        let reader = CsvCommandReader::new(csv_reader(Cursor::new(csv), b',').unwrap()).unwrap();
+        let queue = Arc::new(QueueMetrics::new("read(tx.csv)", 100));
+        let mut reader = PipelineReader::spawn(Box::new(reader), queue.clone());
+        let mut txs = vec![];
+        let mut unparseable = vec![];
+        while let Some(result) = reader.next() {
+            match result {
+                Ok(command) => txs.push(command.tx()),
+                Err(_) => unparseable.push(reader.line()),
+            }
+        }
+        assert_eq!(txs, (1..=1000).filter(|tx| { tx % 100 != 0 }).collect::<Vec<u32>>());
+        assert_eq!(unparseable, (1..=10).map(|n| { n * 100 + 1 }).collect::<Vec<usize>>());
+        assert!(reader.next().is_none());
+        let stats = queue.stats();
+        assert_eq!((stats.queued, stats.sent), (0, 1000));
+        assert!(stats.peak <= 100);
+        assert_eq!(batches(100), (50, 1));
+        assert_eq!(batches(QUEUE_CAPACITY), (BATCH_RECORDS, 63));
+        assert!(queue.is_closed());
+    }
+}
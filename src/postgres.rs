@@ -0,0 +1,159 @@
+//! Sink of account snapshots and events into PostgreSQL tables (e.g. warehouse staging schema).
+//!
+//! Snapshots are upserted into `accounts` keyed by client while events are appended into `events`
+//! (skipping events already appended by idempotency key) within a transaction committed when finished.
+
+use serde_json::{Map, Value};
+use simple_error::*;
+use sqlx::{Connection, PgConnection};
+use tokio::runtime::Runtime;
+
+/// Schemes of destinations written to tables of a PostgreSQL database.
+const SCHEMES: [&str; 2] = ["postgres://", "postgresql://"];
+
+/// Tables created when missing.
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS accounts (
+        client INTEGER PRIMARY KEY,
+        available NUMERIC NOT NULL,
+        held NUMERIC NOT NULL,
+        total NUMERIC NOT NULL,
+        locked BOOLEAN NOT NULL,
+        updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+    );
+    CREATE TABLE IF NOT EXISTS events (
+        client INTEGER NOT NULL,
+        event TEXT NOT NULL,
+        version INTEGER NOT NULL,
+        key UUID NOT NULL,
+        timestamp TIMESTAMPTZ,
+        tx BIGINT,
+        wallet TEXT,
+        merchant INTEGER,
+        category TEXT,
+        amount NUMERIC,
+        PRIMARY KEY (client, event, key)
+    );
+";
+
+/// Upsert of account snapshot balances.
+const UPSERT_ACCOUNT: &str = "
+    INSERT INTO accounts (client, available, held, total, locked)
+    VALUES ($1::integer, $2::numeric, $3::numeric, $4::numeric, $5::boolean)
+    ON CONFLICT (client) DO UPDATE SET
+        available = excluded.available,
+        held = excluded.held,
+        total = excluded.total,
+        locked = excluded.locked,
+        updated_at = now()
+";
+
+/// Append of audit record event.
+const APPEND_EVENT: &str = "
+    INSERT INTO events (client, event, version, key, timestamp, tx, wallet, merchant, category, amount)
+    VALUES ($1::integer, $2, $3::integer, $4::uuid, $5::timestamptz, $6::bigint, $7, $8::integer, $9, $10::numeric)
+    ON CONFLICT DO NOTHING
+";
+
+/// Table records are written to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PostgresTable {
+    /// Account snapshots (client and balances).
+    Accounts,
+    /// Audit records of events.
+    Events,
+}
+
+impl PostgresTable {
+    /// Returns columns of table bound to statement parameters in order.
+    fn columns(self) -> &'static [&'static str] {
+        match self {
+            PostgresTable::Accounts => &["client", "available", "held", "total", "locked"],
+            PostgresTable::Events => &["client", "event", "version", "key", "timestamp", "tx", "wallet", "merchant", "category", "amount"],
+        }
+    }
+
+    /// Returns statement writing a record to table.
+    fn statement(self) -> &'static str {
+        match self {
+            PostgresTable::Accounts => UPSERT_ACCOUNT,
+            PostgresTable::Events => APPEND_EVENT,
+        }
+    }
+}
+
+/// Returns database url of PostgreSQL `destination` (none when destination is not a postgres url).
+pub fn database_url(destination: &str) -> Option<&str> {
+    SCHEMES.iter().any(|scheme| { destination.starts_with(scheme) }).then_some(destination)
+}
+
+/// Writes records to PostgreSQL tables within a transaction.
+pub struct PostgresSink {
+    runtime: Runtime,
+    connection: PgConnection,
+}
+
+impl PostgresSink {
+    /// Returns new `PostgresSink` connected to database at `url` (creating missing tables).
+    pub fn connect(url: &str) -> Result<Self, SimpleError> {
+        let runtime = try_with!(
+            tokio::runtime::Builder::new_current_thread().enable_all().build(),
+            "unable to start postgres runtime"
+        );
+        let mut connection = try_with!(runtime.block_on(PgConnection::connect(url)), "unable to connect to postgres");
+        try_with!(
+            runtime.block_on(sqlx::raw_sql(SCHEMA).execute(&mut connection)),
+            "unable to create postgres tables"
+        );
+        try_with!(runtime.block_on(sqlx::raw_sql("BEGIN").execute(&mut connection)), "unable to begin postgres transaction");
+        Ok(PostgresSink { runtime, connection })
+    }
+
+    /// Writes `record` (having columns of `table`) to `table`.
+    pub fn write(&mut self, table: PostgresTable, record: &Map<String, Value>) -> Result<(), SimpleError> {
+        let mut query = sqlx::query(table.statement());
+        for column in table.columns() {
+            query = query.bind(text(record.get(*column).unwrap_or(&Value::Null)));
+        }
+        try_with!(self.runtime.block_on(query.execute(&mut self.connection)), "unable to write postgres {:?} record", table);
+        Ok(())
+    }
+
+    /// Commits records written.
+    pub fn commit(&mut self) -> Result<(), SimpleError> {
+        try_with!(
+            self.runtime.block_on(sqlx::raw_sql("COMMIT").execute(&mut self.connection)),
+            "unable to commit postgres transaction"
+        );
+        Ok(())
+    }
+}
+
+/// Returns text of record `value` bound to a statement parameter (cast by statement).
+fn text(value: &Value) -> Option<String> {
+    match value {
+        Value::Null => None,
+        Value::String(value) => Some(value.clone()),
+        value => Some(value.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn database_urls_matched() {
+        assert_eq!(database_url("postgres://localhost/staging"), Some("postgres://localhost/staging"));
+        assert_eq!(database_url("postgresql://localhost/staging"), Some("postgresql://localhost/staging"));
+        assert_eq!(database_url("accounts.csv"), None);
+    }
+
+    #[test]
+    fn values_bound_as_text() {
+        assert_eq!(text(&Value::Null), None);
+        assert_eq!(text(&Value::String(String::from("1.5"))), Some(String::from("1.5")));
+        assert_eq!(text(&Value::Bool(true)), Some(String::from("true")));
+        assert_eq!(text(&serde_json::json!(7)), Some(String::from("7")));
+    }
+}
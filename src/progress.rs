@@ -0,0 +1,124 @@
+//! Progress bar of runs drawn on stderr (bytes of sources read against their size, rows/sec and ETA) using `indicatif`.
+//!
+//! The bar stays hidden until a run has taken longer than `DRAW_DELAY` (so small files never show a bar), is redrawn
+//! every `DRAW_INTERVAL` and cleared once sources are handled. Bars are only drawn to terminals and only for local
+//! files read as streams (sizes known), so piped stderr, remote, streaming and xlsx sources never show one.
+//!
+//! Bytes are counted as read from files (before decompression) so progress of compressed sources is measured against
+//! their file size. Records are parsed ahead of being handled so bytes read lead rows handled by a queue at most.
+
+use std::fmt;
+use std::fs;
+use std::io::{self, IsTerminal, Read};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressState, ProgressStyle};
+
+use crate::input::SourceFormat;
+use crate::remote;
+
+/// Time between draws of the bar.
+const DRAW_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Time runs take before the bar is drawn.
+const DRAW_DELAY: Duration = Duration::from_secs(1);
+
+/// Template of the bar (`rows_per_sec` is rows read per second of run).
+const TEMPLATE: &str =
+    "[{bar:30}] {percent:>3}% {binary_bytes}/{binary_total_bytes} {binary_bytes_per_sec} {rows_per_sec} rows/s ETA {eta}";
+
+/// Progress of sources read by a run (cleared once dropped).
+pub struct Progress {
+    bar: ProgressBar,
+    rows: Arc<AtomicU64>,
+}
+
+impl Progress {
+    /// Returns `Progress` of `sources` drawn to stderr (none unless stderr is a terminal and every source is a local
+    /// file read as a stream).
+    pub fn start(sources: &[String]) -> Option<Self> {
+        if !io::stderr().is_terminal() {
+            return None;
+        }
+        let mut total = 0;
+        for source in sources {
+            if remote::is_remote(source) || SourceFormat::from_path(source) == SourceFormat::Xlsx {
+                return None;
+            }
+            let metadata = fs::metadata(source).ok().filter(|metadata| { metadata.is_file() })?;
+            total += metadata.len();
+        }
+        Some(Progress::new(total))
+    }
+
+    /// Returns `Progress` of `total` bytes (hidden until drawn by readers once `DRAW_DELAY` elapsed).
+    fn new(total: u64) -> Self {
+        let rows = Arc::new(AtomicU64::new(0));
+        let counted = rows.clone();
+        let style = ProgressStyle::with_template(TEMPLATE)
+            .expect("valid progress template")
+            .progress_chars("=> ")
+            .with_key("rows_per_sec", move |state: &ProgressState, writer: &mut dyn fmt::Write| {
+                let seconds = state.elapsed().as_secs_f64().max(f64::EPSILON);
+                write!(writer, "{}", (counted.load(Ordering::Relaxed) as f64 / seconds) as u64).ok();
+            });
+        let bar = ProgressBar::with_draw_target(Some(total), ProgressDrawTarget::hidden()).with_style(style);
+        Progress { bar, rows }
+    }
+
+    /// Returns `reader` of a source file counting bytes read.
+    pub fn reader<R: Read>(&self, reader: R) -> CountingReader<R> {
+        CountingReader { reader, bar: self.bar.clone() }
+    }
+
+    /// Counts a row read.
+    pub fn row(&self) {
+        self.rows.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl Drop for Progress {
+    /// Clears the bar (when drawn).
+    fn drop(&mut self) {
+        self.bar.finish_and_clear();
+    }
+}
+
+/// Reader of a source file counting bytes read by its `Progress`.
+pub struct CountingReader<R> {
+    reader: R,
+    bar: ProgressBar,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.reader.read(buf)?;
+        self.bar.inc(read as u64);
+        // bar is drawn (and redrawn while reading stalls) once runs take longer than the delay
+        if self.bar.is_hidden() && self.bar.elapsed() >= DRAW_DELAY && io::stderr().is_terminal() {
+            self.bar.set_draw_target(ProgressDrawTarget::stderr());
+            self.bar.enable_steady_tick(DRAW_INTERVAL);
+        }
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bytes_read_counted_by_bar() {
+        let progress = Progress::new(12);
+        let mut content = String::new();
+        progress.reader("type,client\n".as_bytes()).read_to_string(&mut content).unwrap();
+        progress.row();
+
+        assert_eq!(progress.bar.position(), 12);
+        assert_eq!(progress.bar.length(), Some(12));
+        assert_eq!(progress.rows.load(Ordering::Relaxed), 1);
+        assert!(progress.bar.is_hidden());
+    }
+}
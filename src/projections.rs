@@ -0,0 +1,610 @@
+//! Read models projected from `Account` event streams.
+//!
+//! Projections are decoupled from aggregates and only observe applied events (see CQRS).
+
+use std::fmt;
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+use chrono::{TimeZone, Utc};
+
+use crate::models::{Account, Event, Category, ClientId, TransactionId, Currency, Timestamp, Version};
+
+/// Row of the running balance history read model.
+///
+/// Captures a balance-affecting event along with the resulting `Account` balances.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct HistoryRecord {
+    client: ClientId,
+    tx: TransactionId,
+    event: &'static str,
+    amount: Currency,
+    available: Currency,
+    held: Currency,
+    total: Currency,
+}
+
+impl HistoryRecord {
+    /// Returns `HistoryRecord` for `event` applied to `account` when event affects balances.
+    ///
+    /// Balances are read from `account` thus must be called after `event` has been applied.
+    pub fn from_event(account: &Account, event: &Event) -> Option<Self> {
+        let (tx, amount) = event.transaction()?;
+        Some(HistoryRecord {
+            client: account.client(),
+            tx,
+            event: event.name(),
+            amount,
+            available: account.available(),
+            held: account.held(),
+            total: account.total(),
+        })
+    }
+}
+
+/// Returns start of window having `length` seconds containing `timestamp`.
+///
+/// Windows are aligned to unix epoch (e.g. day windows start at midnight UTC).
+pub fn window_start(timestamp: Timestamp, length: i64) -> Timestamp {
+    let seconds = timestamp.timestamp();
+    Utc.timestamp_opt(seconds - seconds.rem_euclid(length), 0).unwrap()
+}
+
+/// Row of the windowed account snapshots read model.
+///
+/// Captures `Account` balances at the end of the window starting at `window`.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct WindowSnapshot {
+    window: Timestamp,
+    client: ClientId,
+    available: Currency,
+    held: Currency,
+    total: Currency,
+    locked: bool,
+}
+
+impl WindowSnapshot {
+    /// Returns `WindowSnapshot` of `account` for window starting at `window`.
+    pub fn from_account(window: Timestamp, account: &Account) -> Self {
+        WindowSnapshot {
+            window,
+            client: account.client(),
+            available: account.available(),
+            held: account.held(),
+            total: account.total(),
+            locked: account.locked(),
+        }
+    }
+}
+
+/// Row of the extended account snapshot read model.
+///
+/// Captures `Account` balances along with activity counts of its event stream.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ExtendedSnapshot {
+    client: ClientId,
+    available: Currency,
+    held: Currency,
+    total: Currency,
+    locked: bool,
+    transactions: usize,
+    disputes: usize,
+    chargebacks: usize,
+    last_tx: Option<TransactionId>,
+    version: Version,
+}
+
+impl ExtendedSnapshot {
+    /// Returns `ExtendedSnapshot` of `account`.
+    ///
+    /// Transactions are deposits and withdrawals while disputes include those later resolved or charged back.
+    pub fn from_account(account: &Account) -> Self {
+        let mut snapshot = ExtendedSnapshot {
+            client: account.client(),
+            available: account.available(),
+            held: account.held(),
+            total: account.total(),
+            locked: account.locked(),
+            transactions: 0,
+            disputes: 0,
+            chargebacks: 0,
+            last_tx: None,
+            version: account.version(),
+        };
+        for event in account.events() {
+            match event {
+                Event::Credited { tx, .. } | Event::Debited { tx, .. } => {
+                    snapshot.transactions += 1;
+                    snapshot.last_tx = Some(*tx);
+                }
+                Event::Held { .. } => snapshot.disputes += 1,
+                Event::Reversed { .. } => snapshot.chargebacks += 1,
+                Event::Released { .. } | Event::Locked { .. } => {}
+            }
+        }
+        snapshot
+    }
+}
+
+/// Row of the monthly aggregation read model.
+///
+/// Totals of balance-affecting events for a `client` during a `month` (YYYY-MM).
+/// Events without a timestamp are totaled using an empty `month`.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct MonthlyRecord {
+    client: ClientId,
+    month: String,
+    deposits: Currency,
+    withdrawals: Currency,
+    disputes: Currency,
+    chargebacks: Currency,
+    net: Currency,
+}
+
+/// Monthly aggregation read model keyed by client and month.
+#[derive(Debug, Clone, Default)]
+pub struct MonthlyTotals {
+    records: BTreeMap<(ClientId, String), MonthlyRecord>,
+}
+
+impl MonthlyTotals {
+    /// Returns new empty `MonthlyTotals`.
+    pub fn new() -> Self {
+        MonthlyTotals { records: BTreeMap::new() }
+    }
+
+    /// Projects `event` applied to `client` account into totals of month event occurred.
+    pub fn project(&mut self, client: ClientId, event: &Event) {
+        let (_, amount) = match event.transaction() {
+            Some(transaction) => transaction,
+            None => return,
+        };
+        let month = event.timestamp().map_or(String::new(), |t| { t.format("%Y-%m").to_string() });
+        let record = self.records.entry((client, month.clone())).or_insert_with(|| {
+            MonthlyRecord {
+                client,
+                month,
+                deposits: Currency::new(0, 4),
+                withdrawals: Currency::new(0, 4),
+                disputes: Currency::new(0, 4),
+                chargebacks: Currency::new(0, 4),
+                net: Currency::new(0, 4),
+            }
+        });
+        match event {
+            Event::Credited { .. } => record.deposits += amount,
+            Event::Debited { .. } => record.withdrawals += amount,
+            Event::Held { .. } => record.disputes += amount,
+            Event::Reversed { .. } => record.chargebacks += amount,
+            _ => {}
+        }
+        record.net += total_change(event);
+    }
+
+    /// Returns monthly records ordered by client and month.
+    pub fn records(&self) -> impl Iterator<Item = &MonthlyRecord> {
+        self.records.values()
+    }
+}
+
+/// Row of the category (tag) spend breakdown read model.
+///
+/// Totals of deposits and withdrawals for a `client` tagged with a `category`.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct CategoryRecord {
+    client: ClientId,
+    category: Category,
+    deposits: Currency,
+    withdrawals: Currency,
+    net: Currency,
+}
+
+/// Category spend breakdown read model keyed by client and category.
+///
+/// Deposits and withdrawals without a category are not projected.
+#[derive(Debug, Clone, Default)]
+pub struct CategoryTotals {
+    records: BTreeMap<(ClientId, Category), CategoryRecord>,
+}
+
+impl CategoryTotals {
+    /// Returns new empty `CategoryTotals`.
+    pub fn new() -> Self {
+        CategoryTotals { records: BTreeMap::new() }
+    }
+
+    /// Projects `event` applied to `client` account into totals of event category.
+    pub fn project(&mut self, client: ClientId, event: &Event) {
+        let (category, amount) = match (event.category(), event.transaction()) {
+            (Some(category), Some((_, amount))) => (category, amount),
+            _ => return,
+        };
+        let record = self.records.entry((client, category.clone())).or_insert_with(|| {
+            CategoryRecord {
+                client,
+                category: category.clone(),
+                deposits: Currency::new(0, 4),
+                withdrawals: Currency::new(0, 4),
+                net: Currency::new(0, 4),
+            }
+        });
+        match event {
+            Event::Credited { .. } => record.deposits += amount,
+            Event::Debited { .. } => record.withdrawals += amount,
+            _ => {}
+        }
+        record.net = record.deposits - record.withdrawals;
+    }
+
+    /// Returns category records ordered by client and category.
+    pub fn records(&self) -> impl Iterator<Item = &CategoryRecord> {
+        self.records.values()
+    }
+}
+
+/// Returns change of `total` balance caused by `event`.
+fn total_change(event: &Event) -> Currency {
+    match event {
+        Event::Credited { amount, .. } => *amount,
+        Event::Debited { amount, .. } |
+        Event::Reversed { amount, .. } => -*amount,
+        _ => Currency::new(0, 4),
+    }
+}
+
+/// Line of a client `Statement`.
+///
+/// Opening and closing balances are lines without transaction id(`tx`) or `amount`.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct StatementLine {
+    client: ClientId,
+    timestamp: Option<Timestamp>,
+    tx: Option<TransactionId>,
+    event: &'static str,
+    amount: Option<Currency>,
+    #[serde(skip)]
+    change: Currency,
+    balance: Currency,
+}
+
+impl StatementLine {
+    /// Returns time event occurred (or statement bound of opening and closing lines).
+    pub fn timestamp(&self) -> Option<Timestamp> { self.timestamp }
+
+    /// Returns transaction id of event (none for opening and closing lines).
+    pub fn tx(&self) -> Option<TransactionId> { self.tx }
+
+    /// Returns lowercase name of event (or `opening` / `closing`).
+    pub fn event(&self) -> &'static str { self.event }
+
+    /// Returns signed change of total balance caused by event.
+    pub fn change(&self) -> Currency { self.change }
+}
+
+/// Per-client statement itemizing balance-affecting events within a date range.
+///
+/// Range includes `from` and excludes `to`. Events without a timestamp are treated as
+/// occurring before any dated event.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Statement {
+    client: ClientId,
+    from: Option<Timestamp>,
+    to: Option<Timestamp>,
+    opening: Currency,
+    lines: Vec<StatementLine>,
+    closing: Currency,
+}
+
+impl Statement {
+    /// Returns `Statement` for `account` events within `from` and `to` range (unbounded when none).
+    pub fn from_account(account: &Account, from: Option<Timestamp>, to: Option<Timestamp>) -> Self {
+        let client = account.client();
+        let mut opening = Currency::new(0, 4);
+        let mut balance = Currency::new(0, 4);
+        let mut lines = vec![];
+        for event in account.events() {
+            let (tx, amount) = match event.transaction() {
+                Some(transaction) => transaction,
+                None => continue,
+            };
+            let timestamp = event.timestamp();
+            let before = match (from, timestamp) {
+                (Some(from), Some(timestamp)) => timestamp < from,
+                (Some(_), None) => true,
+                (None, _) => false,
+            };
+            let after = match (to, timestamp) {
+                (Some(to), Some(timestamp)) => timestamp >= to,
+                _ => false,
+            };
+            if before {
+                opening += total_change(event);
+                balance = opening;
+            } else if !after {
+                balance += total_change(event);
+                lines.push(StatementLine {
+                    client,
+                    timestamp,
+                    tx: Some(tx),
+                    event: event.name(),
+                    amount: Some(amount),
+                    change: total_change(event),
+                    balance,
+                });
+            }
+        }
+        Statement { client, from, to, opening, lines, closing: balance }
+    }
+
+    /// Returns client of statement.
+    pub fn client(&self) -> ClientId { self.client }
+
+    /// Returns start of statement period (unbounded when none).
+    pub fn from(&self) -> Option<Timestamp> { self.from }
+
+    /// Returns end (excluded) of statement period (unbounded when none).
+    pub fn to(&self) -> Option<Timestamp> { self.to }
+
+    /// Returns total balance at start of statement period.
+    pub fn opening(&self) -> Currency { self.opening }
+
+    /// Returns total balance at end of statement period.
+    pub fn closing(&self) -> Currency { self.closing }
+
+    /// Returns statement lines of events within period (excluding opening and closing balances).
+    pub fn transactions(&self) -> &[StatementLine] { &self.lines }
+
+    /// Returns statement lines including opening and closing balances.
+    pub fn lines(&self) -> Vec<StatementLine> {
+        let mut lines = Vec::with_capacity(self.lines.len() + 2);
+        lines.push(StatementLine {
+            client: self.client,
+            timestamp: self.from,
+            tx: None,
+            event: "opening",
+            amount: None,
+            change: Currency::new(0, 4),
+            balance: self.opening,
+        });
+        lines.extend(self.lines.iter().cloned());
+        lines.push(StatementLine {
+            client: self.client,
+            timestamp: self.to,
+            tx: None,
+            event: "closing",
+            amount: None,
+            change: Currency::new(0, 4),
+            balance: self.closing,
+        });
+        lines
+    }
+}
+
+impl fmt::Display for Statement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bound = |timestamp: Option<Timestamp>| {
+            timestamp.map_or(String::from("-"), |t| { t.to_rfc3339() })
+        };
+        writeln!(f, "Statement for client {} ({} to {})", self.client, bound(self.from), bound(self.to))?;
+        writeln!(f, "{:<26} {:<10} {:>10} {:>16} {:>16}", "Date", "Type", "Tx", "Amount", "Balance")?;
+        writeln!(f, "{:<26} {:<10} {:>10} {:>16} {:>16}", "", "opening", "", "", self.opening)?;
+        for line in &self.lines {
+            writeln!(
+                f,
+                "{:<26} {:<10} {:>10} {:>16} {:>16}",
+                bound(line.timestamp),
+                line.event,
+                line.tx.map_or(String::new(), |tx| { tx.to_string() }),
+                line.amount.map_or(String::new(), |amount| { amount.to_string() }),
+                line.balance
+            )?;
+        }
+        writeln!(f, "{:<26} {:<10} {:>10} {:>16} {:>16}", "", "closing", "", "", self.closing)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::prelude::Decimal;
+    use crate::events::Actor;
+
+    #[test]
+    fn history_record_for_balance_event() {
+        let mut account = Account::new(1);
+        let event = Event::Credited {
+            version: 1,
+            key: [0; 16],
+            timestamp: None,
+            tx: 10,
+            wallet: String::from("main"),
+            merchant: None,
+            category: None,
+            amount: Decimal::new(990000, 4)
+        };
+        account.apply([event.clone()]);
+        let record = HistoryRecord::from_event(&account, &event).unwrap();
+
+        assert_eq!(record.client, 1);
+        assert_eq!(record.tx, 10);
+        assert_eq!(record.event, "credited");
+        assert_eq!(record.amount, Decimal::new(990000, 4));
+        assert_eq!(record.available, Decimal::new(990000, 4));
+        assert_eq!(record.held, Decimal::new(0, 4));
+        assert_eq!(record.total, Decimal::new(990000, 4));
+    }
+
+    #[test]
+    fn extended_snapshot_counts_activity() {
+        let mut account = Account::new(1);
+        account.apply([
+            Event::Credited {
+                version: 1,
+                key: [1; 16],
+                timestamp: None,
+                tx: 10,
+                wallet: String::from("main"),
+                merchant: None,
+                category: None,
+                amount: Decimal::new(990000, 4)
+            },
+            Event::Held { version: 1, key: [1; 16], timestamp: None, tx: 10, wallet: String::from("main"), amount: Decimal::new(990000, 4) },
+            Event::Reversed {
+                version: 1,
+                key: [1; 16],
+                timestamp: None,
+                tx: 10,
+                wallet: String::from("main"),
+                merchant: None,
+                amount: Decimal::new(990000, 4)
+            },
+            Event::Locked { version: 1, key: [2; 16], timestamp: None },
+        ]);
+        let snapshot = ExtendedSnapshot::from_account(&account);
+
+        assert_eq!(snapshot.transactions, 1);
+        assert_eq!(snapshot.disputes, 1);
+        assert_eq!(snapshot.chargebacks, 1);
+        assert_eq!(snapshot.last_tx, Some(10));
+        assert_eq!(snapshot.version, 4);
+        assert!(snapshot.locked);
+    }
+
+    #[test]
+    fn history_record_for_locked_event_none() {
+        let mut account = Account::new(1);
+        let event = Event::Locked { version: 1, key: [0; 16], timestamp: None };
+        account.apply([event.clone()]);
+
+        assert!(HistoryRecord::from_event(&account, &event).is_none());
+    }
+
+    #[test]
+    fn statement_within_range() {
+        let mut account = Account::new(1);
+        let day = |d: u32| { Some(Utc.with_ymd_and_hms(2024, 1, d, 0, 0, 0).unwrap()) };
+        account.apply([
+            Event::Credited {
+                version: 1,
+                key: [0; 16],
+                timestamp: day(1),
+                tx: 10,
+                wallet: String::from("main"),
+                merchant: None,
+                category: None,
+                amount: Decimal::new(990000, 4)
+            },
+            Event::Debited {
+                version: 1,
+                key: [1; 16],
+                timestamp: day(5),
+                tx: 11,
+                wallet: String::from("main"),
+                merchant: None,
+                category: None,
+                amount: Decimal::new(90000, 4)
+            },
+            Event::Credited {
+                version: 1,
+                key: [2; 16],
+                timestamp: day(20),
+                tx: 12,
+                wallet: String::from("main"),
+                merchant: None,
+                category: None,
+                amount: Decimal::new(10000, 4)
+            },
+        ]);
+        let statement = Statement::from_account(&account, day(2), day(10));
+        let lines = statement.lines();
+
+        assert_eq!(statement.opening, Decimal::new(990000, 4));
+        assert_eq!(statement.closing, Decimal::new(900000, 4));
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0].event, "opening");
+        assert_eq!(lines[1].tx, Some(11));
+        assert_eq!(lines[1].balance, Decimal::new(900000, 4));
+        assert_eq!(lines[2].event, "closing");
+    }
+
+    #[test]
+    fn window_start_aligned() {
+        let timestamp = Utc.with_ymd_and_hms(2024, 1, 5, 13, 45, 10).unwrap();
+
+        assert_eq!(window_start(timestamp, 86400), Utc.with_ymd_and_hms(2024, 1, 5, 0, 0, 0).unwrap());
+        assert_eq!(window_start(timestamp, 3600), Utc.with_ymd_and_hms(2024, 1, 5, 13, 0, 0).unwrap());
+    }
+
+    #[test]
+    fn monthly_totals_by_client_and_month() {
+        let month = |m: u32| { Some(Utc.with_ymd_and_hms(2024, m, 1, 0, 0, 0).unwrap()) };
+        let mut totals = MonthlyTotals::new();
+        totals.project(1, &Event::Credited {
+            version: 1,
+            key: [0; 16],
+            timestamp: month(1),
+            tx: 10,
+            wallet: String::from("main"),
+            merchant: None,
+            category: None,
+            amount: Decimal::new(990000, 4)
+        });
+        totals.project(1, &Event::Held {
+            version: 1,
+            key: [0; 16],
+            timestamp: month(2),
+            tx: 10,
+            wallet: String::from("main"),
+            amount: Decimal::new(990000, 4)
+        });
+        totals.project(1, &Event::Reversed {
+            version: 1,
+            key: [0; 16],
+            timestamp: month(2),
+            tx: 10,
+            wallet: String::from("main"),
+            merchant: None,
+            amount: Decimal::new(990000, 4)
+        });
+        totals.project(1, &Event::Locked { version: 1, key: [0; 16], timestamp: month(2) });
+        let records: Vec<&MonthlyRecord> = totals.records().collect();
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].month, "2024-01");
+        assert_eq!(records[0].deposits, Decimal::new(990000, 4));
+        assert_eq!(records[0].net, Decimal::new(990000, 4));
+        assert_eq!(records[1].month, "2024-02");
+        assert_eq!(records[1].disputes, Decimal::new(990000, 4));
+        assert_eq!(records[1].chargebacks, Decimal::new(990000, 4));
+        assert_eq!(records[1].net, Decimal::new(-990000, 4));
+    }
+
+    #[test]
+    fn category_totals_by_client_and_category() {
+        let debited = |tx: TransactionId, category: Option<&str>, amount: Currency| {
+            Event::Debited {
+                version: 1,
+                key: [0; 16],
+                timestamp: None,
+                tx,
+                wallet: String::from("main"),
+                merchant: None,
+                category: category.map(String::from),
+                amount
+            }
+        };
+        let mut totals = CategoryTotals::new();
+        totals.project(1, &debited(10, Some("groceries"), Decimal::new(10000, 4)));
+        totals.project(1, &debited(11, Some("groceries"), Decimal::new(20000, 4)));
+        totals.project(1, &debited(12, Some("travel"), Decimal::new(30000, 4)));
+        totals.project(1, &debited(13, None, Decimal::new(40000, 4)));
+        let records: Vec<&CategoryRecord> = totals.records().collect();
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].category, "groceries");
+        assert_eq!(records[0].withdrawals, Decimal::new(30000, 4));
+        assert_eq!(records[0].net, Decimal::new(-30000, 4));
+        assert_eq!(records[1].category, "travel");
+        assert_eq!(records[1].withdrawals, Decimal::new(30000, 4));
+    }
+}
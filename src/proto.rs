@@ -0,0 +1,431 @@
+//! Protobuf messages (see `proto/accounts.proto`) of the event model and conversions from domain models.
+//!
+//! Messages are declared using prost derives mirroring the `.proto` definitions (no `protoc` needed to build).
+//! Amounts are decimal strings and timestamps are microseconds since the unix epoch.
+
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+use chrono::{TimeZone, Utc};
+use prost::{Enumeration, Message, Oneof};
+use simple_error::*;
+
+use crate::events::Cause;
+use crate::models::{self, Currency, IdempotencyKey, Timestamp};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Enumeration)]
+#[repr(i32)]
+pub enum CommandType {
+    Deposit = 0,
+    Withdraw = 1,
+    Dispute = 2,
+    Resolve = 3,
+    Chargeback = 4,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Command {
+    #[prost(enumeration = "CommandType", tag = "1")]
+    pub r#type: i32,
+    #[prost(uint32, tag = "2")]
+    pub client: u32,
+    #[prost(uint32, tag = "3")]
+    pub tx: u32,
+    #[prost(string, optional, tag = "4")]
+    pub amount: Option<String>,
+    #[prost(string, optional, tag = "5")]
+    pub wallet: Option<String>,
+    #[prost(uint32, optional, tag = "6")]
+    pub merchant: Option<u32>,
+    #[prost(int64, optional, tag = "7")]
+    pub timestamp: Option<i64>,
+    #[prost(string, optional, tag = "8")]
+    pub category: Option<String>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Credited {
+    #[prost(uint32, tag = "1")]
+    pub version: u32,
+    #[prost(bytes = "vec", tag = "2")]
+    pub key: Vec<u8>,
+    #[prost(int64, optional, tag = "3")]
+    pub timestamp: Option<i64>,
+    #[prost(uint32, tag = "4")]
+    pub tx: u32,
+    #[prost(string, tag = "5")]
+    pub wallet: String,
+    #[prost(uint32, optional, tag = "6")]
+    pub merchant: Option<u32>,
+    #[prost(string, optional, tag = "7")]
+    pub category: Option<String>,
+    #[prost(string, tag = "8")]
+    pub amount: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Debited {
+    #[prost(uint32, tag = "1")]
+    pub version: u32,
+    #[prost(bytes = "vec", tag = "2")]
+    pub key: Vec<u8>,
+    #[prost(int64, optional, tag = "3")]
+    pub timestamp: Option<i64>,
+    #[prost(uint32, tag = "4")]
+    pub tx: u32,
+    #[prost(string, tag = "5")]
+    pub wallet: String,
+    #[prost(uint32, optional, tag = "6")]
+    pub merchant: Option<u32>,
+    #[prost(string, optional, tag = "7")]
+    pub category: Option<String>,
+    #[prost(string, tag = "8")]
+    pub amount: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Held {
+    #[prost(uint32, tag = "1")]
+    pub version: u32,
+    #[prost(bytes = "vec", tag = "2")]
+    pub key: Vec<u8>,
+    #[prost(int64, optional, tag = "3")]
+    pub timestamp: Option<i64>,
+    #[prost(uint32, tag = "4")]
+    pub tx: u32,
+    #[prost(string, tag = "5")]
+    pub wallet: String,
+    #[prost(string, tag = "6")]
+    pub amount: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Released {
+    #[prost(uint32, tag = "1")]
+    pub version: u32,
+    #[prost(bytes = "vec", tag = "2")]
+    pub key: Vec<u8>,
+    #[prost(int64, optional, tag = "3")]
+    pub timestamp: Option<i64>,
+    #[prost(uint32, tag = "4")]
+    pub tx: u32,
+    #[prost(string, tag = "5")]
+    pub wallet: String,
+    #[prost(string, tag = "6")]
+    pub amount: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Reversed {
+    #[prost(uint32, tag = "1")]
+    pub version: u32,
+    #[prost(bytes = "vec", tag = "2")]
+    pub key: Vec<u8>,
+    #[prost(int64, optional, tag = "3")]
+    pub timestamp: Option<i64>,
+    #[prost(uint32, tag = "4")]
+    pub tx: u32,
+    #[prost(string, tag = "5")]
+    pub wallet: String,
+    #[prost(uint32, optional, tag = "6")]
+    pub merchant: Option<u32>,
+    #[prost(string, tag = "7")]
+    pub amount: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Locked {
+    #[prost(uint32, tag = "1")]
+    pub version: u32,
+    #[prost(bytes = "vec", tag = "2")]
+    pub key: Vec<u8>,
+    #[prost(int64, optional, tag = "3")]
+    pub timestamp: Option<i64>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Event {
+    #[prost(oneof = "Kind", tags = "1, 2, 3, 4, 5, 6")]
+    pub kind: Option<Kind>,
+}
+
+/// Type of `Event` message.
+#[derive(Clone, PartialEq, Oneof)]
+pub enum Kind {
+    #[prost(message, tag = "1")]
+    Credited(Credited),
+    #[prost(message, tag = "2")]
+    Debited(Debited),
+    #[prost(message, tag = "3")]
+    Held(Held),
+    #[prost(message, tag = "4")]
+    Released(Released),
+    #[prost(message, tag = "5")]
+    Reversed(Reversed),
+    #[prost(message, tag = "6")]
+    Locked(Locked),
+}
+
+/// Event log entry of an `Account` aggregate event.
+#[derive(Clone, PartialEq, Message)]
+pub struct AccountEvent {
+    #[prost(uint32, tag = "1")]
+    pub client: u32,
+    #[prost(message, optional, tag = "2")]
+    pub event: Option<Event>,
+}
+
+impl From<&models::Command> for Command {
+    fn from(command: &models::Command) -> Self {
+        let kind = match command.name() {
+            models::CommandType::Deposit => CommandType::Deposit,
+            models::CommandType::Withdraw => CommandType::Withdraw,
+            models::CommandType::Dispute => CommandType::Dispute,
+            models::CommandType::Resolve => CommandType::Resolve,
+            models::CommandType::Chargeback => CommandType::Chargeback,
+        };
+        Command {
+            r#type: kind as i32,
+            client: command.actor_id() as u32,
+            tx: command.tx(),
+            amount: command.amount().map(|amount| { amount.to_string() }),
+            wallet: command.wallet().cloned(),
+            merchant: command.merchant().map(u32::from),
+            timestamp: command.timestamp().map(to_micros),
+            category: command.category().cloned()
+        }
+    }
+}
+
+impl TryFrom<Command> for models::Command {
+    type Error = SimpleError;
+
+    fn try_from(command: Command) -> Result<Self, Self::Error> {
+        let name = match CommandType::try_from(command.r#type) {
+            Ok(CommandType::Deposit) => models::CommandType::Deposit,
+            Ok(CommandType::Withdraw) => models::CommandType::Withdraw,
+            Ok(CommandType::Dispute) => models::CommandType::Dispute,
+            Ok(CommandType::Resolve) => models::CommandType::Resolve,
+            Ok(CommandType::Chargeback) => models::CommandType::Chargeback,
+            Err(_) => bail!("unsupported command type({}) transaction({})", command.r#type, command.tx),
+        };
+        let amount = command.amount.as_deref().map(to_currency).transpose()?;
+        Ok(
+            models::Command::new(name, to_id(command.client)?, command.tx, amount)
+                .with_wallet(command.wallet)
+                .with_merchant(to_merchant(command.merchant)?)
+                .with_timestamp(to_timestamp(command.timestamp)?)
+                .with_category(command.category)
+        )
+    }
+}
+
+impl From<&models::Event> for Event {
+    fn from(event: &models::Event) -> Self {
+        let kind = match event.clone() {
+            models::Event::Credited { version, key, timestamp, tx, wallet, merchant, category, amount } => {
+                Kind::Credited(Credited {
+                    version,
+                    key: key.to_vec(),
+                    timestamp: timestamp.map(to_micros),
+                    tx,
+                    wallet,
+                    merchant: merchant.map(u32::from),
+                    category,
+                    amount: amount.to_string()
+                })
+            }
+            models::Event::Debited { version, key, timestamp, tx, wallet, merchant, category, amount } => {
+                Kind::Debited(Debited {
+                    version,
+                    key: key.to_vec(),
+                    timestamp: timestamp.map(to_micros),
+                    tx,
+                    wallet,
+                    merchant: merchant.map(u32::from),
+                    category,
+                    amount: amount.to_string()
+                })
+            }
+            models::Event::Held { version, key, timestamp, tx, wallet, amount } => {
+                Kind::Held(Held {
+                    version,
+                    key: key.to_vec(),
+                    timestamp: timestamp.map(to_micros),
+                    tx,
+                    wallet,
+                    amount: amount.to_string()
+                })
+            }
+            models::Event::Released { version, key, timestamp, tx, wallet, amount } => {
+                Kind::Released(Released {
+                    version,
+                    key: key.to_vec(),
+                    timestamp: timestamp.map(to_micros),
+                    tx,
+                    wallet,
+                    amount: amount.to_string()
+                })
+            }
+            models::Event::Reversed { version, key, timestamp, tx, wallet, merchant, amount } => {
+                Kind::Reversed(Reversed {
+                    version,
+                    key: key.to_vec(),
+                    timestamp: timestamp.map(to_micros),
+                    tx,
+                    wallet,
+                    merchant: merchant.map(u32::from),
+                    amount: amount.to_string()
+                })
+            }
+            models::Event::Locked { version, key, timestamp } => {
+                Kind::Locked(Locked {
+                    version,
+                    key: key.to_vec(),
+                    timestamp: timestamp.map(to_micros)
+                })
+            }
+        };
+        Event { kind: Some(kind) }
+    }
+}
+
+impl TryFrom<Event> for models::Event {
+    type Error = SimpleError;
+
+    fn try_from(event: Event) -> Result<Self, Self::Error> {
+        let event = match event.kind {
+            Some(Kind::Credited(e)) => models::Event::Credited {
+                version: e.version,
+                key: to_key(&e.key)?,
+                timestamp: to_timestamp(e.timestamp)?,
+                tx: e.tx,
+                wallet: e.wallet,
+                merchant: to_merchant(e.merchant)?,
+                category: e.category,
+                amount: to_currency(&e.amount)?
+            },
+            Some(Kind::Debited(e)) => models::Event::Debited {
+                version: e.version,
+                key: to_key(&e.key)?,
+                timestamp: to_timestamp(e.timestamp)?,
+                tx: e.tx,
+                wallet: e.wallet,
+                merchant: to_merchant(e.merchant)?,
+                category: e.category,
+                amount: to_currency(&e.amount)?
+            },
+            Some(Kind::Held(e)) => models::Event::Held {
+                version: e.version,
+                key: to_key(&e.key)?,
+                timestamp: to_timestamp(e.timestamp)?,
+                tx: e.tx,
+                wallet: e.wallet,
+                amount: to_currency(&e.amount)?
+            },
+            Some(Kind::Released(e)) => models::Event::Released {
+                version: e.version,
+                key: to_key(&e.key)?,
+                timestamp: to_timestamp(e.timestamp)?,
+                tx: e.tx,
+                wallet: e.wallet,
+                amount: to_currency(&e.amount)?
+            },
+            Some(Kind::Reversed(e)) => models::Event::Reversed {
+                version: e.version,
+                key: to_key(&e.key)?,
+                timestamp: to_timestamp(e.timestamp)?,
+                tx: e.tx,
+                wallet: e.wallet,
+                merchant: to_merchant(e.merchant)?,
+                amount: to_currency(&e.amount)?
+            },
+            Some(Kind::Locked(e)) => models::Event::Locked {
+                version: e.version,
+                key: to_key(&e.key)?,
+                timestamp: to_timestamp(e.timestamp)?
+            },
+            None => bail!("event kind is none"),
+        };
+        Ok(event)
+    }
+}
+
+/// Returns microseconds since unix epoch of `timestamp`.
+fn to_micros(timestamp: Timestamp) -> i64 {
+    timestamp.timestamp() * 1_000_000 + timestamp.timestamp_subsec_micros() as i64
+}
+
+/// Returns timestamp of microseconds since unix epoch.
+fn from_micros(micros: i64) -> Result<Timestamp, SimpleError> {
+    match Utc.timestamp_opt(micros.div_euclid(1_000_000), (micros.rem_euclid(1_000_000) * 1_000) as u32).single() {
+        Some(timestamp) => Ok(timestamp),
+        None => bail!("timestamp out of range({})", micros),
+    }
+}
+
+/// Returns optional timestamp of optional microseconds since unix epoch.
+fn to_timestamp(micros: Option<i64>) -> Result<Option<Timestamp>, SimpleError> {
+    micros.map(from_micros).transpose()
+}
+
+/// Returns client or merchant id of `id`.
+fn to_id(id: u32) -> Result<u16, SimpleError> {
+    Ok(try_with!(u16::try_from(id), "id out of range({})", id))
+}
+
+/// Returns optional merchant id of `merchant`.
+fn to_merchant(merchant: Option<u32>) -> Result<Option<u16>, SimpleError> {
+    merchant.map(to_id).transpose()
+}
+
+/// Returns currency parsed from decimal string `amount`.
+fn to_currency(amount: &str) -> Result<Currency, SimpleError> {
+    Ok(try_with!(Currency::from_str(amount), "invalid amount({})", amount))
+}
+
+/// Returns idempotency key of `bytes`.
+fn to_key(bytes: &[u8]) -> Result<IdempotencyKey, SimpleError> {
+    Ok(try_with!(IdempotencyKey::try_from(bytes), "idempotency key requires 16 bytes"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::prelude::Decimal;
+
+    #[test]
+    fn command_round_trip() {
+        let command = models::Command::new(models::CommandType::Withdraw, 1, 10, Some(Decimal::new(990000, 4)))
+            .with_wallet(Some(String::from("savings")))
+            .with_merchant(Some(7))
+            .with_timestamp(Some(Utc.timestamp_opt(1706695200, 0).unwrap()));
+
+        let bytes = Command::from(&command).encode_to_vec();
+        let decoded = models::Command::try_from(Command::decode(bytes.as_slice()).unwrap()).unwrap();
+
+        assert_eq!(decoded, command);
+    }
+
+    #[test]
+    fn events_round_trip() {
+        let events = vec![
+            models::Event::Reversed {
+                version: 3,
+                key: [7; 16],
+                timestamp: Some(Utc.timestamp_opt(1706695200, 500_000_000).unwrap()),
+                tx: 10,
+                wallet: String::from("main"),
+                merchant: Some(7),
+                amount: Decimal::new(990000, 4)
+            },
+            models::Event::Locked { version: 4, key: [1; 16], timestamp: None },
+        ];
+
+        for event in events {
+            let bytes = Event::from(&event).encode_to_vec();
+
+            assert_eq!(models::Event::try_from(Event::decode(bytes.as_slice()).unwrap()).unwrap(), event);
+        }
+    }
+}
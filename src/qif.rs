@@ -0,0 +1,177 @@
+//! Reader of QIF (Quicken Interchange Format) bank exports mapping entries to `Command`s.
+//!
+//! Entries are mapped to `Command`s of a single client (QIF files describe one account):
+//! - positive amounts (`T` or `U`) are deposits and negative amounts withdrawals
+//! - numeric check numbers (`N`) are transaction ids otherwise the entry position (1-based) is used
+//! - dates (`D`) are US formatted (`MM/DD/YYYY`, `MM/DD'YY` or `MM/DD/YY`) at midnight UTC
+//! - categories (`L`) are kept while payees, memos and splits are ignored
+
+use std::io::{BufRead, Lines};
+use std::str::FromStr;
+
+use chrono::{NaiveDate, TimeZone, Utc};
+use simple_error::*;
+
+use crate::input::SourceReader;
+use crate::models::{ClientId, Command, CommandType, Currency, Timestamp, TransactionId};
+
+/// Reads QIF entries from `R` as deposit or withdraw `Command`s of `client`.
+pub struct QifReader<R: BufRead> {
+    lines: Lines<R>,
+    client: ClientId,
+    entries: TransactionId,
+}
+
+/// Fields of a QIF entry being read.
+#[derive(Default)]
+struct Entry {
+    date: Option<Timestamp>,
+    amount: Option<Currency>,
+    number: Option<TransactionId>,
+    category: Option<String>,
+}
+
+impl<R: BufRead> QifReader<R> {
+    /// Returns new `QifReader` reading entries of `client` from `reader`.
+    pub fn new(reader: R, client: ClientId) -> Self {
+        QifReader { lines: reader.lines(), client, entries: 0 }
+    }
+
+    /// Returns command of next entry or none at end of source.
+    fn read_entry(&mut self) -> Result<Option<Command>, SimpleError> {
+        let mut entry = Entry::default();
+        let mut empty = true;
+        for line in self.lines.by_ref() {
+            let line = try_with!(line, "unable to read qif entry({})", self.entries + 1);
+            let line = line.trim();
+            // headers (e.g. !Type:Bank) and blank lines are not part of entries
+            if line.is_empty() || line.starts_with('!') {
+                continue;
+            }
+            let mut chars = line.chars();
+            let code = chars.next();
+            let value = chars.as_str().trim();
+            match code {
+                Some('^') => {
+                    if empty {
+                        continue;
+                    }
+                    self.entries += 1;
+                    return command(self.client, self.entries, entry).map(Some);
+                }
+                Some('D') => entry.date = Some(parse_date(value)?),
+                Some('T') | Some('U') => entry.amount = Some(parse_amount(value)?),
+                Some('N') => entry.number = value.parse().ok(),
+                Some('L') if !value.is_empty() => entry.category = Some(value.to_string()),
+                _ => {}
+            }
+            empty = false;
+        }
+        if empty {
+            return Ok(None);
+        }
+        // last entry without terminating caret
+        self.entries += 1;
+        command(self.client, self.entries, entry).map(Some)
+    }
+}
+
+impl<R: BufRead> SourceReader for QifReader<R> {
+    fn line(&self) -> usize {
+        self.entries as usize
+    }
+}
+
+impl<R: BufRead> Iterator for QifReader<R> {
+    type Item = Result<Command, SimpleError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_entry().transpose()
+    }
+}
+
+/// Returns deposit or withdraw command of `client` entry at `position`.
+fn command(client: ClientId, position: TransactionId, entry: Entry) -> Result<Command, SimpleError> {
+    let amount = match entry.amount {
+        Some(amount) => amount,
+        None => bail!("amount is none for qif entry({}) account({})", position, client),
+    };
+    let name = if amount.is_sign_negative() { CommandType::Withdraw } else { CommandType::Deposit };
+    let tx = entry.number.unwrap_or(position);
+    Ok(
+        Command::new(name, client, tx, Some(amount.abs()))
+            .with_timestamp(entry.date)
+            .with_category(entry.category)
+    )
+}
+
+/// Returns midnight UTC of US formatted QIF `date` (two digit years before 70 are 20xx).
+fn parse_date(date: &str) -> Result<Timestamp, SimpleError> {
+    let parts: Vec<&str> = date.split(|c| { c == '/' || c == '\'' || c == '-' || c == '.' })
+        .map(str::trim)
+        .collect();
+    let numbers: Vec<u32> = match parts.iter().map(|part| { part.parse::<u32>() }).collect() {
+        Ok(numbers) => numbers,
+        Err(_) => bail!("invalid qif date({})", date),
+    };
+    let (month, day, year) = match numbers[..] {
+        [month, day, year] if year < 70 => (month, day, 2000 + year as i32),
+        [month, day, year] if year < 100 => (month, day, 1900 + year as i32),
+        [month, day, year] => (month, day, year as i32),
+        _ => bail!("invalid qif date({})", date),
+    };
+    match NaiveDate::from_ymd_opt(year, month, day).and_then(|date| { date.and_hms_opt(0, 0, 0) }) {
+        Some(date) => Ok(Utc.from_utc_datetime(&date)),
+        None => bail!("invalid qif date({})", date),
+    }
+}
+
+/// Returns currency of QIF `amount` (thousands separators removed).
+fn parse_amount(amount: &str) -> Result<Currency, SimpleError> {
+    let amount = amount.replace(',', "");
+    Ok(try_with!(Currency::from_str(&amount), "invalid qif amount({})", amount))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::prelude::Decimal;
+
+    use crate::events::Cause;
+
+    fn commands(source: &str) -> Vec<Command> {
+        QifReader::new(source.as_bytes(), 7).map(|command| { command.unwrap() }).collect()
+    }
+
+    #[test]
+    fn entries_read_as_commands() {
+        let commands = commands("!Type:Bank\nD01/15/2024\nT1,234.50\nPEmployer\nLSalary\n^\nD01/16'24\nT-99.00\nN1001\n^\n");
+
+        assert_eq!(commands.len(), 2);
+        assert_eq!(commands[0].name(), &CommandType::Deposit);
+        assert_eq!(commands[0].actor_id(), 7);
+        assert_eq!(commands[0].tx(), 1);
+        assert_eq!(commands[0].amount(), Some(Decimal::new(123450, 2)));
+        assert_eq!(commands[0].category(), Some(&String::from("Salary")));
+        assert_eq!(commands[0].timestamp(), Some(Utc.with_ymd_and_hms(2024, 1, 15, 0, 0, 0).unwrap()));
+        assert_eq!(commands[1].name(), &CommandType::Withdraw);
+        assert_eq!(commands[1].tx(), 1001);
+        assert_eq!(commands[1].amount(), Some(Decimal::new(9900, 2)));
+        assert_eq!(commands[1].timestamp(), Some(Utc.with_ymd_and_hms(2024, 1, 16, 0, 0, 0).unwrap()));
+    }
+
+    #[test]
+    fn unterminated_last_entry_read() {
+        let commands = commands("!Type:Bank\nD1/2/99\nT5\n");
+
+        assert_eq!(commands.len(), 1);
+        assert_eq!(commands[0].timestamp(), Some(Utc.with_ymd_and_hms(1999, 1, 2, 0, 0, 0).unwrap()));
+    }
+
+    #[test]
+    fn entry_without_amount_errors() {
+        let mut reader = QifReader::new("!Type:Bank\nD01/15/2024\n^\n".as_bytes(), 7);
+
+        assert!(reader.next().unwrap().is_err());
+    }
+}
@@ -0,0 +1,105 @@
+//! Rate limiting of commands applied (`--max-rate`).
+//!
+//! Commands are paced by a token bucket refilled at the rate supplied (holding up to a second of tokens) and shared
+//! by every shard, so commands are applied at most at that rate overall whichever thread handles them. Useful when
+//! commands applied reach remote sinks (e.g. databases or webhook targets) that must not be overwhelmed.
+
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use simple_error::*;
+
+/// Commands applied per second at most (parsed from `N/s`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MaxRate(f64);
+
+impl MaxRate {
+    /// Returns commands per second.
+    pub fn per_second(&self) -> f64 { self.0 }
+}
+
+impl FromStr for MaxRate {
+    type Err = SimpleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rate = s.strip_suffix("/s").unwrap_or(s);
+        match rate.parse::<f64>() {
+            Ok(rate) if rate.is_finite() && rate > 0.0 => Ok(MaxRate(rate)),
+            _ => bail!("invalid max rate({}) expected positive commands per second (e.g. 500/s)", s),
+        }
+    }
+}
+
+/// Tokens available and when last refilled.
+struct Bucket {
+    tokens: f64,
+    refilled: Instant,
+}
+
+/// Token bucket pacing commands applied (cloned limiters share tokens).
+#[derive(Clone)]
+pub struct RateLimiter {
+    rate: f64,
+    capacity: f64,
+    bucket: Arc<Mutex<Bucket>>,
+}
+
+impl RateLimiter {
+    /// Returns new `RateLimiter` of `rate` (bucket starts full).
+    pub fn new(rate: MaxRate) -> Self {
+        let capacity = rate.per_second().max(1.0);
+        RateLimiter {
+            rate: rate.per_second(),
+            capacity,
+            bucket: Arc::new(Mutex::new(Bucket { tokens: capacity, refilled: Instant::now() })),
+        }
+    }
+
+    /// Takes a token waiting until it is refilled when the bucket is empty.
+    ///
+    /// Tokens are reserved in order taken (the bucket goes into debt), so waiting threads are paced fairly.
+    pub fn acquire(&self) {
+        let debt = {
+            let mut bucket = self.bucket.lock().unwrap_or_else(|poisoned| { poisoned.into_inner() });
+            let now = Instant::now();
+            let elapsed = now.duration_since(bucket.refilled).as_secs_f64();
+            bucket.tokens = (bucket.tokens + elapsed * self.rate).min(self.capacity) - 1.0;
+            bucket.refilled = now;
+            -bucket.tokens
+        };
+        if debt > 0.0 {
+            thread::sleep(Duration::from_secs_f64(debt / self.rate));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_paced_across_threads() {
+        assert_eq!("500/s".parse::<MaxRate>().unwrap().per_second(), 500.0);
+        assert_eq!("0.5".parse::<MaxRate>().unwrap().per_second(), 0.5);
+        assert!("0/s".parse::<MaxRate>().is_err() && "fast".parse::<MaxRate>().is_err());
+
+        let limiter = RateLimiter::new("200/s".parse().unwrap());
+        let started = Instant::now();
+        let workers: Vec<_> = (0..4).map(|_| {
+            let limiter = limiter.clone();
+            thread::spawn(move || {
+                for _ in 0..75 {
+                    limiter.acquire();
+                }
+            })
+        }).collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+        // a second of tokens is available at once then commands are paced (300 commands take ~0.5s)
+        let elapsed = started.elapsed().as_secs_f64();
+        assert!((0.45..2.0).contains(&elapsed), "elapsed {}s", elapsed);
+    }
+}
@@ -0,0 +1,185 @@
+//! Remote sources read from cloud object stores (`s3://bucket/key`, `gs://bucket/key` or `az://container/key`).
+//!
+//! Objects are streamed rather than downloaded. Sources ending with `/` are prefixes expanded to every object below
+//! them in lexicographic order.
+
+use std::io::{self, Read};
+
+use simple_error::*;
+
+#[cfg(any(feature = "gcs", feature = "azure"))]
+use crate::objectstore::ObjectStoreClient;
+#[cfg(feature = "s3")]
+use crate::s3::S3Client;
+
+/// Object store holding a remote source.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RemoteScheme {
+    /// Amazon S3 (requires `s3` feature).
+    S3,
+    /// Google Cloud Storage (requires `gcs` feature).
+    Gcs,
+    /// Azure Blob Storage (requires `azure` feature).
+    Azure,
+}
+
+impl RemoteScheme {
+    /// Returns url scheme of object store.
+    fn prefix(self) -> &'static str {
+        match self {
+            RemoteScheme::S3 => "s3://",
+            RemoteScheme::Gcs => "gs://",
+            RemoteScheme::Azure => "az://",
+        }
+    }
+
+    /// Returns feature required to read object store.
+    fn feature(self) -> &'static str {
+        match self {
+            RemoteScheme::S3 => "s3",
+            RemoteScheme::Gcs => "gcs",
+            RemoteScheme::Azure => "azure",
+        }
+    }
+}
+
+/// Object (or prefix of objects) of a remote source url.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RemoteObject<'a> {
+    pub scheme: RemoteScheme,
+    pub bucket: &'a str,
+    pub key: &'a str,
+}
+
+impl<'a> RemoteObject<'a> {
+    /// Returns object of remote `url` (none when url is a local path).
+    pub fn parse(url: &'a str) -> Option<Self> {
+        [RemoteScheme::S3, RemoteScheme::Gcs, RemoteScheme::Azure].iter().find_map(|scheme| {
+            let path = url.strip_prefix(scheme.prefix())?;
+            let (bucket, key) = path.split_once('/').unwrap_or((path, ""));
+            Some(RemoteObject { scheme: *scheme, bucket, key })
+        }).filter(|object| { !object.bucket.is_empty() })
+    }
+
+    /// Returns true when url names a prefix of objects (rather than an object).
+    pub fn is_prefix(&self) -> bool {
+        self.key.is_empty() || self.key.ends_with('/')
+    }
+
+    /// Returns url of object `key` of same bucket.
+    fn url(&self, key: &str) -> String {
+        format!("{}{}/{}", self.scheme.prefix(), self.bucket, key)
+    }
+}
+
+/// Returns true when `path` is a remote source url.
+pub fn is_remote(path: &str) -> bool {
+    RemoteObject::parse(path).is_some()
+}
+
+/// Returns reader streaming object of remote `url`.
+pub fn open(url: &str) -> io::Result<Box<dyn Read + Send>> {
+    let object = RemoteObject::parse(url)
+        .ok_or_else(|| { io::Error::new(io::ErrorKind::InvalidInput, format!("invalid remote source({})", url)) })?;
+    reader(&object).map_err(io::Error::other)
+}
+
+/// Returns reader streaming `object` from its object store.
+fn reader(object: &RemoteObject) -> Result<Box<dyn Read + Send>, SimpleError> {
+    match object.scheme {
+        #[cfg(feature = "s3")]
+        RemoteScheme::S3 => Ok(Box::new(S3Client::connect()?.open(object.bucket, object.key)?)),
+        #[cfg(feature = "gcs")]
+        RemoteScheme::Gcs => Ok(Box::new(ObjectStoreClient::gcs(object.bucket)?.open(object.key)?)),
+        #[cfg(feature = "azure")]
+        RemoteScheme::Azure => Ok(Box::new(ObjectStoreClient::azure(object.bucket)?.open(object.key)?)),
+        #[allow(unreachable_patterns)]
+        scheme => bail!("{} sources require {} feature", scheme.prefix(), scheme.feature()),
+    }
+}
+
+/// Returns urls of sources of remote `url` (objects below prefixes in lexicographic order).
+pub fn sources(url: &str) -> Result<Vec<String>, SimpleError> {
+    let object = require_with!(RemoteObject::parse(url), "invalid remote source({})", url);
+    if !object.is_prefix() {
+        return Ok(vec![url.to_string()]);
+    }
+    let keys = list(&object)?;
+    Ok(keys.iter().filter(|key| { !key.ends_with('/') }).map(|key| { object.url(key) }).collect())
+}
+
+/// Returns keys of objects below prefix `object` in lexicographic order.
+fn list(object: &RemoteObject) -> Result<Vec<String>, SimpleError> {
+    match object.scheme {
+        #[cfg(feature = "s3")]
+        RemoteScheme::S3 => S3Client::connect()?.list(object.bucket, object.key),
+        #[cfg(feature = "gcs")]
+        RemoteScheme::Gcs => ObjectStoreClient::gcs(object.bucket)?.list(object.key),
+        #[cfg(feature = "azure")]
+        RemoteScheme::Azure => ObjectStoreClient::azure(object.bucket)?.list(object.key),
+        #[allow(unreachable_patterns)]
+        scheme => bail!("{} sources require {} feature", scheme.prefix(), scheme.feature()),
+    }
+}
+
+/// Reader of an object body received in chunks (each fetched by `next` until none remain).
+pub struct ChunkReader<F> {
+    next: F,
+    chunk: Vec<u8>,
+    offset: usize,
+}
+
+impl<F: FnMut() -> io::Result<Option<Vec<u8>>>> ChunkReader<F> {
+    /// Returns new `ChunkReader` fetching chunks using `next`.
+    #[cfg_attr(not(any(feature = "s3", feature = "gcs", feature = "azure")), allow(dead_code))]
+    pub fn new(next: F) -> Self {
+        ChunkReader { next, chunk: vec![], offset: 0 }
+    }
+}
+
+impl<F: FnMut() -> io::Result<Option<Vec<u8>>>> Read for ChunkReader<F> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.offset == self.chunk.len() {
+            match (self.next)()? {
+                Some(chunk) => {
+                    self.chunk = chunk;
+                    self.offset = 0;
+                }
+                None => return Ok(0),
+            }
+        }
+        let length = buf.len().min(self.chunk.len() - self.offset);
+        buf[..length].copy_from_slice(&self.chunk[self.offset..self.offset + length]);
+        self.offset += length;
+        Ok(length)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remote_urls_parsed() {
+        let object = RemoteObject::parse("s3://dumps/2021/01/transactions.csv.gz").unwrap();
+        assert_eq!(object, RemoteObject { scheme: RemoteScheme::S3, bucket: "dumps", key: "2021/01/transactions.csv.gz" });
+        assert!(!object.is_prefix());
+        assert!(RemoteObject::parse("s3://dumps/2021/").unwrap().is_prefix());
+        assert!(RemoteObject::parse("s3://dumps").unwrap().is_prefix());
+        assert_eq!(RemoteObject::parse("s3://dumps").unwrap().url("a.csv"), "s3://dumps/a.csv");
+        assert_eq!(RemoteObject::parse("gs://dumps/a.csv").unwrap().scheme, RemoteScheme::Gcs);
+        let object = RemoteObject::parse("az://container/2021/").unwrap();
+        assert_eq!((object.scheme, object.bucket, object.key), (RemoteScheme::Azure, "container", "2021/"));
+        assert!(RemoteObject::parse("transactions.csv").is_none());
+        assert!(RemoteObject::parse("s3://").is_none());
+    }
+
+    #[test]
+    fn chunks_read_in_order() {
+        let mut chunks = vec![b"type,cli".to_vec(), vec![], b"ent\n".to_vec()].into_iter();
+        let mut reader = ChunkReader::new(move || { Ok(chunks.next()) });
+        let mut text = String::new();
+        reader.read_to_string(&mut text).unwrap();
+        assert_eq!(text, "type,client\n");
+    }
+}
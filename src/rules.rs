@@ -0,0 +1,328 @@
+//! Validation rules of commands scripted in Rhai (institution-specific rules added without recompiling).
+//!
+//! Scripts define `validate(command, account)` returning `accept()` or `reject(reason)` evaluated before commands
+//! are handled. Commands are maps of transaction fields (`type`, `client`, `tx`, `amount`, `wallet`, `merchant`,
+//! `timestamp` and `category`, unit when absent) and accounts are maps of balances (unit before the account is
+//! opened). Amounts are floats. Commands rejected (or failing the script) are rejected with reason.
+//!
+//! Daemon (streaming) runs reload rules between commands when the script (or the config file naming it) is modified.
+//! Modified config files are validated as a whole then their rules swapped in (`ConfigReloaded`), while settings other
+//! than rules are reported as applying once restarted. Scripts (or configs) failing to load are reported and the
+//! previous rules kept.
+//!
+//! ```rhai
+//! fn validate(command, account) {
+//!     if command.client in [13, 666] {
+//!         return reject("client blocked");
+//!     }
+//!     if command.type == "withdraw" && command.amount > 1000.0 {
+//!         return reject("withdrawal limit exceeded");
+//!     }
+//!     accept()
+//! }
+//! ```
+
+use std::fs;
+use std::time::{Duration, Instant, SystemTime};
+
+use rhai::{AST, Dynamic, Engine, Map, Scope};
+use rust_decimal::prelude::ToPrimitive;
+use simple_error::*;
+use tracing::{info, warn};
+
+use crate::config::Config;
+use crate::events::Cause;
+use crate::hooks::{Accounts, Plugin};
+use crate::models::{Account, Command, CommandType, Currency};
+
+/// Name of script function validating commands.
+const VALIDATE_FN: &str = "validate";
+
+/// Interval between checks of rules (and config) files for modifications.
+const RELOAD_INTERVAL: Duration = Duration::from_secs(1);
+
+/// File whose modification reloaded rules.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Reload {
+    /// Config file naming the rules script (validated as a whole).
+    Config,
+    /// Rules script.
+    Rules,
+}
+
+/// Decision of a rule on a command.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Decision {
+    Accept,
+    Reject(String),
+}
+
+/// Rules of a Rhai script validating commands before handled.
+pub struct ScriptRules {
+    path: String,
+    engine: Engine,
+    ast: AST,
+    modified: Option<SystemTime>,
+}
+
+impl ScriptRules {
+    /// Returns `ScriptRules` of script at `path`.
+    pub fn load(path: &str) -> Result<Self, SimpleError> {
+        let modified = last_modified(path);
+        let script = try_with!(fs::read_to_string(path), "unable to read rules({})", path);
+        Ok(ScriptRules { modified, ..Self::compile(path, &script)? })
+    }
+
+    /// Reloads script when modified since loaded returning true when reloaded.
+    ///
+    /// Rules are only replaced once the modified script compiles (reported once per modification otherwise).
+    pub fn reload(&mut self) -> Result<bool, SimpleError> {
+        let modified = last_modified(&self.path);
+        if modified == self.modified {
+            return Ok(false);
+        }
+        self.modified = modified;
+        *self = ScriptRules::load(&self.path)?;
+        Ok(true)
+    }
+
+    /// Returns `ScriptRules` of `script` (named `path` when reporting errors).
+    fn compile(path: &str, script: &str) -> Result<Self, SimpleError> {
+        let mut engine = Engine::new();
+        engine
+            .register_type_with_name::<Decision>("Decision")
+            .register_fn("accept", || { Decision::Accept })
+            .register_fn("reject", |reason: &str| { Decision::Reject(reason.to_string()) });
+        let ast = try_with!(engine.compile(script), "unable to compile rules({})", path);
+        if !ast.iter_functions().any(|function| { function.name == VALIDATE_FN && function.params.len() == 2 }) {
+            bail!("rules({}) missing {}(command, account) function", path, VALIDATE_FN);
+        }
+        Ok(ScriptRules { path: path.to_string(), engine, ast, modified: None })
+    }
+
+    /// Returns decision of script on `command` of `account` (none before opened).
+    pub fn validate(&self, command: &Command, account: Option<&Account>) -> Result<Decision, SimpleError> {
+        let account = account.map_or(Dynamic::UNIT, |account| { Dynamic::from_map(account_map(account)) });
+        let arguments = (Dynamic::from_map(command_map(command)), account);
+        let result = self.engine.call_fn::<Decision>(&mut Scope::new(), &self.ast, VALIDATE_FN, arguments);
+        Ok(try_with!(result, "rules({}) failed", self.path))
+    }
+}
+
+impl Plugin<Accounts> for ScriptRules {
+    fn on_command(&mut self, command: &mut Command, accounts: &Accounts) -> Result<(), SimpleError> {
+        let account = accounts.get(command.actor_id())?;
+        match self.validate(command, account.as_deref())? {
+            Decision::Accept => Ok(()),
+            Decision::Reject(reason) => bail!("rejected by rules, {}", reason),
+        }
+    }
+}
+
+/// Config file watched for modifications with its settings (other than rules) when last loaded.
+struct WatchedConfig {
+    path: String,
+    modified: Option<SystemTime>,
+    settings: Vec<(&'static str, Option<String>)>,
+}
+
+/// Rules reloaded between commands when their script (or the config file naming it) is modified.
+pub struct RulesWatcher {
+    rules: ScriptRules,
+    config: Option<WatchedConfig>,
+    checked: Instant,
+}
+
+impl RulesWatcher {
+    /// Returns new `RulesWatcher` of `rules` (loaded from `config` file when named by its rules script).
+    ///
+    /// Config files naming a script other than `rules` (overridden by arguments) are not watched.
+    pub fn new(rules: ScriptRules, config: Option<&str>) -> Self {
+        let config = config.and_then(|path| {
+            let config = Config::load(path).ok().filter(|config| { config.rules.script.as_ref() == Some(&rules.path) })?;
+            Some(WatchedConfig { path: path.to_string(), modified: last_modified(path), settings: settings(&config) })
+        });
+        RulesWatcher { rules, config, checked: Instant::now() }
+    }
+
+    /// Reloads rules when their config (validated as a whole) or script is modified returning the file reloaded.
+    ///
+    /// Rules are swapped only once the config and the script it names load (each modification reported once).
+    fn reload(&mut self) -> Result<Option<Reload>, SimpleError> {
+        if let Some(watched) = self.config.as_mut().filter(|watched| { last_modified(&watched.path) != watched.modified }) {
+            watched.modified = last_modified(&watched.path);
+            let config = Config::load(&watched.path)?;
+            let script = require_with!(config.rules.script.as_deref(), "config({}) missing rules script", watched.path);
+            let rules = ScriptRules::load(script)?;
+            let settings = settings(&config);
+            if settings != watched.settings {
+                warn!("config({}) settings other than rules apply once restarted", watched.path);
+                watched.settings = settings;
+            }
+            self.rules = rules;
+            return Ok(Some(Reload::Config));
+        }
+        Ok(self.rules.reload()?.then_some(Reload::Rules))
+    }
+}
+
+impl Plugin<Accounts> for RulesWatcher {
+    /// Validates `command` using rules (reloaded first when modified and not checked within `RELOAD_INTERVAL`).
+    fn on_command(&mut self, command: &mut Command, accounts: &Accounts) -> Result<(), SimpleError> {
+        if self.checked.elapsed() >= RELOAD_INTERVAL {
+            self.checked = Instant::now();
+            match self.reload() {
+                Ok(Some(Reload::Config)) => info!("ConfigReloaded rules({})", self.rules.path),
+                Ok(Some(Reload::Rules)) => info!("RulesReloaded rules({})", self.rules.path),
+                Ok(None) => {}
+                Err(e) => warn!("{} (previous rules kept)", e),
+            }
+        }
+        self.rules.on_command(command, accounts)
+    }
+}
+
+/// Returns settings of `config` other than rules (as cli arguments).
+fn settings(config: &Config) -> Vec<(&'static str, Option<String>)> {
+    config.args().into_iter().filter(|(name, _)| { *name != "rules" }).collect()
+}
+
+/// Returns time file at `path` was last modified (none when unavailable).
+fn last_modified(path: &str) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| { metadata.modified() }).ok()
+}
+
+/// Returns script value of `amount` (unit when none).
+fn amount(amount: Option<Currency>) -> Dynamic {
+    amount.and_then(|amount| { amount.to_f64() }).map_or(Dynamic::UNIT, Dynamic::from_float)
+}
+
+/// Returns script value of optional `value` (unit when none).
+fn optional<T: Into<Dynamic>>(value: Option<T>) -> Dynamic {
+    value.map_or(Dynamic::UNIT, Into::into)
+}
+
+/// Returns script map of `command` fields.
+fn command_map(command: &Command) -> Map {
+    let name = match command.name() {
+        CommandType::Deposit => "deposit",
+        CommandType::Withdraw => "withdraw",
+        CommandType::Dispute => "dispute",
+        CommandType::Resolve => "resolve",
+        CommandType::Chargeback => "chargeback",
+    };
+    let mut map = Map::new();
+    map.insert("type".into(), name.into());
+    map.insert("client".into(), (command.actor_id() as i64).into());
+    map.insert("tx".into(), (command.tx() as i64).into());
+    map.insert("amount".into(), amount(command.amount()));
+    map.insert("wallet".into(), optional(command.wallet().cloned()));
+    map.insert("merchant".into(), optional(command.merchant().map(i64::from)));
+    map.insert("timestamp".into(), optional(command.timestamp().map(|timestamp| { timestamp.to_rfc3339() })));
+    map.insert("category".into(), optional(command.category().cloned()));
+    map
+}
+
+/// Returns script map of `account` balances.
+fn account_map(account: &Account) -> Map {
+    let mut map = Map::new();
+    map.insert("client".into(), (account.client() as i64).into());
+    map.insert("available".into(), amount(Some(account.available())));
+    map.insert("held".into(), amount(Some(account.held())));
+    map.insert("total".into(), amount(Some(account.total())));
+    map.insert("locked".into(), account.locked().into());
+    map
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::prelude::Decimal;
+
+    use crate::events::Actor;
+    use crate::store::{MemoryStore, ProjectionStore};
+    use crate::testing::TempDir;
+
+    const RULES: &str = r#"
+        fn validate(command, account) {
+            if command.client == 13 {
+                return reject("client blocked");
+            }
+            if command.type == "withdraw" && command.amount > account.available / 2.0 {
+                return reject("withdrawal over half of available");
+            }
+            accept()
+        }
+    "#;
+
+    #[test]
+    fn commands_validated_by_script() {
+        let rules = ScriptRules::compile("rules.rhai", RULES).unwrap();
+        let deposit = Command::new(CommandType::Deposit, 1, 1, Some(Decimal::new(10, 0)));
+        assert_eq!(rules.validate(&deposit, None).unwrap(), Decision::Accept);
+        let blocked = Command::new(CommandType::Deposit, 13, 2, Some(Decimal::new(10, 0)));
+        assert_eq!(rules.validate(&blocked, None).unwrap(), Decision::Reject("client blocked".to_string()));
+
+        let mut account = Account::new(1);
+        account.apply(account.handle(deposit).unwrap());
+        let mut accounts = MemoryStore::default();
+        accounts.put(account, 0).unwrap();
+        let mut rules = rules;
+        let mut withdrawal = Command::new(CommandType::Withdraw, 1, 3, Some(Decimal::new(5, 0)));
+        rules.on_command(&mut withdrawal, &accounts).unwrap();
+        let mut withdrawal = Command::new(CommandType::Withdraw, 1, 4, Some(Decimal::new(6, 0)));
+        let e = rules.on_command(&mut withdrawal, &accounts).unwrap_err();
+        assert_eq!(e.as_str(), "rejected by rules, withdrawal over half of available");
+
+        // account of unknown client is unit (failing script accessing balances)
+        let withdrawal = Command::new(CommandType::Withdraw, 2, 5, Some(Decimal::new(1, 0)));
+        assert!(rules.validate(&withdrawal, None).is_err());
+        assert!(ScriptRules::compile("rules.rhai", "fn check(command) { accept() }").is_err());
+    }
+
+    #[test]
+    fn modified_rules_reloaded() {
+        let directory = TempDir::new("rules");
+        let script = directory.join("rules.rhai");
+        let config = directory.join("config.toml");
+        let (script, config) = (script.to_str().unwrap(), config.to_str().unwrap());
+        let modify = |path: &str, contents: &str, seconds: u64| {
+            fs::write(path, contents).unwrap();
+            let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(seconds);
+            fs::File::options().write(true).open(path).unwrap().set_modified(modified).unwrap();
+        };
+        modify(script, "fn validate(command, account) { accept() }", 1);
+        modify(config, &format!("[rules]\nscript = '{}'", script), 1);
+
+        let accounts = MemoryStore::default();
+        let mut command = Command::new(CommandType::Deposit, 1, 1, Some(Decimal::new(10, 0)));
+        let mut watcher = RulesWatcher::new(ScriptRules::load(script).unwrap(), Some(config));
+        assert!(watcher.config.is_some());
+        assert_eq!(watcher.reload().unwrap(), None);
+        watcher.on_command(&mut command, &accounts).unwrap();
+
+        modify(script, "fn validate(command, account) { reject(\"paused\") }", 2);
+        assert_eq!(watcher.reload().unwrap(), Some(Reload::Rules));
+        assert!(watcher.on_command(&mut command, &accounts).is_err());
+
+        // rules failing to compile are reported once and previous rules kept
+        modify(script, "fn validate(command, account) {", 3);
+        assert!(watcher.reload().is_err());
+        assert_eq!(watcher.reload().unwrap(), None);
+        assert!(watcher.on_command(&mut command, &accounts).is_err());
+
+        // invalid config is reported once and previous rules kept
+        let renamed = format!("{}.next", script);
+        modify(&renamed, "fn validate(command, account) { accept() }", 4);
+        modify(config, &format!("[rules]\nscript = '{}'\n[output]\nformats = 'json'", renamed), 2);
+        assert!(watcher.reload().is_err());
+        assert_eq!(watcher.reload().unwrap(), None);
+        assert!(watcher.on_command(&mut command, &accounts).is_err());
+
+        // config naming another script swaps in rules of that script (other settings apply once restarted)
+        modify(config, &format!("[rules]\nscript = '{}'\n[output]\nformat = 'json'", renamed), 3);
+        assert_eq!(watcher.reload().unwrap(), Some(Reload::Config));
+        assert_eq!(watcher.config.as_ref().unwrap().settings, vec![("output-format", Some(String::from("json")))]);
+        watcher.on_command(&mut command, &accounts).unwrap();
+    }
+}
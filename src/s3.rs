@@ -0,0 +1,58 @@
+//! Objects of Amazon S3 buckets streamed as transaction sources.
+//!
+//! Credentials and region are resolved by the AWS default provider chain (environment, profiles, web identity and
+//! instance metadata). Buckets of custom endpoints (`AWS_ENDPOINT_URL`, e.g. MinIO) are addressed path-style.
+
+use std::env;
+use std::io::{self, Read};
+
+use aws_config::BehaviorVersion;
+use aws_sdk_s3::Client;
+use simple_error::*;
+use tokio::runtime::Runtime;
+
+use crate::remote::ChunkReader;
+
+/// Client of S3 buckets blocking on requests.
+pub struct S3Client {
+    runtime: Runtime,
+    client: Client,
+}
+
+impl S3Client {
+    /// Returns new `S3Client` configured by the AWS default provider chain.
+    pub fn connect() -> Result<Self, SimpleError> {
+        let runtime = try_with!(
+            tokio::runtime::Builder::new_current_thread().enable_all().build(),
+            "unable to start s3 runtime"
+        );
+        let config = runtime.block_on(aws_config::load_defaults(BehaviorVersion::latest()));
+        let config = aws_sdk_s3::config::Builder::from(&config)
+            .force_path_style(env::var_os("AWS_ENDPOINT_URL").is_some())
+            .build();
+        Ok(S3Client { runtime, client: Client::from_conf(config) })
+    }
+
+    /// Returns reader streaming object `key` of `bucket`.
+    pub fn open(self, bucket: &str, key: &str) -> Result<impl Read + Send, SimpleError> {
+        let request = self.client.get_object().bucket(bucket).key(key).send();
+        let object = try_with!(self.runtime.block_on(request), "unable to get s3 object({}/{})", bucket, key);
+        let (runtime, mut body) = (self.runtime, object.body);
+        Ok(ChunkReader::new(move || {
+            let chunk = runtime.block_on(body.try_next()).map_err(io::Error::other)?;
+            Ok(chunk.map(|chunk| { chunk.to_vec() }))
+        }))
+    }
+
+    /// Returns keys of objects of `bucket` starting with `prefix` in lexicographic order.
+    pub fn list(&self, bucket: &str, prefix: &str) -> Result<Vec<String>, SimpleError> {
+        let mut keys = vec![];
+        let mut pages = self.client.list_objects_v2().bucket(bucket).prefix(prefix).into_paginator().send();
+        while let Some(page) = self.runtime.block_on(pages.next()) {
+            let page = try_with!(page, "unable to list s3 objects({}/{})", bucket, prefix);
+            keys.extend(page.contents().iter().filter_map(|object| { object.key().map(String::from) }));
+        }
+        keys.sort();
+        Ok(keys)
+    }
+}
@@ -0,0 +1,296 @@
+//! REST API applying transactions to accounts in-process (`serve` subcommand, `server` feature).
+//!
+//! Endpoints:
+//! - `POST /transactions` applies a command (JSON object) or batch of commands (JSON array) returning outcomes
+//! - `GET /accounts/{client}` returns balances of an account
+//! - `GET /accounts` returns balances of every account ordered by client
+//! - `GET /events` streams events applied (of `?client=` when supplied) as server-sent events once subscribed
+//! - `POST /graphql` answers GraphQL queries of accounts and their events (`graphql` feature)
+//! - `GET /healthz` answers liveness and `GET /readyz` readiness of the account store (see `health`)
+//!
+//! Requests are served by an axum server on its own thread, queued to the thread owning accounts (see `Engine`). Event
+//! streams are named by event type with audit records (JSON) as data. Subscribers lagging by more than
+//! `SUBSCRIBER_CAPACITY` events receive a `lagged` event (events skipped) and are disconnected so they can resubscribe.
+
+use std::convert::Infallible;
+use std::net::{SocketAddr, TcpListener};
+use std::thread::JoinHandle;
+
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::sse::{self, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use futures::Stream;
+use serde::{Deserialize, Serialize};
+use simple_error::*;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::audit::AuditRecord;
+use crate::engine::{self, Applied, Engine};
+#[cfg(feature = "graphql")]
+use crate::graphql;
+use crate::events::Cause;
+use crate::health::{self, Check, Readiness};
+use crate::models::{ClientId, Command, CommandType, TransactionId};
+
+/// Command (or batch of commands) posted to `/transactions`.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Transactions {
+    Single(Command),
+    Batch(Vec<Command>),
+}
+
+/// Query of `/events` (events of every client unless supplied).
+#[derive(Debug, Deserialize)]
+struct EventsQuery {
+    client: Option<ClientId>,
+}
+
+/// Outcome of command posted (reason of rejected commands).
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct Outcome {
+    #[serde(rename = "type")]
+    pub name: CommandType,
+    pub client: ClientId,
+    pub tx: TransactionId,
+    pub applied: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+impl From<Applied> for Outcome {
+    fn from(applied: Applied) -> Self {
+        let command = applied.command;
+        Outcome {
+            name: command.name().clone(),
+            client: command.actor_id(),
+            tx: command.tx(),
+            applied: applied.result.is_ok(),
+            reason: applied.result.err(),
+        }
+    }
+}
+
+/// REST API server bound to an address.
+pub struct Server {
+    listener: TcpListener,
+}
+
+impl Server {
+    /// Returns new `Server` bound to `address` (host:port).
+    pub fn bind(address: &str) -> Result<Self, SimpleError> {
+        let listener = try_with!(TcpListener::bind(address), "unable to bind server({})", address);
+        try_with!(listener.set_nonblocking(true), "unable to bind server({})", address);
+        Ok(Server { listener })
+    }
+
+    /// Returns address server is bound to.
+    pub fn local_addr(&self) -> Result<SocketAddr, SimpleError> {
+        Ok(try_with!(self.listener.local_addr(), "unable to read server address"))
+    }
+
+    /// Returns thread serving requests queued to `engine` (runs until the process is stopped).
+    pub fn spawn(self, engine: Engine) -> Result<JoinHandle<Result<(), SimpleError>>, SimpleError> {
+        let listener = self.listener;
+        engine::spawn("server", move || {
+            async move {
+                let listener = try_with!(tokio::net::TcpListener::from_std(listener), "unable to listen");
+                try_with!(axum::serve(listener, router(engine)).await, "server stopped");
+                Ok(())
+            }
+        })
+    }
+}
+
+/// Returns routes of API queueing requests to `engine`.
+fn router(engine: Engine) -> Router {
+    #[cfg(feature = "graphql")]
+    let schema = graphql::schema(engine.clone());
+    let router = Router::new()
+        .route("/transactions", post(post_transactions))
+        .route("/accounts", get(get_accounts))
+        .route("/accounts/{client}", get(get_account))
+        .route("/events", get(get_events))
+        .route("/healthz", get(health::healthz))
+        .route("/readyz", get(get_readyz))
+        .with_state(engine);
+    #[cfg(feature = "graphql")]
+    let router = router.merge(Router::new().route("/graphql", post(post_graphql)).with_state(schema));
+    router
+}
+
+/// Returns response of `error` replied by engine.
+fn unavailable(error: SimpleError) -> Response {
+    (StatusCode::INTERNAL_SERVER_ERROR, error.to_string()).into_response()
+}
+
+/// Applies posted commands answering outcome of a single command (rejected as unprocessable) or of every command.
+async fn post_transactions(State(engine): State<Engine>, Json(transactions): Json<Transactions>) -> Response {
+    let (commands, single) = match transactions {
+        Transactions::Single(command) => (vec![command], true),
+        Transactions::Batch(commands) => (commands, false),
+    };
+    match engine.apply(commands).await {
+        Ok(mut applied) if single => {
+            let outcome = Outcome::from(applied.remove(0));
+            let status = if outcome.applied { StatusCode::OK } else { StatusCode::UNPROCESSABLE_ENTITY };
+            (status, Json(outcome)).into_response()
+        }
+        Ok(applied) => Json(applied.into_iter().map(Outcome::from).collect::<Vec<_>>()).into_response(),
+        Err(e) => unavailable(e),
+    }
+}
+
+async fn get_account(State(engine): State<Engine>, Path(client): Path<ClientId>) -> Response {
+    match engine.account(client).await {
+        Ok(Some(account)) => Json(account).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, format!("account({}) not found", client)).into_response(),
+        Err(e) => unavailable(e),
+    }
+}
+
+async fn get_accounts(State(engine): State<Engine>) -> Response {
+    match engine.accounts().await {
+        Ok(accounts) => Json(accounts).into_response(),
+        Err(e) => unavailable(e),
+    }
+}
+
+/// Streams events applied once subscribed (of queried client when supplied).
+async fn get_events(State(engine): State<Engine>, Query(query): Query<EventsQuery>) -> Sse<impl Stream<Item = Result<sse::Event, Infallible>>> {
+    let (events, queried) = (engine.subscribe(), query.client);
+    let stream = futures::stream::unfold(Some(events), move |events| {
+        async move {
+            let mut events = events?;
+            loop {
+                match events.recv().await {
+                    Ok((client, event)) if queried.is_none_or(|queried| { queried == client }) => {
+                        let record = AuditRecord::from_event(client, &event);
+                        let data = sse::Event::default().event(event.name()).json_data(record).unwrap_or_default();
+                        return Some((Ok(data), Some(events)));
+                    }
+                    Ok(_) => {}
+                    Err(RecvError::Lagged(skipped)) => {
+                        return Some((Ok(sse::Event::default().event("lagged").data(skipped.to_string())), None));
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        }
+    });
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+/// Answers readiness once the store answered a probe queued behind requests.
+async fn get_readyz(State(engine): State<Engine>) -> Readiness {
+    let store = match engine.probe().await {
+        Ok(()) => Check::new("store", true, None),
+        Err(e) => Check::new("store", false, Some(e.to_string())),
+    };
+    Readiness::new(vec![store])
+}
+
+#[cfg(feature = "graphql")]
+async fn post_graphql(State(schema): State<graphql::Schema>, Json(request): Json<async_graphql::Request>) -> Json<async_graphql::Response> {
+    Json(schema.execute(request).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+    use std::thread;
+
+    use serde_json::{json, Value};
+
+    use crate::store::MemoryStore;
+    use crate::handle_command;
+
+    /// Returns status and JSON body (none unless JSON) of request `method` of `path` (with `body`).
+    fn request(address: SocketAddr, method: &str, path: &str, body: Option<Value>) -> (u16, Option<Value>) {
+        let mut stream = TcpStream::connect(address).unwrap();
+        let body = body.map_or_else(String::new, |body| { body.to_string() });
+        write!(
+            stream,
+            "{} {} HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            method, path, body.len(), body
+        ).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let status = response[9..12].parse().unwrap();
+        let (_, body) = response.split_once("\r\n\r\n").unwrap();
+        (status, serde_json::from_str(body).ok())
+    }
+
+    /// Returns text read from `stream` until `marker` is read.
+    fn read_until(stream: &mut TcpStream, marker: &str) -> String {
+        let mut read = String::new();
+        let mut buffer = [0; 1024];
+        while !read.contains(marker) {
+            let count = stream.read(&mut buffer).unwrap();
+            assert!(count > 0, "stream closed before {:?} read: {}", marker, read);
+            read.push_str(&String::from_utf8_lossy(&buffer[..count]));
+        }
+        read
+    }
+
+    #[test]
+    fn transactions_applied_and_accounts_served() {
+        let server = Server::bind("127.0.0.1:0").unwrap();
+        let address = server.local_addr().unwrap();
+        let (engine, requests) = engine::channel();
+        server.spawn(engine).unwrap();
+        thread::spawn(move || {
+            requests.run(&mut MemoryStore::default(), &HashMap::new(), handle_command);
+        });
+
+        // events of client 1 are streamed once subscribed (response headers received)
+        let mut events = TcpStream::connect(address).unwrap();
+        write!(events, "GET /events?client=1 HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        assert!(read_until(&mut events, "\r\n\r\n").contains("text/event-stream"));
+
+        let deposit = json!({ "type": "deposit", "client": 1, "tx": 1, "amount": "2.5" });
+        let (status, outcome) = request(address, "POST", "/transactions", Some(deposit.clone()));
+        assert_eq!(status, 200);
+        assert_eq!(outcome, Some(json!({ "type": "deposit", "client": 1, "tx": 1, "applied": true })));
+        // duplicate transaction is rejected
+        let (status, outcome) = request(address, "POST", "/transactions", Some(deposit));
+        assert_eq!(status, 422);
+        assert_eq!(outcome.unwrap()["applied"], json!(false));
+
+        let batch = json!([
+            { "type": "deposit", "client": 2, "tx": 2, "amount": "1.0" },
+            { "type": "withdraw", "client": 1, "tx": 3, "amount": "5.0" },
+            { "type": "dispute", "client": 1, "tx": 1 },
+        ]);
+        let (status, outcomes) = request(address, "POST", "/transactions", Some(batch));
+        assert_eq!(status, 200);
+        let applied: Vec<bool> = serde_json::from_value::<Vec<Outcome>>(outcomes.unwrap()).unwrap().iter()
+            .map(|outcome| { outcome.applied })
+            .collect();
+        assert_eq!(applied, vec![true, false, true]);
+        assert_eq!(request(address, "POST", "/transactions", Some(json!({ "type": "deposit" }))).0, 422);
+
+        let streamed = read_until(&mut events, "event: held");
+        assert!(streamed.contains("event: credited") && streamed.contains(r#""client":1"#) && !streamed.contains(r#""client":2"#));
+
+        let (status, account) = request(address, "GET", "/accounts/1", None);
+        assert_eq!(status, 200);
+        assert_eq!(account, Some(json!({ "client": 1, "available": "0.0", "held": "2.5", "total": "2.5", "locked": false })));
+        assert_eq!(request(address, "GET", "/accounts/3", None).0, 404);
+        assert_eq!(request(address, "GET", "/accounts/client", None).0, 400);
+
+        let (status, accounts) = request(address, "GET", "/accounts", None);
+        assert_eq!(status, 200);
+        let clients: Vec<Value> = accounts.unwrap().as_array().unwrap().iter().map(|account| { account["client"].clone() }).collect();
+        assert_eq!(clients, vec![json!(1), json!(2)]);
+
+        assert_eq!(request(address, "GET", "/healthz", None).0, 200);
+        assert_eq!(request(address, "GET", "/readyz", None), (200, Some(json!({ "ready": true, "checks": [{ "name": "store", "ok": true }] }))));
+    }
+}
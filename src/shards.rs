@@ -0,0 +1,223 @@
+//! Commands handled by worker threads each owning a shard of accounts (clients routed by `client % shards`).
+//!
+//! Commands of a client are always handled by the same worker in the order routed, so per-client ordering is
+//! preserved while clients are handled in parallel. Outcomes are returned to the routing thread ordered per client
+//! (interleaved across clients) and accounts of every shard are merged once every command routed is handled. Queues of
+//! workers are bounded (`--shard-queue-capacity`) and their occupancy measured by `QueueMetrics`.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::mpsc::{self, Receiver, SyncSender, TryRecvError, TrySendError};
+use std::thread::{self, JoinHandle};
+use std::time::Instant;
+
+use simple_error::*;
+
+use crate::events::{Cause, Effects};
+use crate::metrics::{Metrics, QueueMetrics};
+use crate::models::{Account, AccountMetadata, ClientId, Command, Event};
+use crate::ratelimit::RateLimiter;
+use crate::store::{EventHistory, MemoryStore, ProjectionStore};
+
+/// Commands queued per worker before routing blocks by default.
+pub const QUEUE_CAPACITY: usize = 1_024;
+
+/// Handles command using accounts of shard (and metadata accounts are opened with) returning events applied.
+pub type Handler = fn(&mut dyn ProjectionStore, &HashMap<ClientId, AccountMetadata>, Command) -> Result<Effects<Event>, SimpleError>;
+
+/// Command routed to a shard (with index of source and line read at).
+struct Routed {
+    source: usize,
+    line: usize,
+    command: Command,
+}
+
+/// Outcome of command handled by a shard.
+pub struct Outcome {
+    /// Index of source command was read from.
+    pub source: usize,
+    /// Line of source command was read at.
+    pub line: usize,
+    pub command: Command,
+    /// Events applied or reason rejected.
+    pub result: Result<Effects<Event>, SimpleError>,
+}
+
+/// Worker threads each handling commands of a shard of clients.
+pub struct ShardPool {
+    senders: Vec<SyncSender<Routed>>,
+    queues: Vec<Arc<QueueMetrics>>,
+    outcomes: Receiver<Outcome>,
+    workers: Vec<JoinHandle<MemoryStore>>,
+}
+
+impl ShardPool {
+    /// Returns new `ShardPool` of `threads` workers handling commands using `handler` (queueing up to `capacity`
+    /// commands each, measured by queues of `metrics`).
+    ///
+    /// `accounts` are moved to the shard of their client. Shards keep event `history` of accounts. Workers share the
+    /// tokens of `limiter` (when supplied) so commands are applied at most at its rate overall.
+    #[allow(clippy::too_many_arguments)]
+    pub fn spawn<I: IntoIterator<Item = Account>>(
+        threads: usize,
+        accounts: I,
+        metadata: HashMap<ClientId, AccountMetadata>,
+        handler: Handler,
+        capacity: usize,
+        history: EventHistory,
+        limiter: Option<&RateLimiter>,
+        metrics: &Metrics
+    ) -> Result<Self, SimpleError> {
+        if threads == 0 {
+            bail!("threads must be at least one");
+        }
+        if capacity == 0 {
+            bail!("shard queue capacity must be at least one");
+        }
+        let mut shards: Vec<MemoryStore> = (0..threads).map(|_| { MemoryStore::new(history) }).collect();
+        for account in accounts {
+            shards[account.client() as usize % threads].put(account, 0)?;
+        }
+        let metadata = Arc::new(metadata);
+        let (outcome_sender, outcomes) = mpsc::channel();
+        let mut senders = vec![];
+        let mut queues = vec![];
+        let mut workers = vec![];
+        for (index, mut shard) in shards.into_iter().enumerate() {
+            // command handled by the worker is part of its queue
+            let (sender, receiver) = mpsc::sync_channel::<Routed>(capacity - 1);
+            let queue = metrics.queue(&format!("shard({})", index), capacity);
+            let (metadata, outcome_sender, metrics) = (metadata.clone(), outcome_sender.clone(), queue.clone());
+            let limiter = limiter.cloned();
+            let worker = thread::Builder::new().name(format!("shard-{}", index)).spawn(move || {
+                while let Some(Routed { source, line, command }) = receive(&receiver, &metrics) {
+                    if let Some(limiter) = limiter.as_ref() {
+                        limiter.acquire();
+                    }
+                    let result = handler(&mut shard, &metadata, command.clone());
+                    metrics.received(1);
+                    // routing thread stops receiving outcomes only when panicking
+                    if outcome_sender.send(Outcome { source, line, command, result }).is_err() {
+                        break;
+                    }
+                }
+                metrics.close();
+                shard
+            });
+            senders.push(sender);
+            queues.push(queue);
+            workers.push(try_with!(worker, "unable to spawn shard({}) worker", index));
+        }
+        Ok(ShardPool { senders, queues, outcomes, workers })
+    }
+
+    /// Routes `command` read at `line` of source (index) to worker of its client shard.
+    ///
+    /// Routing blocks while the queue of the worker is full.
+    pub fn route(&self, source: usize, line: usize, command: Command) -> Result<(), SimpleError> {
+        let shard = command.actor_id() as usize % self.senders.len();
+        let routed = match self.senders[shard].try_send(Routed { source, line, command }) {
+            Err(TrySendError::Full(routed)) => {
+                let started = Instant::now();
+                let result = self.senders[shard].send(routed);
+                self.queues[shard].blocked(started);
+                result.is_ok()
+            }
+            result => result.is_ok(),
+        };
+        if !routed {
+            bail!("shard({}) worker stopped", shard);
+        }
+        self.queues[shard].sent(1);
+        Ok(())
+    }
+
+    /// Returns outcomes of commands handled since last received (without waiting).
+    pub fn outcomes(&self) -> impl Iterator<Item = Outcome> + '_ {
+        self.outcomes.try_iter()
+    }
+
+    /// Waits for every command routed to be handled returning outcomes not yet received and accounts of every shard.
+    pub fn join(self) -> Result<(Vec<Outcome>, Vec<Account>), SimpleError> {
+        drop(self.senders);
+        let outcomes = self.outcomes.iter().collect();
+        let mut accounts = vec![];
+        for (index, worker) in self.workers.into_iter().enumerate() {
+            match worker.join() {
+                Ok(shard) => accounts.extend(shard.into_accounts()),
+                Err(_) => bail!("shard({}) worker panicked", index),
+            }
+        }
+        Ok((outcomes, accounts))
+    }
+}
+
+/// Returns command routed to `receiver` waiting while the queue is empty (none once routing stops).
+fn receive(receiver: &Receiver<Routed>, queue: &QueueMetrics) -> Option<Routed> {
+    match receiver.try_recv() {
+        Err(TryRecvError::Empty) => {
+            let started = Instant::now();
+            let routed = receiver.recv().ok();
+            queue.waited(started);
+            routed
+        }
+        routed => routed.ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::prelude::Decimal;
+
+    use crate::events::Actor;
+    use crate::models::CommandType;
+    use crate::handle_command;
+
+    #[test]
+    fn commands_handled_in_client_order() {
+        let mut opened = Account::new(3);
+        opened.apply(opened.handle(Command::new(CommandType::Deposit, 3, 100, Some(Decimal::new(5, 0)))).unwrap());
+        let metrics = Metrics::new();
+        let pool = ShardPool::spawn(3, vec![opened], HashMap::new(), handle_command, 8, EventHistory::Retained, None, &metrics).unwrap();
+        let mut outcomes = vec![];
+        for tx in 1..=300 {
+            let client = (tx % 6) as ClientId;
+            let command = match tx % 4 {
+                0 => Command::new(CommandType::Withdraw, client, tx, Some(Decimal::new(1, 0))),
+                _ => Command::new(CommandType::Deposit, client, tx, Some(Decimal::new(1, 0))),
+            };
+            pool.route(0, tx as usize, command).unwrap();
+            outcomes.extend(pool.outcomes());
+        }
+        let (remaining, accounts) = pool.join().unwrap();
+        outcomes.extend(remaining);
+        assert_eq!(outcomes.len(), 300);
+        // withdrawal of client 4 before any deposit is rejected
+        let rejected: Vec<usize> = outcomes.iter().filter(|outcome| { outcome.result.is_err() }).map(|outcome| { outcome.line }).collect();
+        assert_eq!(rejected, vec![4]);
+        for client in 0..6 {
+            let lines: Vec<usize> = outcomes.iter()
+                .filter(|outcome| { outcome.command.actor_id() == client })
+                .map(|outcome| { outcome.line })
+                .collect();
+            assert!(lines.windows(2).all(|pair| { pair[0] < pair[1] }));
+        }
+
+        let stats = metrics.stats();
+        assert_eq!(stats.iter().map(|stats| { (stats.queued, stats.sent) }).collect::<Vec<_>>(), vec![(0, 100); 3]);
+        assert!(stats.iter().all(|stats| { stats.peak <= 8 }));
+
+        let mut accounts: Vec<Account> = accounts;
+        accounts.sort_unstable_by_key(|account| { account.client() });
+        let totals: Vec<(ClientId, Decimal)> = accounts.iter().map(|account| { (account.client(), account.total()) }).collect();
+        assert_eq!(totals, vec![
+            (0, Decimal::new(0, 0)),
+            (1, Decimal::new(50, 0)),
+            (2, Decimal::new(0, 0)),
+            (3, Decimal::new(55, 0)),
+            (4, Decimal::new(1, 0)),
+            (5, Decimal::new(50, 0)),
+        ]);
+    }
+}
@@ -0,0 +1,115 @@
+//! Processing state (every account) saved between batch runs.
+//!
+//! State is a bincode encoded format version, account count and account records (metadata and events) from which
+//! accounts are rehydrated including their transactions (deduplication) and disputes.
+
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Write};
+
+use simple_error::*;
+
+use crate::input;
+use crate::models::{Account, ClientId};
+use crate::output::partial_path;
+use crate::store::AccountRecord;
+
+/// Version of state format.
+const STATE_VERSION: u32 = 1;
+
+/// Accounts of state at most (one per client).
+const MAX_ACCOUNTS: u64 = ClientId::MAX as u64 + 1;
+
+/// Saves state of `accounts` to `path` (written to a partial file then renamed).
+///
+/// Accounts having discarded their event history can't be saved (they could not be rehydrated).
+pub fn save<'a, I: ExactSizeIterator<Item = &'a Account>>(path: &str, accounts: I) -> Result<(), SimpleError> {
+    let mut writer = BufWriter::new(try_with!(File::create(partial_path(path)), "unable to create state({})", path));
+    try_with!(bincode::serialize_into(&mut writer, &STATE_VERSION), "unable to write state({})", path);
+    try_with!(bincode::serialize_into(&mut writer, &(accounts.len() as u64)), "unable to write state({})", path);
+    for account in accounts {
+        if account.is_projection_only() {
+            bail!("unable to write state({}) account({}) having discarded event history", path, account.client());
+        }
+        let record = AccountRecord::from_account(account);
+        try_with!(bincode::serialize_into(&mut writer, &record), "unable to write state({}) account({})", path, account.client());
+    }
+    try_with!(writer.flush(), "unable to write state({})", path);
+    try_with!(fs::rename(partial_path(path), path), "unable to write state({})", path);
+    Ok(())
+}
+
+/// Returns accounts rehydrated from state saved at `path`.
+///
+/// Account counts of corrupt states are rejected before accounts are allocated (truncated states fail reading).
+pub fn load(path: &str) -> Result<Vec<Account>, SimpleError> {
+    let mut reader = BufReader::new(try_with!(input::open(path), "unable to open state({})", path));
+    let version: u32 = try_with!(bincode::deserialize_from(&mut reader), "unable to read state({})", path);
+    if version != STATE_VERSION {
+        bail!("unsupported state({}) version({})", path, version);
+    }
+    let count: u64 = try_with!(bincode::deserialize_from(&mut reader), "unable to read state({})", path);
+    if count > MAX_ACCOUNTS {
+        bail!("invalid state({}) account count({})", path, count);
+    }
+    let mut accounts = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let record: AccountRecord = try_with!(bincode::deserialize_from(&mut reader), "unable to read state({}) account", path);
+        accounts.push(record.into_account());
+    }
+    Ok(accounts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::prelude::Decimal;
+
+    use crate::events::Actor;
+    use crate::models::{Command, CommandType};
+    use crate::testing::TempDir;
+
+    fn handle(account: &mut Account, name: CommandType, tx: u32, amount: Option<Decimal>) -> Result<(), SimpleError> {
+        let events = account.handle(Command::new(name, account.client(), tx, amount))?;
+        account.apply(events);
+        Ok(())
+    }
+
+    #[test]
+    fn state_keeps_transactions_and_disputes() {
+        let directory = TempDir::new("state");
+        let path = directory.join("accounts.state");
+        let path = path.to_str().unwrap();
+        let mut account = Account::new(1);
+        handle(&mut account, CommandType::Deposit, 1, Some(Decimal::new(15, 1))).unwrap();
+        handle(&mut account, CommandType::Deposit, 2, Some(Decimal::new(10, 1))).unwrap();
+        handle(&mut account, CommandType::Dispute, 1, None).unwrap();
+        save(path, std::iter::once(&account)).unwrap();
+
+        let mut accounts = load(path).unwrap();
+        let account = &mut accounts[0];
+        assert_eq!(account.held(), Decimal::new(15, 1));
+        assert!(handle(account, CommandType::Deposit, 2, Some(Decimal::new(10, 1))).is_err());
+        assert!(handle(account, CommandType::Dispute, 1, None).is_err());
+        handle(account, CommandType::Resolve, 1, None).unwrap();
+        assert_eq!(account.available(), Decimal::new(25, 1));
+        account.discard_history();
+        assert!(save(path, std::iter::once(&*account)).is_err());
+    }
+
+    #[test]
+    fn corrupt_state_rejected() {
+        let directory = TempDir::new("state-corrupt");
+        let path = directory.join("accounts.state");
+        let path = path.to_str().unwrap();
+        let mut state = bincode::serialize(&STATE_VERSION).unwrap();
+        state.extend(bincode::serialize(&u64::MAX).unwrap());
+        fs::write(path, &state).unwrap();
+        assert_eq!(load(path).unwrap_err().as_str(), format!("invalid state({}) account count({})", path, u64::MAX));
+
+        // truncated before every account counted is read
+        let mut state = bincode::serialize(&STATE_VERSION).unwrap();
+        state.extend(bincode::serialize(&2u64).unwrap());
+        fs::write(path, &state).unwrap();
+        assert!(load(path).unwrap_err().as_str().starts_with(&format!("unable to read state({}) account", path)));
+    }
+}
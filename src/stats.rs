@@ -0,0 +1,163 @@
+//! Profile of transaction sources (`stats` subcommand) helping users understand a file before processing it.
+//!
+//! Records are only parsed (transactions aren't handled) so profiles are built in a single pass without accounts.
+//! Amounts of deposits and withdrawals are kept to compute percentiles (nearest rank) once every record is read.
+
+use std::collections::HashSet;
+use std::fmt;
+
+use serde_json::{Value, json};
+
+use crate::events::Cause;
+use crate::models::{ClientId, Command, CommandType, Currency, Timestamp, TransactionId};
+use crate::summary::{NAMES, name_index};
+
+/// Percentiles of amounts reported.
+const PERCENTILES: [(&str, f64); 3] = [("p50", 0.5), ("p90", 0.9), ("p99", 0.99)];
+
+/// Profile of transaction records read.
+pub struct Stats {
+    counts: [usize; 5],
+    unparseable: usize,
+    clients: HashSet<ClientId>,
+    transactions: HashSet<TransactionId>,
+    duplicates: usize,
+    amounts: Vec<Currency>,
+    first: Option<Timestamp>,
+    last: Option<Timestamp>,
+}
+
+impl Stats {
+    /// Returns new `Stats` without records.
+    pub fn new() -> Self {
+        Stats {
+            counts: [0; 5],
+            unparseable: 0,
+            clients: HashSet::new(),
+            transactions: HashSet::new(),
+            duplicates: 0,
+            amounts: vec![],
+            first: None,
+            last: None,
+        }
+    }
+
+    /// Profiles transaction `command` read.
+    pub fn record(&mut self, command: &Command) {
+        self.counts[name_index(command.name())] += 1;
+        self.clients.insert(command.actor_id());
+        // disputes, resolves and chargebacks reference transactions of deposits and withdrawals
+        if let CommandType::Deposit | CommandType::Withdraw = command.name() {
+            if !self.transactions.insert(command.tx()) {
+                self.duplicates += 1;
+            }
+            if let Some(amount) = command.amount() {
+                self.amounts.push(amount);
+            }
+        }
+        if let Some(timestamp) = command.timestamp() {
+            self.first = Some(self.first.map_or(timestamp, |first| { first.min(timestamp) }));
+            self.last = Some(self.last.map_or(timestamp, |last| { last.max(timestamp) }));
+        }
+    }
+
+    /// Counts transaction record unable to be parsed.
+    pub fn count_unparseable(&mut self) {
+        self.unparseable += 1;
+    }
+
+    /// Sorts amounts read so percentiles can be computed (once every record is read).
+    pub fn finish(&mut self) {
+        self.amounts.sort();
+    }
+
+    /// Returns profile (counts by type, clients, amounts percentiles, duplicates and date range) as JSON.
+    pub fn to_json(&self) -> Value {
+        let commands: serde_json::Map<String, Value> = NAMES.iter()
+            .enumerate()
+            .map(|(index, name)| { (name.to_string(), json!(self.counts[index])) })
+            .collect();
+        let mut amounts = serde_json::Map::new();
+        amounts.insert("count".to_string(), json!(self.amounts.len()));
+        amounts.insert("min".to_string(), json!(self.amounts.first()));
+        for (name, percentile) in PERCENTILES {
+            amounts.insert(name.to_string(), json!(self.percentile(percentile)));
+        }
+        amounts.insert("max".to_string(), json!(self.amounts.last()));
+        json!({
+            "records": self.records(),
+            "unparseable": self.unparseable,
+            "commands": commands,
+            "clients": self.clients.len(),
+            "amounts": amounts,
+            "duplicate_tx": self.duplicates,
+            "first_timestamp": self.first,
+            "last_timestamp": self.last,
+        })
+    }
+
+    /// Returns number of transaction records read (including unparseable records).
+    fn records(&self) -> usize {
+        self.counts.iter().sum::<usize>() + self.unparseable
+    }
+
+    /// Returns amount at `percentile` (0 to 1) of sorted amounts (none without amounts).
+    fn percentile(&self, percentile: f64) -> Option<Currency> {
+        let rank = (percentile * self.amounts.len() as f64).ceil() as usize;
+        self.amounts.get(rank.max(1) - 1).copied()
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "transactions: {} (unparseable: {})", self.records(), self.unparseable)?;
+        for (index, name) in NAMES.iter().enumerate() {
+            writeln!(f, "  {}: {}", name, self.counts[index])?;
+        }
+        writeln!(f, "clients: {}", self.clients.len())?;
+        writeln!(f, "amounts: {}", self.amounts.len())?;
+        if let (Some(min), Some(max)) = (self.amounts.first(), self.amounts.last()) {
+            writeln!(f, "  min: {}", min)?;
+            for (name, percentile) in PERCENTILES {
+                writeln!(f, "  {}: {}", name, self.percentile(percentile).unwrap_or_default())?;
+            }
+            writeln!(f, "  max: {}", max)?;
+        }
+        writeln!(f, "duplicate tx: {}", self.duplicates)?;
+        match (self.first, self.last) {
+            (Some(first), Some(last)) => writeln!(f, "dates: {} to {}", first.to_rfc3339(), last.to_rfc3339()),
+            _ => writeln!(f, "dates: none"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+    use rust_decimal::prelude::Decimal;
+
+    #[test]
+    fn records_profiled() {
+        let mut stats = Stats::new();
+        for (index, amount) in (1..=10).rev().enumerate() {
+            let timestamp = Utc.with_ymd_and_hms(2024, 1, 1 + index as u32, 0, 0, 0).unwrap();
+            let command = Command::new(CommandType::Deposit, (amount % 3) as u16, index as u32, Some(Decimal::new(amount, 0)));
+            stats.record(&command.with_timestamp(Some(timestamp)));
+        }
+        stats.record(&Command::new(CommandType::Withdraw, 1, 3, Some(Decimal::new(5, 1))));
+        stats.record(&Command::new(CommandType::Dispute, 1, 3, None));
+        stats.count_unparseable();
+        stats.finish();
+
+        let report = stats.to_string();
+        assert!(report.starts_with("transactions: 13 (unparseable: 1)\n  deposit: 10\n  withdraw: 1\n  dispute: 1\n"), "{}", report);
+        assert!(report.contains("clients: 3\namounts: 11\n  min: 0.5\n  p50: 5\n  p90: 9\n  p99: 10\n  max: 10\n"), "{}", report);
+        assert!(report.contains("duplicate tx: 1\ndates: 2024-01-01T00:00:00+00:00 to 2024-01-10T00:00:00+00:00\n"), "{}", report);
+
+        let profile = stats.to_json();
+        assert_eq!(profile["commands"]["deposit"], json!(10));
+        assert_eq!(profile["amounts"]["p90"], json!("9"));
+        assert_eq!(profile["duplicate_tx"], json!(1));
+    }
+}
@@ -0,0 +1,1640 @@
+//! Stores of `Account` projections keyed by client.
+//!
+//! Accounts are kept in memory by default. Persistent stores keep each account as its metadata and event stream
+//! (rehydrated when read) so state persists across runs and is bounded by disk rather than memory.
+//!
+//! Stores implement `ProjectionStore` so backends are interchangeable behind `open`. Persistent stores can also
+//! write events applied to an outbox (see `outbox`) within the same commit as the account, as well as offsets of
+//! streamed messages (so messages are applied exactly once across interrupted runs).
+
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+#[cfg(any(feature = "sled", feature = "rocksdb", feature = "lmdb"))]
+use std::convert::TryInto;
+use std::str::FromStr;
+
+#[cfg(feature = "redb")]
+use redb::{ReadableDatabase, ReadableTable};
+#[cfg(feature = "sled")]
+use sled::Transactional;
+use serde::{Serialize, Deserialize};
+use simple_error::*;
+
+use crate::models::{Account, AccountMetadata, ClientId, Event, Version};
+use crate::outbox::OutboxEntry;
+
+/// Offsets of stream partitions keyed by partition (offset of the last message applied to the store).
+pub type Offsets = BTreeMap<String, i64>;
+
+/// Kind of store accounts are kept in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StoreKind {
+    Memory,
+    /// Embedded sled database (requires `sled` feature).
+    #[cfg(feature = "sled")]
+    Sled,
+    /// Embedded RocksDB database (requires `rocksdb` feature).
+    #[cfg(feature = "rocksdb")]
+    Rocksdb,
+    /// SQLite database file (requires `sqlite` feature).
+    #[cfg(feature = "sqlite")]
+    Sqlite,
+    /// Embedded LMDB environment (requires `lmdb` feature).
+    #[cfg(feature = "lmdb")]
+    Lmdb,
+    /// Pure-Rust redb database file (requires `redb` feature).
+    #[cfg(feature = "redb")]
+    Redb,
+}
+
+impl StoreKind {
+    /// Returns names of supported stores used for cli arguments.
+    pub fn names() -> Vec<&'static str> {
+        #[allow(unused_mut)]
+        let mut names = vec!["memory"];
+        #[cfg(feature = "sled")]
+        names.push("sled");
+        #[cfg(feature = "rocksdb")]
+        names.push("rocksdb");
+        #[cfg(feature = "sqlite")]
+        names.push("sqlite");
+        #[cfg(feature = "lmdb")]
+        names.push("lmdb");
+        #[cfg(feature = "redb")]
+        names.push("redb");
+        names
+    }
+}
+
+impl FromStr for StoreKind {
+    type Err = SimpleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "memory" => Ok(StoreKind::Memory),
+            #[cfg(feature = "sled")]
+            "sled" => Ok(StoreKind::Sled),
+            #[cfg(feature = "rocksdb")]
+            "rocksdb" => Ok(StoreKind::Rocksdb),
+            #[cfg(feature = "sqlite")]
+            "sqlite" => Ok(StoreKind::Sqlite),
+            #[cfg(feature = "lmdb")]
+            "lmdb" => Ok(StoreKind::Lmdb),
+            #[cfg(feature = "redb")]
+            "redb" => Ok(StoreKind::Redb),
+            _ => Err(SimpleError::new(format!("unsupported store({})", s))),
+        }
+    }
+}
+
+/// Event history kept by accounts of `MemoryStore`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum EventHistory {
+    /// Every event applied is kept (required to save state or replay events of accounts).
+    #[default]
+    Retained,
+    /// Events are discarded once applied (only balances and transaction indexes are kept).
+    Discarded,
+}
+
+/// Returns store of `kind` opened at `path` (required by persistent stores).
+///
+/// Accounts of memory stores keep event `history` (persistent stores always keep events).
+#[cfg_attr(
+    not(any(feature = "sled", feature = "rocksdb", feature = "sqlite", feature = "lmdb", feature = "redb")),
+    allow(unused_variables)
+)]
+pub fn open(kind: StoreKind, path: Option<&str>, history: EventHistory) -> Result<Box<dyn ProjectionStore>, SimpleError> {
+    match kind {
+        StoreKind::Memory => Ok(Box::new(MemoryStore::new(history))),
+        #[cfg(feature = "sled")]
+        StoreKind::Sled => Ok(Box::new(SledStore::open(require_with!(path, "store path is none for sled store"))?)),
+        #[cfg(feature = "rocksdb")]
+        StoreKind::Rocksdb => Ok(Box::new(RocksdbStore::open(require_with!(path, "store path is none for rocksdb store"))?)),
+        #[cfg(feature = "sqlite")]
+        StoreKind::Sqlite => Ok(Box::new(SqliteStore::open(require_with!(path, "store path is none for sqlite store"))?)),
+        #[cfg(feature = "lmdb")]
+        StoreKind::Lmdb => Ok(Box::new(LmdbStore::open(require_with!(path, "store path is none for lmdb store"))?)),
+        #[cfg(feature = "redb")]
+        StoreKind::Redb => Ok(Box::new(RedbStore::open(require_with!(path, "store path is none for redb store"))?)),
+    }
+}
+
+/// Returns `store` of `kind` keeping at most `capacity` accounts in memory (cold accounts are evicted to `store`).
+pub fn spill(kind: StoreKind, store: Box<dyn ProjectionStore>, capacity: usize) -> Result<Box<dyn ProjectionStore>, SimpleError> {
+    if kind == StoreKind::Memory {
+        bail!("accounts can't be evicted to memory store (persistent store required)");
+    }
+    if capacity == 0 {
+        bail!("spill capacity must be at least one account");
+    }
+    Ok(Box::new(SpillStore::new(store, capacity)))
+}
+
+/// Store of `Account` projections keyed by client.
+pub trait ProjectionStore {
+    /// Returns account of `client` (if any).
+    fn get(&self, client: ClientId) -> Result<Option<Cow<'_, Account>>, SimpleError>;
+
+    /// Writes `account` having events applied after `version` (metadata is also written when `version` is zero).
+    fn put(&mut self, account: Account, version: Version) -> Result<(), SimpleError>;
+
+    /// Returns every account of store ordered by client.
+    fn iter(&self) -> Result<Box<dyn Iterator<Item = Cow<'_, Account>> + '_>, SimpleError>;
+
+    /// Writes pending changes of persistent stores to disk.
+    fn flush(&mut self) -> Result<(), SimpleError>;
+
+    /// Returns account of `client` to be updated then put (if any).
+    ///
+    /// Stores able to lend accounts without copying (e.g. in-memory) remove the account until it is put.
+    fn take(&mut self, client: ClientId) -> Result<Option<Account>, SimpleError> {
+        Ok(self.get(client)?.map(Cow::into_owned))
+    }
+
+    /// Returns approximate bytes of accounts kept in memory (zero for stores keeping accounts on disk).
+    fn memory_bytes(&self) -> usize {
+        0
+    }
+
+    /// Releases unused capacity of accounts kept in memory.
+    fn compact(&mut self) {}
+
+    /// Evicts accounts kept in memory to disk until at most `bytes` remain (stores able to spill).
+    fn evict(&mut self, _bytes: usize) -> Result<(), SimpleError> {
+        Ok(())
+    }
+
+    /// Writes events applied by subsequent puts to outbox (within the same commit as the account).
+    fn enable_outbox(&mut self) -> Result<(), SimpleError> {
+        bail!("store has no outbox (persistent store required)")
+    }
+
+    /// Returns up to `limit` events of outbox pending publication ordered by client and version.
+    fn outbox(&self, _limit: usize) -> Result<Vec<OutboxEntry>, SimpleError> {
+        Ok(vec![])
+    }
+
+    /// Marks `entries` of outbox sent (removing them).
+    fn mark_sent(&mut self, _entries: &[OutboxEntry]) -> Result<(), SimpleError> {
+        Ok(())
+    }
+
+    /// Stages `offset` of stream `partition` consumed, written within the same commit as the next put.
+    ///
+    /// Offsets of messages rejected (thus never put) are written once the next offset is staged or store is flushed.
+    fn stage_offset(&mut self, _partition: &str, _offset: i64) -> Result<(), SimpleError> {
+        bail!("store keeps no stream offsets (persistent store required)")
+    }
+
+    /// Returns offsets of stream partitions written.
+    fn offsets(&self) -> Result<Offsets, SimpleError> {
+        bail!("store keeps no stream offsets (persistent store required)")
+    }
+}
+
+impl dyn ProjectionStore + '_ {
+    /// Updates account of `client` (opened by `open` when new) using `update`.
+    ///
+    /// New accounts are only kept when `update` succeeds.
+    pub fn update<T, O, U>(&mut self, client: ClientId, open: O, update: U) -> Result<T, SimpleError>
+    where
+        O: FnOnce() -> Account,
+        U: FnOnce(&mut Account) -> Result<T, SimpleError>,
+    {
+        let existing = self.take(client)?;
+        let is_new = existing.is_none();
+        let mut account = existing.unwrap_or_else(open);
+        let version = account.version();
+        match update(&mut account) {
+            Ok(result) => {
+                self.put(account, version)?;
+                Ok(result)
+            }
+            Err(e) => {
+                // existing accounts are returned (nothing applied is written)
+                if !is_new {
+                    let version = account.version();
+                    self.put(account, version)?;
+                }
+                Err(e)
+            }
+        }
+    }
+}
+
+/// Accounts kept in memory.
+#[derive(Default)]
+pub struct MemoryStore {
+    accounts: HashMap<ClientId, Account>,
+    /// Approximate bytes of accounts.
+    bytes: usize,
+    history: EventHistory,
+}
+
+impl MemoryStore {
+    /// Returns new empty `MemoryStore` keeping event `history` of accounts.
+    pub fn new(history: EventHistory) -> Self {
+        MemoryStore { history, ..Self::default() }
+    }
+
+
+    /// Returns accounts kept (in no particular order).
+    pub fn into_accounts(self) -> impl Iterator<Item = Account> {
+        self.accounts.into_values()
+    }
+}
+
+impl ProjectionStore for MemoryStore {
+    fn get(&self, client: ClientId) -> Result<Option<Cow<'_, Account>>, SimpleError> {
+        Ok(self.accounts.get(&client).map(Cow::Borrowed))
+    }
+
+    fn put(&mut self, mut account: Account, _version: Version) -> Result<(), SimpleError> {
+        if self.history == EventHistory::Discarded {
+            account.discard_history();
+        }
+        self.bytes += account.memory_bytes();
+        if let Some(previous) = self.accounts.insert(account.client(), account) {
+            self.bytes -= previous.memory_bytes();
+        }
+        Ok(())
+    }
+
+    fn iter(&self) -> Result<Box<dyn Iterator<Item = Cow<'_, Account>> + '_>, SimpleError> {
+        let mut accounts: Vec<Cow<Account>> = self.accounts.values().map(Cow::Borrowed).collect();
+        accounts.sort_unstable_by_key(|account| { account.client() });
+        Ok(Box::new(accounts.into_iter()))
+    }
+
+    fn flush(&mut self) -> Result<(), SimpleError> {
+        Ok(())
+    }
+
+    fn take(&mut self, client: ClientId) -> Result<Option<Account>, SimpleError> {
+        let account = self.accounts.remove(&client);
+        if let Some(account) = account.as_ref() {
+            self.bytes -= account.memory_bytes();
+        }
+        Ok(account)
+    }
+
+    fn memory_bytes(&self) -> usize {
+        self.bytes
+    }
+
+    fn compact(&mut self) {
+        self.accounts.shrink_to_fit();
+        for account in self.accounts.values_mut() {
+            account.compact();
+        }
+        self.bytes = self.accounts.values().map(Account::memory_bytes).sum();
+    }
+}
+
+/// Account kept in memory by `SpillStore`.
+struct HotAccount {
+    account: Account,
+    /// Version of account written to cold store.
+    persisted: Version,
+    /// Tick account was last used.
+    used: u64,
+}
+
+/// Accounts kept in memory up to `capacity` with least recently used (cold) accounts evicted to a persistent store.
+///
+/// Evicted accounts are reloaded from the cold store when referenced. Accounts in memory are written to the cold store
+/// when evicted or flushed.
+pub struct SpillStore {
+    hot: HashMap<ClientId, HotAccount>,
+    /// Clients of hot accounts keyed by tick last used (least recently used first).
+    recency: BTreeMap<u64, ClientId>,
+    /// Persisted versions of hot accounts taken to be updated.
+    taken: HashMap<ClientId, Version>,
+    tick: u64,
+    cold: Box<dyn ProjectionStore>,
+    capacity: usize,
+    /// Approximate bytes of hot accounts.
+    bytes: usize,
+}
+
+impl SpillStore {
+    /// Returns store keeping at most `capacity` accounts in memory evicting cold accounts to `cold` store.
+    pub fn new(cold: Box<dyn ProjectionStore>, capacity: usize) -> Self {
+        SpillStore {
+            hot: HashMap::new(),
+            recency: BTreeMap::new(),
+            taken: HashMap::new(),
+            tick: 0,
+            cold,
+            capacity,
+            bytes: 0,
+        }
+    }
+
+    /// Evicts least recently used accounts to cold store until accounts in memory are within capacity and `bytes`.
+    fn evict_until(&mut self, bytes: usize) -> Result<(), SimpleError> {
+        while self.hot.len() > self.capacity || (self.bytes > bytes && !self.hot.is_empty()) {
+            let (_, client) = require_with!(self.recency.pop_first(), "spill store recency is empty");
+            let hot = require_with!(self.hot.remove(&client), "spill store account({}) is none", client);
+            self.bytes -= hot.account.memory_bytes();
+            self.cold.put(hot.account, hot.persisted)?;
+        }
+        Ok(())
+    }
+}
+
+impl ProjectionStore for SpillStore {
+    fn get(&self, client: ClientId) -> Result<Option<Cow<'_, Account>>, SimpleError> {
+        match self.hot.get(&client) {
+            Some(hot) => Ok(Some(Cow::Borrowed(&hot.account))),
+            None => self.cold.get(client),
+        }
+    }
+
+    fn put(&mut self, account: Account, version: Version) -> Result<(), SimpleError> {
+        let client = account.client();
+        let previous = self.hot.remove(&client).map(|hot| {
+            self.recency.remove(&hot.used);
+            self.bytes -= hot.account.memory_bytes();
+            hot.persisted
+        });
+        let persisted = self.taken.remove(&client).or(previous).unwrap_or(version);
+        self.tick += 1;
+        self.recency.insert(self.tick, client);
+        self.bytes += account.memory_bytes();
+        self.hot.insert(client, HotAccount { account, persisted, used: self.tick });
+        self.evict_until(usize::MAX)
+    }
+
+    fn iter(&self) -> Result<Box<dyn Iterator<Item = Cow<'_, Account>> + '_>, SimpleError> {
+        // hot accounts are merged with cold accounts (cold copies of hot accounts are stale)
+        let mut hot: Vec<&Account> = self.hot.values().map(|hot| { &hot.account }).collect();
+        hot.sort_unstable_by_key(|account| { account.client() });
+        let mut hot = hot.into_iter().peekable();
+        let hot_clients = &self.hot;
+        let mut cold = self.cold.iter()?.filter(move |account| { !hot_clients.contains_key(&account.client()) }).peekable();
+        Ok(Box::new(std::iter::from_fn(move || {
+            match (hot.peek(), cold.peek()) {
+                (Some(h), Some(c)) if c.client() < h.client() => cold.next(),
+                (Some(_), _) => hot.next().map(Cow::Borrowed),
+                (None, _) => cold.next(),
+            }
+        })))
+    }
+
+    fn flush(&mut self) -> Result<(), SimpleError> {
+        for hot in self.hot.values_mut() {
+            self.cold.put(hot.account.clone(), hot.persisted)?;
+            hot.persisted = hot.account.version();
+        }
+        self.cold.flush()
+    }
+
+    fn take(&mut self, client: ClientId) -> Result<Option<Account>, SimpleError> {
+        match self.hot.remove(&client) {
+            Some(hot) => {
+                self.recency.remove(&hot.used);
+                self.bytes -= hot.account.memory_bytes();
+                self.taken.insert(client, hot.persisted);
+                Ok(Some(hot.account))
+            }
+            None => self.cold.take(client),
+        }
+    }
+
+    fn memory_bytes(&self) -> usize {
+        self.bytes
+    }
+
+    fn compact(&mut self) {
+        self.hot.shrink_to_fit();
+        for hot in self.hot.values_mut() {
+            hot.account.compact();
+        }
+        self.bytes = self.hot.values().map(|hot| { hot.account.memory_bytes() }).sum();
+    }
+
+    fn enable_outbox(&mut self) -> Result<(), SimpleError> {
+        self.cold.enable_outbox()
+    }
+
+    /// Returns events of outbox of persistent store (events of hot accounts are written once flushed or evicted).
+    fn outbox(&self, limit: usize) -> Result<Vec<OutboxEntry>, SimpleError> {
+        self.cold.outbox(limit)
+    }
+
+    fn mark_sent(&mut self, entries: &[OutboxEntry]) -> Result<(), SimpleError> {
+        self.cold.mark_sent(entries)
+    }
+
+    fn evict(&mut self, bytes: usize) -> Result<(), SimpleError> {
+        self.evict_until(bytes)
+    }
+}
+
+/// Persisted form of an `Account` (metadata and events applied).
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AccountRecord {
+    metadata: AccountMetadata,
+    events: Vec<Event>,
+}
+
+impl AccountRecord {
+    /// Returns record of `account`.
+    pub fn from_account(account: &Account) -> Self {
+        AccountRecord { metadata: account.metadata(), events: account.events().to_vec() }
+    }
+
+    /// Returns account rehydrated from record.
+    pub fn into_account(self) -> Account {
+        let mut account = Account::with_metadata(&self.metadata);
+        account.replay(self.events);
+        account
+    }
+
+    /// Returns record of `client` account decoded from `value`.
+    #[cfg(feature = "sled")]
+    fn decode(client: ClientId, value: &[u8]) -> Result<Self, SimpleError> {
+        Ok(try_with!(bincode::deserialize(value), "unable to decode account({})", client))
+    }
+}
+
+/// Outbox (key-value stores) of events keyed by big-endian client and version.
+#[cfg(any(feature = "sled", feature = "rocksdb", feature = "lmdb"))]
+const OUTBOX: &str = "outbox";
+
+/// Offsets (key-value stores) of stream partitions keyed by partition.
+#[cfg(any(feature = "sled", feature = "rocksdb", feature = "lmdb"))]
+const OFFSETS: &str = "offsets";
+
+/// Returns key of `client` event `version` (big-endian so keys are ordered by client then version).
+#[cfg(any(feature = "sled", feature = "rocksdb", feature = "lmdb"))]
+fn event_key(client: ClientId, version: Version) -> Vec<u8> {
+    [client.to_be_bytes().as_slice(), &version.to_be_bytes()].concat()
+}
+
+/// Returns outbox entry of `key` (client and version) and encoded event `value`.
+#[cfg(any(feature = "sled", feature = "rocksdb", feature = "lmdb"))]
+fn outbox_entry(key: &[u8], value: &[u8]) -> Result<OutboxEntry, SimpleError> {
+    if key.len() != 6 {
+        bail!("invalid outbox key({:?})", key);
+    }
+    let client = ClientId::from_be_bytes([key[0], key[1]]);
+    let version = Version::from_be_bytes([key[2], key[3], key[4], key[5]]);
+    let event = try_with!(bincode::deserialize(value), "unable to decode account({}) event({})", client, version);
+    Ok(OutboxEntry { client, version, event })
+}
+
+/// Returns partition of `key` and its big-endian offset `value`.
+#[cfg(any(feature = "sled", feature = "rocksdb", feature = "lmdb"))]
+fn offset_entry(key: &[u8], value: &[u8]) -> Result<(String, i64), SimpleError> {
+    let partition = try_with!(String::from_utf8(key.to_vec()), "invalid offset partition({:?})", key);
+    let offset = try_with!(value.try_into(), "invalid offset({}) of partition({})", value.len(), partition);
+    Ok((partition, i64::from_be_bytes(offset)))
+}
+
+/// Accounts (records) in a sled database keyed by big-endian client (iterated ordered by client).
+///
+/// Events are written to the `outbox` tree (when enabled) and stream offsets to the `offsets` tree within the same
+/// transaction as their account.
+#[cfg(feature = "sled")]
+pub struct SledStore {
+    db: sled::Db,
+    outbox: Option<sled::Tree>,
+    offsets: sled::Tree,
+    offset: Option<(String, i64)>,
+}
+
+#[cfg(feature = "sled")]
+impl SledStore {
+    /// Returns sled store opened at `path`.
+    pub fn open(path: &str) -> Result<Self, SimpleError> {
+        Self::new(try_with!(sled::open(path), "unable to open sled store({})", path))
+    }
+
+    /// Returns sled store of `db`.
+    fn new(db: sled::Db) -> Result<Self, SimpleError> {
+        let offsets = try_with!(db.open_tree(OFFSETS), "unable to open sled store offsets");
+        Ok(SledStore { db, outbox: None, offsets, offset: None })
+    }
+
+    /// Writes offset staged (if any).
+    fn write_offset(&mut self) -> Result<(), SimpleError> {
+        if let Some((partition, offset)) = self.offset.take() {
+            try_with!(self.offsets.insert(partition.as_bytes(), &offset.to_be_bytes()), "unable to write offset({})", partition);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "sled")]
+impl ProjectionStore for SledStore {
+    fn get(&self, client: ClientId) -> Result<Option<Cow<'_, Account>>, SimpleError> {
+        match try_with!(self.db.get(client.to_be_bytes()), "unable to read account({})", client) {
+            Some(value) => Ok(Some(Cow::Owned(AccountRecord::decode(client, &value)?.into_account()))),
+            None => Ok(None),
+        }
+    }
+
+    fn put(&mut self, account: Account, version: Version) -> Result<(), SimpleError> {
+        let client = account.client();
+        let value = try_with!(bincode::serialize(&AccountRecord::from_account(&account)), "unable to encode account({})", client);
+        let offset = self.offset.take();
+        if self.outbox.is_none() && offset.is_none() {
+            try_with!(self.db.insert(client.to_be_bytes(), value), "unable to write account({})", client);
+            return Ok(());
+        }
+        let mut events = vec![];
+        for (index, event) in account.events().iter().enumerate().skip(version as usize).filter(|_| { self.outbox.is_some() }) {
+            let version = index as Version + 1;
+            let value = try_with!(bincode::serialize(event), "unable to encode account({}) event({})", client, version);
+            events.push((event_key(client, version), value));
+        }
+        let mut trees = vec![&*self.db, &self.offsets];
+        trees.extend(self.outbox.as_ref());
+        let result: sled::transaction::TransactionResult<(), ()> = trees[..].transaction(|trees| {
+            trees[0].insert(&client.to_be_bytes(), value.as_slice())?;
+            if let Some((partition, offset)) = offset.as_ref() {
+                trees[1].insert(partition.as_bytes(), &offset.to_be_bytes())?;
+            }
+            for (key, event) in events.iter() {
+                trees[2].insert(key.as_slice(), event.as_slice())?;
+            }
+            Ok(())
+        });
+        if let Err(e) = result {
+            bail!("unable to write account({}), {:?}", client, e);
+        }
+        Ok(())
+    }
+
+    fn iter(&self) -> Result<Box<dyn Iterator<Item = Cow<'_, Account>> + '_>, SimpleError> {
+        let mut accounts = vec![];
+        for entry in self.db.iter() {
+            let (key, value) = try_with!(entry, "unable to read accounts");
+            let client = ClientId::from_be_bytes([key[0], key[1]]);
+            accounts.push(Cow::Owned(AccountRecord::decode(client, &value)?.into_account()));
+        }
+        Ok(Box::new(accounts.into_iter()))
+    }
+
+    fn flush(&mut self) -> Result<(), SimpleError> {
+        self.write_offset()?;
+        try_with!(self.db.flush(), "unable to flush sled store");
+        Ok(())
+    }
+
+    fn enable_outbox(&mut self) -> Result<(), SimpleError> {
+        self.outbox = Some(try_with!(self.db.open_tree(OUTBOX), "unable to open sled store outbox"));
+        Ok(())
+    }
+
+    fn outbox(&self, limit: usize) -> Result<Vec<OutboxEntry>, SimpleError> {
+        let mut entries = vec![];
+        for entry in self.outbox.iter().flat_map(|outbox| { outbox.iter().take(limit) }) {
+            let (key, value) = try_with!(entry, "unable to read outbox");
+            entries.push(outbox_entry(&key, &value)?);
+        }
+        Ok(entries)
+    }
+
+    fn mark_sent(&mut self, entries: &[OutboxEntry]) -> Result<(), SimpleError> {
+        if let Some(outbox) = self.outbox.as_ref() {
+            let mut batch = sled::Batch::default();
+            for entry in entries {
+                batch.remove(event_key(entry.client, entry.version));
+            }
+            try_with!(outbox.apply_batch(batch), "unable to mark outbox events sent");
+        }
+        Ok(())
+    }
+
+    fn stage_offset(&mut self, partition: &str, offset: i64) -> Result<(), SimpleError> {
+        self.write_offset()?;
+        self.offset = Some((partition.to_string(), offset));
+        Ok(())
+    }
+
+    fn offsets(&self) -> Result<Offsets, SimpleError> {
+        let mut offsets = Offsets::new();
+        for entry in self.offsets.iter() {
+            let (key, value) = try_with!(entry, "unable to read offsets");
+            let (partition, offset) = offset_entry(&key, &value)?;
+            offsets.insert(partition, offset);
+        }
+        Ok(offsets)
+    }
+}
+
+/// Column family (or lmdb database) of account metadata keyed by client.
+#[cfg(any(feature = "rocksdb", feature = "lmdb"))]
+const ACCOUNTS: &str = "accounts";
+
+/// Column family (or lmdb database) of account events keyed by client and version.
+#[cfg(any(feature = "rocksdb", feature = "lmdb"))]
+const EVENTS: &str = "events";
+
+/// Column family of versions of events opening transactions (deposits and withdrawals) keyed by client and tx.
+#[cfg(feature = "rocksdb")]
+const TRANSACTIONS: &str = "transactions";
+
+/// Column families of rocksdb stores.
+#[cfg(feature = "rocksdb")]
+const COLUMN_FAMILIES: [&str; 5] = [ACCOUNTS, EVENTS, TRANSACTIONS, OUTBOX, OFFSETS];
+
+/// Accounts (metadata), events and transaction indexes in column families keyed by big-endian client.
+///
+/// Events are written to the `outbox` column family (when enabled) and stream offsets to the `offsets` column family
+/// within the same batch as their account.
+#[cfg(feature = "rocksdb")]
+pub struct RocksdbStore {
+    db: rocksdb::DB,
+    outbox: bool,
+    offset: Option<(String, i64)>,
+}
+
+#[cfg(feature = "rocksdb")]
+impl RocksdbStore {
+    /// Returns rocksdb store opened (created when missing) at `path`.
+    pub fn open(path: &str) -> Result<Self, SimpleError> {
+        let mut options = rocksdb::Options::default();
+        options.create_if_missing(true);
+        options.create_missing_column_families(true);
+        let db = try_with!(rocksdb::DB::open_cf(&options, path, COLUMN_FAMILIES), "unable to open rocksdb store({})", path);
+        Ok(RocksdbStore { db, outbox: false, offset: None })
+    }
+
+    /// Returns column family `name`.
+    fn cf(&self, name: &str) -> Result<&rocksdb::ColumnFamily, SimpleError> {
+        Ok(require_with!(self.db.cf_handle(name), "column family({}) is none for rocksdb store", name))
+    }
+
+    /// Writes offset staged (if any).
+    fn write_offset(&mut self) -> Result<(), SimpleError> {
+        if let Some((partition, offset)) = self.offset.take() {
+            let cf = self.cf(OFFSETS)?;
+            try_with!(self.db.put_cf(cf, partition.as_bytes(), offset.to_be_bytes()), "unable to write offset({})", partition);
+        }
+        Ok(())
+    }
+
+    /// Returns account of `client` rehydrated from metadata and events (if any).
+    fn load(&self, client: ClientId) -> Result<Option<Account>, SimpleError> {
+        let prefix = client.to_be_bytes();
+        let metadata = match try_with!(self.db.get_cf(self.cf(ACCOUNTS)?, prefix), "unable to read account({})", client) {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        let metadata: AccountMetadata = try_with!(bincode::deserialize(&metadata), "unable to decode account({})", client);
+        let mut events = vec![];
+        let mode = rocksdb::IteratorMode::From(&prefix, rocksdb::Direction::Forward);
+        for entry in self.db.iterator_cf(self.cf(EVENTS)?, mode) {
+            let (key, value) = try_with!(entry, "unable to read account({}) events", client);
+            if !key.starts_with(&prefix) {
+                break;
+            }
+            let event: Event = try_with!(bincode::deserialize(&value), "unable to decode account({}) event", client);
+            events.push(event);
+        }
+        let mut account = Account::with_metadata(&metadata);
+        account.replay(events);
+        Ok(Some(account))
+    }
+}
+
+#[cfg(feature = "rocksdb")]
+impl ProjectionStore for RocksdbStore {
+    fn get(&self, client: ClientId) -> Result<Option<Cow<'_, Account>>, SimpleError> {
+        Ok(self.load(client)?.map(Cow::Owned))
+    }
+
+    /// Writes events applied after `version` (and metadata when `version` is zero) atomically.
+    fn put(&mut self, account: Account, version: Version) -> Result<(), SimpleError> {
+        let client = account.client();
+        let mut batch = rocksdb::WriteBatch::default();
+        if version == 0 {
+            let metadata = try_with!(bincode::serialize(&account.metadata()), "unable to encode account({})", client);
+            batch.put_cf(self.cf(ACCOUNTS)?, client.to_be_bytes(), metadata);
+        }
+        for (index, event) in account.events().iter().enumerate().skip(version as usize) {
+            let version = index as Version + 1;
+            let key = event_key(client, version);
+            let value = try_with!(bincode::serialize(event), "unable to encode account({}) event({})", client, version);
+            if self.outbox {
+                batch.put_cf(self.cf(OUTBOX)?, &key, &value);
+            }
+            batch.put_cf(self.cf(EVENTS)?, key, value);
+            if let Event::Credited { tx, .. } | Event::Debited { tx, .. } = event {
+                let key = [client.to_be_bytes().as_slice(), &tx.to_be_bytes()].concat();
+                batch.put_cf(self.cf(TRANSACTIONS)?, key, version.to_be_bytes());
+            }
+        }
+        if let Some((partition, offset)) = self.offset.take() {
+            batch.put_cf(self.cf(OFFSETS)?, partition.as_bytes(), offset.to_be_bytes());
+        }
+        try_with!(self.db.write(batch), "unable to write account({})", client);
+        Ok(())
+    }
+
+    fn iter(&self) -> Result<Box<dyn Iterator<Item = Cow<'_, Account>> + '_>, SimpleError> {
+        let mut accounts = vec![];
+        for entry in self.db.iterator_cf(self.cf(ACCOUNTS)?, rocksdb::IteratorMode::Start) {
+            let (key, _) = try_with!(entry, "unable to read accounts");
+            let client = ClientId::from_be_bytes([key[0], key[1]]);
+            if let Some(account) = self.load(client)? {
+                accounts.push(Cow::Owned(account));
+            }
+        }
+        Ok(Box::new(accounts.into_iter()))
+    }
+
+    fn flush(&mut self) -> Result<(), SimpleError> {
+        self.write_offset()?;
+        for name in COLUMN_FAMILIES {
+            try_with!(self.db.flush_cf(self.cf(name)?), "unable to flush rocksdb store({})", name);
+        }
+        Ok(())
+    }
+
+    fn enable_outbox(&mut self) -> Result<(), SimpleError> {
+        self.outbox = true;
+        Ok(())
+    }
+
+    fn outbox(&self, limit: usize) -> Result<Vec<OutboxEntry>, SimpleError> {
+        let mut entries = vec![];
+        for entry in self.db.iterator_cf(self.cf(OUTBOX)?, rocksdb::IteratorMode::Start).take(limit) {
+            let (key, value) = try_with!(entry, "unable to read outbox");
+            entries.push(outbox_entry(&key, &value)?);
+        }
+        Ok(entries)
+    }
+
+    fn mark_sent(&mut self, entries: &[OutboxEntry]) -> Result<(), SimpleError> {
+        let mut batch = rocksdb::WriteBatch::default();
+        for entry in entries {
+            batch.delete_cf(self.cf(OUTBOX)?, event_key(entry.client, entry.version));
+        }
+        try_with!(self.db.write(batch), "unable to mark outbox events sent");
+        Ok(())
+    }
+
+    fn stage_offset(&mut self, partition: &str, offset: i64) -> Result<(), SimpleError> {
+        self.write_offset()?;
+        self.offset = Some((partition.to_string(), offset));
+        Ok(())
+    }
+
+    fn offsets(&self) -> Result<Offsets, SimpleError> {
+        let mut offsets = Offsets::new();
+        for entry in self.db.iterator_cf(self.cf(OFFSETS)?, rocksdb::IteratorMode::Start) {
+            let (key, value) = try_with!(entry, "unable to read offsets");
+            let (partition, offset) = offset_entry(&key, &value)?;
+            offsets.insert(partition, offset);
+        }
+        Ok(offsets)
+    }
+}
+
+/// Tables of sqlite stores (events and outbox keyed by client and version).
+#[cfg(feature = "sqlite")]
+const SQLITE_SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS store_accounts (client INTEGER PRIMARY KEY, metadata BLOB NOT NULL);
+    CREATE TABLE IF NOT EXISTS store_events (
+        client INTEGER NOT NULL,
+        version INTEGER NOT NULL,
+        event BLOB NOT NULL,
+        PRIMARY KEY (client, version)
+    );
+    CREATE TABLE IF NOT EXISTS store_outbox (
+        client INTEGER NOT NULL,
+        version INTEGER NOT NULL,
+        event BLOB NOT NULL,
+        PRIMARY KEY (client, version)
+    );
+    CREATE TABLE IF NOT EXISTS store_offsets (stream_partition TEXT PRIMARY KEY, stream_offset INTEGER NOT NULL);
+";
+
+/// Account metadata and events in `store_accounts` and `store_events` tables (committed when flushed).
+///
+/// Events are written to the `store_outbox` table (when enabled) and stream offsets to the `store_offsets` table within
+/// the same transaction as their account.
+#[cfg(feature = "sqlite")]
+pub struct SqliteStore {
+    connection: rusqlite::Connection,
+    outbox: bool,
+}
+
+#[cfg(feature = "sqlite")]
+impl SqliteStore {
+    /// Returns sqlite store opened (tables created when missing) at `path`.
+    pub fn open(path: &str) -> Result<Self, SimpleError> {
+        let connection = try_with!(rusqlite::Connection::open(path), "unable to open sqlite store({})", path);
+        try_with!(connection.execute_batch(SQLITE_SCHEMA), "unable to create sqlite store({}) tables", path);
+        Ok(SqliteStore { connection, outbox: false })
+    }
+
+    /// Returns account of `client` rehydrated from metadata and events (if any).
+    fn load(&self, client: ClientId) -> Result<Option<Account>, SimpleError> {
+        use rusqlite::OptionalExtension;
+
+        let metadata: Option<Vec<u8>> = try_with!(
+            self.connection.prepare_cached("SELECT metadata FROM store_accounts WHERE client = ?")
+                .and_then(|mut statement| { statement.query_row([client], |row| { row.get(0) }).optional() }),
+            "unable to read account({})",
+            client
+        );
+        let metadata: AccountMetadata = match metadata {
+            Some(metadata) => try_with!(bincode::deserialize(&metadata), "unable to decode account({})", client),
+            None => return Ok(None),
+        };
+        let mut statement = try_with!(
+            self.connection.prepare_cached("SELECT event FROM store_events WHERE client = ? ORDER BY version"),
+            "unable to read account({}) events",
+            client
+        );
+        let rows = try_with!(statement.query_map([client], |row| { row.get::<_, Vec<u8>>(0) }), "unable to read account({}) events", client);
+        let mut events = vec![];
+        for row in rows {
+            let row = try_with!(row, "unable to read account({}) events", client);
+            let event: Event = try_with!(bincode::deserialize(&row), "unable to decode account({}) event", client);
+            events.push(event);
+        }
+        let mut account = Account::with_metadata(&metadata);
+        account.replay(events);
+        Ok(Some(account))
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl ProjectionStore for SqliteStore {
+    fn get(&self, client: ClientId) -> Result<Option<Cow<'_, Account>>, SimpleError> {
+        Ok(self.load(client)?.map(Cow::Owned))
+    }
+
+    /// Writes events applied after `version` (and metadata when `version` is zero).
+    ///
+    /// Writes are batched in a transaction committed when the store is flushed.
+    fn put(&mut self, account: Account, version: Version) -> Result<(), SimpleError> {
+        let client = account.client();
+        if self.connection.is_autocommit() {
+            try_with!(self.connection.execute_batch("BEGIN"), "unable to write account({})", client);
+        }
+        if version == 0 {
+            let metadata = try_with!(bincode::serialize(&account.metadata()), "unable to encode account({})", client);
+            try_with!(
+                self.connection.prepare_cached("INSERT OR REPLACE INTO store_accounts (client, metadata) VALUES (?, ?)")
+                    .and_then(|mut statement| { statement.execute(rusqlite::params![client, metadata]) }),
+                "unable to write account({})",
+                client
+            );
+        }
+        for (index, event) in account.events().iter().enumerate().skip(version as usize) {
+            let version = index as Version + 1;
+            let value = try_with!(bincode::serialize(event), "unable to encode account({}) event({})", client, version);
+            try_with!(
+                self.connection.prepare_cached("INSERT INTO store_events (client, version, event) VALUES (?, ?, ?)")
+                    .and_then(|mut statement| { statement.execute(rusqlite::params![client, version, value]) }),
+                "unable to write account({}) event({})",
+                client,
+                version
+            );
+            if self.outbox {
+                try_with!(
+                    self.connection.prepare_cached("INSERT INTO store_outbox (client, version, event) VALUES (?, ?, ?)")
+                        .and_then(|mut statement| { statement.execute(rusqlite::params![client, version, value]) }),
+                    "unable to write account({}) event({}) to outbox",
+                    client,
+                    version
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn iter(&self) -> Result<Box<dyn Iterator<Item = Cow<'_, Account>> + '_>, SimpleError> {
+        let mut statement = try_with!(self.connection.prepare("SELECT client FROM store_accounts ORDER BY client"), "unable to read accounts");
+        let clients = try_with!(statement.query_map([], |row| { row.get::<_, ClientId>(0) }), "unable to read accounts");
+        let mut accounts = vec![];
+        for client in clients {
+            let client = try_with!(client, "unable to read accounts");
+            if let Some(account) = self.load(client)? {
+                accounts.push(Cow::Owned(account));
+            }
+        }
+        Ok(Box::new(accounts.into_iter()))
+    }
+
+    fn flush(&mut self) -> Result<(), SimpleError> {
+        if !self.connection.is_autocommit() {
+            try_with!(self.connection.execute_batch("COMMIT"), "unable to commit sqlite store");
+        }
+        Ok(())
+    }
+
+    fn enable_outbox(&mut self) -> Result<(), SimpleError> {
+        self.outbox = true;
+        Ok(())
+    }
+
+    fn outbox(&self, limit: usize) -> Result<Vec<OutboxEntry>, SimpleError> {
+        let mut statement = try_with!(
+            self.connection.prepare_cached("SELECT client, version, event FROM store_outbox ORDER BY client, version LIMIT ?"),
+            "unable to read outbox"
+        );
+        let rows = try_with!(
+            statement.query_map([limit as i64], |row| { Ok((row.get::<_, ClientId>(0)?, row.get::<_, Version>(1)?, row.get::<_, Vec<u8>>(2)?)) }),
+            "unable to read outbox"
+        );
+        let mut entries = vec![];
+        for row in rows {
+            let (client, version, value) = try_with!(row, "unable to read outbox");
+            let event = try_with!(bincode::deserialize(&value), "unable to decode account({}) event({})", client, version);
+            entries.push(OutboxEntry { client, version, event });
+        }
+        Ok(entries)
+    }
+
+    /// Removes `entries` from outbox (committed when flushed).
+    fn mark_sent(&mut self, entries: &[OutboxEntry]) -> Result<(), SimpleError> {
+        if self.connection.is_autocommit() {
+            try_with!(self.connection.execute_batch("BEGIN"), "unable to mark outbox events sent");
+        }
+        for entry in entries {
+            try_with!(
+                self.connection.prepare_cached("DELETE FROM store_outbox WHERE client = ? AND version = ?")
+                    .and_then(|mut statement| { statement.execute(rusqlite::params![entry.client, entry.version]) }),
+                "unable to mark outbox event({}/{}) sent",
+                entry.client,
+                entry.version
+            );
+        }
+        Ok(())
+    }
+
+    /// Writes `offset` of `partition` within the transaction of accounts (committed when flushed).
+    fn stage_offset(&mut self, partition: &str, offset: i64) -> Result<(), SimpleError> {
+        if self.connection.is_autocommit() {
+            try_with!(self.connection.execute_batch("BEGIN"), "unable to write offset({})", partition);
+        }
+        try_with!(
+            self.connection.prepare_cached("INSERT OR REPLACE INTO store_offsets (stream_partition, stream_offset) VALUES (?, ?)")
+                .and_then(|mut statement| { statement.execute(rusqlite::params![partition, offset]) }),
+            "unable to write offset({})",
+            partition
+        );
+        Ok(())
+    }
+
+    fn offsets(&self) -> Result<Offsets, SimpleError> {
+        let mut statement = try_with!(self.connection.prepare_cached("SELECT stream_partition, stream_offset FROM store_offsets"), "unable to read offsets");
+        let rows = try_with!(
+            statement.query_map([], |row| { Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?)) }),
+            "unable to read offsets"
+        );
+        let mut offsets = Offsets::new();
+        for row in rows {
+            let (partition, offset) = try_with!(row, "unable to read offsets");
+            offsets.insert(partition, offset);
+        }
+        Ok(offsets)
+    }
+}
+
+/// Untyped lmdb database of big-endian keys and bincode values.
+#[cfg(feature = "lmdb")]
+type LmdbDatabase = heed::Database<heed::types::Bytes, heed::types::Bytes>;
+
+/// Maximum size of lmdb stores (address space reserved rather than allocated).
+#[cfg(feature = "lmdb")]
+const LMDB_MAP_SIZE: usize = 1 << 38;
+
+/// Account metadata and events in `accounts` and `events` databases keyed by big-endian client (synced when flushed).
+///
+/// Events are written to the `outbox` database (when enabled) and stream offsets to the `offsets` database within the
+/// same transaction as their account.
+#[cfg(feature = "lmdb")]
+pub struct LmdbStore {
+    env: heed::Env,
+    accounts: LmdbDatabase,
+    events: LmdbDatabase,
+    outbox: Option<LmdbDatabase>,
+    offsets: LmdbDatabase,
+    offset: Option<(String, i64)>,
+}
+
+#[cfg(feature = "lmdb")]
+impl LmdbStore {
+    /// Returns lmdb store opened (created when missing) at directory `path`.
+    pub fn open(path: &str) -> Result<Self, SimpleError> {
+        try_with!(std::fs::create_dir_all(path), "unable to create lmdb store({})", path);
+        // SAFETY: the environment is opened once per process and writes are synced when flushed
+        let env = try_with!(
+            unsafe {
+                heed::EnvOpenOptions::new()
+                    .map_size(LMDB_MAP_SIZE)
+                    .max_dbs(4)
+                    .flags(heed::EnvFlags::NO_SYNC)
+                    .open(path)
+            },
+            "unable to open lmdb store({})",
+            path
+        );
+        let mut txn = try_with!(env.write_txn(), "unable to open lmdb store({})", path);
+        let accounts = try_with!(env.create_database(&mut txn, Some(ACCOUNTS)), "unable to open lmdb store({})", path);
+        let events = try_with!(env.create_database(&mut txn, Some(EVENTS)), "unable to open lmdb store({})", path);
+        let offsets = try_with!(env.create_database(&mut txn, Some(OFFSETS)), "unable to open lmdb store({})", path);
+        try_with!(txn.commit(), "unable to open lmdb store({})", path);
+        Ok(LmdbStore { env, accounts, events, outbox: None, offsets, offset: None })
+    }
+
+    /// Writes offset staged (if any).
+    fn write_offset(&mut self) -> Result<(), SimpleError> {
+        if let Some((partition, offset)) = self.offset.take() {
+            let mut txn = try_with!(self.env.write_txn(), "unable to write offset({})", partition);
+            try_with!(self.offsets.put(&mut txn, partition.as_bytes(), &offset.to_be_bytes()), "unable to write offset({})", partition);
+            try_with!(txn.commit(), "unable to write offset({})", partition);
+        }
+        Ok(())
+    }
+
+    /// Returns account of `client` rehydrated from metadata and events (if any).
+    fn load(&self, client: ClientId) -> Result<Option<Account>, SimpleError> {
+        let txn = try_with!(self.env.read_txn(), "unable to read account({})", client);
+        let prefix = client.to_be_bytes();
+        let metadata: AccountMetadata = match try_with!(self.accounts.get(&txn, &prefix), "unable to read account({})", client) {
+            Some(value) => try_with!(bincode::deserialize(value), "unable to decode account({})", client),
+            None => return Ok(None),
+        };
+        let mut events = vec![];
+        for entry in try_with!(self.events.prefix_iter(&txn, &prefix), "unable to read account({}) events", client) {
+            let (_, value) = try_with!(entry, "unable to read account({}) events", client);
+            let event: Event = try_with!(bincode::deserialize(value), "unable to decode account({}) event", client);
+            events.push(event);
+        }
+        let mut account = Account::with_metadata(&metadata);
+        account.replay(events);
+        Ok(Some(account))
+    }
+}
+
+#[cfg(feature = "lmdb")]
+impl ProjectionStore for LmdbStore {
+    fn get(&self, client: ClientId) -> Result<Option<Cow<'_, Account>>, SimpleError> {
+        Ok(self.load(client)?.map(Cow::Owned))
+    }
+
+    /// Writes events applied after `version` (and metadata when `version` is zero) atomically.
+    fn put(&mut self, account: Account, version: Version) -> Result<(), SimpleError> {
+        let client = account.client();
+        let mut txn = try_with!(self.env.write_txn(), "unable to write account({})", client);
+        if version == 0 {
+            let metadata = try_with!(bincode::serialize(&account.metadata()), "unable to encode account({})", client);
+            try_with!(self.accounts.put(&mut txn, &client.to_be_bytes(), &metadata), "unable to write account({})", client);
+        }
+        for (index, event) in account.events().iter().enumerate().skip(version as usize) {
+            let version = index as Version + 1;
+            let key = event_key(client, version);
+            let value = try_with!(bincode::serialize(event), "unable to encode account({}) event({})", client, version);
+            try_with!(self.events.put(&mut txn, &key, &value), "unable to write account({}) event({})", client, version);
+            if let Some(outbox) = self.outbox.as_ref() {
+                try_with!(outbox.put(&mut txn, &key, &value), "unable to write account({}) event({}) to outbox", client, version);
+            }
+        }
+        if let Some((partition, offset)) = self.offset.take() {
+            try_with!(self.offsets.put(&mut txn, partition.as_bytes(), &offset.to_be_bytes()), "unable to write offset({})", partition);
+        }
+        try_with!(txn.commit(), "unable to write account({})", client);
+        Ok(())
+    }
+
+    fn iter(&self) -> Result<Box<dyn Iterator<Item = Cow<'_, Account>> + '_>, SimpleError> {
+        // clients are read before accounts as lmdb allows one read transaction per thread
+        let mut clients = vec![];
+        let txn = try_with!(self.env.read_txn(), "unable to read accounts");
+        for entry in try_with!(self.accounts.iter(&txn), "unable to read accounts") {
+            let (key, _) = try_with!(entry, "unable to read accounts");
+            clients.push(ClientId::from_be_bytes([key[0], key[1]]));
+        }
+        drop(txn);
+        let mut accounts = vec![];
+        for client in clients {
+            if let Some(account) = self.load(client)? {
+                accounts.push(Cow::Owned(account));
+            }
+        }
+        Ok(Box::new(accounts.into_iter()))
+    }
+
+    fn flush(&mut self) -> Result<(), SimpleError> {
+        self.write_offset()?;
+        try_with!(self.env.force_sync(), "unable to flush lmdb store");
+        Ok(())
+    }
+
+    fn enable_outbox(&mut self) -> Result<(), SimpleError> {
+        let mut txn = try_with!(self.env.write_txn(), "unable to open lmdb store outbox");
+        let outbox = try_with!(self.env.create_database(&mut txn, Some(OUTBOX)), "unable to open lmdb store outbox");
+        try_with!(txn.commit(), "unable to open lmdb store outbox");
+        self.outbox = Some(outbox);
+        Ok(())
+    }
+
+    fn outbox(&self, limit: usize) -> Result<Vec<OutboxEntry>, SimpleError> {
+        let mut entries = vec![];
+        if let Some(outbox) = self.outbox.as_ref() {
+            let txn = try_with!(self.env.read_txn(), "unable to read outbox");
+            for entry in try_with!(outbox.iter(&txn), "unable to read outbox").take(limit) {
+                let (key, value) = try_with!(entry, "unable to read outbox");
+                entries.push(outbox_entry(key, value)?);
+            }
+        }
+        Ok(entries)
+    }
+
+    fn mark_sent(&mut self, entries: &[OutboxEntry]) -> Result<(), SimpleError> {
+        if let Some(outbox) = self.outbox.as_ref() {
+            let mut txn = try_with!(self.env.write_txn(), "unable to mark outbox events sent");
+            for entry in entries {
+                try_with!(outbox.delete(&mut txn, &event_key(entry.client, entry.version)), "unable to mark outbox events sent");
+            }
+            try_with!(txn.commit(), "unable to mark outbox events sent");
+        }
+        Ok(())
+    }
+
+    fn stage_offset(&mut self, partition: &str, offset: i64) -> Result<(), SimpleError> {
+        self.write_offset()?;
+        self.offset = Some((partition.to_string(), offset));
+        Ok(())
+    }
+
+    fn offsets(&self) -> Result<Offsets, SimpleError> {
+        let mut offsets = Offsets::new();
+        let txn = try_with!(self.env.read_txn(), "unable to read offsets");
+        for entry in try_with!(self.offsets.iter(&txn), "unable to read offsets") {
+            let (key, value) = try_with!(entry, "unable to read offsets");
+            let (partition, offset) = offset_entry(key, value)?;
+            offsets.insert(partition, offset);
+        }
+        Ok(offsets)
+    }
+}
+
+/// Table of redb stores having account metadata keyed by client.
+#[cfg(feature = "redb")]
+const REDB_ACCOUNTS: redb::TableDefinition<ClientId, &[u8]> = redb::TableDefinition::new("accounts");
+
+/// Table of redb stores having account events keyed by client and version.
+#[cfg(feature = "redb")]
+const REDB_EVENTS: redb::TableDefinition<(ClientId, Version), &[u8]> = redb::TableDefinition::new("events");
+
+/// Table of redb stores having events pending publication keyed by client and version.
+#[cfg(feature = "redb")]
+const REDB_OUTBOX: redb::TableDefinition<(ClientId, Version), &[u8]> = redb::TableDefinition::new("outbox");
+
+/// Table of redb stores having offsets of stream partitions keyed by partition.
+#[cfg(feature = "redb")]
+const REDB_OFFSETS: redb::TableDefinition<&str, i64> = redb::TableDefinition::new("offsets");
+
+/// Account metadata and events in `accounts` and `events` tables keyed by client (persisted when flushed).
+///
+/// Events are written to the `outbox` table (when enabled) and stream offsets to the `offsets` table within the same
+/// transaction as their account.
+#[cfg(feature = "redb")]
+pub struct RedbStore {
+    db: redb::Database,
+    outbox: bool,
+    offset: Option<(String, i64)>,
+}
+
+#[cfg(feature = "redb")]
+impl RedbStore {
+    /// Returns redb store opened (created when missing) at `path`.
+    pub fn open(path: &str) -> Result<Self, SimpleError> {
+        let db = try_with!(redb::Database::create(path), "unable to open redb store({})", path);
+        // tables are created up front so read transactions of empty stores find them
+        let txn = try_with!(db.begin_write(), "unable to open redb store({})", path);
+        try_with!(txn.open_table(REDB_ACCOUNTS), "unable to open redb store({})", path);
+        try_with!(txn.open_table(REDB_EVENTS), "unable to open redb store({})", path);
+        try_with!(txn.open_table(REDB_OUTBOX), "unable to open redb store({})", path);
+        try_with!(txn.open_table(REDB_OFFSETS), "unable to open redb store({})", path);
+        try_with!(txn.commit(), "unable to open redb store({})", path);
+        Ok(RedbStore { db, outbox: false, offset: None })
+    }
+
+    /// Writes offset staged (if any) within transaction `txn`.
+    fn write_offset(&mut self, txn: &redb::WriteTransaction) -> Result<(), SimpleError> {
+        if let Some((partition, offset)) = self.offset.take() {
+            let mut offsets = try_with!(txn.open_table(REDB_OFFSETS), "unable to write offset({})", partition);
+            try_with!(offsets.insert(partition.as_str(), offset), "unable to write offset({})", partition);
+        }
+        Ok(())
+    }
+
+    /// Returns account of `client` rehydrated from metadata and events (if any).
+    fn load(&self, client: ClientId) -> Result<Option<Account>, SimpleError> {
+        let txn = try_with!(self.db.begin_read(), "unable to read account({})", client);
+        let accounts = try_with!(txn.open_table(REDB_ACCOUNTS), "unable to read account({})", client);
+        let metadata: AccountMetadata = match try_with!(accounts.get(client), "unable to read account({})", client) {
+            Some(value) => try_with!(bincode::deserialize(value.value()), "unable to decode account({})", client),
+            None => return Ok(None),
+        };
+        let table = try_with!(txn.open_table(REDB_EVENTS), "unable to read account({}) events", client);
+        let mut events = vec![];
+        for entry in try_with!(table.range((client, 0)..=(client, Version::MAX)), "unable to read account({}) events", client) {
+            let (_, value) = try_with!(entry, "unable to read account({}) events", client);
+            let event: Event = try_with!(bincode::deserialize(value.value()), "unable to decode account({}) event", client);
+            events.push(event);
+        }
+        let mut account = Account::with_metadata(&metadata);
+        account.replay(events);
+        Ok(Some(account))
+    }
+}
+
+#[cfg(feature = "redb")]
+impl ProjectionStore for RedbStore {
+    fn get(&self, client: ClientId) -> Result<Option<Cow<'_, Account>>, SimpleError> {
+        Ok(self.load(client)?.map(Cow::Owned))
+    }
+
+    /// Writes events applied after `version` (and metadata when `version` is zero) atomically.
+    ///
+    /// Writes are committed without syncing (persisted when the store is flushed).
+    fn put(&mut self, account: Account, version: Version) -> Result<(), SimpleError> {
+        let client = account.client();
+        let mut txn = try_with!(self.db.begin_write(), "unable to write account({})", client);
+        try_with!(txn.set_durability(redb::Durability::None), "unable to write account({})", client);
+        {
+            if version == 0 {
+                let metadata = try_with!(bincode::serialize(&account.metadata()), "unable to encode account({})", client);
+                let mut accounts = try_with!(txn.open_table(REDB_ACCOUNTS), "unable to write account({})", client);
+                try_with!(accounts.insert(client, metadata.as_slice()), "unable to write account({})", client);
+            }
+            let mut events = try_with!(txn.open_table(REDB_EVENTS), "unable to write account({})", client);
+            let mut outbox = try_with!(txn.open_table(REDB_OUTBOX), "unable to write account({})", client);
+            for (index, event) in account.events().iter().enumerate().skip(version as usize) {
+                let version = index as Version + 1;
+                let value = try_with!(bincode::serialize(event), "unable to encode account({}) event({})", client, version);
+                try_with!(events.insert((client, version), value.as_slice()), "unable to write account({}) event({})", client, version);
+                if self.outbox {
+                    try_with!(
+                        outbox.insert((client, version), value.as_slice()),
+                        "unable to write account({}) event({}) to outbox",
+                        client,
+                        version
+                    );
+                }
+            }
+        }
+        self.write_offset(&txn)?;
+        try_with!(txn.commit(), "unable to write account({})", client);
+        Ok(())
+    }
+
+    fn iter(&self) -> Result<Box<dyn Iterator<Item = Cow<'_, Account>> + '_>, SimpleError> {
+        let txn = try_with!(self.db.begin_read(), "unable to read accounts");
+        let table = try_with!(txn.open_table(REDB_ACCOUNTS), "unable to read accounts");
+        let mut accounts = vec![];
+        for entry in try_with!(table.iter(), "unable to read accounts") {
+            let (client, _) = try_with!(entry, "unable to read accounts");
+            if let Some(account) = self.load(client.value())? {
+                accounts.push(Cow::Owned(account));
+            }
+        }
+        Ok(Box::new(accounts.into_iter()))
+    }
+
+    fn flush(&mut self) -> Result<(), SimpleError> {
+        // an immediate commit persists every preceding non-durable commit
+        let mut txn = try_with!(self.db.begin_write(), "unable to flush redb store");
+        try_with!(txn.set_durability(redb::Durability::Immediate), "unable to flush redb store");
+        self.write_offset(&txn)?;
+        try_with!(txn.commit(), "unable to flush redb store");
+        Ok(())
+    }
+
+    fn enable_outbox(&mut self) -> Result<(), SimpleError> {
+        self.outbox = true;
+        Ok(())
+    }
+
+    fn outbox(&self, limit: usize) -> Result<Vec<OutboxEntry>, SimpleError> {
+        let txn = try_with!(self.db.begin_read(), "unable to read outbox");
+        let table = try_with!(txn.open_table(REDB_OUTBOX), "unable to read outbox");
+        let mut entries = vec![];
+        for entry in try_with!(table.iter(), "unable to read outbox").take(limit) {
+            let (key, value) = try_with!(entry, "unable to read outbox");
+            let (client, version) = key.value();
+            let event = try_with!(bincode::deserialize(value.value()), "unable to decode account({}) event({})", client, version);
+            entries.push(OutboxEntry { client, version, event });
+        }
+        Ok(entries)
+    }
+
+    /// Removes `entries` from outbox (persisted when flushed).
+    fn mark_sent(&mut self, entries: &[OutboxEntry]) -> Result<(), SimpleError> {
+        let mut txn = try_with!(self.db.begin_write(), "unable to mark outbox events sent");
+        try_with!(txn.set_durability(redb::Durability::None), "unable to mark outbox events sent");
+        {
+            let mut outbox = try_with!(txn.open_table(REDB_OUTBOX), "unable to mark outbox events sent");
+            for entry in entries {
+                try_with!(outbox.remove((entry.client, entry.version)), "unable to mark outbox events sent");
+            }
+        }
+        try_with!(txn.commit(), "unable to mark outbox events sent");
+        Ok(())
+    }
+
+    fn stage_offset(&mut self, partition: &str, offset: i64) -> Result<(), SimpleError> {
+        if self.offset.is_some() {
+            let mut txn = try_with!(self.db.begin_write(), "unable to write offset({})", partition);
+            try_with!(txn.set_durability(redb::Durability::None), "unable to write offset({})", partition);
+            self.write_offset(&txn)?;
+            try_with!(txn.commit(), "unable to write offset({})", partition);
+        }
+        self.offset = Some((partition.to_string(), offset));
+        Ok(())
+    }
+
+    fn offsets(&self) -> Result<Offsets, SimpleError> {
+        let txn = try_with!(self.db.begin_read(), "unable to read offsets");
+        let table = try_with!(txn.open_table(REDB_OFFSETS), "unable to read offsets");
+        let mut offsets = Offsets::new();
+        for entry in try_with!(table.iter(), "unable to read offsets") {
+            let (partition, offset) = try_with!(entry, "unable to read offsets");
+            offsets.insert(partition.value().to_string(), offset.value());
+        }
+        Ok(offsets)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::prelude::Decimal;
+
+    use crate::events::{Actor, Effects};
+    use crate::models::{Command, CommandType};
+    #[cfg(any(feature = "sled", feature = "rocksdb", feature = "sqlite", feature = "lmdb", feature = "redb"))]
+    use crate::testing::TempDir;
+
+    fn deposit(store: &mut dyn ProjectionStore, client: ClientId, tx: u32) -> Result<Effects<Event>, SimpleError> {
+        store.update(client, || { Account::new(client) }, |account| {
+            let events = account.handle(Command::new(CommandType::Deposit, client, tx, Some(Decimal::new(15, 1))))?;
+            account.apply(events.clone());
+            Ok(events)
+        })
+    }
+
+    fn assert_store(store: &mut dyn ProjectionStore) {
+        deposit(store, 2, 1).unwrap();
+        deposit(store, 1, 2).unwrap();
+        deposit(store, 1, 3).unwrap();
+        assert!(deposit(store, 1, 3).is_err());
+        assert!(store.update(3, || { Account::new(3) }, |_| { bail!("declined") as Result<(), _> }).is_err());
+
+        let clients: Vec<ClientId> = store.iter().unwrap().map(|account| { account.client() }).collect();
+        assert_eq!(clients, vec![1, 2]);
+        assert_eq!(store.get(1).unwrap().unwrap().total(), Decimal::new(30, 1));
+        assert!(store.get(3).unwrap().is_none());
+    }
+
+    /// Asserts events applied to accounts of `store` (see `assert_store`) are written to its outbox until sent.
+    #[cfg(any(feature = "sled", feature = "rocksdb", feature = "sqlite", feature = "lmdb", feature = "redb"))]
+    fn assert_outbox(store: &mut dyn ProjectionStore) {
+        let pending = |store: &dyn ProjectionStore| -> Vec<(ClientId, Version)> {
+            store.outbox(10).unwrap().iter().map(|entry| { (entry.client, entry.version) }).collect()
+        };
+        store.enable_outbox().unwrap();
+        deposit(store, 2, 10).unwrap();
+        deposit(store, 1, 11).unwrap();
+        assert!(deposit(store, 1, 11).is_err());
+        store.flush().unwrap();
+        assert_eq!(pending(store), vec![(1, 3), (2, 2)]);
+        let entries = store.outbox(1).unwrap();
+        assert_eq!(entries[0].event, store.get(1).unwrap().unwrap().events()[2]);
+        store.mark_sent(&entries).unwrap();
+        store.flush().unwrap();
+        assert_eq!(pending(store), vec![(2, 2)]);
+    }
+
+    /// Asserts messages consumed by `store` of `kind` at `path` are applied exactly once across crashes.
+    ///
+    /// Stores are dropped unflushed (after a message is applied or once its offset is staged) then reopened resuming
+    /// after offsets written; balances must equal those of an uninterrupted run.
+    #[cfg(any(feature = "sled", feature = "rocksdb", feature = "sqlite", feature = "lmdb", feature = "redb"))]
+    fn assert_exactly_once(kind: StoreKind, path: &std::path::Path) {
+        // message 4 repeats transaction of message 1 (rejected)
+        let messages: Vec<(ClientId, u32)> = vec![(1, 1), (2, 2), (3, 3), (1, 4), (2, 2), (3, 6), (1, 7), (2, 8), (3, 9), (1, 10)];
+        // sled releases its lock once its flusher thread stops (shortly after store is dropped)
+        let reopen = || {
+            for _ in 0..100 {
+                match open(kind, path.to_str(), EventHistory::Retained) {
+                    Ok(store) => return store,
+                    Err(_) => std::thread::sleep(std::time::Duration::from_millis(10)),
+                }
+            }
+            open(kind, path.to_str(), EventHistory::Retained).unwrap()
+        };
+        let consume = |crash: Option<(i64, bool)>| {
+            let mut store = reopen();
+            let resumed = store.offsets().unwrap().get("topic/0").copied();
+            for (offset, (client, tx)) in messages.iter().enumerate() {
+                let offset = offset as i64;
+                if resumed.is_some_and(|resumed| { offset <= resumed }) {
+                    continue;
+                }
+                store.stage_offset("topic/0", offset).unwrap();
+                if crash == Some((offset, false)) {
+                    return;
+                }
+                deposit(store.as_mut(), *client, *tx).ok();
+                if crash == Some((offset, true)) {
+                    return;
+                }
+            }
+            store.flush().unwrap();
+        };
+        consume(Some((2, true)));
+        consume(Some((4, true)));
+        consume(Some((6, false)));
+        consume(None);
+
+        let store = reopen();
+        assert_eq!(store.offsets().unwrap().get("topic/0"), Some(&9));
+        let totals: Vec<(ClientId, Decimal)> = store.iter().unwrap().map(|account| { (account.client(), account.total()) }).collect();
+        assert_eq!(totals, vec![(1, Decimal::new(60, 1)), (2, Decimal::new(30, 1)), (3, Decimal::new(45, 1))]);
+    }
+
+    #[test]
+    fn memory_store_updates_accounts() {
+        assert_store(&mut MemoryStore::default());
+        assert!(MemoryStore::default().enable_outbox().is_err());
+        assert!(MemoryStore::default().stage_offset("topic/0", 0).is_err() && MemoryStore::default().offsets().is_err());
+
+        let mut store = MemoryStore::new(EventHistory::Discarded);
+        assert_store(&mut store);
+        let account = store.get(1).unwrap().unwrap();
+        assert!(account.is_projection_only() && account.events().is_empty());
+        assert_eq!(account.version(), 2);
+    }
+
+    #[test]
+    fn spill_store_evicts_and_reloads_accounts() {
+        assert_store(&mut SpillStore::new(Box::new(MemoryStore::default()), 1));
+
+        let mut store = SpillStore::new(Box::new(MemoryStore::default()), 2);
+        for client in 1..=4 {
+            deposit(&mut store, client, client as u32).unwrap();
+        }
+        assert_eq!(store.hot.len(), 2);
+        assert!(store.cold.get(1).unwrap().is_some());
+        // evicted account is reloaded when referenced
+        deposit(&mut store, 1, 5).unwrap();
+        assert!(deposit(&mut store, 1, 5).is_err());
+        assert_eq!(store.get(1).unwrap().unwrap().total(), Decimal::new(30, 1));
+        let clients: Vec<ClientId> = store.iter().unwrap().map(|account| { account.client() }).collect();
+        assert_eq!(clients, vec![1, 2, 3, 4]);
+        store.flush().unwrap();
+        assert_eq!(store.cold.get(1).unwrap().unwrap().version(), 2);
+    }
+
+    #[cfg(feature = "sled")]
+    #[test]
+    fn sled_store_updates_accounts() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let mut store = SledStore::new(db).unwrap();
+        assert_store(&mut store);
+        assert_outbox(&mut store);
+
+        let directory = TempDir::new("store-sled");
+        let path = directory.join("offsets");
+        assert_exactly_once(StoreKind::Sled, &path);
+    }
+
+    #[cfg(feature = "rocksdb")]
+    #[test]
+    fn rocksdb_store_updates_accounts() {
+        let directory = TempDir::new("store-rocksdb");
+        let path = directory.join("accounts");
+        {
+            let mut store = open(StoreKind::Rocksdb, path.to_str(), EventHistory::Retained).unwrap();
+            assert_store(store.as_mut());
+        }
+        // accounts persist across runs
+        let mut store = open(StoreKind::Rocksdb, path.to_str(), EventHistory::Retained).unwrap();
+        assert_eq!(store.get(1).unwrap().unwrap().events().len(), 2);
+        assert_outbox(store.as_mut());
+        drop(store);
+
+        let path = directory.join("offsets");
+        assert_exactly_once(StoreKind::Rocksdb, &path);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn sqlite_store_updates_accounts() {
+        let directory = TempDir::new("store-sqlite");
+        let path = directory.join("accounts.db");
+        {
+            let mut store = open(StoreKind::Sqlite, path.to_str(), EventHistory::Retained).unwrap();
+            assert_store(store.as_mut());
+            store.flush().unwrap();
+        }
+        // accounts persist across runs
+        let mut store = open(StoreKind::Sqlite, path.to_str(), EventHistory::Retained).unwrap();
+        assert_eq!(store.get(1).unwrap().unwrap().events().len(), 2);
+        assert_outbox(store.as_mut());
+        drop(store);
+
+        let path = directory.join("offsets.db");
+        assert_exactly_once(StoreKind::Sqlite, &path);
+    }
+
+    #[cfg(feature = "lmdb")]
+    #[test]
+    fn lmdb_store_updates_accounts() {
+        let directory = TempDir::new("store-lmdb");
+        let path = directory.join("accounts");
+        {
+            let mut store = open(StoreKind::Lmdb, path.to_str(), EventHistory::Retained).unwrap();
+            assert_store(store.as_mut());
+            store.flush().unwrap();
+        }
+        // accounts persist across runs
+        let mut store = open(StoreKind::Lmdb, path.to_str(), EventHistory::Retained).unwrap();
+        assert_eq!(store.get(1).unwrap().unwrap().events().len(), 2);
+        assert_outbox(store.as_mut());
+        drop(store);
+
+        let path = directory.join("offsets");
+        assert_exactly_once(StoreKind::Lmdb, &path);
+    }
+
+    #[cfg(feature = "redb")]
+    #[test]
+    fn redb_store_updates_accounts() {
+        let directory = TempDir::new("store-redb");
+        let path = directory.join("accounts.redb");
+        {
+            let mut store = open(StoreKind::Redb, path.to_str(), EventHistory::Retained).unwrap();
+            assert_store(store.as_mut());
+            store.flush().unwrap();
+        }
+        // accounts persist across runs
+        let mut store = open(StoreKind::Redb, path.to_str(), EventHistory::Retained).unwrap();
+        assert_eq!(store.get(1).unwrap().unwrap().events().len(), 2);
+        assert_outbox(store.as_mut());
+        drop(store);
+
+        let path = directory.join("offsets.redb");
+        assert_exactly_once(StoreKind::Redb, &path);
+    }
+
+    /// Compares throughput of stores (run using `cargo test --release --features rocksdb -- --ignored --nocapture`).
+    #[test]
+    #[ignore = "benchmark"]
+    fn store_throughput() {
+        use std::time::Instant;
+
+        let run = |name: &str, store: &mut dyn ProjectionStore| {
+            let started = Instant::now();
+            for tx in 0..100_000 {
+                deposit(store, (tx % 1_000) as ClientId, tx).unwrap();
+            }
+            store.flush().unwrap();
+            let elapsed = started.elapsed().as_secs_f64();
+            println!("{}: {:.3}s ({:.0} transactions/s)", name, elapsed, 100_000.0 / elapsed);
+        };
+        run("memory", &mut MemoryStore::default());
+        #[cfg(feature = "sled")]
+        run("sled", &mut SledStore::new(sled::Config::new().temporary(true).open().unwrap()).unwrap());
+        #[cfg(feature = "rocksdb")]
+        {
+            let directory = TempDir::new("bench-rocksdb");
+            let path = directory.join("accounts");
+            run("rocksdb", open(StoreKind::Rocksdb, path.to_str(), EventHistory::Retained).unwrap().as_mut());
+        }
+        #[cfg(feature = "sqlite")]
+        {
+            let directory = TempDir::new("bench-sqlite");
+            let path = directory.join("accounts.db");
+            run("sqlite", open(StoreKind::Sqlite, path.to_str(), EventHistory::Retained).unwrap().as_mut());
+        }
+        #[cfg(feature = "lmdb")]
+        {
+            let directory = TempDir::new("bench-lmdb");
+            let path = directory.join("accounts");
+            run("lmdb", open(StoreKind::Lmdb, path.to_str(), EventHistory::Retained).unwrap().as_mut());
+        }
+        #[cfg(feature = "redb")]
+        {
+            let directory = TempDir::new("bench-redb");
+            let path = directory.join("accounts.redb");
+            run("redb", open(StoreKind::Redb, path.to_str(), EventHistory::Retained).unwrap().as_mut());
+        }
+    }
+}
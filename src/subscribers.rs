@@ -0,0 +1,297 @@
+//! Subscribers observing account events applied by the processing loop (projections, sinks and notifications).
+//!
+//! The `EventBus` dispatches events applied by each command to every subscriber in the order subscribed, so read
+//! models and destinations are added by subscribing rather than by wiring them into the processing loop. Subscribers
+//! are finished (reports written, writers flushed) once every source is processed.
+
+use std::collections::HashMap;
+use std::io::Write;
+
+use csv::Writer;
+use simple_error::*;
+
+use crate::audit::AuditRecord;
+use crate::eventlog::EventLogWriter;
+use crate::events::{Actor, Cause};
+use crate::eventstore::{EventStore, SegmentEventStore};
+use crate::merchants::{Merchant, Settlement};
+use crate::models::{ClientId, Event, MerchantId};
+use crate::output::RecordWriter;
+use crate::projections::{CategoryTotals, HistoryRecord, MonthlyTotals};
+use crate::store::ProjectionStore;
+
+/// Observer of account events applied.
+pub trait EventSubscriber {
+    /// Observes `events` applied to account of `client` (already updated in `accounts`).
+    fn on_events(&mut self, client: ClientId, events: &[Event], accounts: &dyn ProjectionStore) -> Result<(), SimpleError>;
+
+    /// Completes subscriber once every event is observed (e.g. writes reports or flushes writers).
+    fn finish(&mut self) -> Result<(), SimpleError> {
+        Ok(())
+    }
+}
+
+/// Dispatches account events applied to subscribers (in the order subscribed).
+#[derive(Default)]
+pub struct EventBus {
+    subscribers: Vec<Box<dyn EventSubscriber>>,
+}
+
+impl EventBus {
+    /// Returns new `EventBus` without subscribers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `subscriber` observing events published after.
+    pub fn subscribe(&mut self, subscriber: Box<dyn EventSubscriber>) {
+        self.subscribers.push(subscriber);
+    }
+
+    /// Dispatches `events` applied to account of `client` to every subscriber.
+    pub fn publish(&mut self, client: ClientId, events: &[Event], accounts: &dyn ProjectionStore) -> Result<(), SimpleError> {
+        for subscriber in self.subscribers.iter_mut() {
+            subscriber.on_events(client, events, accounts)?;
+        }
+        Ok(())
+    }
+
+    /// Finishes every subscriber.
+    pub fn finish(&mut self) -> Result<(), SimpleError> {
+        for subscriber in self.subscribers.iter_mut() {
+            subscriber.finish()?;
+        }
+        Ok(())
+    }
+}
+
+/// Running balance history of balance-affecting events written as csv.
+pub struct History<W: Write> {
+    writer: Writer<W>,
+}
+
+impl<W: Write> History<W> {
+    /// Returns new `History` writing records to `writer`.
+    pub fn new(writer: Writer<W>) -> Self {
+        History { writer }
+    }
+}
+
+impl<W: Write> EventSubscriber for History<W> {
+    fn on_events(&mut self, client: ClientId, events: &[Event], accounts: &dyn ProjectionStore) -> Result<(), SimpleError> {
+        if let Some(account) = accounts.get(client)? {
+            for record in events.iter().filter_map(|event| { HistoryRecord::from_event(&account, event) }) {
+                try_with!(self.writer.serialize(record), "unable to write account({}) history", client);
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), SimpleError> {
+        try_with!(self.writer.flush(), "unable to flush history");
+        Ok(())
+    }
+}
+
+impl EventSubscriber for SegmentEventStore {
+    fn on_events(&mut self, client: ClientId, events: &[Event], _: &dyn ProjectionStore) -> Result<(), SimpleError> {
+        self.append(client, events)
+    }
+
+    fn finish(&mut self) -> Result<(), SimpleError> {
+        self.flush()
+    }
+}
+
+impl<W: Write> EventSubscriber for EventLogWriter<W> {
+    fn on_events(&mut self, client: ClientId, events: &[Event], _: &dyn ProjectionStore) -> Result<(), SimpleError> {
+        for event in events {
+            try_with!(self.write(client, event), "unable to write account({}) event to event log", client);
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), SimpleError> {
+        try_with!(self.flush(), "unable to flush event log");
+        Ok(())
+    }
+}
+
+/// Audit trail of every event written as `AuditRecord`s.
+pub struct AuditTrail<W: Write + Send> {
+    writer: RecordWriter<W>,
+}
+
+impl<W: Write + Send> AuditTrail<W> {
+    /// Returns new `AuditTrail` writing records to `writer`.
+    pub fn new(writer: RecordWriter<W>) -> Self {
+        AuditTrail { writer }
+    }
+}
+
+impl<W: Write + Send> EventSubscriber for AuditTrail<W> {
+    fn on_events(&mut self, client: ClientId, events: &[Event], _: &dyn ProjectionStore) -> Result<(), SimpleError> {
+        for event in events {
+            try_with!(self.writer.serialize(AuditRecord::from_event(client, event)), "unable to write account({}) event to audit trail", client);
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), SimpleError> {
+        try_with!(self.writer.finish(), "unable to finish audit trail");
+        Ok(())
+    }
+}
+
+/// Totals (monthly or category) projected from events written as csv report once finished.
+pub struct TotalsReport<T, W: Write> {
+    totals: T,
+    writer: Writer<W>,
+}
+
+impl<T, W: Write> TotalsReport<T, W> {
+    /// Returns new `TotalsReport` projecting `totals` written to `writer`.
+    pub fn new(totals: T, writer: Writer<W>) -> Self {
+        TotalsReport { totals, writer }
+    }
+}
+
+impl<W: Write> EventSubscriber for TotalsReport<MonthlyTotals, W> {
+    fn on_events(&mut self, client: ClientId, events: &[Event], _: &dyn ProjectionStore) -> Result<(), SimpleError> {
+        for event in events {
+            self.totals.project(client, event);
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), SimpleError> {
+        for record in self.totals.records() {
+            try_with!(self.writer.serialize(record), "unable to write monthly totals");
+        }
+        try_with!(self.writer.flush(), "unable to flush monthly totals");
+        Ok(())
+    }
+}
+
+impl<W: Write> EventSubscriber for TotalsReport<CategoryTotals, W> {
+    fn on_events(&mut self, client: ClientId, events: &[Event], _: &dyn ProjectionStore) -> Result<(), SimpleError> {
+        for event in events {
+            self.totals.project(client, event);
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), SimpleError> {
+        for record in self.totals.records() {
+            try_with!(self.writer.serialize(record), "unable to write category totals");
+        }
+        try_with!(self.writer.flush(), "unable to flush category totals");
+        Ok(())
+    }
+}
+
+/// Merchants settling the other side of account events written (ordered by id) as csv report once finished.
+pub struct SettlementReport<W: Write> {
+    merchants: HashMap<MerchantId, Merchant>,
+    writer: Writer<W>,
+}
+
+impl<W: Write> SettlementReport<W> {
+    /// Returns new `SettlementReport` writing merchants to `writer`.
+    pub fn new(writer: Writer<W>) -> Self {
+        SettlementReport { merchants: HashMap::new(), writer }
+    }
+}
+
+impl<W: Write> EventSubscriber for SettlementReport<W> {
+    fn on_events(&mut self, client: ClientId, events: &[Event], _: &dyn ProjectionStore) -> Result<(), SimpleError> {
+        for settlement in events.iter().filter_map(|event| { Settlement::from_event(client, event) }) {
+            let merchant = self.merchants
+                .entry(settlement.actor_id())
+                .or_insert_with_key(|id| { Merchant::new(*id) });
+            if let Ok(events) = merchant.handle(settlement) {
+                merchant.apply(events);
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), SimpleError> {
+        let mut merchants: Vec<(&MerchantId, &Merchant)> = self.merchants.iter().collect();
+        merchants.sort_unstable_by_key(|(id, _)| { **id });
+        for (_, merchant) in merchants {
+            try_with!(self.writer.serialize(merchant), "unable to write merchant settlements");
+        }
+        try_with!(self.writer.flush(), "unable to flush merchant settlements");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use rust_decimal::prelude::Decimal;
+
+    use crate::models::{Account, Command, CommandType};
+    use crate::store::MemoryStore;
+    use crate::testing::TempDir;
+
+    /// Subscriber recording name of subscriber and events observed (or finish).
+    struct Recorder {
+        name: &'static str,
+        observed: Rc<RefCell<Vec<String>>>,
+    }
+
+    impl EventSubscriber for Recorder {
+        fn on_events(&mut self, client: ClientId, events: &[Event], accounts: &dyn ProjectionStore) -> Result<(), SimpleError> {
+            assert!(accounts.get(client)?.is_some());
+            for event in events {
+                self.observed.borrow_mut().push(format!("{} {} {}", self.name, client, event.name()));
+            }
+            Ok(())
+        }
+
+        fn finish(&mut self) -> Result<(), SimpleError> {
+            self.observed.borrow_mut().push(format!("{} finished", self.name));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn events_dispatched_in_order_subscribed() {
+        let mut accounts = MemoryStore::default();
+        let mut account = Account::new(1);
+        let events = account.handle(Command::new(CommandType::Deposit, 1, 1, Some(Decimal::new(15, 1)))).unwrap();
+        account.apply(events.clone());
+        accounts.put(account, 0).unwrap();
+
+        let observed = Rc::new(RefCell::new(vec![]));
+        let mut bus = EventBus::new();
+        for name in ["first", "second"] {
+            bus.subscribe(Box::new(Recorder { name, observed: observed.clone() }));
+        }
+        bus.publish(1, &events, &accounts).unwrap();
+        bus.finish().unwrap();
+        assert_eq!(*observed.borrow(), vec!["first 1 credited", "second 1 credited", "first finished", "second finished"]);
+    }
+
+    #[test]
+    fn settlements_written_by_merchant() {
+        let mut bus = EventBus::new();
+        let directory = TempDir::new("settlements");
+        let path = directory.join("settlements.csv");
+        bus.subscribe(Box::new(SettlementReport::new(Writer::from_path(&path).unwrap())));
+        let accounts = MemoryStore::default();
+        for (merchant, tx) in [(2, 1), (1, 2), (2, 3)] {
+            let command = Command::new(CommandType::Deposit, 1, tx, Some(Decimal::new(1, 0))).with_merchant(Some(merchant));
+            let events = Account::new(1).handle(command).unwrap();
+            bus.publish(1, &events, &accounts).unwrap();
+        }
+        bus.finish().unwrap();
+        let report = std::fs::read_to_string(&path).unwrap();
+        let merchants: Vec<&str> = report.lines().skip(1).map(|line| { line.split(',').next().unwrap() }).collect();
+        assert_eq!(merchants, vec!["1", "2"]);
+    }
+}
@@ -0,0 +1,209 @@
+//! End-of-run summary of transactions processed and resulting account balances.
+//!
+//! Summaries are rendered as text (`--summary`) or as JSON metrics (`--metrics-json`) exported by batch jobs to
+//! monitoring.
+
+use std::borrow::Borrow;
+use std::fmt;
+use std::time::Duration;
+
+use serde_json::{Value, json};
+
+use crate::metrics::QueueStats;
+use crate::models::{Account, CommandType, Currency};
+
+/// Names of command types ordered as counted.
+pub const NAMES: [&str; 5] = ["deposit", "withdraw", "dispute", "resolve", "chargeback"];
+
+/// Returns index of command type `name` in `NAMES`.
+pub fn name_index(name: &CommandType) -> usize {
+    match name {
+        CommandType::Deposit => 0,
+        CommandType::Withdraw => 1,
+        CommandType::Dispute => 2,
+        CommandType::Resolve => 3,
+        CommandType::Chargeback => 4,
+    }
+}
+
+/// Counts of transactions (accepted and rejected by type) and totals of accounts for a run.
+pub struct Summary {
+    accepted: [usize; 5],
+    rejected: [usize; 5],
+    unparseable: usize,
+    accounts: usize,
+    locked: usize,
+    available: Currency,
+    held: Currency,
+    total: Currency,
+    elapsed: Duration,
+    queues: Vec<QueueStats>,
+}
+
+impl Summary {
+    /// Returns new `Summary` without transactions or accounts.
+    pub fn new() -> Self {
+        Summary {
+            accepted: [0; 5],
+            rejected: [0; 5],
+            unparseable: 0,
+            accounts: 0,
+            locked: 0,
+            available: Currency::new(0, 4),
+            held: Currency::new(0, 4),
+            total: Currency::new(0, 4),
+            elapsed: Duration::default(),
+            queues: vec![],
+        }
+    }
+
+    /// Counts transaction of type `name` as accepted or rejected.
+    pub fn count(&mut self, name: &CommandType, accepted: bool) {
+        let index = name_index(name);
+        if accepted {
+            self.accepted[index] += 1;
+        } else {
+            self.rejected[index] += 1;
+        }
+    }
+
+    /// Counts transaction record unable to be parsed.
+    pub fn count_unparseable(&mut self) {
+        self.unparseable += 1;
+    }
+
+    /// Completes summary using `accounts` (borrowed or owned) balances and `elapsed` wall-clock time of run.
+    pub fn finish<A: Borrow<Account>, I: IntoIterator<Item = A>>(&mut self, accounts: I, elapsed: Duration) {
+        for account in accounts {
+            let account = account.borrow();
+            self.accounts += 1;
+            if account.locked() {
+                self.locked += 1;
+            }
+            self.available += account.available();
+            self.held += account.held();
+            self.total += account.total();
+        }
+        self.elapsed = elapsed;
+    }
+
+    /// Sets metrics of queues between stages of run.
+    pub fn queues(&mut self, queues: Vec<QueueStats>) {
+        self.queues = queues;
+    }
+
+    /// Returns metrics of run (records read, commands applied and rejected, elapsed time, throughput and queues) as
+    /// JSON.
+    pub fn to_json(&self) -> Value {
+        let commands: serde_json::Map<String, Value> = NAMES.iter()
+            .enumerate()
+            .map(|(index, name)| {
+                (name.to_string(), json!({ "applied": self.accepted[index], "rejected": self.rejected[index] }))
+            })
+            .collect();
+        let queues: Vec<Value> = self.queues.iter()
+            .map(|queue| {
+                json!({
+                    "name": queue.name,
+                    "capacity": queue.capacity,
+                    "queued": queue.queued,
+                    "peak": queue.peak,
+                    "sent": queue.sent,
+                    "blocked_seconds": queue.blocked.as_secs_f64(),
+                    "waited_seconds": queue.waited.as_secs_f64(),
+                })
+            })
+            .collect();
+        json!({
+            "records": self.processed(),
+            "applied": self.accepted.iter().sum::<usize>(),
+            "rejected": self.rejected.iter().sum::<usize>(),
+            "unparseable": self.unparseable,
+            "commands": commands,
+            "accounts": self.accounts,
+            "locked": self.locked,
+            "elapsed_seconds": self.elapsed.as_secs_f64(),
+            "rows_per_second": self.throughput(),
+            "queues": queues,
+        })
+    }
+
+    /// Returns number of transactions read (including unparseable records).
+    fn processed(&self) -> usize {
+        self.accepted.iter().sum::<usize>() + self.rejected.iter().sum::<usize>() + self.unparseable
+    }
+
+    /// Returns transactions read per second of elapsed time.
+    fn throughput(&self) -> f64 {
+        let seconds = self.elapsed.as_secs_f64();
+        if seconds > 0.0 { self.processed() as f64 / seconds } else { 0.0 }
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let processed = self.processed();
+        writeln!(
+            f,
+            "transactions: {} (accepted: {}, rejected: {}, unparseable: {})",
+            processed,
+            self.accepted.iter().sum::<usize>(),
+            self.rejected.iter().sum::<usize>(),
+            self.unparseable
+        )?;
+        for (index, name) in NAMES.iter().enumerate() {
+            writeln!(f, "  {}: {} (rejected: {})", name, self.accepted[index] + self.rejected[index], self.rejected[index])?;
+        }
+        writeln!(f, "accounts: {} (locked: {})", self.accounts, self.locked)?;
+        writeln!(f, "  available: {}", self.available)?;
+        writeln!(f, "  held: {}", self.held)?;
+        writeln!(f, "  total: {}", self.total)?;
+        if !self.queues.is_empty() {
+            writeln!(f, "queues: {}", self.queues.len())?;
+            for queue in self.queues.iter() {
+                writeln!(f, "  {}", queue)?;
+            }
+        }
+        writeln!(f, "elapsed: {:.3}s ({:.0} transactions/s)", self.elapsed.as_secs_f64(), self.throughput())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::prelude::Decimal;
+
+    use crate::events::Actor;
+    use crate::metrics::QueueMetrics;
+    use crate::models::Command;
+
+    #[test]
+    fn summary_counts_transactions_and_accounts() {
+        let mut account = Account::new(1);
+        let events = account.handle(Command::new(CommandType::Deposit, 1, 1, Some(Decimal::new(15, 1)))).unwrap();
+        account.apply(events);
+
+        let mut summary = Summary::new();
+        summary.count(&CommandType::Deposit, true);
+        summary.count(&CommandType::Withdraw, false);
+        summary.count_unparseable();
+        summary.finish(vec![&account, &Account::new(2)], Duration::from_secs(2));
+        let queue = QueueMetrics::new("read(tx.csv)", 256);
+        queue.sent(3);
+        queue.received(3);
+        summary.queues(vec![queue.stats()]);
+        let report = summary.to_string();
+
+        assert!(report.starts_with("transactions: 3 (accepted: 1, rejected: 1, unparseable: 1)\n"));
+        assert!(report.contains("  withdraw: 1 (rejected: 1)\n"));
+        assert!(report.contains("accounts: 2 (locked: 0)\n  available: 1.5"));
+        assert!(report.contains("queues: 1\n  read(tx.csv): 0/256 queued (peak: 3, sent: 3, blocked: 0.000s, waited: 0.000s)\n"));
+        assert!(report.ends_with("elapsed: 2.000s (2 transactions/s)\n"));
+
+        let metrics = summary.to_json();
+        assert_eq!((metrics["records"].as_u64(), metrics["applied"].as_u64(), metrics["rejected"].as_u64()), (Some(3), Some(1), Some(1)));
+        assert_eq!(metrics["commands"]["withdraw"], json!({ "applied": 0, "rejected": 1 }));
+        assert_eq!((metrics["elapsed_seconds"].as_f64(), metrics["rows_per_second"].as_f64()), (Some(2.0), Some(1.5)));
+        assert_eq!(metrics["queues"][0]["peak"].as_u64(), Some(3));
+    }
+}
@@ -0,0 +1,180 @@
+//! Tracing of runs: logs written to stderr and spans of processing stages (exported using OTLP with `otlp` feature).
+//!
+//! Stages of a run are traced by spans so slow stages can be identified on real data:
+//! - `run` of every stage of a batch run
+//! - `handle` of commands of a source (or of sources merged or sorted) with `parse` of records on its reading thread
+//! - `apply` of accounts to the store (subscribers finished, accounts flushed and outbox relayed)
+//! - `write` of accounts to outputs
+//!
+//! Commands are traced by `command` spans (`trace` level). Logs and spans are filtered by `RUST_LOG` (`info` by default,
+//! e.g. `RUST_LOG=trace` traces every command) unless a `Verbosity` is requested using `-v` (repeated) or `--quiet`:
+//! - `--quiet` logs errors only
+//! - `-v` logs rejects, progress of sources and timing of stages (`debug` level)
+//! - `-vv` logs every command handled (`trace` level of this crate)
+//! - `-vvv` logs everything (`trace` level of dependencies too)
+//!
+//! Logs are plain text or JSON objects (one per line) with fields of events at the top level (e.g. `client`, `tx` and
+//! `code` of rejects), so log pipelines ingest them without regexes.
+
+use std::io;
+use std::str::FromStr;
+
+use simple_error::*;
+use tracing_subscriber::fmt;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::EnvFilter;
+
+#[cfg(feature = "otlp")]
+use opentelemetry::trace::TracerProvider as _;
+#[cfg(feature = "otlp")]
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+#[cfg(feature = "otlp")]
+use opentelemetry_sdk::trace::SdkTracerProvider;
+
+/// Name of service spans are exported as.
+#[cfg(feature = "otlp")]
+const SERVICE_NAME: &str = "accounts-aggregate";
+
+/// Records of a source handled between progress logs (`debug` level).
+pub const PROGRESS_RECORDS: u64 = 1_000_000;
+
+/// Level of logs and spans unless filtered by `RUST_LOG`.
+const DEFAULT_FILTER: &str = "info";
+
+/// Verbosity of logs requested on the command line.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Verbosity {
+    /// Errors only.
+    Quiet,
+    /// Filtered by `RUST_LOG` (`info` by default).
+    Default,
+    /// Number of `-v` flags supplied.
+    Verbose(u64),
+}
+
+impl Verbosity {
+    /// Returns verbosity of `quiet` flag and number of `verbose` flags supplied (quiet taking precedence).
+    pub fn new(quiet: bool, verbose: u64) -> Self {
+        match (quiet, verbose) {
+            (true, _) => Verbosity::Quiet,
+            (false, 0) => Verbosity::Default,
+            (false, verbose) => Verbosity::Verbose(verbose),
+        }
+    }
+
+    /// Returns filter directives of verbosity (none by default so `RUST_LOG` applies).
+    fn directives(&self) -> Option<&'static str> {
+        match self {
+            Verbosity::Quiet => Some("error"),
+            Verbosity::Default => None,
+            Verbosity::Verbose(1) => Some("debug"),
+            Verbosity::Verbose(2) => Some("debug,accounts_aggregate=trace"),
+            Verbosity::Verbose(_) => Some("trace"),
+        }
+    }
+
+    /// Returns filter of logs and spans (verbosity requested, otherwise `RUST_LOG` or `DEFAULT_FILTER`).
+    fn filter(&self) -> EnvFilter {
+        match self.directives() {
+            Some(directives) => EnvFilter::new(directives),
+            None => EnvFilter::try_from_default_env().unwrap_or_else(|_| { EnvFilter::new(DEFAULT_FILTER) }),
+        }
+    }
+}
+
+/// Format of logs written to stderr.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogFormat {
+    /// Human readable lines.
+    Text,
+    /// JSON object per line (timestamp, level, target, message and fields).
+    Json,
+}
+
+impl LogFormat {
+    /// Returns names of supported formats used for cli arguments.
+    pub fn names() -> Vec<&'static str> {
+        vec!["text", "json"]
+    }
+}
+
+impl FromStr for LogFormat {
+    type Err = SimpleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(SimpleError::new(format!("unsupported log format({})", s))),
+        }
+    }
+}
+
+/// Tracing installed for the process (spans exported are flushed once dropped).
+pub struct Telemetry {
+    #[cfg(feature = "otlp")]
+    provider: Option<SdkTracerProvider>,
+}
+
+/// Installs tracing of the process logging to stderr in `format` filtered by `verbosity` and exporting spans to OTLP/HTTP
+/// `otlp_endpoint` (when supplied).
+#[cfg_attr(not(feature = "otlp"), allow(unused_variables))]
+pub fn init(format: LogFormat, verbosity: Verbosity, otlp_endpoint: Option<&str>) -> Result<Telemetry, SimpleError> {
+    let filter = verbosity.filter();
+    let (text, json) = match format {
+        LogFormat::Text => (Some(fmt::layer().with_writer(io::stderr)), None),
+        LogFormat::Json => (None, Some(fmt::layer().with_writer(io::stderr).json().flatten_event(true))),
+    };
+    let registry = tracing_subscriber::registry().with(filter).with(text).with(json);
+    #[cfg(feature = "otlp")]
+    {
+        let provider = match otlp_endpoint {
+            Some(endpoint) => {
+                let exporter = try_with!(
+                    SpanExporter::builder().with_http().with_endpoint(endpoint).build(),
+                    "unable to export spans to otlp endpoint({})", endpoint
+                );
+                let resource = opentelemetry_sdk::Resource::builder().with_service_name(SERVICE_NAME).build();
+                Some(SdkTracerProvider::builder().with_batch_exporter(exporter).with_resource(resource).build())
+            }
+            None => None,
+        };
+        let layer = provider.as_ref().map(|provider| {
+            tracing_opentelemetry::layer().with_tracer(provider.tracer(SERVICE_NAME))
+        });
+        try_with!(registry.with(layer).try_init(), "unable to install tracing");
+        Ok(Telemetry { provider })
+    }
+    #[cfg(not(feature = "otlp"))]
+    {
+        try_with!(registry.try_init(), "unable to install tracing");
+        Ok(Telemetry {})
+    }
+}
+
+impl Drop for Telemetry {
+    /// Flushes spans not yet exported.
+    fn drop(&mut self) {
+        #[cfg(feature = "otlp")]
+        if let Some(provider) = self.provider.take() {
+            if let Err(e) = provider.shutdown() {
+                tracing::warn!("unable to export spans, {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verbosity_of_flags() {
+        assert_eq!(Verbosity::new(true, 2), Verbosity::Quiet);
+        assert_eq!(Verbosity::new(false, 0).directives(), None);
+        assert_eq!(Verbosity::new(false, 1).directives(), Some("debug"));
+        assert_eq!(Verbosity::new(false, 2).directives(), Some("debug,accounts_aggregate=trace"));
+        assert_eq!(Verbosity::new(false, 5).directives(), Some("trace"));
+    }
+}
@@ -0,0 +1,39 @@
+//! Fixtures shared by unit tests.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Count of temporary directories created by tests of process (keeping directories of parallel tests apart).
+static CREATED: AtomicUsize = AtomicUsize::new(0);
+
+/// Temporary directory unique to a test (removed with its files when dropped).
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// Creates temporary directory named using `name`, process id and count of directories created.
+    pub fn new(name: &str) -> Self {
+        let count = CREATED.fetch_add(1, Ordering::Relaxed);
+        let path = env::temp_dir().join(format!("accounts-aggregate-{}-{}-{}", name, process::id(), count));
+        fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+
+    /// Returns path of temporary directory.
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    /// Returns path of `file` within temporary directory.
+    pub fn join<P: AsRef<Path>>(&self, file: P) -> PathBuf {
+        self.0.join(file)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
@@ -0,0 +1,176 @@
+//! Validation of transaction sources without processing them (`verify` subcommand).
+//!
+//! Csv sources are checked for the columns of transactions, then every row is parsed and checked against rules not
+//! depending on account state (amounts of deposits and withdrawals supplied, positive and of at most `MAX_SCALE`
+//! decimal places) so bad files are caught before a long processing run. Rows failing are reported by line and the
+//! exit code tells valid sources (0) apart from sources having invalid rows (1) or that can't be read (2).
+
+use std::fmt;
+use std::io::{Read, Write};
+
+use simple_error::*;
+
+use crate::input::{self, CsvCommandReader, SourceReader, csv_reader, missing_header};
+use crate::models::{Command, CommandType};
+
+/// Decimal places of amounts at most.
+pub const MAX_SCALE: u32 = 4;
+
+/// Outcome of verifying sources (ordered by severity).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verdict {
+    /// Every row of every source is valid.
+    Valid,
+    /// Rows of sources are invalid.
+    Invalid,
+    /// Sources can't be read (missing, unreadable or without columns of transactions).
+    Unreadable,
+}
+
+impl Verdict {
+    /// Returns exit code of verdict.
+    pub fn code(&self) -> i32 {
+        match self {
+            Verdict::Valid => 0,
+            Verdict::Invalid => 1,
+            Verdict::Unreadable => 2,
+        }
+    }
+}
+
+/// Error of `source` row at `line` (line 0 when source itself is invalid).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    source: String,
+    line: usize,
+    reason: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            0 => write!(f, "{}: {}", self.source, self.reason),
+            line => write!(f, "{}:{}: {}", self.source, line, self.reason),
+        }
+    }
+}
+
+/// Verification of sources writing findings (a line each) to `W` as found.
+pub struct Verification<W: Write> {
+    writer: W,
+    sources: usize,
+    rows: usize,
+    invalid: usize,
+    verdict: Verdict,
+}
+
+impl<W: Write> Verification<W> {
+    /// Returns new `Verification` writing findings to `writer`.
+    pub fn new(writer: W) -> Self {
+        Verification { writer, sources: 0, rows: 0, invalid: 0, verdict: Verdict::Valid }
+    }
+
+    /// Reports `source` as unreadable for `reason`.
+    pub fn unreadable(&mut self, source: &str, reason: &SimpleError) -> Result<(), SimpleError> {
+        self.sources += 1;
+        self.verdict = Verdict::Unreadable;
+        self.report(Finding { source: source.to_string(), line: 0, reason: reason.to_string() })
+    }
+
+    /// Parses and checks every row of `source` read by `reader`.
+    pub fn rows<R: SourceReader + ?Sized>(&mut self, source: &str, reader: &mut R) -> Result<(), SimpleError> {
+        self.sources += 1;
+        while let Some(result) = reader.next() {
+            self.rows += 1;
+            if let Err(e) = result.and_then(|command| { check(&command) }) {
+                self.invalid += 1;
+                self.verdict = self.verdict.max(Verdict::Invalid);
+                self.report(Finding { source: source.to_string(), line: reader.line(), reason: e.to_string() })?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns verdict of sources verified so far.
+    pub fn verdict(&self) -> Verdict {
+        self.verdict
+    }
+
+    /// Writes `finding` to underlying writer.
+    fn report(&mut self, finding: Finding) -> Result<(), SimpleError> {
+        try_with!(writeln!(self.writer, "{}", finding), "unable to write finding");
+        Ok(())
+    }
+}
+
+impl<W: Write> fmt::Display for Verification<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "verified {} sources, {} rows, {} invalid", self.sources, self.rows, self.invalid)
+    }
+}
+
+/// Returns reader of csv transactions `source` (fields separated by `delimiter`) having columns of transactions.
+pub fn open_csv(source: &str, delimiter: u8) -> Result<CsvCommandReader<Box<dyn Read + Send>>, SimpleError> {
+    let file = try_with!(input::open(source), "unable to open source");
+    let reader = try_with!(csv_reader(file, delimiter), "invalid headers");
+    let reader = try_with!(CsvCommandReader::new(reader), "invalid headers");
+    if let Some(missing) = missing_header(reader.headers()) {
+        bail!("missing {} column", missing);
+    }
+    Ok(reader)
+}
+
+/// Checks rules of `command` not depending on account state.
+pub fn check(command: &Command) -> Result<(), SimpleError> {
+    if let CommandType::Deposit | CommandType::Withdraw = command.name() {
+        let amount = match command.amount() {
+            Some(amount) => amount,
+            None => bail!("amount is none for transaction({})", command.tx()),
+        };
+        if amount.is_sign_negative() || amount.is_zero() {
+            bail!("amount({}) isn't positive for transaction({})", amount, command.tx());
+        }
+        if amount.scale() > MAX_SCALE {
+            bail!("amount({}) has more than {} decimal places for transaction({})", amount, MAX_SCALE, command.tx());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::testing::TempDir;
+
+    #[test]
+    fn rows_failing_reported_by_line() {
+        let source = "type,client,tx,amount\ndeposit,1,1,1.0\nwithdraw,1,2,\ndeposit,x,3,1.0\ndeposit,1,4,-1.0\ndeposit,1,5,0.00001\ndispute,1,1,\n";
+        let mut reader = CsvCommandReader::new(csv_reader(source.as_bytes(), b',').unwrap()).unwrap();
+        let mut verification = Verification::new(vec![]);
+        verification.rows("source.csv", &mut reader).unwrap();
+        assert_eq!(verification.verdict(), Verdict::Invalid);
+        assert_eq!(verification.to_string(), "verified 1 sources, 6 rows, 4 invalid");
+
+        verification.unreadable("missing.csv", &SimpleError::new("unable to open source")).unwrap();
+        assert_eq!(verification.verdict().code(), 2);
+        let findings = String::from_utf8(verification.writer).unwrap();
+        let lines: Vec<&str> = findings.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert!(lines[0].starts_with("source.csv:3: amount is none"), "{}", lines[0]);
+        assert!(lines[1].starts_with("source.csv:4: "), "{}", lines[1]);
+        assert!(lines[2].starts_with("source.csv:5: amount(-1.0) isn't positive"), "{}", lines[2]);
+        assert!(lines[3].starts_with("source.csv:6: amount(0.00001) has more than 4 decimal places"), "{}", lines[3]);
+        assert_eq!(lines[4], "missing.csv: unable to open source");
+    }
+
+    #[test]
+    fn sources_without_transaction_columns_unreadable() {
+        let directory = TempDir::new("verify");
+        let path = directory.join("transactions.csv");
+        std::fs::write(&path, "client;tx\n1;1\n").unwrap();
+        let error = open_csv(path.to_str().unwrap(), b',').err().unwrap();
+        assert_eq!(error.to_string(), "missing type column");
+        assert!(open_csv("missing.csv", b',').is_err());
+    }
+}
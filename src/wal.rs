@@ -0,0 +1,103 @@
+//! Write-ahead log of commands handled by persistent account stores.
+//!
+//! Commands are appended as CRC-checked frames (see `eventstore`) before they are applied, so a run interrupted
+//! before its store is flushed is recovered by replaying the commands logged since the last checkpoint (the tail).
+//! Replayed commands already applied to the store are rejected as duplicates.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use simple_error::*;
+
+use crate::eventstore::{decode_frame, encode_frame};
+use crate::models::Command;
+
+/// Log of commands appended before being applied to a store.
+pub struct WriteAheadLog {
+    path: PathBuf,
+    file: File,
+    pending: usize,
+}
+
+impl WriteAheadLog {
+    /// Returns write-ahead log opened (created when missing) at `path` and commands logged since the last checkpoint.
+    ///
+    /// A frame torn at the end of the log (interrupted append) was never applied and is dropped.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<(Self, Vec<Command>), SimpleError> {
+        let path = path.as_ref().to_path_buf();
+        let bytes = match fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => vec![],
+            Err(e) => bail!("unable to read write-ahead log({}), {}", path.display(), e),
+        };
+        let mut tail = vec![];
+        let mut offset = 0;
+        while let Some((command, length)) = try_with!(decode_frame(&bytes[offset..]), "unable to read write-ahead log({})", path.display()) {
+            tail.push(command);
+            offset += length;
+        }
+        let file = try_with!(
+            OpenOptions::new().create(true).append(true).open(&path),
+            "unable to open write-ahead log({})",
+            path.display()
+        );
+        try_with!(file.set_len(offset as u64), "unable to truncate write-ahead log({})", path.display());
+        let pending = tail.len();
+        Ok((WriteAheadLog { path, file, pending }, tail))
+    }
+
+    /// Appends `command` to log (written before the command is applied).
+    pub fn append(&mut self, command: &Command) -> Result<(), SimpleError> {
+        let frame = encode_frame(command)?;
+        try_with!(self.file.write_all(&frame), "unable to append to write-ahead log({})", self.path.display());
+        self.pending += 1;
+        Ok(())
+    }
+
+    /// Returns commands logged since the last checkpoint.
+    #[cfg_attr(not(feature = "server"), allow(dead_code))]
+    pub fn pending(&self) -> usize {
+        self.pending
+    }
+
+    /// Truncates log once every logged command is flushed to the store.
+    pub fn checkpoint(&mut self) -> Result<(), SimpleError> {
+        try_with!(self.file.set_len(0), "unable to truncate write-ahead log({})", self.path.display());
+        try_with!(self.file.sync_all(), "unable to sync write-ahead log({})", self.path.display());
+        self.pending = 0;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::prelude::Decimal;
+
+    use crate::models::CommandType;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn write_ahead_log_replays_tail_until_checkpoint() {
+        let directory = TempDir::new("wal");
+        let path = directory.join("commands.wal");
+        let deposit = Command::new(CommandType::Deposit, 1, 1, Some(Decimal::new(15, 1)));
+        let dispute = Command::new(CommandType::Dispute, 1, 1, None);
+        {
+            let (mut wal, tail) = WriteAheadLog::open(&path).unwrap();
+            assert!(tail.is_empty());
+            wal.append(&deposit).unwrap();
+            wal.append(&dispute).unwrap();
+        }
+        // interrupted append
+        OpenOptions::new().append(true).open(&path).unwrap().write_all(&[9, 0]).unwrap();
+        {
+            let (mut wal, tail) = WriteAheadLog::open(&path).unwrap();
+            assert_eq!(tail, vec![deposit, dispute]);
+            wal.checkpoint().unwrap();
+        }
+        let (_, tail) = WriteAheadLog::open(&path).unwrap();
+        assert!(tail.is_empty());
+    }
+}
@@ -0,0 +1,177 @@
+//! Ingestion of csv files dropped into a directory (`--watch`), replacing shell scripts looping over a drop directory.
+//!
+//! Files are read one at a time (oldest name first) as a single source, so accounts are kept across files. Files are
+//! only read once unmodified for `SETTLE_INTERVAL` (producers still writing them are waited on) and names starting with
+//! `.` are ignored (temporary files renamed once written). Files are moved to `processed/` once every row is handled
+//! or to `failed/` when they can't be read as transaction csv (e.g. missing `type`, `client` or `tx` columns).
+
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+use simple_error::*;
+use tracing::{info, warn};
+
+use crate::input::{CsvCommandReader, SourceReader, csv_reader, missing_header};
+use crate::models::Command;
+
+/// Subdirectory of watched directory files processed are moved to.
+pub const PROCESSED_DIR: &str = "processed";
+
+/// Subdirectory of watched directory files failing are moved to.
+pub const FAILED_DIR: &str = "failed";
+
+/// Duration waited between listings of watched directory (when no file is ready).
+pub const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Duration files are unmodified before read.
+pub const SETTLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Reads commands of csv files dropped into a directory (forever).
+pub struct WatchReader {
+    directory: PathBuf,
+    delimiter: u8,
+    reading: Option<(PathBuf, CsvCommandReader<BufReader<File>>)>,
+    position: usize,
+}
+
+impl WatchReader {
+    /// Returns new `WatchReader` of csv files (separated by `delimiter`) dropped into `directory`.
+    pub fn open<P: AsRef<Path>>(directory: P, delimiter: u8) -> Result<Self, SimpleError> {
+        let directory = directory.as_ref().to_path_buf();
+        for subdirectory in [PROCESSED_DIR, FAILED_DIR] {
+            let path = directory.join(subdirectory);
+            try_with!(fs::create_dir_all(&path), "unable to create directory({})", path.display());
+        }
+        Ok(WatchReader { directory, delimiter, reading: None, position: 0 })
+    }
+
+    /// Returns next csv file ready to be read (oldest name first), if any.
+    fn ready(&self) -> Result<Option<PathBuf>, SimpleError> {
+        let entries = try_with!(fs::read_dir(&self.directory), "unable to watch directory({})", self.directory.display());
+        let mut ready = vec![];
+        for entry in entries {
+            let entry = try_with!(entry, "unable to watch directory({})", self.directory.display());
+            let path = entry.path();
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name.starts_with('.') || !name.to_lowercase().ends_with(".csv") {
+                continue;
+            }
+            let metadata = try_with!(entry.metadata(), "unable to read metadata({})", path.display());
+            let settled = metadata.modified().ok()
+                .and_then(|modified| { modified.elapsed().ok() })
+                .is_some_and(|elapsed| { elapsed >= SETTLE_INTERVAL });
+            if metadata.is_file() && settled {
+                ready.push(path);
+            }
+        }
+        Ok(ready.into_iter().min())
+    }
+
+    /// Returns reader of csv file at `path`.
+    fn open_file(&self, path: &Path) -> Result<CsvCommandReader<BufReader<File>>, SimpleError> {
+        let file = try_with!(File::open(path), "unable to open source({})", path.display());
+        let reader = try_with!(csv_reader(BufReader::new(file), self.delimiter), "invalid source({})", path.display());
+        let reader = try_with!(CsvCommandReader::new(reader), "invalid source({})", path.display());
+        if let Some(missing) = missing_header(reader.headers()) {
+            bail!("invalid source({}), missing {} column", path.display(), missing);
+        }
+        Ok(reader)
+    }
+
+    /// Moves file at `path` to `subdirectory` of watched directory.
+    fn move_to(&self, path: &Path, subdirectory: &str) -> Result<(), SimpleError> {
+        let destination = self.directory.join(subdirectory).join(path.file_name().unwrap_or_default());
+        try_with!(fs::rename(path, &destination), "unable to move source({}) to {}", path.display(), destination.display());
+        Ok(())
+    }
+}
+
+impl SourceReader for WatchReader {
+    fn line(&self) -> usize {
+        self.position
+    }
+}
+
+impl Iterator for WatchReader {
+    type Item = Result<Command, SimpleError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((path, reader)) = self.reading.as_mut() {
+                if let Some(result) = reader.next() {
+                    self.position = reader.line();
+                    return Some(result);
+                }
+                // every row of file is handled once the next row is read
+                let path = path.clone();
+                self.reading = None;
+                info!("processed {}", path.display());
+                if let Err(e) = self.move_to(&path, PROCESSED_DIR) {
+                    return Some(Err(e));
+                }
+                continue;
+            }
+            let path = match self.ready() {
+                Ok(Some(path)) => path,
+                Ok(None) => {
+                    thread::sleep(POLL_INTERVAL);
+                    continue;
+                }
+                Err(e) => return Some(Err(e)),
+            };
+            match self.open_file(&path) {
+                Ok(reader) => self.reading = Some((path, reader)),
+                Err(e) => {
+                    warn!("{}", e);
+                    if let Err(e) = self.move_to(&path, FAILED_DIR) {
+                        return Some(Err(e));
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::SystemTime;
+
+    use crate::models::CommandType;
+    use crate::testing::TempDir;
+
+    /// Writes `contents` to `path` modified before settling.
+    fn drop_file(path: &Path, contents: &str) {
+        fs::write(path, contents).unwrap();
+        File::options().write(true).open(path).unwrap().set_modified(SystemTime::now() - SETTLE_INTERVAL).unwrap();
+    }
+
+    #[test]
+    fn files_dropped_are_read_and_moved() {
+        let directory = TempDir::new("watch");
+        drop_file(&directory.join("b.csv"), "type,client,tx,amount\nwithdraw,1,2,1.0\n");
+        drop_file(&directory.join("a.csv"), "type,client,tx,amount\ndeposit,1,1,2.0\n");
+        drop_file(&directory.join("c.csv"), "client;tx\n");
+        drop_file(&directory.join(".d.csv"), "type,client,tx,amount\ndeposit,2,3,2.0\n");
+        let mut reader = WatchReader::open(directory.path(), b',').unwrap();
+
+        let commands: Vec<Command> = reader.by_ref().take(2).map(Result::unwrap).collect();
+        assert_eq!(commands.iter().map(|command| { (command.name().clone(), command.tx()) }).collect::<Vec<_>>(), vec![
+            (CommandType::Deposit, 1),
+            (CommandType::Withdraw, 2),
+        ]);
+        assert_eq!(reader.line(), 2);
+        assert!(directory.join(PROCESSED_DIR).join("a.csv").exists() && directory.join("b.csv").exists());
+
+        // reading pauses once every file is read (listings repeated) so files left are checked by a separate thread
+        let watched = directory.path().to_path_buf();
+        thread::spawn(move || { reader.next() });
+        thread::sleep(POLL_INTERVAL * 2);
+        assert!(watched.join(PROCESSED_DIR).join("b.csv").exists());
+        assert!(watched.join(FAILED_DIR).join("c.csv").exists());
+        assert!(watched.join(".d.csv").exists());
+    }
+}
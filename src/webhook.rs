@@ -0,0 +1,173 @@
+//! Webhook notifications of account events posted (as JSON) while transactions are processed.
+//!
+//! Accounts locked (by chargebacks) are notified so risk teams learn of locks during processing rather than from the
+//! accounts output. Requests failing (transport errors, `429` or `5xx` responses) are retried with exponential
+//! backoff; other responses are not retried.
+
+use std::thread;
+use std::time::Duration;
+
+use serde::Serialize;
+use simple_error::*;
+use tracing::warn;
+
+use crate::audit::AuditRecord;
+use crate::models::{Account, ClientId, Event};
+use crate::store::ProjectionStore;
+use crate::subscribers::EventSubscriber;
+
+/// Delay before the first retry of a failed request (doubled for each further retry).
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+
+/// Timeout of each request.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Body of webhook requests.
+#[derive(Debug, Serialize)]
+pub struct Notification<'a> {
+    event: AuditRecord,
+    account: &'a Account,
+}
+
+impl<'a> Notification<'a> {
+    /// Returns notification of `event` applied to `account`.
+    pub fn new(account: &'a Account, event: &Event) -> Self {
+        Notification { event: AuditRecord::from_event(account.client(), event), account }
+    }
+}
+
+/// Posts notifications of account events to a webhook url.
+pub struct Webhook {
+    url: String,
+    agent: ureq::Agent,
+    retries: u32,
+    backoff: Duration,
+}
+
+impl Webhook {
+    /// Returns new `Webhook` posting to `url` retrying failed requests up to `retries` times.
+    pub fn new(url: &str, retries: u32) -> Self {
+        let agent = ureq::AgentBuilder::new().timeout(TIMEOUT).build();
+        Webhook { url: url.to_string(), agent, retries, backoff: INITIAL_BACKOFF }
+    }
+
+    /// Returns true when `event` is notified (account locks).
+    pub fn notifies(event: &Event) -> bool {
+        matches!(event, Event::Locked { .. })
+    }
+
+    /// Posts notification of `event` applied to `account` (retrying failed requests).
+    pub fn notify(&self, account: &Account, event: &Event) -> Result<(), SimpleError> {
+        let client: ClientId = account.client();
+        let body = try_with!(serde_json::to_string(&Notification::new(account, event)), "unable to encode webhook notification");
+        let mut backoff = self.backoff;
+        let mut attempt = 0;
+        loop {
+            let result = self.agent.post(&self.url).set("Content-Type", "application/json").send_string(&body);
+            let error = match result {
+                Ok(_) => return Ok(()),
+                Err(ureq::Error::Status(status, _)) if status != 429 && status < 500 => {
+                    bail!("webhook({}) rejected account({}) {} notification({})", self.url, client, event.name(), status)
+                }
+                Err(e) => e,
+            };
+            if attempt == self.retries {
+                bail!("webhook({}) failed to notify account({}) {} after {} attempts, {}", self.url, client, event.name(), attempt + 1, error);
+            }
+            thread::sleep(backoff);
+            backoff *= 2;
+            attempt += 1;
+        }
+    }
+}
+
+impl EventSubscriber for Webhook {
+    /// Notifies account locks (notifications failing every retry are logged rather than failing).
+    fn on_events(&mut self, client: ClientId, events: &[Event], accounts: &dyn ProjectionStore) -> Result<(), SimpleError> {
+        if !events.iter().any(Webhook::notifies) {
+            return Ok(());
+        }
+        let account = require_with!(accounts.get(client)?, "account({}) not found", client);
+        for event in events.iter().filter(|event| { Webhook::notifies(event) }) {
+            if let Err(e) = self.notify(&account, event) {
+                warn!(client, event = event.name(), "{}", e);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use rust_decimal::prelude::Decimal;
+
+    use crate::events::Actor;
+    use crate::models::{Command, CommandType};
+
+    /// Serves `statuses` (one request each) returning bodies of requests received.
+    fn serve(statuses: Vec<u16>) -> (String, thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hooks/locks", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            statuses.into_iter().map(|status| {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if let Some(value) = line.to_lowercase().strip_prefix("content-length:") {
+                        length = value.trim().parse().unwrap();
+                    }
+                    if line == "\r\n" {
+                        break;
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                let response = format!("HTTP/1.1 {} Status\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status);
+                reader.get_mut().write_all(response.as_bytes()).unwrap();
+                String::from_utf8(body).unwrap()
+            }).collect()
+        });
+        (url, server)
+    }
+
+    fn locked_account() -> (Account, Event) {
+        let mut account = Account::new(1);
+        for command in [
+            Command::new(CommandType::Deposit, 1, 1, Some(Decimal::new(15, 1))),
+            Command::new(CommandType::Dispute, 1, 1, None),
+            Command::new(CommandType::Chargeback, 1, 1, None),
+        ] {
+            let events = account.handle(command).unwrap();
+            account.apply(events);
+        }
+        let event = account.events().last().unwrap().clone();
+        (account, event)
+    }
+
+    #[test]
+    fn failed_notifications_retried() {
+        let (account, event) = locked_account();
+        assert!(Webhook::notifies(&event));
+
+        let (url, server) = serve(vec![503, 200]);
+        let mut webhook = Webhook::new(&url, 2);
+        webhook.backoff = Duration::from_millis(1);
+        webhook.notify(&account, &event).unwrap();
+        let bodies = server.join().unwrap();
+        assert_eq!(bodies.len(), 2);
+        let body: serde_json::Value = serde_json::from_str(&bodies[1]).unwrap();
+        assert_eq!(body["event"]["event"], "locked");
+        assert_eq!(body["account"]["client"], 1);
+        assert_eq!(body["account"]["locked"], true);
+
+        let (url, server) = serve(vec![400]);
+        assert!(Webhook::new(&url, 2).notify(&account, &event).unwrap_err().as_str().contains("rejected"));
+        server.join().unwrap();
+    }
+}
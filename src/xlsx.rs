@@ -0,0 +1,146 @@
+//! Reader of XLSX workbooks mapping rows of a worksheet to `Command`s.
+//!
+//! The first row of the worksheet (first sheet unless named) has headers matched like csv sources
+//! (trimmed and case-insensitive) while numbers, booleans and dates of cells are read as their values.
+
+use calamine::{open_workbook, Data, Range, Reader, Xlsx};
+use csv::StringRecord;
+use simple_error::*;
+
+use crate::input::SourceReader;
+use crate::models::Command;
+
+/// Reads rows of an XLSX worksheet as `Command`s.
+pub struct XlsxReader {
+    rows: std::vec::IntoIter<(usize, Result<StringRecord, SimpleError>)>,
+    headers: StringRecord,
+    position: usize,
+}
+
+impl XlsxReader {
+    /// Returns new `XlsxReader` reading `sheet` (or first sheet) of workbook at `path`.
+    pub fn open(path: &str, sheet: Option<&str>) -> Result<Self, SimpleError> {
+        let mut workbook: Xlsx<_> = try_with!(open_workbook(path), "unable to open workbook({})", path);
+        let range = match sheet {
+            Some(sheet) => try_with!(workbook.worksheet_range(sheet), "unable to read workbook({}) sheet({})", path, sheet),
+            None => match workbook.worksheet_range_at(0) {
+                Some(range) => try_with!(range, "unable to read workbook({}) first sheet", path),
+                None => bail!("workbook({}) has no sheets", path),
+            },
+        };
+        Self::from_range(&range)
+    }
+
+    /// Returns new `XlsxReader` reading rows of worksheet `range` having headers in first row.
+    fn from_range(range: &Range<Data>) -> Result<Self, SimpleError> {
+        let mut rows = range.rows();
+        let headers = match rows.next() {
+            Some(headers) => headers.iter().map(|cell| { cell.to_string().trim().to_lowercase() }).collect(),
+            None => StringRecord::new(),
+        };
+        let mut records = vec![];
+        for (index, row) in rows.enumerate() {
+            // rows are 1-based and follow headers
+            let position = index + 2;
+            if row.iter().all(|cell| { cell == &Data::Empty }) {
+                continue;
+            }
+            let record = row.iter().map(|cell| { value(cell, position) }).collect();
+            records.push((position, record));
+        }
+        Ok(XlsxReader { rows: records.into_iter(), headers, position: 1 })
+    }
+
+    /// Returns lowercased headers of worksheet.
+    pub fn headers(&self) -> &StringRecord {
+        &self.headers
+    }
+}
+
+impl SourceReader for XlsxReader {
+    fn line(&self) -> usize {
+        self.position
+    }
+}
+
+impl Iterator for XlsxReader {
+    type Item = Result<Command, SimpleError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (position, record) = self.rows.next()?;
+        self.position = position;
+        let command = record.and_then(|record| {
+            record.deserialize(Some(&self.headers))
+                .map_err(|e| { SimpleError::new(format!("invalid xlsx row({}): {}", position, e)) })
+        });
+        Some(command)
+    }
+}
+
+/// Returns trimmed value of worksheet `cell` in row at `position` (dates as RFC 3339 UTC).
+fn value(cell: &Data, position: usize) -> Result<String, SimpleError> {
+    match cell {
+        Data::DateTime(datetime) => match datetime.as_datetime() {
+            Some(datetime) => Ok(datetime.format("%Y-%m-%dT%H:%M:%S%.fZ").to_string()),
+            None => bail!("invalid xlsx row({}) date({})", position, datetime),
+        },
+        Data::Error(error) => bail!("xlsx row({}) has cell error({})", position, error),
+        cell => Ok(cell.to_string().trim().to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use calamine::ExcelDateTime;
+    use chrono::{TimeZone, Utc};
+    use rust_decimal::prelude::Decimal;
+
+    use crate::events::Cause;
+    use crate::models::CommandType;
+
+    fn range(rows: Vec<Vec<Data>>) -> Range<Data> {
+        let mut range = Range::new((0, 0), (rows.len() as u32 - 1, rows[0].len() as u32 - 1));
+        for (row, cells) in rows.into_iter().enumerate() {
+            for (column, cell) in cells.into_iter().enumerate() {
+                range.set_value((row as u32, column as u32), cell);
+            }
+        }
+        range
+    }
+
+    fn string(value: &str) -> Data {
+        Data::String(value.to_string())
+    }
+
+    #[test]
+    fn rows_read_as_commands() {
+        let timestamp = ExcelDateTime::new(45306.5, calamine::ExcelDateTimeType::DateTime, false);
+        let range = range(vec![
+            vec![string(" Type"), string("Client"), string("TX"), string("Amount"), string("Timestamp")],
+            vec![string("deposit"), Data::Float(7.0), Data::Int(1), Data::Float(1.5), Data::DateTime(timestamp)],
+            vec![Data::Empty, Data::Empty, Data::Empty, Data::Empty, Data::Empty],
+            vec![string("dispute"), Data::Float(7.0), Data::Int(1), Data::Empty, Data::Empty],
+        ]);
+        let commands: Vec<Command> = XlsxReader::from_range(&range).unwrap().map(|command| { command.unwrap() }).collect();
+
+        assert_eq!(commands.len(), 2);
+        assert_eq!(commands[0].name(), &CommandType::Deposit);
+        assert_eq!(commands[0].actor_id(), 7);
+        assert_eq!(commands[0].tx(), 1);
+        assert_eq!(commands[0].amount(), Some(Decimal::new(15, 1)));
+        assert_eq!(commands[0].timestamp(), Some(Utc.with_ymd_and_hms(2024, 1, 15, 12, 0, 0).unwrap()));
+        assert_eq!(commands[1].name(), &CommandType::Dispute);
+        assert_eq!(commands[1].amount(), None);
+    }
+
+    #[test]
+    fn invalid_row_errors() {
+        let range = range(vec![
+            vec![string("type"), string("client"), string("tx"), string("amount")],
+            vec![string("deposit"), string("seven"), Data::Int(1), Data::Float(1.5)],
+        ]);
+
+        assert!(XlsxReader::from_range(&range).unwrap().next().unwrap().is_err());
+    }
+}
@@ -0,0 +1,30 @@
+//! Runs of the cli using account metadata sources.
+
+use std::env;
+use std::fs;
+use std::process::Command;
+
+#[test]
+fn invalid_limits_rejected() {
+    let directory = env::temp_dir().join(format!("accounts-aggregate-metadata-{}", std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    let transactions = directory.join("transactions.csv");
+    fs::write(&transactions, "type,client,tx,amount\ndeposit,1,1,1.0\nwithdraw,1,2,0.5\n").unwrap();
+    let accounts = directory.join("accounts.csv");
+    for row in ["1,savings,2.5", "1,savings,-1", "1,credit,-100"] {
+        fs::write(&accounts, format!("client,type,limit\n{}\n", row)).unwrap();
+        let output = Command::new(env!("CARGO_BIN_EXE_accounts-aggregate"))
+            .args([transactions.to_str().unwrap(), "--accounts", accounts.to_str().unwrap()])
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(65), "{}", row);
+        assert!(String::from_utf8_lossy(&output.stderr).contains("invalid limit"), "{}", row);
+    }
+    fs::write(&accounts, "client,type,limit\n1,savings,2\n").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_accounts-aggregate"))
+        .args([transactions.to_str().unwrap(), "--accounts", accounts.to_str().unwrap()])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    fs::remove_dir_all(directory).unwrap();
+}
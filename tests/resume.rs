@@ -0,0 +1,67 @@
+//! Runs of the cli interrupted then resumed from their checkpoint.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+/// Returns new directory of test `name` (removed when already present).
+fn temp_dir(name: &str) -> PathBuf {
+    let directory = env::temp_dir().join(format!("accounts-aggregate-{}-{}", name, std::process::id()));
+    fs::remove_dir_all(&directory).ok();
+    fs::create_dir_all(&directory).unwrap();
+    directory
+}
+
+/// Runs the cli using `args`.
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_accounts-aggregate")).args(args).output().unwrap()
+}
+
+#[test]
+fn resume_rejected_without_persistent_store() {
+    let directory = temp_dir("resume-memory");
+    let checkpoint = directory.join("checkpoint.json");
+    let output = run(&["transactions.csv", "--checkpoint", checkpoint.to_str().unwrap(), "--resume"]);
+    assert_eq!(output.status.code(), Some(64));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("resume requires a persistent store"));
+    fs::remove_dir_all(directory).unwrap();
+}
+
+#[cfg(feature = "sled")]
+#[test]
+fn interrupted_run_resumed_from_checkpoint() {
+    let directory = temp_dir("resume-sled");
+    // deposits of 20,000 records (spread across clients) interrupted by an unparseable record
+    let (mut source, mut clean) = (String::from("type,client,tx,amount\n"), String::from("type,client,tx,amount\n"));
+    for tx in 1..=20_000 {
+        if tx == 15_000 {
+            source.push_str("deposit,x,y,z\n");
+        }
+        let record = format!("deposit,{},{},1.5\n", tx % 5000 + 1, tx);
+        source.push_str(&record);
+        clean.push_str(&record);
+    }
+    let (path, clean_path) = (directory.join("transactions.csv"), directory.join("clean.csv"));
+    fs::write(&path, source).unwrap();
+    fs::write(&clean_path, clean).unwrap();
+    let (path, clean_path) = (path.to_str().unwrap(), clean_path.to_str().unwrap());
+    let checkpoint = directory.join("checkpoint.json");
+    let store = directory.join("accounts.sled");
+    let args = ["--store", "sled", "--store-path", store.to_str().unwrap(), "--checkpoint", checkpoint.to_str().unwrap()];
+
+    let interrupted = run(&[&[path, "--max-unparseable", "0"], &args[..]].concat());
+    assert_eq!(interrupted.status.code(), Some(65));
+    assert_eq!(fs::read_to_string(&checkpoint).unwrap(), format!("{{\"source\":\"{}\",\"records\":10000}}", path));
+
+    // records applied after the checkpoint are rejected as duplicates once resumed (records before it are skipped)
+    let metrics = directory.join("metrics.json");
+    let resumed = run(&[&[path, "--resume", "--metrics-json", metrics.to_str().unwrap()], &args[..]].concat());
+    assert!(resumed.status.success(), "{}", String::from_utf8_lossy(&resumed.stderr));
+    let metrics = fs::read_to_string(metrics).unwrap();
+    assert!(metrics.contains("\"applied\":5001,\"rejected\":4999,\"unparseable\":1"), "{}", metrics);
+    let expected = run(&[clean_path]);
+    assert!(expected.status.success());
+    assert_eq!(String::from_utf8_lossy(&resumed.stdout), String::from_utf8_lossy(&expected.stdout));
+    fs::remove_dir_all(directory).unwrap();
+}
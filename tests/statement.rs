@@ -0,0 +1,52 @@
+//! Statements of the cli rendered from account event logs, audit trails and transactions.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+/// Returns new directory of test `name` (removed when already present).
+fn temp_dir(name: &str) -> PathBuf {
+    let directory = env::temp_dir().join(format!("accounts-aggregate-{}-{}", name, std::process::id()));
+    fs::remove_dir_all(&directory).ok();
+    fs::create_dir_all(&directory).unwrap();
+    directory
+}
+
+/// Runs the cli using `args`.
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_accounts-aggregate")).args(args).output().unwrap()
+}
+
+#[test]
+fn statements_rendered_from_event_logs() {
+    let directory = temp_dir("statement");
+    let transactions = directory.join("transactions.csv");
+    fs::write(&transactions, "type,client,tx,amount\ndeposit,1,1,10.0\nwithdraw,1,2,3.0\nwithdraw,1,3,50.0\n").unwrap();
+    let log = directory.join("events.log");
+    let audit = directory.join("audit.jsonl");
+    let output = run(&[
+        transactions.to_str().unwrap(),
+        "--event-log", log.to_str().unwrap(),
+        "--export-events", audit.to_str().unwrap(),
+    ]);
+    assert!(output.status.success());
+
+    let logged = run(&["statement", log.to_str().unwrap(), "--client", "1"]);
+    assert!(logged.status.success());
+    let statement = String::from_utf8_lossy(&logged.stdout).to_string();
+    assert!(statement.contains("debited"));
+    assert!(statement.lines().any(|line| { line.contains("closing") && line.ends_with("7.0") }));
+
+    let audited = run(&["statement", audit.to_str().unwrap(), "--audit", "--client", "1"]);
+    assert!(audited.status.success());
+    assert_eq!(String::from_utf8_lossy(&audited.stdout), statement);
+
+    // transactions are only handled when requested reporting rows rejected
+    let handled = run(&["statement", transactions.to_str().unwrap(), "--transactions", "--client", "1"]);
+    assert!(handled.status.success());
+    assert_eq!(String::from_utf8_lossy(&handled.stdout), statement);
+    assert!(String::from_utf8_lossy(&handled.stderr).contains("transactions.csv:4: amount(50.0) exceeds available(7.0)"));
+    assert_eq!(run(&["statement", transactions.to_str().unwrap()]).status.code(), Some(65));
+    fs::remove_dir_all(directory).unwrap();
+}
@@ -0,0 +1,153 @@
+//! WebAssembly build of the core engine (`Account` aggregates handling commands) with a JS-facing API.
+//!
+//! Domain models are those of the `core` crate shared with the cli so browsers and edge functions apply the same
+//! business rules. The core does no file I/O: transactions are supplied by the host as JSON commands or csv
+//! text read however the host reads files (fetch, file inputs, request bodies).
+//!
+//! ```js
+//! import init, { Accounts } from "./pkg/accounts_aggregate_wasm.js";
+//!
+//! await init();
+//! const accounts = new Accounts();
+//! accounts.handle('{"type":"deposit","client":1,"tx":1,"amount":"2.5"}');
+//! accounts.processCsv("type,client,tx,amount\nwithdraw,1,2,1.0\n");
+//! console.log(accounts.toCsv());
+//! ```
+
+use std::collections::BTreeMap;
+
+use csv::{ReaderBuilder, Trim, Writer};
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+use accounts_aggregate_core::events::{Actor, Cause};
+use accounts_aggregate_core::models::{Account, ClientId, Command};
+
+/// Outcome of a command handled.
+#[derive(Debug, Serialize)]
+struct Outcome {
+    applied: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<String>,
+    /// Names of events applied (e.g. `credited`).
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    events: Vec<&'static str>,
+}
+
+/// Commands handled of csv text.
+#[derive(Debug, Serialize)]
+struct Processed {
+    applied: usize,
+    rejected: usize,
+}
+
+/// Accounts (ordered by client) applying commands handled.
+#[wasm_bindgen]
+#[derive(Default)]
+pub struct Accounts {
+    accounts: BTreeMap<ClientId, Account>,
+}
+
+#[wasm_bindgen]
+impl Accounts {
+    /// Returns new `Accounts` without accounts.
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Handles command (JSON object) returning its outcome as JSON (`applied`, `reason` when rejected and `events`).
+    ///
+    /// Throws when the command can't be parsed.
+    pub fn handle(&mut self, command: &str) -> Result<String, String> {
+        let command: Command = serde_json::from_str(command).map_err(|e| { format!("invalid command, {}", e) })?;
+        let outcome = match self.apply(command) {
+            Ok(events) => Outcome { applied: true, reason: None, events },
+            Err(reason) => Outcome { applied: false, reason: Some(reason), events: vec![] },
+        };
+        serde_json::to_string(&outcome).map_err(|e| { e.to_string() })
+    }
+
+    /// Handles every row of csv `text` (with headers) returning commands applied and rejected as JSON.
+    ///
+    /// Throws when a row can't be parsed (rows before it are applied).
+    #[wasm_bindgen(js_name = processCsv)]
+    pub fn process_csv(&mut self, text: &str) -> Result<String, String> {
+        let mut reader = ReaderBuilder::new().trim(Trim::All).flexible(true).from_reader(text.as_bytes());
+        let mut processed = Processed { applied: 0, rejected: 0 };
+        for (index, result) in reader.deserialize().enumerate() {
+            // headers are the first line
+            let command: Command = result.map_err(|e| { format!("invalid row({}), {}", index + 2, e) })?;
+            match self.apply(command) {
+                Ok(_) => processed.applied += 1,
+                Err(_) => processed.rejected += 1,
+            }
+        }
+        serde_json::to_string(&processed).map_err(|e| { e.to_string() })
+    }
+
+    /// Returns balances of account of `client` as JSON (undefined when not found).
+    pub fn account(&self, client: ClientId) -> Option<String> {
+        self.accounts.get(&client).and_then(|account| { serde_json::to_string(account).ok() })
+    }
+
+    /// Returns balances of every account ordered by client as JSON.
+    pub fn accounts(&self) -> String {
+        serde_json::to_string(&self.accounts.values().collect::<Vec<_>>()).unwrap_or_default()
+    }
+
+    /// Returns balances of every account ordered by client as csv (as written by the cli).
+    #[wasm_bindgen(js_name = toCsv)]
+    pub fn to_csv(&self) -> Result<String, String> {
+        let mut writer = Writer::from_writer(vec![]);
+        for account in self.accounts.values() {
+            writer.serialize(account).map_err(|e| { e.to_string() })?;
+        }
+        let csv = writer.into_inner().map_err(|e| { e.to_string() })?;
+        String::from_utf8(csv).map_err(|e| { e.to_string() })
+    }
+}
+
+impl Accounts {
+    /// Handles `command` using existing or new `Account` returning names of events applied or reason rejected.
+    ///
+    /// New accounts are only kept when `command` is accepted.
+    fn apply(&mut self, command: Command) -> Result<Vec<&'static str>, String> {
+        let client = command.actor_id();
+        let existing = self.accounts.remove(&client);
+        let opened = existing.is_none();
+        let mut account = existing.unwrap_or_else(|| { Account::new(client) });
+        let result = account.handle(command).map(|events| {
+            let names = events.iter().map(|event| { event.name() }).collect();
+            account.apply(events);
+            names
+        });
+        if result.is_ok() || !opened {
+            self.accounts.insert(client, account);
+        }
+        result.map_err(|e| { e.to_string() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_handled_as_in_cli() {
+        let mut accounts = Accounts::new();
+        assert_eq!(
+            accounts.handle(r#"{"type":"deposit","client":1,"tx":1,"amount":"2.5"}"#).unwrap(),
+            r#"{"applied":true,"events":["credited"]}"#
+        );
+        let rejected = accounts.handle(r#"{"type":"withdraw","client":2,"tx":2,"amount":"1.0"}"#).unwrap();
+        assert!(rejected.starts_with(r#"{"applied":false,"reason":"#), "{}", rejected);
+        assert!(accounts.account(2).is_none());
+        assert!(accounts.handle(r#"{"type":"deposit"}"#).is_err());
+
+        let csv = "type, client, tx, amount\ndeposit,2,3,1.0\nwithdraw,1,4,5.0\ndispute,1,1,\n";
+        assert_eq!(accounts.process_csv(csv).unwrap(), r#"{"applied":2,"rejected":1}"#);
+        assert_eq!(accounts.to_csv().unwrap(), "client,available,held,total,locked\n1,0.0,2.5,2.5,false\n2,1.0,0.0000,1.0,false\n");
+        assert!(accounts.process_csv("type,client,tx,amount\ndeposit,x,5,1.0\n").unwrap_err().starts_with("invalid row(2)"));
+    }
+}